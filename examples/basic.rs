@@ -13,15 +13,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Configure the launch agent
     let mut agent = LaunchAgent::new(&label);
-    agent.program_arguments = vec![
-        "/usr/bin/tail".to_string(),
-        "-f".to_string(),
-        "/dev/null".to_string(),
-    ];
-    agent.keep_alive = true;
+    agent.program_arguments =
+        vec!["/usr/bin/tail".into(), "-f".into(), "/dev/null".into()];
+    agent.keep_alive = true.into();
     agent.run_at_load = true;
 
-    println!("Writing plist to {}", agent.path().display());
+    println!("Writing plist to {}", agent.path()?.display());
     agent.write()?;
 
     println!("Bootstrapping '{}'", agent.label);
@@ -36,9 +33,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Booting out '{}'", agent.label);
     agent.boot_out()?;
 
-    println!("Removing plist {}", agent.path().display());
+    println!("Removing plist {}", agent.path()?.display());
     agent.remove()?;
 
     Ok(())
 }
-
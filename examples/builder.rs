@@ -12,14 +12,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let agent = LaunchAgentBuilder::default()
         .label(label)
-        .arg("/usr/bin/tail".into())
-        .arg("-f".into())
-        .arg("/dev/null".into())
+        .args(["/usr/bin/tail", "-f", "/dev/null"])
         .keep_alive(true)
         .run_at_load(true)
         .build()?;
 
-    println!("Writing plist to {}", agent.path().display());
+    println!("Writing plist to {}", agent.path()?.display());
     agent.write()?;
 
     println!("Bootstrapping '{}'", agent.label);
@@ -31,10 +29,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Booting out '{}'", agent.label);
     agent.boot_out()?;
 
-    println!("Removing plist {}", agent.path().display());
+    println!("Removing plist {}", agent.path()?.display());
     agent.remove()?;
 
     Ok(())
 }
-
-
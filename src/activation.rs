@@ -0,0 +1,239 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::agent::LaunchAgent;
+use crate::keepalive::KeepAlive;
+use crate::paths::LaunchDirectory;
+
+/// When launchd loads a job, which is when its triggers start to apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoadTime {
+    /// Daemons are loaded at boot, before anyone logs in.
+    Boot,
+    /// Agents limited to the `LoginWindow` session are loaded when the login
+    /// window appears.
+    LoginWindow,
+    /// Other agents are loaded when their user logs in, never at boot.
+    Login,
+}
+
+/// Reason launchd starts a loaded job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    /// `RunAtLoad`: once, when the job is loaded.
+    Load,
+    /// `KeepAlive` set to `true`: when the job is loaded and whenever it
+    /// exits.
+    KeepAlive,
+    /// `KeepAlive` conditions: whenever one of them holds.
+    KeepAliveConditions,
+    /// `StartInterval`.
+    Interval(Duration),
+    /// `StartCalendarInterval`.
+    Calendar,
+    /// `Sockets`: on the first connection.
+    Socket,
+    /// `MachServices`: on the first message.
+    MachService,
+    /// `WatchPaths`: when a watched path changes.
+    WatchPaths,
+    /// `QueueDirectories`: while a queue directory is not empty.
+    QueueDirectories,
+    /// `StartOnMount`: when a file system is mounted.
+    Mount,
+    /// `LaunchEvents`: on a matching system event.
+    LaunchEvent,
+}
+
+/// When a job actually runs, derived from the keys of its configuration by
+/// [`LaunchAgent::activation_profile`].
+///
+/// `Display` explains it in a sentence, e.g. "loaded at login, starts when
+/// loaded and every 300s".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActivationProfile {
+    pub load_time: LoadTime,
+    pub triggers: Vec<Trigger>,
+}
+
+impl ActivationProfile {
+    /// Check if the job starts as soon as it is loaded, at boot or at login
+    /// depending on [`load_time`](Self::load_time).
+    pub fn starts_at_load(&self) -> bool {
+        self.triggers
+            .iter()
+            .any(|trigger| matches!(trigger, Trigger::Load | Trigger::KeepAlive))
+    }
+
+    /// Check if the job only starts when something asks for it, e.g. a
+    /// connection, a schedule or a file change.
+    pub fn is_on_demand(&self) -> bool {
+        !self.starts_at_load() && !self.triggers.is_empty()
+    }
+
+    /// Check if nothing but `launchctl kickstart` starts the job.
+    pub fn is_manual(&self) -> bool {
+        self.triggers.is_empty()
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Load => f.write_str("when loaded"),
+            Self::KeepAlive => {
+                f.write_str("when loaded and again whenever it exits")
+            }
+            Self::KeepAliveConditions => {
+                f.write_str("whenever a KeepAlive condition holds")
+            }
+            Self::Interval(interval) => write!(f, "every {}s", interval.as_secs()),
+            Self::Calendar => f.write_str("on its calendar schedule"),
+            Self::Socket => f.write_str("on a socket connection"),
+            Self::MachService => f.write_str("on a Mach message"),
+            Self::WatchPaths => f.write_str("when a watched path changes"),
+            Self::QueueDirectories => {
+                f.write_str("while a queue directory has files")
+            }
+            Self::Mount => f.write_str("when a volume is mounted"),
+            Self::LaunchEvent => f.write_str("on a launch event"),
+        }
+    }
+}
+
+impl fmt::Display for ActivationProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.load_time {
+            LoadTime::Boot => "loaded at boot",
+            LoadTime::LoginWindow => "loaded at the login window",
+            LoadTime::Login => "loaded at login",
+        })?;
+        if self.is_manual() {
+            return f.write_str(", starts only when kickstarted");
+        }
+        for (i, trigger) in self.triggers.iter().enumerate() {
+            let separator = match i {
+                0 => ", starts ",
+                _ if i + 1 == self.triggers.len() => " and ",
+                _ => ", ",
+            };
+            write!(f, "{separator}{trigger}")?;
+        }
+        Ok(())
+    }
+}
+
+impl LaunchAgent {
+    /// Returns when the agent runs if it is installed as a user agent.
+    ///
+    /// User agents are loaded at login, so `RunAtLoad` starts them when the
+    /// user logs in, not at boot. Use
+    /// [`activation_profile_in`](Self::activation_profile_in) for other
+    /// directories, e.g. for daemons.
+    pub fn activation_profile(&self) -> ActivationProfile {
+        self.activation_profile_in(LaunchDirectory::UserAgents)
+    }
+
+    /// Returns when the job runs if it is installed in `directory`.
+    pub fn activation_profile_in(
+        &self,
+        directory: LaunchDirectory,
+    ) -> ActivationProfile {
+        let load_time = match directory {
+            LaunchDirectory::GlobalDaemons | LaunchDirectory::SystemDaemons => {
+                LoadTime::Boot
+            }
+            _ if self.session_types() == ["LoginWindow"] => LoadTime::LoginWindow,
+            _ => LoadTime::Login,
+        };
+
+        let mut triggers = Vec::new();
+        if self.run_at_load {
+            triggers.push(Trigger::Load);
+        }
+        match &self.keep_alive {
+            KeepAlive::Always(true) => triggers.push(Trigger::KeepAlive),
+            KeepAlive::Conditions(_) => triggers.push(Trigger::KeepAliveConditions),
+            KeepAlive::Always(false) => {}
+        }
+        if let Some(seconds) = self
+            .extra
+            .get("StartInterval")
+            .and_then(plist::Value::as_unsigned_integer)
+        {
+            triggers.push(Trigger::Interval(Duration::from_secs(seconds)));
+        }
+        let keys = [
+            ("StartCalendarInterval", Trigger::Calendar),
+            ("MachServices", Trigger::MachService),
+            ("WatchPaths", Trigger::WatchPaths),
+            ("QueueDirectories", Trigger::QueueDirectories),
+            ("LaunchEvents", Trigger::LaunchEvent),
+        ];
+        if !self.sockets.is_empty() {
+            triggers.push(Trigger::Socket);
+        }
+        for (key, trigger) in keys {
+            if self.extra.contains_key(key) {
+                triggers.push(trigger);
+            }
+        }
+        if self
+            .extra
+            .get("StartOnMount")
+            .and_then(plist::Value::as_boolean)
+            .unwrap_or(false)
+        {
+            triggers.push(Trigger::Mount);
+        }
+
+        ActivationProfile {
+            load_time,
+            triggers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::SocketConfig;
+
+    #[test]
+    fn test_activation_profile() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        let profile = agent.activation_profile();
+        assert!(profile.is_manual());
+        assert_eq!(
+            profile.to_string(),
+            "loaded at login, starts only when kickstarted"
+        );
+
+        agent.run_at_load = true;
+        agent.extra.insert("StartInterval".to_string(), 300.into());
+        let profile = agent.activation_profile();
+        assert!(profile.starts_at_load());
+        assert_eq!(
+            profile.to_string(),
+            "loaded at login, starts when loaded and every 300s"
+        );
+        assert_eq!(
+            agent
+                .activation_profile_in(LaunchDirectory::GlobalDaemons)
+                .load_time,
+            LoadTime::Boot
+        );
+
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent
+            .sockets
+            .insert("Listener".to_string(), SocketConfig::tcp(8080));
+        agent
+            .extra
+            .insert("LimitLoadToSessionType".to_string(), "LoginWindow".into());
+        let profile = agent.activation_profile();
+        assert!(profile.is_on_demand());
+        assert_eq!(profile.load_time, LoadTime::LoginWindow);
+        assert_eq!(profile.triggers, vec![Trigger::Socket]);
+    }
+}
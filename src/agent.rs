@@ -1,15 +1,107 @@
-use std::fs::File;
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fs::{File, Permissions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use derive_builder::Builder;
 
 use serde::{Deserialize, Serialize};
 
+use crate::bundle::{bundle_program, set_bundle_program_key, BUNDLE_PROGRAM_KEY};
+use crate::context::LaunchctlContext;
+use crate::env::Environment;
+use crate::filename::nfc;
+use crate::keepalive::KeepAlive;
+use crate::metadata::DESCRIPTION_KEY;
+use crate::paths::{is_protected_path, LaunchDirectory};
+use crate::permissions::PLIST_MODE;
+use crate::power::wrap_ac_power;
+use crate::report::WriteReport;
+use crate::schedule::{append_schedule, Schedule, TimeOfDay, Weekday};
+use crate::wake::set_wake_event;
+use crate::wrapper::program_index;
 use crate::LaunchAgentError;
 
 /// The path to the null device.
 pub(crate) const DEV_NULL: &str = "/dev/null";
 
+/// Default for keys that launchd points to the null device when missing.
+fn dev_null() -> PathBuf {
+    PathBuf::from(DEV_NULL)
+}
+
+/// Optional keys stored as plain values.
+///
+/// Flattened structs make plist encode `Option` values as explicit
+/// `Some`/`None` dictionaries, which launchd does not understand.
+mod present {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[allow(clippy::ref_option)]
+    pub(super) fn serialize<S, T>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        match value {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D, T>(
+        deserializer: D,
+    ) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        T::deserialize(deserializer).map(Some)
+    }
+}
+
+/// Lists of OS strings stored as plist strings.
+mod os_strings {
+    use std::ffi::OsString;
+
+    use serde::ser::{Error, SerializeSeq};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S>(
+        values: &[OsString],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(values.len()))?;
+        for value in values {
+            let value = value.to_str().ok_or_else(|| {
+                S::Error::custom(format!(
+                    "argument is not valid UTF-8: {}",
+                    value.to_string_lossy()
+                ))
+            })?;
+            seq.serialize_element(value)?;
+        }
+        seq.end()
+    }
+
+    pub(super) fn deserialize<'de, D>(
+        deserializer: D,
+    ) -> Result<Vec<OsString>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let values = Vec::<String>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(OsString::from).collect())
+    }
+}
+
 /// Launch Agent configuration.
 ///
 /// A Launch Agent is a macOS mechanism for automatically starting user-level processes
@@ -21,32 +113,336 @@ pub(crate) const DEV_NULL: &str = "/dev/null";
 ///
 /// More information:
 /// [`https://developer.apple.com/library/archive/documentation/MacOSX/Conceptual/BPSystemStartup/Chapters/CreatingLaunchdJobs.html`](Apple Developer Documentation)
-#[derive(Deserialize, Clone, Serialize, Builder)]
+#[derive(Deserialize, Clone, Serialize, Builder, Debug, PartialEq)]
+#[builder(build_fn(validate = "Self::validate_bundle_program"))]
 #[serde(rename_all = "PascalCase")]
 pub struct LaunchAgent {
     #[builder(setter(into))]
     pub label: String,
 
-    #[builder(default, setter(each = "arg"))]
-    pub program_arguments: Vec<String>,
+    /// Arguments are kept as OS strings so paths survive unchanged. plists
+    /// store text, so writing fails for arguments that are not valid UTF-8.
+    #[serde(default, with = "os_strings")]
+    #[builder(default, setter(each(name = "arg", into)))]
+    pub program_arguments: Vec<OsString>,
 
+    #[serde(default = "dev_null")]
     #[builder(default = "PathBuf::from(DEV_NULL)", setter(into))]
     pub standard_out_path: PathBuf,
 
+    #[serde(default = "dev_null")]
     #[builder(default = "PathBuf::from(DEV_NULL)", setter(into))]
     pub standard_error_path: PathBuf,
 
-    #[builder(default)]
-    pub keep_alive: bool,
+    /// Whether and when launchd restarts the job after it exits.
+    #[serde(default)]
+    #[builder(default, setter(into))]
+    pub keep_alive: KeepAlive,
 
+    #[serde(default)]
     #[builder(default)]
     pub run_at_load: bool,
 
+    #[serde(default)]
     #[builder(default)]
     pub process_type: ProcessType,
+
+    /// Environment of the job. Secret values are redacted in `Debug` output.
+    #[serde(default, skip_serializing_if = "Environment::is_empty")]
+    #[builder(default)]
+    pub environment_variables: Environment,
+
+    /// Sockets that launchd listens on and hands over to the job on demand.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[builder(default)]
+    pub sockets: BTreeMap<String, SocketConfig>,
+
+    /// Recommended idle time out (in seconds) for on-demand jobs.
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub time_out: Option<u32>,
+
+    /// Keep processes spawned by the job alive when the job exits.
+    /// launchd kills the whole process group by default.
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub abandon_process_group: Option<bool>,
+
+    /// Scheduling priority of the job, from -20 (highest) to 20 (lowest).
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub nice: Option<i32>,
+
+    /// Throttle file system I/O of the job.
+    #[serde(
+        rename = "LowPriorityIO",
+        default,
+        with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[builder(default, setter(strip_option))]
+    pub low_priority_io: Option<bool>,
+
+    /// Minimum number of seconds between job spawns. launchd uses 10 if unset.
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub throttle_interval: Option<u32>,
+
+    /// Name of the plist file without extension, if it differs from the
+    /// label. Not stored in the plist.
+    #[serde(skip)]
+    #[builder(default, setter(into, strip_option))]
+    pub file_stem: Option<String>,
+
+    /// Keys not modelled by this struct. They are kept as is when the
+    /// configuration is read and written back.
+    #[serde(flatten)]
+    #[builder(default)]
+    pub extra: plist::Dictionary,
+}
+
+impl LaunchAgentBuilder {
+    /// Store a human-readable description of the agent in the plist, see
+    /// [`LaunchAgent::description`].
+    pub fn description(&mut self, description: &str) -> &mut Self {
+        self.extra
+            .get_or_insert_with(plist::Dictionary::new)
+            .insert(DESCRIPTION_KEY.to_string(), description.into());
+        self
+    }
+
+    /// Run the program at `relative` inside the current app bundle, see
+    /// [`ProgramLocation::BundleRelative`](crate::ProgramLocation::BundleRelative).
+    /// It becomes the first program argument. `build()` fails if the running
+    /// executable is not in an app bundle.
+    pub fn bundle_program<P: AsRef<Path>>(&mut self, relative: P) -> &mut Self {
+        let relative = relative.as_ref();
+        set_bundle_program_key(
+            self.extra.get_or_insert_with(plist::Dictionary::new),
+            relative,
+        );
+        if let Ok(program) = bundle_program(relative) {
+            let args = self.program_arguments.get_or_insert_with(Vec::new);
+            args.insert(program_index(args), program.into_os_string());
+        }
+        self
+    }
+
+    /// Fail the build if the program is set relative to the app bundle but
+    /// could not be resolved, see [`bundle_program`](Self::bundle_program).
+    fn validate_bundle_program(&self) -> Result<(), String> {
+        let Some(relative) = self
+            .extra
+            .as_ref()
+            .and_then(|extra| extra.get(BUNDLE_PROGRAM_KEY))
+            .and_then(plist::Value::as_string)
+        else {
+            return Ok(());
+        };
+        let resolved = self.program_arguments.as_ref().is_some_and(|args| {
+            args.get(program_index(args))
+                .is_some_and(|program| Path::new(program).is_absolute())
+        });
+        if resolved {
+            return Ok(());
+        }
+        Err(LaunchAgentError::NoAppBundle(PathBuf::from(relative)).to_string())
+    }
+
+    /// Unset the keys the priority presets own, such as
+    /// [`as_background_task`](Self::as_background_task).
+    pub(crate) fn clear_priority(&mut self) -> &mut Self {
+        self.process_type = None;
+        self.low_priority_io = None;
+        self.nice = None;
+        self.throttle_interval = None;
+        self
+    }
+
+    /// Append program arguments, e.g. `args(["/bin/echo", "hello"])`.
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg.as_ref());
+        }
+        self
+    }
+
+    /// Set an environment variable.
+    pub fn env(&mut self, key: &str, value: &str) -> &mut Self {
+        self.environment_variables
+            .get_or_insert_with(Environment::new)
+            .set(key, value);
+        self
+    }
+
+    /// Set an environment variable whose value is redacted in `Debug`
+    /// output and [`LaunchAgent::redacted`] copies.
+    pub fn env_secret(&mut self, key: &str, value: &str) -> &mut Self {
+        self.environment_variables
+            .get_or_insert_with(Environment::new)
+            .set_secret(key, value);
+        self
+    }
+
+    /// Keep the job running while the path exists.
+    pub fn keep_alive_while_path_exists<P: Into<PathBuf>>(
+        &mut self,
+        path: P,
+    ) -> &mut Self {
+        self.keep_alive_path_state(path.into(), true)
+    }
+
+    /// Keep the job running until the path exists.
+    pub fn keep_alive_until_path_exists<P: Into<PathBuf>>(
+        &mut self,
+        path: P,
+    ) -> &mut Self {
+        self.keep_alive_path_state(path.into(), false)
+    }
+
+    /// Keep the job running while the network is up, via
+    /// `KeepAlive.NetworkState`. The key is unreliable on modern macOS, see
+    /// [`LaunchAgent::diagnose`].
+    pub fn keep_alive_when_network_available(&mut self) -> &mut Self {
+        self.keep_alive
+            .get_or_insert_with(KeepAlive::default)
+            .conditions_mut()
+            .network_state = Some(true);
+        self
+    }
+
+    /// Run the job only on AC power, see [`LaunchAgent::set_ac_power_only`].
+    ///
+    /// Call it after setting the program with `program_arguments`, which
+    /// replaces the wrapper. Arguments added later with `arg` are passed to
+    /// the program.
+    pub fn ac_power_only(&mut self) -> &mut Self {
+        wrap_ac_power(
+            self.program_arguments.get_or_insert_with(Vec::new),
+            self.extra.as_mut(),
+        );
+        self
+    }
+
+    /// Start the job when the system wakes up, see
+    /// [`LaunchAgent::set_on_wake`] for what the program has to do.
+    pub fn on_wake(&mut self) -> &mut Self {
+        set_wake_event(self.extra.get_or_insert_with(plist::Dictionary::new), true);
+        self
+    }
+
+    /// Add calendar entries to `StartCalendarInterval`. Warnings about the
+    /// schedule are logged, see [`Schedule`].
+    pub fn schedule(&mut self, schedule: &Schedule) -> &mut Self {
+        let label = self.label.clone().unwrap_or_default();
+        append_schedule(
+            self.extra.get_or_insert_with(plist::Dictionary::new),
+            &label,
+            schedule,
+        );
+        self
+    }
+
+    /// Run every day at `minute` past each of `hours`, e.g.
+    /// `daily_at(&[9, 17], 0)` for 9:00 and 17:00.
+    pub fn daily_at(&mut self, hours: &[u8], minute: u8) -> &mut Self {
+        let schedule = hours.iter().fold(Schedule::new(), |schedule, &hour| {
+            schedule.daily((hour, minute))
+        });
+        self.schedule(&schedule)
+    }
+
+    /// Run every week on `weekday` at `time`.
+    pub fn weekly_on(
+        &mut self,
+        weekday: impl Into<Weekday>,
+        time: impl Into<TimeOfDay>,
+    ) -> &mut Self {
+        self.schedule(&Schedule::new().weekly(weekday, time))
+    }
+
+    /// Run every month on `day` at `time`.
+    pub fn monthly_on(&mut self, day: u8, time: impl Into<TimeOfDay>) -> &mut Self {
+        self.schedule(&Schedule::new().monthly(day, time))
+    }
+
+    fn keep_alive_path_state(&mut self, path: PathBuf, exists: bool) -> &mut Self {
+        self.keep_alive
+            .get_or_insert_with(KeepAlive::default)
+            .conditions_mut()
+            .path_state
+            .insert(path, exists);
+        self
+    }
 }
 
-#[derive(Clone)]
+/// Launch Daemon configuration.
+///
+/// Daemons in `/Library/LaunchDaemons` use the same plist format as agents.
+/// Managing them requires root, so this crate only reads them, e.g. through
+/// [`Inspector::daemons`](crate::Inspector::daemons).
+pub type LaunchDaemon = LaunchAgent;
+
+/// Socket definition from the `Sockets` dictionary.
+///
+/// launchd creates the socket itself and starts the job when the first
+/// connection arrives. The job receives the descriptors via
+/// [`activate_socket`](crate::activate_socket).
+#[derive(Deserialize, Clone, Serialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct SocketConfig {
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub sock_type: Option<String>,
+
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub sock_passive: Option<bool>,
+
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub sock_node_name: Option<String>,
+
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub sock_service_name: Option<String>,
+
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub sock_family: Option<String>,
+
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub sock_path_name: Option<PathBuf>,
+
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub sock_path_mode: Option<u32>,
+
+    /// Keys not modelled by this struct, such as `SockPathOwner`. They are
+    /// kept as is when the configuration is read and written back.
+    #[serde(flatten)]
+    pub extra: plist::Dictionary,
+}
+
+impl SocketConfig {
+    /// Unix domain stream socket at the given path.
+    pub fn unix<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            sock_family: Some("Unix".to_string()),
+            sock_path_name: Some(path.into()),
+            ..Self::default()
+        }
+    }
+
+    /// TCP socket listening on the given port of the loopback interface.
+    pub fn tcp(port: u16) -> Self {
+        Self {
+            sock_node_name: Some("localhost".to_string()),
+            sock_service_name: Some(port.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ProcessType {
     /// Background jobs are generally processes that do work that was not
     /// directly requested by the user. The resource limits applied to
@@ -66,6 +462,7 @@ pub enum ProcessType {
     Interactive,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for ProcessType {
     fn default() -> Self {
         Self::Standard
@@ -78,10 +475,10 @@ impl Serialize for ProcessType {
         S: serde::Serializer,
     {
         serializer.serialize_str(match self {
-            Self::Background => "background",
-            Self::Standard => "standard",
-            Self::Adaptive => "adaptive",
-            Self::Interactive => "interactive",
+            Self::Background => "Background",
+            Self::Standard => "Standard",
+            Self::Adaptive => "Adaptive",
+            Self::Interactive => "Interactive",
         })
     }
 }
@@ -92,7 +489,7 @@ impl<'de> Deserialize<'de> for ProcessType {
         D: serde::Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        Ok(match s.as_str() {
+        Ok(match s.to_lowercase().as_str() {
             "background" => Self::Background,
             "standard" => Self::Standard,
             "adaptive" => Self::Adaptive,
@@ -110,57 +507,124 @@ impl LaunchAgent {
             program_arguments: vec![],
             standard_out_path: PathBuf::from(DEV_NULL),
             standard_error_path: PathBuf::from(DEV_NULL),
-            keep_alive: false,
+            keep_alive: KeepAlive::default(),
             run_at_load: false,
             process_type: ProcessType::default(),
+            environment_variables: Environment::new(),
+            sockets: BTreeMap::new(),
+            time_out: None,
+            abandon_process_group: None,
+            nice: None,
+            low_priority_io: None,
+            throttle_interval: None,
+            file_stem: None,
+            extra: plist::Dictionary::new(),
+        }
+    }
+
+    /// Returns a copy with secret environment values redacted, safe to log,
+    /// diff or store in audit records.
+    #[must_use]
+    pub fn redacted(&self) -> Self {
+        Self {
+            environment_variables: self.environment_variables.redacted(),
+            ..self.clone()
         }
     }
 
     /// Check if a Launch Agent configuration exists.
     pub fn exists(label: &str) -> bool {
-        let path = Self::path_for(label);
-        path.exists()
+        Self::path_for(label).is_ok_and(|path| path.exists())
     }
 
     /// Loads a Launch Agent configuration from `~/Library/LaunchAgents` by agent label.
     pub fn from_file(label: &str) -> Result<Self, LaunchAgentError> {
-        let path = Self::path_for(label);
+        let path = Self::path_for(label)?;
 
-        let agent = plist::from_file(path)?;
+        let agent = Self::read_plist(&path)?;
 
         Ok(agent)
     }
 
-    /// Returns the path to the Launch Agent configuration file for the given label.
-    fn path_for(label: &str) -> PathBuf {
-        let home = std::env::var("HOME").unwrap();
-        let file_name = format!("{label}.plist");
-        PathBuf::from(home)
-            .join("Library")
-            .join("LaunchAgents")
-            .join(file_name)
+    /// Loads a Launch Agent configuration from `~/Library/LaunchAgents` by the
+    /// plist file name, for plists whose name does not match their label.
+    pub fn from_file_stem(file_stem: &str) -> Result<Self, LaunchAgentError> {
+        let mut agent = Self::read_plist(&Self::path_for(file_stem)?)?;
+        if agent.label != file_stem {
+            agent.file_stem = Some(file_stem.to_string());
+        }
+        Ok(agent)
+    }
+
+    /// Loads the plist currently on disk at this agent's path,
+    /// `None` if there is none.
+    pub(crate) fn read_installed(&self) -> Result<Option<Self>, LaunchAgentError> {
+        self.read_installed_at(&self.path()?)
+    }
+
+    /// Same as `read_installed()`, reading the plist at `path`.
+    pub(crate) fn read_installed_at(
+        &self,
+        path: &Path,
+    ) -> Result<Option<Self>, LaunchAgentError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut agent = Self::read_plist(path)?;
+        agent.file_stem.clone_from(&self.file_stem);
+        Ok(Some(agent))
+    }
+
+    /// Returns the path to the Launch Agent configuration file with the given
+    /// name, which is the label unless the agent sets `file_stem`.
+    pub(crate) fn path_for(file_stem: &str) -> Result<PathBuf, LaunchAgentError> {
+        Ok(LaunchDirectory::UserAgents.plist_path(&nfc(file_stem))?)
     }
 }
 
 impl LaunchAgent {
     /// Writes the Launch Agent configuration to the current user's `LaunchAgents` directory.
+    ///
+    /// The plist is written to a temporary file first and then renamed over the
+    /// target, so launchd never sees a partially written configuration.
+    /// Other processes using this crate are locked out while writing.
     pub fn write(&self) -> Result<(), LaunchAgentError> {
-        let path = Self::path_for(&self.label);
-        let mut file = File::create(path)?;
-        self.to_writer(&mut file)?;
-        Ok(())
+        self.write_report().map(|_| ())
     }
 
     /// Removes the Launch Agent configuration from the current user's `LaunchAgents` directory.
     pub fn remove(&self) -> Result<(), LaunchAgentError> {
-        let path = Self::path_for(&self.label);
-        std::fs::remove_file(path)?;
-        Ok(())
+        LaunchctlContext::default().remove(self)
+    }
+
+    /// Write the plist to `path` instead of the agent's own path, without
+    /// locking.
+    pub(crate) fn write_plist_to(
+        &self,
+        path: &Path,
+    ) -> Result<WriteReport, LaunchAgentError> {
+        let bytes = self.to_bytes()?;
+        write_atomically(path, |file| Ok(file.write_all(&bytes)?))?;
+        Ok(WriteReport::new(path, &bytes))
+    }
+
+    /// Same as `remove()`, for callers already holding the lock.
+    /// The lock file stays, see [`AgentLock`](crate::AgentLock).
+    pub(crate) fn remove_plist(&self) -> Result<(), LaunchAgentError> {
+        LaunchctlContext::default().remove_plist(self)
+    }
+
+    /// Returns the path to the Launch Agent configuration file. Fails if the
+    /// home directory is unknown.
+    pub fn path(&self) -> Result<PathBuf, LaunchAgentError> {
+        Self::path_for(self.file_stem.as_deref().unwrap_or(&self.label))
     }
 
-    /// Returns the path to the Launch Agent configuration file for the given label.
-    pub fn path(&self) -> PathBuf {
-        Self::path_for(&self.label)
+    /// Returns the plist as written to disk.
+    pub(crate) fn to_bytes(&self) -> Result<Vec<u8>, LaunchAgentError> {
+        let mut bytes = Vec::new();
+        self.to_writer(&mut bytes)?;
+        Ok(bytes)
     }
 
     /// Writes the Launch Agent configuration to provided writer.
@@ -170,6 +634,37 @@ impl LaunchAgent {
     }
 }
 
+/// Fail with [`LaunchAgentError::SIPProtected`] for plists on the system
+/// volume instead of letting the write fail with a permission error.
+pub(crate) fn ensure_unprotected(path: &Path) -> Result<(), LaunchAgentError> {
+    if is_protected_path(path) {
+        return Err(LaunchAgentError::SIPProtected(path.to_path_buf()));
+    }
+    Ok(())
+}
+
+/// Write a plist to a temporary file and rename it over `path`, so launchd
+/// never sees a partially written configuration.
+pub(crate) fn write_atomically<F>(
+    path: &Path,
+    write: F,
+) -> Result<(), LaunchAgentError>
+where
+    F: FnOnce(&mut File) -> Result<(), LaunchAgentError>,
+{
+    ensure_unprotected(path)?;
+    let tmp_path = path.with_extension("plist.tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.set_permissions(Permissions::from_mode(PLIST_MODE))?;
+    if let Err(e) = write(&mut file) {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    file.sync_all()?;
+    std::fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::BufWriter;
@@ -179,13 +674,8 @@ mod tests {
     #[test]
     fn test_format_plist() {
         let agent = LaunchAgent {
-            label: "co.myrt.ajam".to_string(),
-            program_arguments: vec!["ajam".to_string(), "run".to_string()],
-            standard_out_path: PathBuf::from(DEV_NULL),
-            standard_error_path: PathBuf::from(DEV_NULL),
-            keep_alive: false,
-            run_at_load: false,
-            process_type: ProcessType::default(),
+            program_arguments: vec!["ajam".into(), "run".into()],
+            ..LaunchAgent::new("co.myrt.ajam")
         };
 
         let mut buf = BufWriter::new(Vec::new());
@@ -207,54 +697,30 @@ mod tests {
 
     #[test]
     fn test_path() {
-        let agent = LaunchAgent {
-            label: "co.myrt.ajam".to_string(),
-            program_arguments: vec![],
-            standard_out_path: PathBuf::from(DEV_NULL),
-            standard_error_path: PathBuf::from(DEV_NULL),
-            keep_alive: false,
-            run_at_load: false,
-            process_type: ProcessType::default(),
-        };
+        let agent = LaunchAgent::new("co.myrt.ajam");
         let path = PathBuf::from("Library/LaunchAgents/co.myrt.ajam.plist");
         let abs_path = PathBuf::from(std::env::var("HOME").unwrap()).join(path);
-        assert_eq!(agent.path(), abs_path);
+        assert_eq!(agent.path().unwrap(), abs_path);
     }
 
     #[test]
     fn test_write() {
         let label = format!("co.myrt.ajam.test.{}", rand::random_range(0.0..=1e9));
 
-        let agent = LaunchAgent {
-            label,
-            program_arguments: vec![],
-            standard_out_path: PathBuf::from(DEV_NULL),
-            standard_error_path: PathBuf::from(DEV_NULL),
-            keep_alive: false,
-            run_at_load: false,
-            process_type: ProcessType::default(),
-        };
-        let path = agent.path();
+        let agent = LaunchAgent::new(&label);
+        let path = agent.path().unwrap();
 
         agent.write().unwrap();
         assert!(path.exists());
 
-        std::fs::remove_file(path).unwrap();
+        agent.remove().unwrap();
     }
 
     #[test]
     fn test_remove() {
         let label = format!("co.myrt.ajam.test.{}", rand::random_range(0.0..=1e9));
-        let agent = LaunchAgent {
-            label,
-            program_arguments: vec![],
-            standard_out_path: PathBuf::from(DEV_NULL),
-            standard_error_path: PathBuf::from(DEV_NULL),
-            keep_alive: false,
-            run_at_load: false,
-            process_type: ProcessType::default(),
-        };
-        let path = agent.path();
+        let agent = LaunchAgent::new(&label);
+        let path = agent.path().unwrap();
 
         agent.write().unwrap();
         assert!(path.exists());
@@ -266,15 +732,7 @@ mod tests {
     #[test]
     fn test_exists() {
         let label = format!("co.myrt.ajam.test.{}", rand::random_range(0.0..=1e9));
-        let agent = LaunchAgent {
-            label: label.clone(),
-            program_arguments: vec![],
-            standard_out_path: PathBuf::from(DEV_NULL),
-            standard_error_path: PathBuf::from(DEV_NULL),
-            keep_alive: false,
-            run_at_load: false,
-            process_type: ProcessType::default(),
-        };
+        let agent = LaunchAgent::new(&label);
 
         assert!(!LaunchAgent::exists(&label));
 
@@ -284,4 +742,165 @@ mod tests {
         agent.remove().unwrap();
         assert!(!LaunchAgent::exists(&label));
     }
+
+    #[test]
+    fn test_write_protected_path() {
+        let path = Path::new("/System/Library/LaunchAgents/com.apple.x.plist");
+        let result = write_atomically(path, |_| Ok(()));
+        assert!(
+            matches!(result, Err(LaunchAgentError::SIPProtected(p)) if p == path)
+        );
+    }
+
+    #[test]
+    fn test_os_string_arguments() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = PathBuf::from("/Applications/Ajam Ü.app/ajam");
+        let mut agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .args([path.as_os_str(), OsStr::new("--run")])
+            .build()
+            .unwrap();
+
+        let mut buf = Vec::new();
+        agent.to_writer(&mut buf).unwrap();
+        let parsed: LaunchAgent = plist::from_bytes(&buf).unwrap();
+        assert_eq!(
+            parsed.program_arguments,
+            vec![path.as_os_str(), "--run".as_ref()]
+        );
+
+        agent
+            .program_arguments
+            .push(OsStr::from_bytes(b"\xff").to_os_string());
+        assert!(agent.to_writer(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_file_stem() {
+        let label = format!("co.myrt.ajam.{}", rand::random_range(0..u32::MAX));
+        let stem = format!("{label}.vendor");
+        let agent = LaunchAgentBuilder::default()
+            .label(label.as_str())
+            .file_stem(stem.as_str())
+            .build()
+            .unwrap();
+        assert!(agent.path().unwrap().ends_with(format!("{stem}.plist")));
+
+        agent.write().unwrap();
+        assert!(!LaunchAgent::exists(&label));
+        assert_eq!(
+            LaunchAgent::from_file_stem(&stem).unwrap(),
+            *agent.stamped()
+        );
+
+        agent.remove().unwrap();
+        assert!(!agent.path().unwrap().exists());
+    }
+
+    #[test]
+    fn test_sockets_round_trip() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent.time_out = Some(30);
+        agent.sockets.insert(
+            "Listeners".to_string(),
+            SocketConfig::unix("/tmp/ajam.sock"),
+        );
+
+        let mut buf = Vec::new();
+        agent.to_writer(&mut buf).unwrap();
+
+        let plist = String::from_utf8(buf.clone()).unwrap();
+        assert!(plist.contains("<key>Sockets</key>"));
+        assert!(plist.contains("<key>SockPathName</key>"));
+        assert!(plist.contains("<key>TimeOut</key>\n\t<integer>30</integer>"));
+        assert!(!plist.contains("<key>Some</key>"));
+
+        let parsed: LaunchAgent = plist::from_bytes(&buf).unwrap();
+        assert_eq!(parsed.time_out, Some(30));
+        assert_eq!(
+            parsed.sockets["Listeners"],
+            SocketConfig::unix("/tmp/ajam.sock")
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_preserved() {
+        let source = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>co.myrt.ajam</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>ajam</string>
+    </array>
+    <key>StandardOutPath</key>
+    <string>/dev/null</string>
+    <key>StandardErrorPath</key>
+    <string>/dev/null</string>
+    <key>KeepAlive</key>
+    <false/>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>ProcessType</key>
+    <string>background</string>
+    <key>Nice</key>
+    <integer>5</integer>
+    <key>Umask</key>
+    <integer>18</integer>
+    <key>EnvironmentVariables</key>
+    <dict>
+        <key>PATH</key>
+        <string>/usr/bin</string>
+    </dict>
+</dict>
+</plist>"#;
+
+        let agent: LaunchAgent = plist::from_bytes(source.as_bytes()).unwrap();
+        assert!(agent.run_at_load);
+        assert_eq!(agent.nice, Some(5));
+        assert_eq!(agent.environment_variables.get("PATH"), Some("/usr/bin"));
+        assert_eq!(agent.extra.len(), 1);
+        assert_eq!(
+            agent
+                .extra
+                .get("Umask")
+                .and_then(plist::Value::as_signed_integer),
+            Some(18)
+        );
+
+        let mut buf = Vec::new();
+        agent.to_writer(&mut buf).unwrap();
+        let written = String::from_utf8(buf).unwrap();
+        assert!(written.contains("<key>Nice</key>"));
+        assert!(written.contains("<key>Umask</key>"));
+        assert!(written.contains("<key>EnvironmentVariables</key>"));
+        assert!(written.contains("<string>/usr/bin</string>"));
+    }
+
+    #[test]
+    fn test_missing_keys_use_defaults() {
+        let source = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>com.vendor.agent</string>
+    <key>Program</key>
+    <string>/usr/local/bin/agent</string>
+    <key>ProcessType</key>
+    <string>Interactive</string>
+</dict>
+</plist>"#;
+
+        let agent: LaunchAgent = plist::from_bytes(source.as_bytes()).unwrap();
+        assert_eq!(agent.label, "com.vendor.agent");
+        assert!(agent.program_arguments.is_empty());
+        assert_eq!(agent.standard_out_path, PathBuf::from(DEV_NULL));
+        assert!(!agent.keep_alive.is_enabled());
+        assert_eq!(agent.process_type, ProcessType::Interactive);
+        assert!(agent.extra.contains_key("Program"));
+    }
 }
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::time::SystemTime;
+
+use crate::agent::LaunchAgent;
+use crate::stats::CrashLoop;
+
+/// Condition a [`Supervisor`](crate::Supervisor) raises an [`Alert`] for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AlertKind {
+    /// The agent should be running but is not.
+    Stopped,
+    /// The agent keeps exiting and being respawned.
+    CrashLoop,
+    /// The agent is running but does not pass its health check.
+    HealthCheckFailed,
+    /// The user disabled the agent, e.g. in Login Items settings.
+    Disabled,
+}
+
+/// Failure of a supervised agent, with the context needed to report it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub label: String,
+    /// Human-readable description of the agent, if it has one.
+    pub description: Option<String>,
+    pub time: SystemTime,
+    pub last_exit_code: Option<i32>,
+    /// Details of the crash loop for [`AlertKind::CrashLoop`].
+    pub crash_loop: Option<CrashLoop>,
+}
+
+impl Alert {
+    pub(crate) fn new(kind: AlertKind, agent: &LaunchAgent) -> Self {
+        Self {
+            kind,
+            label: agent.label.clone(),
+            description: agent.description().map(ToString::to_string),
+            time: SystemTime::now(),
+            last_exit_code: None,
+            crash_loop: None,
+        }
+    }
+
+    /// Name of the agent for messages, its description if it has one.
+    pub fn name(&self) -> &str {
+        self.description.as_deref().unwrap_or(&self.label)
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.name();
+        match self.kind {
+            AlertKind::Stopped => write!(f, "{name} stopped")?,
+            AlertKind::CrashLoop => write!(f, "{name} keeps crashing")?,
+            AlertKind::HealthCheckFailed => {
+                write!(f, "{name} does not respond")?;
+            }
+            AlertKind::Disabled => write!(f, "{name} was disabled")?,
+        }
+        if let Some(code) = self.last_exit_code {
+            write!(f, " (last exit code {code})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Receives the alerts of a [`Supervisor`](crate::Supervisor), e.g. to show a
+/// notification or forward them to an alerting pipeline.
+///
+/// Alerts are raised once when the condition starts and again only after it
+/// cleared. Sinks are called from the thread running the supervisor.
+pub trait AlertSink: Send + Sync {
+    fn alert(&self, alert: &Alert);
+}
+
+impl<F> AlertSink for F
+where
+    F: Fn(&Alert) + Send + Sync,
+{
+    fn alert(&self, alert: &Alert) {
+        self(alert);
+    }
+}
+
+/// Sink logging alerts as warnings to the `lunchctl` log target.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+impl AlertSink for LogSink {
+    fn alert(&self, alert: &Alert) {
+        log::warn!(target: "lunchctl", "{alert} [{}]", alert.label);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+
+    #[test]
+    fn test_display() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .description("Ajam sync helper")
+            .build()
+            .unwrap();
+        let mut alert = Alert::new(AlertKind::CrashLoop, &agent);
+        alert.last_exit_code = Some(78);
+        assert_eq!(
+            alert.to_string(),
+            "Ajam sync helper keeps crashing (last exit code 78)"
+        );
+
+        let alert =
+            Alert::new(AlertKind::Disabled, &LaunchAgent::new("co.myrt.ajam"));
+        assert_eq!(alert.to_string(), "co.myrt.ajam was disabled");
+    }
+}
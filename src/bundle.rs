@@ -0,0 +1,196 @@
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::agent::LaunchAgent;
+use crate::wrapper::program_index;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Extra plist key holding the program path relative to the app bundle,
+/// see [`ProgramLocation::BundleRelative`].
+pub const BUNDLE_PROGRAM_KEY: &str = "co.myrt.lunchctl.BundleRelativeProgram";
+
+/// Where the agent's program is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgramLocation {
+    Absolute(PathBuf),
+    /// Path inside the current app bundle, e.g. `Contents/MacOS/helper`,
+    /// see [`current_app_bundle`]. If the program went missing, it is
+    /// resolved again whenever the plist is written, so `ensure_installed()`
+    /// repairs the agent after the app moved.
+    BundleRelative(PathBuf),
+}
+
+/// Returns the app bundle the running executable belongs to. For helpers
+/// nested in another app, such as login items, the outermost bundle is
+/// returned.
+pub fn current_app_bundle() -> Option<PathBuf> {
+    let executable = std::env::current_exe().ok()?;
+    app_bundle_of(&executable)
+}
+
+/// Returns the outermost app bundle containing `path`.
+pub(crate) fn app_bundle_of(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .filter(|dir| dir.extension().is_some_and(|ext| ext == "app"))
+        .last()
+        .map(Path::to_path_buf)
+}
+
+impl LaunchAgent {
+    /// Set the program, replacing the first program argument, or the
+    /// program inside the `/bin/sh` wrappers of e.g.
+    /// [`set_ac_power_only`](Self::set_ac_power_only), and the `Program`
+    /// key if it is set.
+    ///
+    /// Fails with [`NoAppBundle`](LaunchAgentError::NoAppBundle) for a
+    /// [`ProgramLocation::BundleRelative`] program if the running executable
+    /// is not in an app bundle, e.g. a command line tool.
+    pub fn set_program(&mut self, location: ProgramLocation) -> LaunchctlResult<()> {
+        let program = match location {
+            ProgramLocation::Absolute(path) => {
+                self.extra.remove(BUNDLE_PROGRAM_KEY);
+                path
+            }
+            ProgramLocation::BundleRelative(relative) => {
+                let program = bundle_program(&relative)?;
+                set_bundle_program_key(&mut self.extra, &relative);
+                program
+            }
+        };
+        if let Some(plist::Value::String(key)) = self.extra.get_mut("Program") {
+            *key = program.display().to_string();
+        }
+        set_program_argument(&mut self.program_arguments, program);
+        Ok(())
+    }
+
+    /// Returns the program path relative to the app bundle, if the program
+    /// was set as [`ProgramLocation::BundleRelative`].
+    pub fn bundle_relative_program(&self) -> Option<&Path> {
+        self.extra
+            .get(BUNDLE_PROGRAM_KEY)
+            .and_then(plist::Value::as_string)
+            .map(Path::new)
+    }
+
+    /// Returns the configuration with a bundle-relative program resolved
+    /// against the current app bundle.
+    pub(crate) fn resolved(&self) -> Cow<'_, Self> {
+        self.resolved_in(current_app_bundle().as_deref())
+    }
+
+    /// Returns the configuration with a bundle-relative program resolved
+    /// against `bundle`.
+    ///
+    /// Only a program that went missing is resolved, and only if `bundle`
+    /// has it. A process running from another bundle, such as an updater,
+    /// would otherwise point the agent at itself.
+    pub(crate) fn resolved_in(&self, bundle: Option<&Path>) -> Cow<'_, Self> {
+        let index = program_index(&self.program_arguments);
+        let current = self.program_arguments.get(index).map(Path::new);
+        let program = self
+            .bundle_relative_program()
+            .zip(bundle)
+            .map(|(relative, bundle)| bundle.join(relative))
+            .filter(|program| {
+                program.exists() && !current.is_some_and(Path::exists)
+            });
+        let Some(program) = program else {
+            return Cow::Borrowed(self);
+        };
+        let mut agent = self.clone();
+        set_program_argument(&mut agent.program_arguments, program);
+        Cow::Owned(agent)
+    }
+}
+
+/// Returns the path of `relative` inside the current app bundle.
+pub(crate) fn bundle_program(relative: &Path) -> LaunchctlResult<PathBuf> {
+    current_app_bundle()
+        .map(|bundle| bundle.join(relative))
+        .ok_or_else(|| LaunchAgentError::NoAppBundle(relative.to_path_buf()))
+}
+
+pub(crate) fn set_bundle_program_key(
+    extra: &mut plist::Dictionary,
+    relative: &Path,
+) {
+    extra.insert(
+        BUNDLE_PROGRAM_KEY.to_string(),
+        relative.display().to_string().into(),
+    );
+}
+
+/// Replace the program, past the wrappers of the arguments.
+fn set_program_argument(args: &mut Vec<OsString>, program: PathBuf) {
+    let index = program_index(args);
+    match args.get_mut(index) {
+        Some(current) => *current = program.into_os_string(),
+        None => args.push(program.into_os_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+
+    #[test]
+    fn test_app_bundle_of() {
+        assert_eq!(
+            app_bundle_of(Path::new(
+                "/Applications/Ajam.app/Contents/Library/LoginItems/Helper.app/Contents/MacOS/helper"
+            )),
+            Some(PathBuf::from("/Applications/Ajam.app"))
+        );
+        assert_eq!(app_bundle_of(Path::new("/usr/local/bin/ajam")), None);
+    }
+
+    #[test]
+    fn test_bundle_relative_program() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        let relative = PathBuf::from("Contents/MacOS/helper");
+        // Tests do not run from an app bundle.
+        assert!(matches!(
+            agent.set_program(ProgramLocation::BundleRelative(relative.clone())),
+            Err(LaunchAgentError::NoAppBundle(_))
+        ));
+        assert!(LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .bundle_program(&relative)
+            .build()
+            .is_err());
+
+        let dir = std::env::temp_dir().join(format!(
+            "lunchctl-bundle-{}",
+            rand::random_range(0..u32::MAX)
+        ));
+        let (old, new) = (dir.join("old/Ajam.app"), dir.join("new/Ajam.app"));
+        std::fs::create_dir_all(new.join("Contents/MacOS")).unwrap();
+        std::fs::write(new.join(&relative), "").unwrap();
+        agent.program_arguments = vec![old.join(&relative).into()];
+        set_bundle_program_key(&mut agent.extra, &relative);
+        assert_eq!(agent.bundle_relative_program(), Some(relative.as_path()));
+
+        agent.set_ac_power_only(true);
+        let moved = agent.resolved_in(Some(&new));
+        assert!(moved.is_ac_power_only());
+        assert_eq!(moved.program_arguments[3], new.join(&relative));
+        assert!(matches!(moved.resolved_in(Some(&new)), Cow::Borrowed(_)));
+        // The program exists, so another bundle does not take it over.
+        let other = dir.join("other/Updater.app");
+        std::fs::create_dir_all(other.join("Contents/MacOS")).unwrap();
+        std::fs::write(other.join(&relative), "").unwrap();
+        assert!(matches!(moved.resolved_in(Some(&other)), Cow::Borrowed(_)));
+        assert!(matches!(agent.resolved_in(None), Cow::Borrowed(_)));
+
+        agent
+            .set_program(ProgramLocation::Absolute(PathBuf::from("/usr/bin/true")))
+            .unwrap();
+        agent.set_ac_power_only(false);
+        assert_eq!(agent.bundle_relative_program(), None);
+        assert_eq!(agent.program_arguments, ["/usr/bin/true"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
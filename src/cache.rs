@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::info::ServiceInfo;
+use crate::LaunchctlResult;
+
+/// `launchctl print` output of an agent, reused until it is older than the
+/// TTL or [`refresh`](Self::refresh) is called.
+///
+/// Querying the state, process ID and last exit code separately runs
+/// launchctl once per query. Within one operation, such as rendering a
+/// status line, a cache runs it once.
+pub struct CachedInfo<'a> {
+    agent: &'a LaunchAgent,
+    context: LaunchctlContext,
+    ttl: Duration,
+    cached: Option<(Instant, Option<ServiceInfo>)>,
+}
+
+impl LaunchAgent {
+    /// Returns a cache of the agent's `launchctl print` output that is
+    /// queried again once it is older than `ttl`.
+    pub fn cached_info(&self, ttl: Duration) -> CachedInfo<'_> {
+        LaunchctlContext::default().cached_info(self, ttl)
+    }
+}
+
+impl LaunchctlContext {
+    /// Returns a cache of the agent's state in the context's domain, see
+    /// [`LaunchAgent::cached_info`].
+    pub fn cached_info<'a>(
+        &self,
+        agent: &'a LaunchAgent,
+        ttl: Duration,
+    ) -> CachedInfo<'a> {
+        CachedInfo {
+            agent,
+            context: self.clone(),
+            ttl,
+            cached: None,
+        }
+    }
+}
+
+impl CachedInfo<'_> {
+    /// Returns the agent's state, `None` if the agent is not loaded.
+    /// launchctl runs only if the cached state is missing or expired.
+    pub fn get(&mut self) -> LaunchctlResult<Option<&ServiceInfo>> {
+        let expired = self
+            .cached
+            .as_ref()
+            .map_or(true, |(fetched, _)| fetched.elapsed() >= self.ttl);
+        if expired {
+            self.refresh()?;
+        }
+        Ok(self.cached.as_ref().and_then(|(_, info)| info.as_ref()))
+    }
+
+    /// Query the agent's state now, replacing the cached one.
+    pub fn refresh(&mut self) -> LaunchctlResult<()> {
+        let info = self.context.info(self.agent)?;
+        self.cached = Some((Instant::now(), info));
+        Ok(())
+    }
+
+    /// Drop the cached state so the next query runs launchctl.
+    pub fn invalidate(&mut self) {
+        self.cached = None;
+    }
+
+    pub fn is_loaded(&mut self) -> LaunchctlResult<bool> {
+        Ok(self.get()?.is_some())
+    }
+
+    pub fn is_running(&mut self) -> LaunchctlResult<bool> {
+        Ok(self
+            .get()?
+            .is_some_and(|info| info.state() == Some("running")))
+    }
+
+    pub fn pid(&mut self) -> LaunchctlResult<Option<u32>> {
+        Ok(self.get()?.and_then(ServiceInfo::pid))
+    }
+
+    /// Returns the last exit code, `None` if the agent never exited or is not
+    /// loaded.
+    pub fn last_exit_code(&mut self) -> LaunchctlResult<Option<i32>> {
+        Ok(self.get()?.and_then(ServiceInfo::last_exit_code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_info_expires() {
+        let agent = LaunchAgent::new("co.myrt.ajam.cache.missing");
+        let context = LaunchctlContext::idle();
+        let mut info = context.cached_info(&agent, Duration::from_secs(60));
+        assert!(!info.is_loaded().unwrap());
+        let fetched = info.cached.as_ref().unwrap().0;
+        assert_eq!(info.pid().unwrap(), None);
+        assert_eq!(info.cached.as_ref().unwrap().0, fetched);
+
+        info.invalidate();
+        assert!(info.cached.is_none());
+        assert!(!info.is_running().unwrap());
+        assert!(info.cached.is_some());
+
+        let mut info = context.cached_info(&agent, Duration::ZERO);
+        info.refresh().unwrap();
+        let fetched = info.cached.as_ref().unwrap().0;
+        assert_eq!(info.last_exit_code().unwrap(), None);
+        assert!(info.cached.as_ref().unwrap().0 > fetched);
+    }
+}
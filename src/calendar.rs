@@ -0,0 +1,280 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use plist::{Dictionary, Value};
+
+use crate::agent::LaunchAgent;
+
+/// Days searched for the next run before giving up, enough to reach the
+/// next February 29 on a given weekday.
+const SEARCH_DAYS: i32 = 366 * 8;
+
+/// Entry of `StartCalendarInterval`. Fields that are `None` match any value,
+/// like `*` in crontab.
+///
+/// launchd evaluates entries in local time. As in cron, an entry with both
+/// `day` and `weekday` matches days satisfying either.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CalendarInterval {
+    pub minute: Option<u8>,
+    pub hour: Option<u8>,
+    /// Day of the month, 1 to 31.
+    pub day: Option<u8>,
+    /// Day of the week, 0 or 7 for Sunday.
+    pub weekday: Option<u8>,
+    /// Month, 1 to 12.
+    pub month: Option<u8>,
+}
+
+impl CalendarInterval {
+    /// Parse an entry from its plist dictionary, ignoring unknown keys.
+    pub fn from_dictionary(dictionary: &Dictionary) -> Self {
+        let field = |key: &str| {
+            dictionary
+                .get(key)
+                .and_then(Value::as_unsigned_integer)
+                .and_then(|value| u8::try_from(value).ok())
+        };
+        Self {
+            minute: field("Minute"),
+            hour: field("Hour"),
+            day: field("Day"),
+            weekday: field("Weekday"),
+            month: field("Month"),
+        }
+    }
+
+    /// Returns the plist dictionary of the entry.
+    pub fn to_dictionary(&self) -> Dictionary {
+        let mut dictionary = Dictionary::new();
+        let fields = [
+            ("Minute", self.minute),
+            ("Hour", self.hour),
+            ("Day", self.day),
+            ("Weekday", self.weekday),
+            ("Month", self.month),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                dictionary.insert(key.to_string(), u64::from(value).into());
+            }
+        }
+        dictionary
+    }
+
+    /// Returns the first time after `after` the entry matches, `None` if it
+    /// never does.
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let after =
+            libc::time_t::try_from(after.duration_since(UNIX_EPOCH).ok()?.as_secs())
+                .ok()?;
+        let start = local_time(after)?;
+        let hours: Vec<i32> =
+            self.hour.map_or((0..24).collect(), |h| vec![h.into()]);
+        let minutes: Vec<i32> =
+            self.minute.map_or((0..60).collect(), |m| vec![m.into()]);
+
+        for offset in 0..SEARCH_DAYS {
+            let mut date = start;
+            date.tm_mday += offset;
+            date.tm_hour = 12;
+            date.tm_min = 0;
+            date.tm_sec = 0;
+            make_time(&mut date)?;
+            if !self.matches_date(&date) {
+                continue;
+            }
+            for &hour in &hours {
+                for &minute in &minutes {
+                    let mut time = date;
+                    time.tm_hour = hour;
+                    time.tm_min = minute;
+                    let seconds = make_time(&mut time)?;
+                    if seconds > after {
+                        let seconds = u64::try_from(seconds).ok()?;
+                        return Some(UNIX_EPOCH + Duration::from_secs(seconds));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_date(&self, date: &libc::tm) -> bool {
+        let month = self
+            .month
+            .map_or(true, |month| i32::from(month) == date.tm_mon + 1);
+        let day = self.day.map(|day| i32::from(day) == date.tm_mday);
+        let weekday = self
+            .weekday
+            .map(|weekday| i32::from(weekday % 7) == date.tm_wday);
+        let day = match (day, weekday) {
+            (Some(day), Some(weekday)) => day || weekday,
+            (day, weekday) => day.or(weekday).unwrap_or(true),
+        };
+        month && day
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the entries of `StartCalendarInterval`, a dictionary or an
+    /// array of them.
+    pub fn calendar_intervals(&self) -> Vec<CalendarInterval> {
+        match self.extra.get("StartCalendarInterval") {
+            Some(Value::Dictionary(entry)) => {
+                vec![CalendarInterval::from_dictionary(entry)]
+            }
+            Some(Value::Array(entries)) => entries
+                .iter()
+                .filter_map(Value::as_dictionary)
+                .map(CalendarInterval::from_dictionary)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns when launchd next starts the agent by its calendar schedule,
+    /// `None` if it has none.
+    pub fn next_calendar_run(&self, after: SystemTime) -> Option<SystemTime> {
+        self.calendar_intervals()
+            .iter()
+            .filter_map(|interval| interval.next_after(after))
+            .min()
+    }
+}
+
+/// How daylight saving time affects a local time of day over the next year.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct DstAnomaly {
+    /// The time does not exist on the day clocks go forward.
+    pub(crate) skipped: bool,
+    /// The time occurs twice on the day clocks go back.
+    pub(crate) repeated: bool,
+}
+
+/// Check the local time `hour:minute` on every day of the next year.
+pub(crate) fn dst_anomaly(hour: u8, minute: u8) -> DstAnomaly {
+    let mut anomaly = DstAnomaly::default();
+    let Some(now) = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|now| libc::time_t::try_from(now.as_secs()).ok())
+    else {
+        return anomaly;
+    };
+    let Some(start) = local_time(now) else {
+        return anomaly;
+    };
+    let is_wall_time = |tm: &libc::tm| {
+        tm.tm_hour == i32::from(hour) && tm.tm_min == i32::from(minute)
+    };
+
+    for offset in 0..366 {
+        let mut time = start;
+        time.tm_mday += offset;
+        time.tm_hour = hour.into();
+        time.tm_min = minute.into();
+        time.tm_sec = 0;
+        let Some(seconds) = make_time(&mut time) else {
+            continue;
+        };
+        if !is_wall_time(&time) {
+            anomaly.skipped = true;
+            continue;
+        }
+        anomaly.repeated |= [-3600, -1800, 1800, 3600]
+            .iter()
+            .filter_map(|shift| local_time(seconds + shift))
+            .any(|tm| is_wall_time(&tm));
+    }
+    anomaly
+}
+
+/// Returns the broken-down local time of a Unix timestamp.
+fn local_time(seconds: libc::time_t) -> Option<libc::tm> {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::localtime_r(&seconds, &mut tm) };
+    (!result.is_null()).then_some(tm)
+}
+
+/// Normalize a local time and return its Unix timestamp. Whether daylight
+/// saving time applies is determined from the time itself.
+fn make_time(tm: &mut libc::tm) -> Option<libc::time_t> {
+    tm.tm_isdst = -1;
+    let seconds = unsafe { libc::mktime(tm) };
+    (seconds != -1).then_some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_after() {
+        let interval = CalendarInterval {
+            minute: Some(30),
+            hour: Some(3),
+            ..CalendarInterval::default()
+        };
+        let now = SystemTime::now();
+        let next = interval.next_after(now).unwrap();
+        assert!(next > now);
+        assert!(next.duration_since(now).unwrap() <= Duration::from_secs(25 * 3600));
+
+        let seconds = next.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let tm = local_time(libc::time_t::try_from(seconds).unwrap()).unwrap();
+        assert_eq!((tm.tm_hour, tm.tm_min, tm.tm_sec), (3, 30, 0));
+        assert_eq!(
+            interval.next_after(next - Duration::from_secs(1)),
+            Some(next)
+        );
+    }
+
+    #[test]
+    fn test_weekday_and_impossible_dates() {
+        let sunday = CalendarInterval {
+            minute: Some(0),
+            hour: Some(12),
+            weekday: Some(7),
+            ..CalendarInterval::default()
+        };
+        let next = sunday.next_after(SystemTime::now()).unwrap();
+        let seconds = next.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        assert_eq!(
+            local_time(libc::time_t::try_from(seconds).unwrap())
+                .unwrap()
+                .tm_wday,
+            0
+        );
+
+        let never = CalendarInterval {
+            day: Some(31),
+            month: Some(2),
+            ..CalendarInterval::default()
+        };
+        assert_eq!(never.next_after(SystemTime::now()), None);
+    }
+
+    #[test]
+    fn test_calendar_intervals() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        assert!(agent.calendar_intervals().is_empty());
+        assert_eq!(agent.next_calendar_run(SystemTime::now()), None);
+
+        let mut entry = Dictionary::new();
+        entry.insert("Hour".to_string(), 9.into());
+        entry.insert("Weekday".to_string(), 1.into());
+        agent.extra.insert(
+            "StartCalendarInterval".to_string(),
+            Value::Array(vec![entry.into()]),
+        );
+        assert_eq!(
+            agent.calendar_intervals(),
+            [CalendarInterval {
+                hour: Some(9),
+                weekday: Some(1),
+                ..CalendarInterval::default()
+            }]
+        );
+        assert!(agent.next_calendar_run(SystemTime::now()).is_some());
+    }
+}
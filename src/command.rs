@@ -0,0 +1,209 @@
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::os::unix::ffi::OsStrExt;
+use std::process::Command;
+
+use crate::os::run_raw;
+use crate::telemetry::DEFAULT_SLOW_CALL_THRESHOLD;
+use crate::LaunchctlResult;
+
+/// `PATH` of commands run by this crate.
+pub const COMMAND_PATH: &str = "/usr/bin:/bin:/usr/sbin:/sbin";
+
+/// Give the command the environment and working directory of commands run
+/// by this crate.
+///
+/// Unless `inherit_environment` is set, commands run with an empty
+/// environment except for `PATH`, set to [`COMMAND_PATH`], so variables like
+/// `DYLD_INSERT_LIBRARIES` or a `PATH` shadowing `launchctl` set up by the
+/// embedding app do not change their behavior. The working directory is `/`
+/// either way.
+pub(crate) fn harden(command: &mut Command, inherit_environment: bool) {
+    if !inherit_environment {
+        command.env_clear().env("PATH", COMMAND_PATH);
+    }
+    command.current_dir("/");
+}
+
+/// Command this crate runs, as program and arguments.
+///
+/// Every launchctl invocation can be previewed before it runs, e.g. to log
+/// it or to show users a command they can copy and run manually. `Display`
+/// renders it as a shell command line with arguments quoted where needed.
+///
+/// The command runs with the arguments as they are, without a shell, so
+/// paths that are not valid UTF-8 reach launchctl unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<OsString>,
+}
+
+impl CommandSpec {
+    pub fn new<I, S>(program: &str, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        Self {
+            program: program.to_string(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// `launchctl` with the given arguments.
+    pub fn launchctl<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        Self::new("launchctl", args)
+    }
+
+    /// Returns a [`Command`] ready to run the program with the arguments, in
+    /// the environment this crate runs commands in.
+    pub fn to_command(&self) -> Command {
+        self.command(false)
+    }
+
+    /// Same as [`to_command`](Self::to_command), keeping the environment of
+    /// the process if `inherit_environment` is set.
+    pub(crate) fn command(&self, inherit_environment: bool) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        harden(&mut command, inherit_environment);
+        command
+    }
+
+    /// Run the command the way this crate does and capture its output
+    /// unchanged. Fails only if the command cannot be started.
+    pub fn output(&self) -> LaunchctlResult<CommandOutput> {
+        run_raw(self, DEFAULT_SLOW_CALL_THRESHOLD)
+    }
+}
+
+/// Exit code and unmodified output of a command run by [`CommandSpec::output`].
+///
+/// launchctl prints paths as they are, which need not be valid UTF-8. The
+/// bytes are kept so such paths survive; the `_lossy` accessors are for
+/// display and text parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// Exit code, `-1` if the process was killed by a signal.
+    pub code: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.code == 0
+    }
+
+    /// Returns stdout with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn stdout_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    /// Returns stderr with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn stderr_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+
+    /// Returns the value of a `key = value` line of stdout, e.g. a path in
+    /// `launchctl print` output, without decoding it.
+    pub fn stdout_value(&self, key: &str) -> Option<&OsStr> {
+        let prefix = format!("{key} = ");
+        self.stdout
+            .split(|&byte| byte == b'\n')
+            .map(<[u8]>::trim_ascii)
+            .find_map(|line| line.strip_prefix(prefix.as_bytes()))
+            .map(OsStr::from_bytes)
+    }
+}
+
+impl fmt::Display for CommandSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", quote(&self.program))?;
+        for arg in &self.args {
+            write!(f, " {}", quote(&arg.to_string_lossy()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote a word for POSIX shells unless it is made of safe characters only.
+fn quote(word: &str) -> String {
+    let safe = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c));
+    if safe {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let spec = CommandSpec::launchctl([
+            "bootstrap",
+            "gui/501",
+            "/Users/me/Library/LaunchAgents/co.myrt.ajam.plist",
+        ]);
+        assert_eq!(
+            spec.to_string(),
+            "launchctl bootstrap gui/501 /Users/me/Library/LaunchAgents/co.myrt.ajam.plist"
+        );
+
+        let spec = CommandSpec::new("echo", ["it's here", "", "a b"]);
+        assert_eq!(spec.to_string(), r"echo 'it'\''s here' '' 'a b'");
+    }
+
+    #[test]
+    fn test_output_keeps_bytes() {
+        let spec = CommandSpec::new("printf", [r"\tpath = /tmp/caf\351\n"]);
+        let output = spec.output().unwrap();
+        assert!(output.success());
+        assert_eq!(output.stdout, b"\tpath = /tmp/caf\xe9\n");
+        assert_eq!(output.stdout_lossy(), "\tpath = /tmp/caf\u{fffd}\n");
+        assert_eq!(
+            output.stdout_value("path").unwrap().as_bytes(),
+            b"/tmp/caf\xe9"
+        );
+        assert_eq!(output.stdout_value("program"), None);
+    }
+
+    #[test]
+    fn test_arguments_are_passed_unchanged() {
+        let path = OsStr::from_bytes(b"/tmp/caf\xe9 $HOME;");
+        let spec = CommandSpec::new("printf", [OsStr::new("%s"), path]);
+        assert_eq!(spec.output().unwrap().stdout, path.as_bytes());
+    }
+
+    #[test]
+    fn test_to_command() {
+        let spec = CommandSpec::new("echo", ["a b"]);
+        let output = spec.to_command().output().unwrap();
+        assert_eq!(output.stdout, b"a b\n");
+    }
+
+    #[test]
+    fn test_environment_is_pinned() {
+        let spec =
+            CommandSpec::new("sh", ["-c", "echo \"$PATH|$DYLD_LIBRARY_PATH\"; pwd"]);
+        let mut command = spec.to_command();
+        command.env("DYLD_LIBRARY_PATH", "/tmp/evil");
+        harden(&mut command, false);
+        let output = command.output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("{COMMAND_PATH}|\n/\n")
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::path::Path;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::domain::DomainTarget;
+use crate::find::LabelLocation;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+impl LaunchAgent {
+    /// Check that no other plist or loaded service uses the agent's label.
+    ///
+    /// The agent's own plist and its registration in the current user's GUI
+    /// domain are not conflicts. Returns [`LaunchAgentError::LabelConflict`] listing every
+    /// other place the label was found.
+    pub fn check_conflicts(&self) -> LaunchctlResult<()> {
+        LaunchctlContext::default().check_conflicts(self)
+    }
+
+    fn conflicts_among(
+        locations: Vec<LabelLocation>,
+        own_path: &Path,
+        own_domain: DomainTarget,
+    ) -> Vec<LabelLocation> {
+        locations
+            .into_iter()
+            .filter(|location| match location {
+                LabelLocation::Plist { path, .. }
+                | LabelLocation::OtherPlist(path) => path != own_path,
+                LabelLocation::Loaded(domain) => *domain != own_domain,
+            })
+            .collect()
+    }
+}
+
+impl LaunchctlContext {
+    /// Check that no other plist or loaded service uses the agent's label,
+    /// see [`LaunchAgent::check_conflicts`]. The agent's plist in the
+    /// context's directory and its registration in the context's domain
+    /// are not conflicts.
+    pub fn check_conflicts(&self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        let path = self.path(agent)?;
+        let locations = self.find(&agent.label)?;
+        let conflicts = LaunchAgent::conflicts_among(locations, &path, self.domain);
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        Err(LaunchAgentError::LabelConflict(
+            agent.label.clone(),
+            conflicts,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::paths::LaunchDirectory;
+
+    fn foreign_path() -> PathBuf {
+        PathBuf::from("/Library/LaunchDaemons/co.myrt.ajam.plist")
+    }
+
+    #[test]
+    fn test_conflicts_among() {
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        let foreign = LabelLocation::Plist {
+            directory: LaunchDirectory::GlobalDaemons,
+            path: foreign_path(),
+        };
+        let locations = vec![
+            LabelLocation::Plist {
+                directory: LaunchDirectory::UserAgents,
+                path: agent.path().unwrap(),
+            },
+            LabelLocation::Loaded(DomainTarget::Gui(501)),
+            foreign.clone(),
+            LabelLocation::Loaded(DomainTarget::System),
+        ];
+
+        assert_eq!(
+            LaunchAgent::conflicts_among(
+                locations.clone(),
+                &agent.path().unwrap(),
+                DomainTarget::Gui(501)
+            ),
+            vec![foreign, LabelLocation::Loaded(DomainTarget::System)]
+        );
+        assert_eq!(
+            LaunchAgent::conflicts_among(
+                locations,
+                &foreign_path(),
+                DomainTarget::System
+            ),
+            vec![
+                LabelLocation::Plist {
+                    directory: LaunchDirectory::UserAgents,
+                    path: agent.path().unwrap(),
+                },
+                LabelLocation::Loaded(DomainTarget::Gui(501)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conflict_message() {
+        let err = LaunchAgentError::LabelConflict(
+            "co.myrt.ajam".to_string(),
+            vec![
+                LabelLocation::Plist {
+                    directory: LaunchDirectory::GlobalDaemons,
+                    path: PathBuf::from("/Library/LaunchDaemons/x.plist"),
+                },
+                LabelLocation::Loaded(DomainTarget::System),
+            ],
+        );
+        assert_eq!(
+            err.to_string(),
+            "Label 'co.myrt.ajam' is already in use: \
+             plist /Library/LaunchDaemons/x.plist, loaded in system"
+        );
+    }
+}
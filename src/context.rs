@@ -0,0 +1,1153 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::agent::{ensure_unprotected, write_atomically, LaunchAgent};
+use crate::command::{CommandOutput, CommandSpec};
+use crate::control::BootstrapOutcome;
+use crate::diagnose::Finding;
+use crate::dirs::{crate_state_dir, create_private_dir};
+use crate::failure::LaunchctlFailure;
+use crate::domain::{DomainTarget, ServiceTarget};
+use crate::info::ServiceInfo;
+use crate::lint::LintConfig;
+use crate::lock::{AgentLock, DEFAULT_LOCK_TIMEOUT};
+use crate::os::{checked, command_error, get_user_id, spawn};
+use crate::paths::LaunchDirectory;
+use crate::permissions::PLIST_MODE;
+use crate::poll::{poll, PollOptions};
+use crate::report::WriteReport;
+use crate::spawned::{is_interactive, needs_as_user};
+use crate::telemetry::{record_call, DEFAULT_SLOW_CALL_THRESHOLD};
+use crate::write_policy::WritePolicy;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Runs the commands of a [`LaunchctlContext`].
+///
+/// Implement it to run commands elsewhere, e.g. through a privileged helper,
+/// or to return canned output in tests. Runners that ask the user for
+/// authorization should return [`LaunchAgentError::ElevationDeclined`] when
+/// the user cancels, so callers can tell it apart from a failed command.
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, command: &CommandSpec) -> LaunchctlResult<CommandOutput>;
+}
+
+/// Runner used by default, running commands as child processes of the
+/// calling process.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShellRunner {
+    /// Let commands inherit the environment of the process. By default they
+    /// run with only `PATH` set, to [`COMMAND_PATH`](crate::COMMAND_PATH),
+    /// so variables like `DYLD_INSERT_LIBRARIES` or a `PATH` shadowing
+    /// `launchctl` set up by the embedding app do not change their behavior.
+    pub inherit_environment: bool,
+}
+
+impl CommandRunner for ShellRunner {
+    fn run(&self, command: &CommandSpec) -> LaunchctlResult<CommandOutput> {
+        spawn(command, self.inherit_environment)
+    }
+}
+
+/// Runner answering like a launchd with no services loaded and no
+/// disabled overrides, for tests that must not touch the machine's launchd.
+#[cfg(test)]
+pub(crate) struct IdleRunner;
+
+#[cfg(test)]
+impl CommandRunner for IdleRunner {
+    fn run(&self, command: &CommandSpec) -> LaunchctlResult<CommandOutput> {
+        let (code, stdout, stderr) = match command.args[0].to_str() {
+            Some("print") => (113, "", "Could not find service"),
+            Some("print-disabled") => (0, "disabled services = {\n}\n", ""),
+            _ => (0, "", ""),
+        };
+        Ok(CommandOutput {
+            code,
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        })
+    }
+}
+
+/// How a [`LaunchctlContext`] gains the privileges launchctl needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Elevation {
+    /// Run launchctl as the current user.
+    #[default]
+    None,
+    /// Run launchctl with `sudo -n`, failing instead of asking for a
+    /// password.
+    Sudo,
+    /// Run launchctl with `sudo`, asking for a password on the terminal if
+    /// needed.
+    SudoPrompt,
+}
+
+/// Why elevating a command failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElevationFailure {
+    /// The user cancelled the prompt or did not enter the right password.
+    Declined,
+    /// A password is needed but the elevation may not prompt for it.
+    PasswordRequired,
+    /// The user may not run the command with elevated privileges.
+    NotAllowed,
+}
+
+impl ElevationFailure {
+    /// Classify the exit code and error output of an elevated command.
+    /// Returns `None` if elevation succeeded and the command itself failed.
+    pub fn classify(code: i32, stderr: &str) -> Option<Self> {
+        let stderr = stderr.to_lowercase();
+        if stderr.contains("incorrect password attempt")
+            || stderr.contains("no password was provided")
+            // Interrupted at the prompt.
+            || (code == 130 && stderr.trim().is_empty())
+        {
+            Some(Self::Declined)
+        } else if stderr.contains("a password is required")
+            || stderr.contains("a terminal is required")
+        {
+            Some(Self::PasswordRequired)
+        } else if stderr.contains("not in the sudoers file")
+            || stderr.contains("is not allowed to execute")
+        {
+            Some(Self::NotAllowed)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for ElevationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Declined => "authorization was declined",
+            Self::PasswordRequired => "a password is required",
+            Self::NotAllowed => "not allowed to run commands as root",
+        })
+    }
+}
+
+type CommandHook = Arc<dyn Fn(&CommandSpec) + Send + Sync>;
+
+/// Settings the launchctl operations run with.
+///
+/// Every operation runs through a context. The [`LaunchAgent`] and
+/// [`LaunchControllable`](crate::LaunchControllable) methods use the
+/// default one: `launchctl` from `PATH`, plists in `~/Library/LaunchAgents`,
+/// the current user's GUI domain, [`WritePolicy::OverwriteAlways`] and
+/// commands run by [`ShellRunner`]. A context runs the same operations with
+/// other settings, e.g. for daemons in the system domain or to preview
+/// commands with [`dry_run`](Self::dry_run). [`Manifest`](crate::Manifest),
+/// [`Supervisor`](crate::Supervisor) and
+/// [`LabelNamespace`](crate::LabelNamespace) take one too.
+#[derive(Clone)]
+pub struct LaunchctlContext {
+    launchctl: String,
+    directory: Option<PathBuf>,
+    pub(crate) domain: DomainTarget,
+    runner: Arc<dyn CommandRunner>,
+    elevation: Elevation,
+    as_user: Option<u32>,
+    dry_run: bool,
+    hooks: Vec<CommandHook>,
+    pub(crate) lint: LintConfig,
+    pub(crate) write_policy: WritePolicy,
+    pub(crate) require_ownership: bool,
+    slow_call_threshold: Duration,
+}
+
+impl Default for LaunchctlContext {
+    fn default() -> Self {
+        Self {
+            launchctl: "launchctl".to_string(),
+            directory: None,
+            domain: DomainTarget::current_gui(),
+            runner: Arc::new(ShellRunner::default()),
+            elevation: Elevation::None,
+            as_user: None,
+            dry_run: false,
+            hooks: Vec::new(),
+            lint: LintConfig::default(),
+            write_policy: WritePolicy::default(),
+            require_ownership: false,
+            slow_call_threshold: DEFAULT_SLOW_CALL_THRESHOLD,
+        }
+    }
+}
+
+impl fmt::Debug for LaunchctlContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LaunchctlContext")
+            .field("launchctl", &self.launchctl)
+            .field("directory", &self.directory)
+            .field("domain", &self.domain)
+            .field("elevation", &self.elevation)
+            .field("as_user", &self.as_user)
+            .field("dry_run", &self.dry_run)
+            .field("hooks", &self.hooks.len())
+            .field("lint", &self.lint)
+            .field("write_policy", &self.write_policy)
+            .field("require_ownership", &self.require_ownership)
+            .field("slow_call_threshold", &self.slow_call_threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LaunchctlContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Context running commands against an [`IdleRunner`].
+    #[cfg(test)]
+    pub(crate) fn idle() -> Self {
+        Self::default().runner(IdleRunner)
+    }
+
+    /// Run launchctl from `path` instead of looking it up in `PATH`.
+    #[must_use]
+    pub fn launchctl(mut self, path: &str) -> Self {
+        self.launchctl = path.to_string();
+        self
+    }
+
+    /// Read and write plists in `directory` instead of
+    /// `~/Library/LaunchAgents`.
+    #[must_use]
+    pub fn directory<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Load services into `domain` instead of the current user's GUI domain.
+    #[must_use]
+    pub fn domain(mut self, domain: DomainTarget) -> Self {
+        self.domain = domain;
+        self
+    }
+
+    /// Run commands with `runner`.
+    #[must_use]
+    pub fn runner<R: CommandRunner + 'static>(mut self, runner: R) -> Self {
+        self.runner = Arc::new(runner);
+        self
+    }
+
+    /// Gain privileges with `elevation`. Without a terminal, e.g. when the
+    /// process runs as a launch agent, [`Elevation::SudoPrompt`] fails like
+    /// [`Elevation::Sudo`] instead of waiting for a password.
+    #[must_use]
+    pub fn elevation(mut self, elevation: Elevation) -> Self {
+        self.elevation = elevation;
+        self
+    }
+
+    /// Run launchctl in the session of the user with `uid` through
+    /// `launchctl asuser`, see [`needs_as_user`](crate::needs_as_user).
+    #[must_use]
+    pub fn as_user(mut self, uid: u32) -> Self {
+        self.as_user = Some(uid);
+        self
+    }
+
+    /// Run launchctl through `launchctl asuser` if the calling process is
+    /// outside the session of the context's domain, e.g. because it runs as
+    /// a daemon or a background agent itself.
+    ///
+    /// `asuser` needs root, so it is only used if the process runs as root
+    /// or the context has an [`elevation`](Self::elevation). Otherwise
+    /// commands keep addressing the domain directly.
+    pub fn adapt_to_session(self) -> LaunchctlResult<Self> {
+        let privileged = get_user_id() == 0 || self.elevation != Elevation::None;
+        match self.domain {
+            DomainTarget::Gui(uid) | DomainTarget::User(uid)
+                if privileged && needs_as_user(self.domain)? =>
+            {
+                Ok(self.as_user(uid))
+            }
+            _ => Ok(self),
+        }
+    }
+
+    /// Report commands to the hooks without running them or touching any
+    /// files. Commands then succeed with empty output.
+    #[must_use]
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Check if the context only reports commands, see
+    /// [`dry_run`](Self::dry_run).
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Check if the context runs commands with
+    /// [`elevation`](Self::elevation).
+    pub(crate) fn is_elevated(&self) -> bool {
+        self.elevation != Elevation::None
+    }
+
+    /// Call `hook` with every command before it runs, dry runs included.
+    #[must_use]
+    pub fn on_command<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&CommandSpec) + Send + Sync + 'static,
+    {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Lint agents with `config` in [`lint`](Self::lint).
+    #[must_use]
+    pub fn lint_config(mut self, config: LintConfig) -> Self {
+        self.lint = config;
+        self
+    }
+
+    /// Follow `policy` when writing an agent whose plist exists with
+    /// different contents, [`WritePolicy::OverwriteAlways`] by default.
+    #[must_use]
+    pub fn write_policy(mut self, policy: WritePolicy) -> Self {
+        self.write_policy = policy;
+        self
+    }
+
+    /// Make overwriting, uninstalling and removing plists refuse ones that
+    /// are not managed by this app, see [`LaunchAgent::is_managed`]. Bulk
+    /// removals, such as
+    /// [`LabelNamespace::uninstall_all`](crate::LabelNamespace::uninstall_all),
+    /// skip them instead.
+    #[must_use]
+    pub fn require_ownership(mut self, require: bool) -> Self {
+        self.require_ownership = require;
+        self
+    }
+
+    /// Log launchctl invocations slower than `threshold` as warnings,
+    /// [`DEFAULT_SLOW_CALL_THRESHOLD`] unless changed.
+    ///
+    /// Every invocation is logged at debug level with its duration, so the
+    /// `lunchctl` log target shows where the time of an install goes.
+    #[must_use]
+    pub fn slow_call_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_call_threshold = threshold;
+        self
+    }
+
+    /// Check if the findings of [`lint`](Self::lint) fail the context's
+    /// lint rules.
+    pub fn is_lint_failure(&self, findings: &[Finding]) -> bool {
+        self.lint.is_failure(findings)
+    }
+
+    /// Returns the path of the agent's plist in the context's directory.
+    pub fn path(&self, agent: &LaunchAgent) -> LaunchctlResult<PathBuf> {
+        match &self.directory {
+            Some(directory) => {
+                let stem = agent.file_stem.as_deref().unwrap_or(&agent.label);
+                Ok(directory.join(format!("{stem}.plist")))
+            }
+            None => agent.path(),
+        }
+    }
+
+    /// Returns the directory the context reads and writes plists in.
+    pub(crate) fn agents_directory(&self) -> LaunchctlResult<PathBuf> {
+        match &self.directory {
+            Some(directory) => Ok(directory.clone()),
+            None => Ok(LaunchDirectory::UserAgents.path()?),
+        }
+    }
+
+    /// Returns the agent's service in the context's domain.
+    pub fn service_target(&self, agent: &LaunchAgent) -> ServiceTarget {
+        ServiceTarget::new(self.domain, &agent.label)
+    }
+
+    /// Returns `launchctl` with the arguments, as the context runs it.
+    pub fn command<I, S>(&self, args: I) -> CommandSpec
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let mut command = CommandSpec::new(&self.launchctl, args);
+        if let Some(uid) = self.as_user {
+            let mut args = vec![OsString::from("asuser"), uid.to_string().into()];
+            args.push(command.program.into());
+            args.extend(command.args);
+            command = CommandSpec::new(&self.launchctl, args);
+        }
+        self.elevated(command)
+    }
+
+    /// Returns `command` run with the context's elevation.
+    pub(crate) fn elevated(&self, command: CommandSpec) -> CommandSpec {
+        let mut args = match self.elevation {
+            Elevation::None => return command,
+            Elevation::SudoPrompt if is_interactive() => Vec::new(),
+            Elevation::Sudo | Elevation::SudoPrompt => vec![OsString::from("-n")],
+        };
+        args.push(command.program.into());
+        args.extend(command.args);
+        CommandSpec::new("sudo", args)
+    }
+
+    /// Run a command with the context's runner, unless it is a dry run.
+    ///
+    /// Fails with [`LaunchAgentError::ElevationDeclined`] if the user
+    /// cancels the password prompt and [`LaunchAgentError::ElevationFailed`]
+    /// if the command could not be elevated otherwise.
+    pub fn run(&self, command: &CommandSpec) -> LaunchctlResult<CommandOutput> {
+        for hook in &self.hooks {
+            hook(command);
+        }
+        if self.dry_run {
+            return Ok(CommandOutput {
+                code: 0,
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            });
+        }
+        let started = Instant::now();
+        let output = self.runner.run(command);
+        if let Some(call) = self.launchctl_call(command) {
+            let success = output.as_ref().is_ok_and(CommandOutput::success);
+            record_call(&call, started.elapsed(), success, self.slow_call_threshold);
+        }
+        let output = output?;
+        if self.elevation == Elevation::None || output.success() {
+            return Ok(output);
+        }
+        match ElevationFailure::classify(output.code, &output.stderr_lossy()) {
+            Some(ElevationFailure::Declined) => {
+                Err(LaunchAgentError::ElevationDeclined)
+            }
+            Some(failure) => Err(LaunchAgentError::ElevationFailed(failure)),
+            None => Ok(output),
+        }
+    }
+
+    /// Returns the launchctl invocation `command` runs, as it was before
+    /// [`elevation`](Self::elevation) and [`as_user`](Self::as_user)
+    /// wrapped it. `None` for other commands, such as `install`.
+    fn launchctl_call(&self, command: &CommandSpec) -> Option<CommandSpec> {
+        let mut args = command.args.as_slice();
+        if command.program != self.launchctl {
+            if command.program != "sudo" {
+                return None;
+            }
+            while args.first().is_some_and(|arg| arg == "-n") {
+                args = &args[1..];
+            }
+            let (program, rest) = args.split_first()?;
+            if *program != *self.launchctl {
+                return None;
+            }
+            args = rest;
+        }
+        if let [asuser, _, program, rest @ ..] = args {
+            if asuser == "asuser" && *program == *self.launchctl {
+                args = rest;
+            }
+        }
+        Some(CommandSpec::new(&self.launchctl, args.iter().cloned()))
+    }
+
+    /// Write the agent's plist following the context's write policy, see
+    /// [`LaunchAgent::write`].
+    ///
+    /// With [`elevation`](Self::elevation), the plist is staged in the
+    /// user's state directory and copied into place with `install` run
+    /// through the runner, so it ends up owned by root as launchd requires
+    /// of daemons.
+    pub fn write(&self, agent: &LaunchAgent) -> LaunchctlResult<WriteReport> {
+        let _lock = self.lock(agent)?;
+        self.write_plist(agent)
+    }
+
+    /// Remove the agent's plist, see [`LaunchAgent::remove`].
+    pub fn remove(&self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        let _lock = self.lock(agent)?;
+        self.remove_plist(agent)
+    }
+
+    /// Lock the agent's plist, see [`LaunchAgent::lock`]. Plists written
+    /// with elevation are locked in the user's state directory, since their
+    /// directory is not writable. Dry runs take no lock.
+    pub(crate) fn lock(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<Option<AgentLock>> {
+        if self.dry_run {
+            return Ok(None);
+        }
+        let path = if self.elevation == Elevation::None {
+            AgentLock::path_for(&self.path(agent)?, &agent.label)
+        } else {
+            let dir = crate_state_dir()?.join("locks");
+            create_private_dir(&dir)?;
+            dir.join(format!("{}.lock", agent.label))
+        };
+        AgentLock::acquire(path, DEFAULT_LOCK_TIMEOUT).map(Some)
+    }
+
+    /// Same as [`write`](Self::write), for callers already holding the lock.
+    ///
+    /// A plist of the same agent spelled with another case, see
+    /// [`LaunchAgent::case_collision`], is replaced: the policy and
+    /// ownership requirement decide over it first, its registration is
+    /// booted out, and it is removed once the new plist is written.
+    pub(crate) fn write_plist(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<WriteReport> {
+        let duplicate = if self.directory.is_none() && !self.dry_run {
+            agent.case_duplicate()
+        } else {
+            None
+        };
+        let Some((collision, other)) = duplicate else {
+            return self.write_plist_file(agent);
+        };
+        agent.to_write_over(
+            &collision,
+            self.write_policy,
+            self.require_ownership,
+        )?;
+        if self.is_loaded(&other)? {
+            self.boot_out_target(&other)?;
+        }
+        let report = self.write_plist_file(agent)?;
+        agent.remove_case_collision(&collision, &report.path)?;
+        Ok(report)
+    }
+
+    /// Write the plist to the agent's path, following the policy.
+    fn write_plist_file(&self, agent: &LaunchAgent) -> LaunchctlResult<WriteReport> {
+        let path = self.path(agent)?;
+        let written =
+            agent.to_write_over(&path, self.write_policy, self.require_ownership)?;
+        if self.elevation == Elevation::None && !self.dry_run {
+            return written.write_plist_to(&path);
+        }
+        self.write_plist_bytes(agent, &written.to_bytes()?)
+    }
+
+    /// Write plist contents to the agent's path as they are, e.g. ones
+    /// captured in a [`Snapshot`](crate::Snapshot).
+    ///
+    /// With [`elevation`](Self::elevation), the plist is staged in the
+    /// user's state directory and copied into place with `install` run
+    /// through the runner.
+    pub(crate) fn write_plist_bytes(
+        &self,
+        agent: &LaunchAgent,
+        bytes: &[u8],
+    ) -> LaunchctlResult<WriteReport> {
+        let path = self.path(agent)?;
+        if self.elevation == Elevation::None {
+            if !self.dry_run {
+                write_atomically(&path, |file| Ok(file.write_all(bytes)?))?;
+            }
+            return Ok(WriteReport::new(&path, bytes));
+        }
+
+        ensure_unprotected(&path)?;
+        let staging = crate_state_dir()?.join("staging");
+        let staged = staging.join(format!("{}.plist", agent.label));
+        let install = self.elevated(CommandSpec::new(
+            "/usr/bin/install",
+            [
+                OsString::from("-m"),
+                format!("{PLIST_MODE:o}").into(),
+                staged.clone().into_os_string(),
+                path.clone().into_os_string(),
+            ],
+        ));
+        if self.dry_run {
+            self.run(&install)?;
+            return Ok(WriteReport::new(&path, bytes));
+        }
+        create_private_dir(&staging)?;
+        write_atomically(&staged, |file| Ok(file.write_all(bytes)?))?;
+        let result = self.run_checked(&install);
+        let _ = std::fs::remove_file(&staged);
+        result?;
+        Ok(WriteReport::new(&path, bytes))
+    }
+
+    /// Same as [`remove`](Self::remove), for callers already holding the
+    /// lock.
+    pub(crate) fn remove_plist(&self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        let path = self.path(agent)?;
+        ensure_unprotected(&path)?;
+        self.check_ownership(agent)?;
+        if self.elevation == Elevation::None {
+            if !self.dry_run {
+                std::fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        let remove = CommandSpec::new(
+            "/bin/rm",
+            [OsString::from("-f"), path.into_os_string()],
+        );
+        self.run_checked(&self.elevated(remove))
+    }
+
+    /// Fail with [`LaunchAgentError::NotManaged`] if ownership is required
+    /// and the installed plist is not managed by this app.
+    pub(crate) fn check_ownership(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<()> {
+        if !self.require_ownership {
+            return Ok(());
+        }
+        let path = self.path(agent)?;
+        match agent.read_installed_at(&path)? {
+            Some(installed) if !installed.is_managed() => {
+                Err(LaunchAgentError::NotManaged(path))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check if the plist in the context's directory matches the agent.
+    /// Keys preserved by [`WritePolicy::MergePreservingUnknownKeys`] are
+    /// not a difference.
+    pub(crate) fn is_installed_as_is(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<bool> {
+        let Some(installed) = agent.read_installed_at(&self.path(agent)?)? else {
+            return Ok(false);
+        };
+        let ours = agent.stamped();
+        if self.write_policy == WritePolicy::MergePreservingUnknownKeys {
+            return Ok(installed == ours.merged_with(&installed));
+        }
+        Ok(installed == *ours)
+    }
+
+    /// Bootstrap the agent.
+    ///
+    /// Succeeds with [`BootstrapOutcome::AlreadyLoaded`] if launchd already
+    /// serves the agent from its plist. If the label is loaded from another
+    /// plist, the "already loaded" error is returned; see
+    /// [`LaunchAgent::rebootstrap`].
+    pub fn bootstrap(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<BootstrapOutcome> {
+        let Err(err) = self.run_path_command("bootstrap", agent) else {
+            return Ok(BootstrapOutcome::NewlyLoaded);
+        };
+        // Recent macOS reports a loaded service as a generic I/O error, so
+        // both are checked against the registration.
+        if !matches!(
+            err.failure(),
+            Some(LaunchctlFailure::AlreadyLoaded | LaunchctlFailure::InputOutput)
+        ) {
+            return Err(err);
+        }
+        match self.info(agent) {
+            Ok(Some(info))
+                if agent
+                    .registration_mismatch_at(&self.path(agent)?, &info)
+                    .is_none() =>
+            {
+                Ok(BootstrapOutcome::AlreadyLoaded)
+            }
+            _ => Err(err),
+        }
+    }
+
+    pub fn boot_out(&self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        self.run_path_command("bootout", agent)
+    }
+
+    pub fn enable(&self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        self.run_target_command("enable", agent)
+    }
+
+    pub fn disable(&self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        self.run_target_command("disable", agent)
+    }
+
+    /// Start the agent, restarting it first if `kill` is set.
+    pub fn kickstart(&self, agent: &LaunchAgent, kill: bool) -> LaunchctlResult<()> {
+        self.kickstart_target(&self.service_target(agent), kill)
+    }
+
+    /// Send a signal to the agent's main process.
+    pub fn kill(&self, agent: &LaunchAgent, signal: i32) -> LaunchctlResult<()> {
+        self.kill_target(&self.service_target(agent), signal)
+    }
+
+    /// Run a legacy verb (`start`, `stop` or `remove`) on the agent's label.
+    pub(crate) fn legacy(
+        &self,
+        agent: &LaunchAgent,
+        verb: &str,
+    ) -> LaunchctlResult<()> {
+        self.run_checked(&self.command([verb, agent.label.as_str()]))
+    }
+
+    /// Wait until the agent is running, see
+    /// [`LaunchControllable::wait_until_running`](crate::LaunchControllable::wait_until_running).
+    pub fn wait_until_running(
+        &self,
+        agent: &LaunchAgent,
+        timeout: Duration,
+    ) -> LaunchctlResult<bool> {
+        poll(|| self.is_running(agent), &PollOptions::new(timeout))
+    }
+
+    /// Returns the agent's state, `None` if it is not loaded.
+    pub fn info(&self, agent: &LaunchAgent) -> LaunchctlResult<Option<ServiceInfo>> {
+        self.print_target(&self.service_target(agent))
+    }
+
+    /// Start any service, e.g. a daemon in the system domain or, as root,
+    /// an agent in another user's `gui/<uid>` domain. Restarts it first if
+    /// `kill` is set.
+    ///
+    /// Fails with [`LaunchAgentError::NotPermitted`] if the caller may not
+    /// manage the service.
+    pub fn kickstart_target(
+        &self,
+        target: &ServiceTarget,
+        kill: bool,
+    ) -> LaunchctlResult<()> {
+        let spec = target.kickstart_command(kill);
+        checked(&self.run_on_target(target, &spec)?).map(|_| ())
+    }
+
+    /// Send a signal to the main process of any service, see
+    /// [`kickstart_target`](Self::kickstart_target).
+    pub fn kill_target(
+        &self,
+        target: &ServiceTarget,
+        signal: i32,
+    ) -> LaunchctlResult<()> {
+        let spec = target.kill_command(signal);
+        checked(&self.run_on_target(target, &spec)?).map(|_| ())
+    }
+
+    /// Returns the state of any service, `None` if it is not loaded, see
+    /// [`kickstart_target`](Self::kickstart_target). Other failures, e.g. of
+    /// `sudo` or a missing domain, are returned as errors.
+    pub fn print_target(
+        &self,
+        target: &ServiceTarget,
+    ) -> LaunchctlResult<Option<ServiceInfo>> {
+        let output = self.run_on_target(target, &target.print_command())?;
+        if !output.success() {
+            return Ok(None);
+        }
+        let output = output.stdout_lossy();
+        if output.trim().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(ServiceInfo::parse(&output)))
+    }
+
+    pub fn is_loaded(&self, agent: &LaunchAgent) -> LaunchctlResult<bool> {
+        Ok(self.info(agent)?.is_some())
+    }
+
+    pub fn is_running(&self, agent: &LaunchAgent) -> LaunchctlResult<bool> {
+        let info = self.info(agent)?;
+        Ok(info.is_some_and(|info| LaunchAgent::check_is_running(info.raw())))
+    }
+
+    pub fn pid(&self, agent: &LaunchAgent) -> LaunchctlResult<Option<u32>> {
+        Ok(self.info(agent)?.and_then(|info| info.pid()))
+    }
+
+    /// `launchctl <subcommand> <domain> <plist path>`
+    fn run_path_command(
+        &self,
+        subcommand: &str,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<()> {
+        let command = self.command([
+            OsString::from(subcommand),
+            self.domain.to_string().into(),
+            self.path(agent)?.into_os_string(),
+        ]);
+        self.run_checked(&command)
+    }
+
+    /// Boot out the agent's service by label, whichever plist it was loaded
+    /// from.
+    pub(crate) fn boot_out_target(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<()> {
+        self.run_target_command("bootout", agent)
+    }
+
+    /// `launchctl <subcommand> <domain>/<label>`
+    fn run_target_command(
+        &self,
+        subcommand: &str,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<()> {
+        let target = self.service_target(agent);
+        let spec = self.command([subcommand.to_string(), target.to_string()]);
+        checked(&self.run_on_target(&target, &spec)?).map(|_| ())
+    }
+
+    /// Run a launchctl command addressing `target`. Fails with
+    /// [`LaunchAgentError::NotPermitted`] if the caller may not manage the
+    /// service and with [`LaunchAgentError::CommandFailed`] on other
+    /// failures, except the service not being loaded, which is left to the
+    /// caller.
+    pub(crate) fn run_on_target(
+        &self,
+        target: &ServiceTarget,
+        spec: &CommandSpec,
+    ) -> LaunchctlResult<CommandOutput> {
+        let output = self.run(&self.command(spec.args.clone()))?;
+        if output.success() {
+            return Ok(output);
+        }
+        let err = command_error(&output);
+        match err.failure() {
+            Some(LaunchctlFailure::NotLoaded) => Ok(output),
+            Some(LaunchctlFailure::PermissionDenied) => {
+                Err(LaunchAgentError::NotPermitted(target.to_string()))
+            }
+            _ => Err(err),
+        }
+    }
+
+    fn run_checked(&self, command: &CommandSpec) -> LaunchctlResult<()> {
+        checked(&self.run(command)?).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn test_dry_run_reports_commands() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let context = LaunchctlContext::new()
+            .directory("/Library/LaunchDaemons")
+            .domain(DomainTarget::System)
+            .elevation(Elevation::Sudo)
+            .dry_run(true)
+            .on_command(move |command| {
+                recorder.lock().unwrap().push(command.to_string());
+            });
+        let agent = LaunchAgent::new("co.myrt.ajam");
+
+        context.write(&agent).unwrap();
+        context.bootstrap(&agent).unwrap();
+        context.kickstart(&agent, true).unwrap();
+        assert!(!context.is_loaded(&agent).unwrap());
+
+        let mut seen = seen.lock().unwrap();
+        let install = seen.remove(0);
+        assert!(install.starts_with("sudo -n /usr/bin/install -m 644 "));
+        assert!(install.ends_with(" /Library/LaunchDaemons/co.myrt.ajam.plist"));
+        assert_eq!(
+            *seen,
+            [
+                "sudo -n launchctl bootstrap system /Library/LaunchDaemons/co.myrt.ajam.plist",
+                "sudo -n launchctl kickstart -k system/co.myrt.ajam",
+                "sudo -n launchctl print system/co.myrt.ajam",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_queries_run_through_context() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let context = LaunchctlContext::new()
+            .domain(DomainTarget::System)
+            .elevation(Elevation::Sudo)
+            .dry_run(true)
+            .on_command(move |command| {
+                recorder.lock().unwrap().push(command.to_string());
+            });
+        let agent = LaunchAgent::new("co.myrt.ajam");
+
+        context.blame(&agent).unwrap();
+        context.registered_path(&agent).unwrap();
+        context.list().unwrap();
+        context.mach_services(DomainTarget::System).unwrap();
+        context.dumpstate_for("co.myrt.ajam").unwrap();
+        context.current_session().unwrap();
+        assert!(context
+            .find("co.myrt.ajam")
+            .unwrap()
+            .iter()
+            .all(|location| !matches!(location, crate::LabelLocation::Loaded(_))));
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(
+            seen[..6],
+            [
+                "sudo -n launchctl blame system/co.myrt.ajam",
+                "sudo -n launchctl print system/co.myrt.ajam",
+                "sudo -n launchctl list",
+                "sudo -n launchctl print system",
+                "sudo -n launchctl dumpstate",
+                "sudo -n launchctl managername",
+            ]
+        );
+        assert_eq!(seen.len(), 9);
+        assert!(seen.iter().all(|command| command.starts_with("sudo -n ")));
+    }
+
+    #[test]
+    fn test_as_user() {
+        let context = LaunchctlContext::new()
+            .domain(DomainTarget::Gui(501))
+            .as_user(501)
+            .elevation(Elevation::Sudo);
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        assert_eq!(
+            context
+                .command(["kickstart", "gui/501/co.myrt.ajam"])
+                .to_string(),
+            "sudo -n launchctl asuser 501 launchctl kickstart gui/501/co.myrt.ajam"
+        );
+        assert_eq!(
+            context.service_target(&agent).to_string(),
+            "gui/501/co.myrt.ajam"
+        );
+    }
+
+    #[test]
+    fn test_adapt_to_session() {
+        let other = get_user_id().wrapping_add(1);
+        let context = LaunchctlContext::new().domain(DomainTarget::Gui(other));
+        let direct = context.clone().adapt_to_session().unwrap();
+        assert_eq!(direct.as_user.is_some(), get_user_id() == 0);
+        let elevated = context
+            .elevation(Elevation::Sudo)
+            .adapt_to_session()
+            .unwrap();
+        assert_eq!(elevated.as_user, Some(other));
+    }
+
+    struct FakeRunner;
+
+    impl CommandRunner for FakeRunner {
+        fn run(&self, command: &CommandSpec) -> LaunchctlResult<CommandOutput> {
+            let stdout = match command.args[0].to_str() {
+                Some("print") => {
+                    b"system/co.myrt.ajam = {\n\tstate = running\n\tpid = 42\n}\n"
+                        .to_vec()
+                }
+                _ => Vec::new(),
+            };
+            Ok(CommandOutput {
+                code: i32::from(command.args[0] == "bootout"),
+                stdout,
+                stderr: b"Boot-out failed: 5: Input/output error".to_vec(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_custom_runner() {
+        let context = LaunchctlContext::new()
+            .launchctl("/bin/launchctl")
+            .runner(FakeRunner);
+        let agent = LaunchAgent::new("co.myrt.ajam");
+
+        assert!(context.is_running(&agent).unwrap());
+        assert_eq!(context.pid(&agent).unwrap(), Some(42));
+        assert!(matches!(
+            context.boot_out(&agent),
+            Err(LaunchAgentError::CommandFailed(1, _))
+        ));
+    }
+
+    struct SudoRunner(&'static str);
+
+    impl CommandRunner for SudoRunner {
+        fn run(&self, _: &CommandSpec) -> LaunchctlResult<CommandOutput> {
+            Ok(CommandOutput {
+                code: 1,
+                stdout: Vec::new(),
+                stderr: self.0.as_bytes().to_vec(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_elevation_failures() {
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        let context = |stderr| {
+            LaunchctlContext::new()
+                .elevation(Elevation::SudoPrompt)
+                .runner(SudoRunner(stderr))
+        };
+
+        let declined = context("sudo: 3 incorrect password attempts");
+        assert!(matches!(
+            declined.bootstrap(&agent),
+            Err(LaunchAgentError::ElevationDeclined)
+        ));
+        let required = context("sudo: a password is required");
+        assert!(matches!(
+            required.bootstrap(&agent),
+            Err(LaunchAgentError::ElevationFailed(
+                ElevationFailure::PasswordRequired
+            ))
+        ));
+        let failed = context("Bootstrap failed: 5: Input/output error");
+        assert!(matches!(
+            failed.bootstrap(&agent),
+            Err(LaunchAgentError::CommandFailed(1, _))
+        ));
+    }
+
+    #[test]
+    fn test_agent_commands_classify_failures() {
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        let denied = LaunchctlContext::new().runner(SudoRunner(
+            "Could not kickstart service \"gui/501/co.myrt.ajam\": 1: Operation not permitted",
+        ));
+        for result in [
+            denied.kickstart(&agent, false),
+            denied.kill(&agent, 15),
+            denied.disable(&agent),
+            denied.info(&agent).map(|_| ()),
+        ] {
+            assert!(matches!(result, Err(LaunchAgentError::NotPermitted(_))));
+        }
+
+        let broken = LaunchctlContext::new()
+            .runner(SudoRunner("Could not find domain for port: 1"));
+        assert!(matches!(
+            broken.info(&agent),
+            Err(LaunchAgentError::CommandFailed(1, _))
+        ));
+        assert_eq!(LaunchctlContext::idle().info(&agent).unwrap(), None);
+    }
+
+    #[test]
+    fn test_other_domains() {
+        let denied = LaunchctlContext::new().runner(SudoRunner(
+            "Could not kickstart service \"system/com.apple.ajam\": 1: Operation not permitted",
+        ));
+        let daemon = ServiceTarget::new(DomainTarget::System, "com.apple.ajam");
+        let err = denied.kickstart_target(&daemon, false).unwrap_err();
+        assert!(matches!(&err, LaunchAgentError::NotPermitted(target)
+            if target == "system/com.apple.ajam"));
+        assert_eq!(err.failure(), Some(LaunchctlFailure::PermissionDenied));
+        assert!(matches!(
+            denied.print_target(&daemon),
+            Err(LaunchAgentError::NotPermitted(_))
+        ));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let context = LaunchctlContext::new()
+            .elevation(Elevation::Sudo)
+            .dry_run(true)
+            .on_command(move |command| {
+                recorder.lock().unwrap().push(command.to_string());
+            });
+        let agent = ServiceTarget::new(DomainTarget::Gui(502), "co.myrt.ajam");
+        context.kill_target(&agent, 15).unwrap();
+        assert_eq!(context.print_target(&agent).unwrap(), None);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                "sudo -n launchctl kill 15 gui/502/co.myrt.ajam",
+                "sudo -n launchctl print gui/502/co.myrt.ajam",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_to_directory() {
+        let directory = std::env::temp_dir().join(format!(
+            "lunchctl-context-{}",
+            rand::random_range(0..u32::MAX)
+        ));
+        std::fs::create_dir(&directory).unwrap();
+        let context = LaunchctlContext::new().directory(&directory);
+        let agent = LaunchAgent::new("co.myrt.ajam");
+
+        context.write(&agent).unwrap();
+        let path = directory.join("co.myrt.ajam.plist");
+        assert_eq!(context.path(&agent).unwrap(), path);
+        let written: LaunchAgent = plist::from_file(&path).unwrap();
+        assert_eq!(written.label, "co.myrt.ajam");
+        context.remove(&agent).unwrap();
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_elevated_calls_are_recorded_unwrapped() {
+        let args = [OsString::from("print"), OsString::from("gui/501")];
+        let plain = LaunchctlContext::new().as_user(501);
+        let elevated = plain.clone().elevation(Elevation::Sudo);
+
+        for context in [&plain, &elevated] {
+            let command = context.command(args.clone());
+            let call = context.launchctl_call(&command).unwrap();
+            assert_eq!(call.to_string(), "launchctl print gui/501");
+        }
+        let install = elevated.elevated(CommandSpec::new("install", args));
+        assert!(elevated.launchctl_call(&install).is_none());
+    }
+
+    #[test]
+    fn test_settings_apply_to_writes() {
+        let directory = std::env::temp_dir().join(format!(
+            "lunchctl-context-{}",
+            rand::random_range(0..u32::MAX)
+        ));
+        std::fs::create_dir(&directory).unwrap();
+        let context = LaunchctlContext::new().directory(&directory);
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent
+            .write_plist_to(&context.path(&agent).unwrap())
+            .unwrap();
+        agent.run_at_load = true;
+
+        let exclusive = context.clone().write_policy(WritePolicy::ErrorIfExists);
+        assert!(matches!(
+            exclusive.write(&agent),
+            Err(LaunchAgentError::PlistExists(_))
+        ));
+        let owned = context.clone().require_ownership(true);
+        assert!(matches!(
+            owned.write(&agent),
+            Err(LaunchAgentError::NotManaged(_))
+        ));
+        assert!(matches!(
+            owned.remove(&agent),
+            Err(LaunchAgentError::NotManaged(_))
+        ));
+        assert!(context.path(&agent).unwrap().exists());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
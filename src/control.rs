@@ -1,128 +1,310 @@
+use std::ffi::OsString;
+use std::time::Duration;
+
 use crate::agent::LaunchAgent;
-use crate::os::{get_user_id, run_shell};
+use crate::command::CommandSpec;
+use crate::domain::{DomainTarget, ServiceTarget};
+use crate::context::LaunchctlContext;
+use crate::poll::{poll, PollOptions};
 use crate::LaunchctlResult;
 
+/// What bootstrapping or installing an agent changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootstrapOutcome {
+    /// The agent was already loaded from its plist; nothing changed.
+    AlreadyLoaded,
+    /// The agent was not loaded and is now.
+    NewlyLoaded,
+    /// A loaded registration of the label was booted out and the agent
+    /// loaded in its place.
+    ReplacedStale,
+}
+
 /// Trait for controlling launch agents via launchctl.
 pub trait LaunchControllable {
     /// Bootstrap the launch agent.
-    fn bootstrap(&self) -> LaunchctlResult<()>;
+    fn bootstrap(&self) -> LaunchctlResult<BootstrapOutcome>;
 
     /// Boot out the launch agent.
     fn boot_out(&self) -> LaunchctlResult<()>;
 
     /// Check if the launch agent is running.
     fn is_running(&self) -> LaunchctlResult<bool>;
+
+    /// Check if the launch agent is loaded into launchd, running or not.
+    fn is_loaded(&self) -> LaunchctlResult<bool>;
+
+    /// Enable the launch agent, clearing a disabled override.
+    fn enable(&self) -> LaunchctlResult<()>;
+
+    /// Disable the launch agent. Disabled agents cannot be bootstrapped.
+    fn disable(&self) -> LaunchctlResult<()>;
+
+    /// Start the launch agent, restarting it first if `kill` is set.
+    fn kickstart(&self, kill: bool) -> LaunchctlResult<()>;
+
+    /// Returns the process ID of the running launch agent.
+    fn pid(&self) -> LaunchctlResult<Option<u32>>;
+
+    /// Send a signal to the launch agent's main process.
+    ///
+    /// Only the main process is signalled. Use
+    /// [`LaunchAgent::kill_process_group`] to reach its children as well.
+    fn kill(&self, signal: i32) -> LaunchctlResult<()>;
+
+    /// Wait until the launch agent is running.
+    /// Returns `false` if it did not start within `timeout`.
+    fn wait_until_running(&self, timeout: Duration) -> LaunchctlResult<bool> {
+        self.wait_until_running_with(&PollOptions::new(timeout))
+    }
+
+    /// Wait until the launch agent is running, polling as configured in
+    /// `options`, e.g. to make the wait cancellable.
+    /// Returns `false` if it did not start within the timeout.
+    fn wait_until_running_with(
+        &self,
+        options: &PollOptions,
+    ) -> LaunchctlResult<bool> {
+        poll(|| self.is_running(), options)
+    }
 }
 
 impl LaunchAgent {
-    /// Format a launchctl command.
-    /// If the command is empty, it will return an empty string.
-    fn format_command(&self, command: &str) -> String {
-        if command.is_empty() {
-            return String::new();
-        }
-        format!(
-            "launchctl {} gui/{} '{}'",
-            command,
-            get_user_id(),
-            self.path().display()
-        )
+    /// Returns the agent's service in the current user's GUI domain.
+    pub fn service_target(&self) -> ServiceTarget {
+        ServiceTarget::current_gui(&self.label)
+    }
+
+    /// Start the launch agent with the legacy `launchctl start <label>`.
+    ///
+    /// Legacy verbs address the label in the caller's context instead of a
+    /// domain target. Prefer [`kickstart`](LaunchControllable::kickstart);
+    /// this is for compatibility with scripts and for when the modern verbs
+    /// misbehave.
+    pub fn legacy_start(&self) -> LaunchctlResult<()> {
+        LaunchctlContext::default().legacy(self, "start")
+    }
+
+    /// Stop the launch agent with the legacy `launchctl stop <label>`.
+    /// launchd restarts agents that are kept alive.
+    pub fn legacy_stop(&self) -> LaunchctlResult<()> {
+        LaunchctlContext::default().legacy(self, "stop")
+    }
+
+    /// Unload the launch agent with the legacy `launchctl remove <label>`.
+    /// The plist stays in place. Prefer
+    /// [`boot_out`](LaunchControllable::boot_out).
+    pub fn legacy_remove(&self) -> LaunchctlResult<()> {
+        LaunchctlContext::default().legacy(self, "remove")
     }
 
-    fn format_bootstrap_command(&self) -> String {
-        self.format_command("bootstrap")
+    /// Returns the command that bootstraps the agent's plist. Fails if the
+    /// home directory is unknown.
+    pub fn bootstrap_command(&self) -> LaunchctlResult<CommandSpec> {
+        self.path_command("bootstrap")
     }
 
-    fn format_boot_out_command(&self) -> String {
-        self.format_command("bootout")
+    /// Returns the command that boots out the agent's plist.
+    pub fn boot_out_command(&self) -> LaunchctlResult<CommandSpec> {
+        self.path_command("bootout")
     }
 
-    fn format_print_command(&self) -> String {
-        format!("launchctl print gui/{}/{}", get_user_id(), self.label)
+    /// Returns the command that prints the agent's state.
+    pub fn print_command(&self) -> CommandSpec {
+        self.service_target().print_command()
+    }
+
+    /// Returns the command that enables or disables the agent.
+    pub fn enable_command(&self, enable: bool) -> CommandSpec {
+        self.target_command(if enable { "enable" } else { "disable" })
+    }
+
+    /// Returns the command that starts the agent, restarting it if `kill`
+    /// is set.
+    pub fn kickstart_command(&self, kill: bool) -> CommandSpec {
+        self.service_target().kickstart_command(kill)
+    }
+
+    /// Returns the command that sends a signal to the agent's process.
+    pub fn kill_command(&self, signal: i32) -> CommandSpec {
+        self.service_target().kill_command(signal)
+    }
+
+    /// Returns the command running a legacy verb (`start`, `stop` or
+    /// `remove`) on the agent's label.
+    pub fn legacy_command(&self, verb: &str) -> CommandSpec {
+        CommandSpec::launchctl([verb, self.label.as_str()])
+    }
+
+    /// `launchctl <subcommand> gui/<uid> <plist path>`
+    fn path_command(&self, subcommand: &str) -> LaunchctlResult<CommandSpec> {
+        Ok(CommandSpec::launchctl([
+            OsString::from(subcommand),
+            DomainTarget::current_gui().to_string().into(),
+            self.path()?.into_os_string(),
+        ]))
+    }
+
+    /// `launchctl <subcommand> gui/<uid>/<label>`
+    fn target_command(&self, subcommand: &str) -> CommandSpec {
+        CommandSpec::launchctl([
+            subcommand.to_string(),
+            self.service_target().to_string(),
+        ])
     }
 
     /// Check if the output contains agent is running indicator.
-    fn check_is_running(output: &str) -> bool {
+    pub(crate) fn check_is_running(output: &str) -> bool {
         output.contains("state = running")
     }
 }
 
+/// The agent is controlled through the default [`LaunchctlContext`].
 impl LaunchControllable for LaunchAgent {
-    /// Bootstrap the launch agent.
-    fn bootstrap(&self) -> LaunchctlResult<()> {
-        let cmd = self.format_bootstrap_command();
-        run_shell(&cmd).map(|_| ())
+    /// Bootstrap the launch agent, see [`LaunchctlContext::bootstrap`].
+    fn bootstrap(&self) -> LaunchctlResult<BootstrapOutcome> {
+        LaunchctlContext::default().bootstrap(self)
     }
 
     /// Boot out the launch agent.
     /// It means not only stop, but also deactivate the launch agent.
     fn boot_out(&self) -> LaunchctlResult<()> {
-        let cmd = self.format_boot_out_command();
-        run_shell(&cmd).map(|_| ())
+        LaunchctlContext::default().boot_out(self)
     }
 
     /// Check if the launch agent is running.
     fn is_running(&self) -> LaunchctlResult<bool> {
-        let cmd = self.format_print_command();
+        LaunchctlContext::default().is_running(self)
+    }
+
+    /// Check if the launch agent is loaded into launchd, running or not.
+    fn is_loaded(&self) -> LaunchctlResult<bool> {
+        LaunchctlContext::default().is_loaded(self)
+    }
+
+    /// Enable the launch agent, clearing a disabled override.
+    fn enable(&self) -> LaunchctlResult<()> {
+        LaunchctlContext::default().enable(self)
+    }
+
+    /// Disable the launch agent. Disabled agents cannot be bootstrapped.
+    fn disable(&self) -> LaunchctlResult<()> {
+        LaunchctlContext::default().disable(self)
+    }
 
-        let output = run_shell(&cmd)?;
-        Ok(LaunchAgent::check_is_running(&output))
+    /// Start the launch agent, restarting it first if `kill` is set.
+    fn kickstart(&self, kill: bool) -> LaunchctlResult<()> {
+        LaunchctlContext::default().kickstart(self, kill)
+    }
+
+    /// Returns the process ID of the running launch agent.
+    fn pid(&self) -> LaunchctlResult<Option<u32>> {
+        LaunchctlContext::default().pid(self)
+    }
+
+    /// Send a signal to the launch agent's main process.
+    fn kill(&self, signal: i32) -> LaunchctlResult<()> {
+        LaunchctlContext::default().kill(self, signal)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::os::get_user_id;
 
     #[test]
-    fn test_format_command() {
+    fn test_bootstrap_command() {
         let agent = LaunchAgent::new("test");
-        let agent_path = agent.path().display().to_string();
         let user_id = get_user_id();
+        let agent_path = agent.path().unwrap().display().to_string();
 
         assert_eq!(
-            agent.format_command("subcommand"),
-            format!("launchctl subcommand gui/{user_id} '{agent_path}'")
+            agent.bootstrap_command().unwrap(),
+            CommandSpec::launchctl([
+                "bootstrap".to_string(),
+                format!("gui/{user_id}"),
+                agent_path.clone(),
+            ])
         );
         assert_eq!(
-            agent.format_command("manageruid"),
-            format!("launchctl manageruid gui/{user_id} '{agent_path}'")
+            agent.bootstrap_command().unwrap().to_string(),
+            format!("launchctl bootstrap gui/{user_id} {agent_path}")
         );
-        assert_eq!(agent.format_command(""), "");
     }
 
     #[test]
-    fn test_format_bootstrap_command() {
+    fn test_boot_out_command() {
         let agent = LaunchAgent::new("test");
         let user_id = get_user_id();
-        let agent_path = agent.path().display().to_string();
+        let agent_path = agent.path().unwrap().display().to_string();
 
         assert_eq!(
-            agent.format_bootstrap_command(),
-            format!("launchctl bootstrap gui/{user_id} '{agent_path}'")
+            agent.boot_out_command().unwrap().to_string(),
+            format!("launchctl bootout gui/{user_id} {agent_path}")
         );
     }
 
     #[test]
-    fn test_format_bootout_command() {
+    fn test_print_command() {
         let agent = LaunchAgent::new("test");
         let user_id = get_user_id();
-        let agent_path = agent.path().display().to_string();
 
         assert_eq!(
-            agent.format_boot_out_command(),
-            format!("launchctl bootout gui/{user_id} '{agent_path}'")
+            agent.print_command().to_string(),
+            format!("launchctl print gui/{user_id}/test")
         );
     }
 
     #[test]
-    fn test_check_info_command() {
+    fn test_enable_command() {
         let agent = LaunchAgent::new("test");
         let user_id = get_user_id();
 
         assert_eq!(
-            agent.format_print_command(),
-            format!("launchctl print gui/{user_id}/test")
+            agent.enable_command(true).to_string(),
+            format!("launchctl enable gui/{user_id}/test")
+        );
+        assert_eq!(
+            agent.enable_command(false).to_string(),
+            format!("launchctl disable gui/{user_id}/test")
+        );
+    }
+
+    #[test]
+    fn test_kickstart_command() {
+        let agent = LaunchAgent::new("test");
+        let user_id = get_user_id();
+
+        assert_eq!(
+            agent.kickstart_command(false).to_string(),
+            format!("launchctl kickstart gui/{user_id}/test")
+        );
+        assert_eq!(
+            agent.kickstart_command(true).to_string(),
+            format!("launchctl kickstart -k gui/{user_id}/test")
+        );
+    }
+
+    #[test]
+    fn test_kill_command() {
+        let agent = LaunchAgent::new("test");
+        let user_id = get_user_id();
+
+        assert_eq!(
+            agent.kill_command(15).to_string(),
+            format!("launchctl kill 15 gui/{user_id}/test")
+        );
+    }
+
+    #[test]
+    fn test_label_is_quoted() {
+        let agent = LaunchAgent::new("it's a test");
+        let user_id = get_user_id();
+
+        assert_eq!(
+            agent.print_command().to_string(),
+            format!(r"launchctl print 'gui/{user_id}/it'\''s a test'")
         );
     }
 
@@ -157,4 +339,17 @@ mod tests {
         ";
         assert!(!LaunchAgent::check_is_running(output));
     }
+
+    #[test]
+    fn test_legacy_command() {
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        assert_eq!(
+            agent.legacy_command("stop").to_string(),
+            "launchctl stop co.myrt.ajam"
+        );
+        assert_eq!(
+            agent.legacy_command("remove"),
+            CommandSpec::launchctl(["remove", "co.myrt.ajam"])
+        );
+    }
 }
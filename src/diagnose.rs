@@ -0,0 +1,260 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::fix::Fix;
+use crate::keepalive::KeepAlive;
+use crate::lint::{
+    CASE_COLLISION, DISABLED, MISSING_LOG_DIRECTORY, MISSING_PROGRAM, NETWORK_STATE,
+    PLIST_PERMISSIONS, QOS_CONFLICT, STALE_REGISTRATION, UNRELIABLE_PATH,
+};
+use crate::permissions::PlistPermissions;
+use crate::LaunchctlResult;
+
+/// How serious a [`Finding`] is. Serialized as `warning` or `error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Works, but may not do what the author expects.
+    Warning,
+    /// launchd rejects or misbehaves with the configuration.
+    Error,
+}
+
+/// Problem found in an agent configuration by [`LaunchAgent::diagnose`].
+///
+/// Findings serialize to a map of their fields, e.g. for a JSON report.
+/// Tools should match on `code`, since messages may change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Stable identifier of the check, e.g. `network-state`.
+    pub code: &'static str,
+    pub message: String,
+    /// How to resolve the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+    /// Remediation [`fix`](Self::fix) can apply.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<Fix>,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+impl Finding {
+    pub fn warning(code: &'static str, message: &str) -> Self {
+        Self {
+            severity: Severity::Warning,
+            code,
+            message: message.to_string(),
+            suggestion: None,
+            fix: None,
+        }
+    }
+
+    pub fn error(code: &'static str, message: &str) -> Self {
+        Self {
+            severity: Severity::Error,
+            ..Self::warning(code, message)
+        }
+    }
+
+    #[must_use]
+    pub fn with_suggestion(mut self, suggestion: &str) -> Self {
+        self.suggestion = Some(suggestion.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code, self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " ({suggestion})")?;
+        }
+        Ok(())
+    }
+}
+
+impl LaunchAgent {
+    /// Check the configuration for mistakes launchd accepts silently.
+    ///
+    /// Nothing is run. Besides the configuration, only the file systems of
+    /// the paths it references are inspected, see
+    /// [`unreliable_paths`](Self::unreliable_paths). Use
+    /// [`diagnose_loaded`](Self::diagnose_loaded) to check the loaded agent
+    /// as well.
+    pub fn diagnose(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        if self.program_arguments.is_empty() && !self.extra.contains_key("Program") {
+            findings.push(
+                MISSING_PROGRAM
+                    .error("neither Program nor ProgramArguments is set")
+                    .with_suggestion(
+                        "add the executable path as the first program argument",
+                    ),
+            );
+        }
+
+        if let KeepAlive::Conditions(conditions) = &self.keep_alive {
+            if conditions.network_state.is_some() {
+                let message =
+                    "KeepAlive.NetworkState is deprecated and nearly always true";
+                findings.push(NETWORK_STATE.warning(message).with_suggestion(
+                    "keep the job alive and wait for connectivity in the program",
+                ));
+            }
+        }
+
+        for conflict in self.qos_conflicts() {
+            findings.push(QOS_CONFLICT.warning(conflict).with_suggestion(
+                "use a LaunchAgentBuilder preset such as as_background_task()",
+            ));
+        }
+
+        if let Some(collision) = self.case_collision() {
+            findings.push(
+                CASE_COLLISION
+                    .warning(&format!(
+                        "{} differs from the agent's plist name only by case",
+                        collision.display()
+                    ))
+                    .with_suggestion("writing the agent replaces it"),
+            );
+        }
+
+        for unreliable in self.unreliable_paths() {
+            let message =
+                format!("{unreliable}, launchd may fail to access it at login");
+            findings.push(UNRELIABLE_PATH.warning(&message).with_suggestion(
+                "move it to a local folder such as ~/Library/Application Support",
+            ));
+        }
+
+        for log in self.log_paths() {
+            let Some(dir) = log.parent().filter(|dir| !dir.exists()) else {
+                continue;
+            };
+            findings.push(
+                MISSING_LOG_DIRECTORY
+                    .error(&format!(
+                        "{} does not exist, launchd cannot open {}",
+                        dir.display(),
+                        log.display()
+                    ))
+                    .with_fix(Fix::CreateDirectory(dir.to_path_buf())),
+            );
+        }
+
+        if let Some(problem) = self.path().ok().and_then(|path| {
+            PlistPermissions::of(&path)
+                .ok()
+                .and_then(|permissions| permissions.problem(&path))
+        }) {
+            findings.push(
+                PLIST_PERMISSIONS
+                    .error(&format!("launchd rejects the plist: {problem}"))
+                    .with_fix(Fix::FixPermissions),
+            );
+        }
+
+        findings
+    }
+
+    /// Same as [`diagnose`](Self::diagnose), adding findings about the
+    /// loaded agent: a disabled override, a registration from another
+    /// plist, see [`verify_registration`](Self::verify_registration), and a
+    /// likely lack of Full Disk Access, see
+    /// [`tcc_identifier`](Self::tcc_identifier).
+    pub fn diagnose_loaded(&self) -> LaunchctlResult<Vec<Finding>> {
+        LaunchctlContext::default().diagnose_loaded(self)
+    }
+}
+
+impl LaunchctlContext {
+    /// Diagnose the agent and its registration in the context's domain, see
+    /// [`LaunchAgent::diagnose_loaded`].
+    pub fn diagnose_loaded(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<Vec<Finding>> {
+        let mut findings = agent.diagnose();
+        if self.is_disabled(agent)? {
+            findings.push(
+                DISABLED
+                    .error(
+                        "a disabled override keeps launchd from loading the agent",
+                    )
+                    .with_fix(Fix::Enable),
+            );
+        }
+        let Some(info) = self.info(agent)? else {
+            return Ok(findings);
+        };
+        if let Some(mismatch) =
+            agent.registration_mismatch_at(&self.path(agent)?, &info)
+        {
+            findings.push(
+                STALE_REGISTRATION
+                    .error(&mismatch)
+                    .with_fix(Fix::Rebootstrap),
+            );
+        }
+        findings.extend(agent.full_disk_access_finding(&info));
+        Ok(findings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+
+    #[test]
+    fn test_finding_serialization() {
+        let finding = Finding::error("missing-program", "no program")
+            .with_suggestion("add one");
+        let value = plist::to_value(&finding).unwrap();
+        let dictionary = value.as_dictionary().unwrap();
+        assert_eq!(dictionary["severity"].as_string(), Some("error"));
+        assert_eq!(dictionary["code"].as_string(), Some("missing-program"));
+        assert_eq!(dictionary["suggestion"].as_string(), Some("add one"));
+
+        let value = plist::to_value(&Finding::warning("network-state", "")).unwrap();
+        let dictionary = value.as_dictionary().unwrap();
+        assert_eq!(dictionary["severity"].as_string(), Some("warning"));
+        assert!(!dictionary.contains_key("suggestion"));
+    }
+
+    #[test]
+    fn test_network_state_finding() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .arg("/usr/local/bin/ajam".to_string())
+            .keep_alive_when_network_available()
+            .build()
+            .unwrap();
+
+        let findings = agent.diagnose();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].code, "network-state");
+        assert_eq!(findings[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_missing_program_finding() {
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        let codes: Vec<_> = agent.diagnose().iter().map(|f| f.code).collect();
+        assert_eq!(codes, vec!["missing-program"]);
+    }
+}
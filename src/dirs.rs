@@ -0,0 +1,183 @@
+use std::fs::DirBuilder;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use crate::agent::LaunchAgent;
+use crate::os::{get_user_id, home_dir};
+use crate::LaunchctlResult;
+
+/// Mode of directories created for agents, private to their owner.
+const DIR_MODE: u32 = 0o700;
+
+impl LaunchAgent {
+    /// Returns the agent's state directory,
+    /// `~/Library/Application Support/<label>`.
+    pub fn state_dir(&self) -> LaunchctlResult<PathBuf> {
+        Ok(home_dir()?
+            .join("Library")
+            .join("Application Support")
+            .join(&self.label))
+    }
+
+    /// Create the state directory, private to the user, if it does not exist.
+    pub fn create_state_dir(&self) -> LaunchctlResult<PathBuf> {
+        let dir = self.state_dir()?;
+        create_private_dir(&dir)?;
+        Ok(dir)
+    }
+
+    /// Delete the state directory with its contents.
+    /// Returns `false` if there was nothing to delete.
+    pub fn remove_state_dir(&self) -> LaunchctlResult<bool> {
+        remove_dir(&self.state_dir()?)
+    }
+
+    /// Returns the agent's cache directory, `~/Library/Caches/<label>`.
+    pub fn cache_dir(&self) -> LaunchctlResult<PathBuf> {
+        Ok(home_dir()?.join("Library").join("Caches").join(&self.label))
+    }
+
+    /// Create the cache directory, private to the user, if it does not exist.
+    pub fn create_cache_dir(&self) -> LaunchctlResult<PathBuf> {
+        let dir = self.cache_dir()?;
+        create_private_dir(&dir)?;
+        Ok(dir)
+    }
+
+    /// Delete the cache directory with its contents.
+    /// Returns `false` if there was nothing to delete.
+    pub fn remove_cache_dir(&self) -> LaunchctlResult<bool> {
+        remove_dir(&self.cache_dir()?)
+    }
+
+    /// Returns the agent's temporary directory, `<label>` in the user's
+    /// temporary directory (`$TMPDIR`).
+    pub fn temp_dir(&self) -> PathBuf {
+        std::env::temp_dir().join(&self.label)
+    }
+
+    /// Create the temporary directory, private to the user, if it does not
+    /// exist.
+    ///
+    /// `$TMPDIR` may be shared, so an existing directory is only reused if it
+    /// is a real directory owned by the user. Otherwise creation fails rather
+    /// than handing out a path someone else controls.
+    pub fn create_temp_dir(&self) -> LaunchctlResult<PathBuf> {
+        let dir = self.temp_dir();
+        create_owned_dir(&dir)?;
+        Ok(dir)
+    }
+
+    /// Delete the temporary directory with its contents.
+    /// Returns `false` if there was nothing to delete.
+    pub fn remove_temp_dir(&self) -> LaunchctlResult<bool> {
+        remove_dir(&self.temp_dir())
+    }
+
+    /// Pass the state directory to the program in the environment variable.
+    pub fn set_state_dir_env(&mut self, name: &str) -> LaunchctlResult<()> {
+        let dir = self.state_dir()?;
+        self.environment_variables
+            .set(name, &dir.display().to_string());
+        Ok(())
+    }
+
+    /// Pass the state directory to the program as arguments, after `flag`
+    /// if it is given.
+    pub fn push_state_dir_arg(&mut self, flag: Option<&str>) -> LaunchctlResult<()> {
+        let dir = self.state_dir()?;
+        if let Some(flag) = flag {
+            self.program_arguments.push(flag.into());
+        }
+        self.program_arguments.push(dir.into_os_string());
+        Ok(())
+    }
+}
+
+/// Returns the directory this crate keeps its own state in,
+/// `~/Library/Application Support/lunchctl`.
+pub(crate) fn crate_state_dir() -> io::Result<PathBuf> {
+    Ok(home_dir()?
+        .join("Library")
+        .join("Application Support")
+        .join("lunchctl"))
+}
+
+pub(crate) fn create_private_dir(dir: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).mode(DIR_MODE).create(dir)
+}
+
+/// Create a private directory in a shared location. An existing directory
+/// is only accepted if it is a real directory owned by the user.
+pub(crate) fn create_owned_dir(dir: &Path) -> io::Result<()> {
+    create_private_dir(dir)?;
+    let metadata = std::fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != get_user_id() {
+        let message = format!("{} is not owned by the user", dir.display());
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, message));
+    }
+    Ok(())
+}
+
+fn remove_dir(dir: &Path) -> LaunchctlResult<bool> {
+    if !dir.is_dir() {
+        return Ok(false);
+    }
+    std::fs::remove_dir_all(dir)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_state_dir_lifecycle() {
+        let label =
+            format!("co.myrt.ajam.state.{}", rand::random_range(0..u32::MAX));
+        let mut agent = LaunchAgent::new(&label);
+        assert!(agent
+            .state_dir()
+            .unwrap()
+            .ends_with(format!("Application Support/{label}")));
+
+        let dir = agent.create_state_dir().unwrap();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, DIR_MODE);
+
+        agent.set_state_dir_env("AJAM_STATE").unwrap();
+        agent.push_state_dir_arg(Some("--state")).unwrap();
+        let state = dir.display().to_string();
+        assert_eq!(agent.environment_variables.get("AJAM_STATE"), Some(&*state));
+        assert_eq!(
+            agent.program_arguments,
+            ["--state".into(), dir.into_os_string()]
+        );
+
+        assert!(agent.remove_state_dir().unwrap());
+        assert!(!agent.remove_state_dir().unwrap());
+    }
+
+    #[test]
+    fn test_cache_and_temp_dirs() {
+        let label =
+            format!("co.myrt.ajam.cache.{}", rand::random_range(0..u32::MAX));
+        let agent = LaunchAgent::new(&label);
+        assert!(agent
+            .cache_dir()
+            .unwrap()
+            .ends_with(format!("Library/Caches/{label}")));
+
+        let cache = agent.create_cache_dir().unwrap();
+        let temp = agent.create_temp_dir().unwrap();
+        assert!(cache.is_dir() && temp.is_dir());
+        // Reusing the existing directory is fine.
+        agent.create_temp_dir().unwrap();
+
+        assert!(agent.remove_cache_dir().unwrap());
+        assert!(agent.remove_temp_dir().unwrap());
+        assert!(!temp.exists());
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::os::checked;
+use crate::LaunchctlResult;
+
+/// Returns the disabled-override database of the current user's GUI domain,
+/// mapping service labels to their disabled flag.
+///
+/// Entries appear after `launchctl disable` or `launchctl enable` and persist
+/// across reboots until changed again.
+pub fn disabled_overrides() -> LaunchctlResult<BTreeMap<String, bool>> {
+    LaunchctlContext::default().disabled_overrides()
+}
+
+/// Parse `launchctl print-disabled` output.
+///
+/// Newer macOS releases print `"label" => disabled`/`enabled`, older ones
+/// `"label" => true`/`false`. Only the `disabled services` block is read.
+fn parse_disabled_overrides(output: &str) -> BTreeMap<String, bool> {
+    let mut overrides = BTreeMap::new();
+    let mut in_block = false;
+
+    for line in output.lines().map(str::trim) {
+        if !in_block {
+            in_block = line.starts_with("disabled services = {");
+            continue;
+        }
+        if line == "}" {
+            break;
+        }
+        let Some((label, state)) = line.split_once("=>") else {
+            continue;
+        };
+        let label = label.trim().trim_matches('"');
+        let disabled = match state.trim() {
+            "disabled" | "true" => true,
+            "enabled" | "false" => false,
+            _ => continue,
+        };
+        overrides.insert(label.to_string(), disabled);
+    }
+
+    overrides
+}
+
+impl LaunchAgent {
+    /// Check if a disabled override prevents the agent from being bootstrapped.
+    pub fn is_disabled_by_override(&self) -> LaunchctlResult<bool> {
+        LaunchctlContext::default().is_disabled(self)
+    }
+}
+
+impl LaunchctlContext {
+    /// Returns the disabled-override database of the context's domain, see
+    /// [`disabled_overrides`].
+    pub fn disabled_overrides(&self) -> LaunchctlResult<BTreeMap<String, bool>> {
+        let command =
+            self.command(["print-disabled".to_string(), self.domain.to_string()]);
+        let output = checked(&self.run(&command)?)?;
+        Ok(parse_disabled_overrides(&output))
+    }
+
+    /// Check if a disabled override in the context's domain prevents the
+    /// agent from being bootstrapped, see
+    /// [`LaunchAgent::is_disabled_by_override`].
+    pub fn is_disabled(&self, agent: &LaunchAgent) -> LaunchctlResult<bool> {
+        Ok(self
+            .disabled_overrides()?
+            .get(&agent.label)
+            .copied()
+            .unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_disabled_overrides() {
+        let output = r#"
+disabled services = {
+	"com.apple.ScreenReaderUIServer" => disabled
+	"co.myrt.ajam" => enabled
+	"com.docker.helper" => disabled
+}
+
+login item associations = {
+	"com.example.item" => enabled
+}
+"#;
+        let overrides = parse_disabled_overrides(output);
+        assert_eq!(overrides.len(), 3);
+        assert!(overrides["com.apple.ScreenReaderUIServer"]);
+        assert!(!overrides["co.myrt.ajam"]);
+        assert!(!overrides.contains_key("com.example.item"));
+    }
+
+    #[test]
+    fn test_parse_legacy_disabled_overrides() {
+        let output = r#"
+disabled services = {
+	"co.myrt.ajam" => true
+	"co.myrt.other" => false
+}
+"#;
+        let overrides = parse_disabled_overrides(output);
+        assert!(overrides["co.myrt.ajam"]);
+        assert!(!overrides["co.myrt.other"]);
+    }
+}
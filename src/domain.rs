@@ -0,0 +1,131 @@
+use std::fmt;
+
+use crate::command::CommandSpec;
+use crate::os::get_user_id;
+
+/// launchd domain a service can be loaded into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DomainTarget {
+    /// System-wide domain of daemons.
+    System,
+    /// Login session domain of a user, where agents with GUI access live.
+    Gui(u32),
+    /// Background domain of a user, available without a login session.
+    User(u32),
+}
+
+impl DomainTarget {
+    /// GUI domain of the current user.
+    pub fn current_gui() -> Self {
+        Self::Gui(get_user_id())
+    }
+
+    /// Background domain of the current user.
+    pub fn current_user() -> Self {
+        Self::User(get_user_id())
+    }
+}
+
+/// Service in a launchd domain, formatted as `<domain>/<label>`.
+///
+/// This is the target string `launchctl` subcommands such as `print`,
+/// `kickstart` and `kill` expect.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ServiceTarget {
+    pub domain: DomainTarget,
+    pub label: String,
+}
+
+impl ServiceTarget {
+    pub fn new(domain: DomainTarget, label: &str) -> Self {
+        Self {
+            domain,
+            label: label.to_string(),
+        }
+    }
+
+    /// Service with the label in the current user's GUI domain.
+    pub fn current_gui(label: &str) -> Self {
+        Self::new(DomainTarget::current_gui(), label)
+    }
+
+    /// Returns the command that prints the service's state.
+    pub fn print_command(&self) -> CommandSpec {
+        CommandSpec::launchctl(["print".to_string(), self.to_string()])
+    }
+
+    /// Returns the command that starts the service, restarting it if `kill`
+    /// is set.
+    pub fn kickstart_command(&self, kill: bool) -> CommandSpec {
+        let mut args = vec!["kickstart".to_string()];
+        if kill {
+            args.push("-k".to_string());
+        }
+        args.push(self.to_string());
+        CommandSpec::launchctl(args)
+    }
+
+    /// Returns the command that sends a signal to the service's process.
+    pub fn kill_command(&self, signal: i32) -> CommandSpec {
+        CommandSpec::launchctl([
+            "kill".to_string(),
+            signal.to_string(),
+            self.to_string(),
+        ])
+    }
+}
+
+impl fmt::Display for ServiceTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.domain, self.label)
+    }
+}
+
+impl fmt::Display for DomainTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::System => write!(f, "system"),
+            Self::Gui(uid) => write!(f, "gui/{uid}"),
+            Self::User(uid) => write!(f, "user/{uid}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(DomainTarget::System.to_string(), "system");
+        assert_eq!(DomainTarget::Gui(501).to_string(), "gui/501");
+        assert_eq!(DomainTarget::User(501).to_string(), "user/501");
+    }
+
+    #[test]
+    fn test_service_target_display() {
+        let target = ServiceTarget::new(DomainTarget::Gui(501), "co.myrt.ajam");
+        assert_eq!(target.to_string(), "gui/501/co.myrt.ajam");
+        assert_eq!(
+            ServiceTarget::new(DomainTarget::System, "co.myrt.ajam").to_string(),
+            "system/co.myrt.ajam"
+        );
+    }
+
+    #[test]
+    fn test_service_target_commands() {
+        let target = ServiceTarget::new(DomainTarget::Gui(502), "co.myrt.ajam");
+        assert_eq!(
+            target.print_command().to_string(),
+            "launchctl print gui/502/co.myrt.ajam"
+        );
+        assert_eq!(
+            target.kickstart_command(true).to_string(),
+            "launchctl kickstart -k gui/502/co.myrt.ajam"
+        );
+        assert_eq!(
+            target.kill_command(15).to_string(),
+            "launchctl kill 15 gui/502/co.myrt.ajam"
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use crate::context::LaunchctlContext;
+use crate::info::ServiceInfo;
+use crate::LaunchctlResult;
+
+/// Section of `launchctl dumpstate` output describing one service.
+///
+/// `dumpstate` reports details that `print` omits for services that failed
+/// to bootstrap, which makes it useful for debugging such failures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceDump {
+    /// Service target the section belongs to, e.g. `gui/501/co.myrt.ajam`.
+    pub target: String,
+    /// Mach services and other endpoints registered for the service.
+    pub endpoints: Vec<String>,
+    /// Services and conditions the service depends on.
+    pub dependencies: Vec<String>,
+    /// Reason the last spawn attempt failed, if it did.
+    pub last_spawn_error: Option<String>,
+    /// Top-level values of the section, parsed like `print` output.
+    pub info: ServiceInfo,
+}
+
+/// Find the service with the label in `launchctl dumpstate` output.
+///
+/// Returns the first service with the label in any domain, `None` if there is
+/// none. `dumpstate` may require root on recent macOS releases, in which case
+/// nothing is found.
+pub fn dumpstate_for(label: &str) -> LaunchctlResult<Option<ServiceDump>> {
+    LaunchctlContext::default().dumpstate_for(label)
+}
+
+impl LaunchctlContext {
+    /// Find the service with the label in `launchctl dumpstate` output, see
+    /// [`dumpstate_for`].
+    pub fn dumpstate_for(
+        &self,
+        label: &str,
+    ) -> LaunchctlResult<Option<ServiceDump>> {
+        let output = self.run(&self.command(["dumpstate"]))?;
+        Ok(ServiceDump::find(&output.stdout_lossy(), label))
+    }
+}
+
+impl ServiceDump {
+    /// Extract and parse the section of the service with the label.
+    pub fn find(output: &str, label: &str) -> Option<Self> {
+        let section = service_section(output, label)?;
+        let target = section.lines().next()?.trim_end_matches(" = {").to_string();
+        let info = ServiceInfo::parse(&section);
+        let last_spawn_error = info
+            .get("last spawn error")
+            .or_else(|| info.get("spawn error"))
+            .map(ToString::to_string);
+
+        Some(Self {
+            target,
+            endpoints: block_entries(&section, "endpoints"),
+            dependencies: block_entries(&section, "dependencies"),
+            last_spawn_error,
+            info,
+        })
+    }
+}
+
+/// Returns the unindented block of the service, from its header to the
+/// closing brace.
+fn service_section(output: &str, label: &str) -> Option<String> {
+    let header = format!("/{label} = {{");
+    let mut lines = output.lines();
+    let first = lines.find(|line| {
+        !line.starts_with(char::is_whitespace) && line.ends_with(&header)
+    })?;
+
+    let mut section = vec![first];
+    for line in lines {
+        section.push(line);
+        if line == "}" {
+            break;
+        }
+    }
+    Some(section.join("\n"))
+}
+
+/// Returns the names of the entries of a nested block at the top level of
+/// the section, such as the keys of `endpoints = { ... }`.
+fn block_entries(section: &str, name: &str) -> Vec<String> {
+    let header = format!("{name} = {{");
+    let mut entries = Vec::new();
+    let mut depth = 0usize;
+    let mut in_block = false;
+
+    for line in section.lines().map(str::trim) {
+        if line == "}" {
+            depth = depth.saturating_sub(1);
+            if in_block && depth == 1 {
+                in_block = false;
+            }
+            continue;
+        }
+        if in_block && depth == 2 {
+            let entry = line
+                .split(" = ")
+                .next()
+                .and_then(|entry| entry.split(" => ").next())
+                .unwrap_or(line)
+                .trim_end_matches('{')
+                .trim()
+                .trim_matches('"');
+            if !entry.is_empty() {
+                entries.push(entry.to_string());
+            }
+        }
+        if depth == 1 && line == header {
+            in_block = true;
+        }
+        if line.ends_with('{') {
+            depth += 1;
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMPSTATE: &str = "gui/501/co.myrt.other = {
+	state = running
+}
+
+gui/501/co.myrt.ajam = {
+	state = not running
+	program = /usr/local/bin/ajam
+	last spawn error = 78: Function not implemented
+
+	endpoints = {
+		\"co.myrt.ajam.xpc\" = {
+			port = 0x1234
+			active = 0
+		}
+	}
+
+	dependencies = {
+		co.myrt.other
+	}
+}
+
+system/co.myrt.ajam = {
+	state = running
+}
+";
+
+    #[test]
+    fn test_find_service_dump() {
+        let dump = ServiceDump::find(DUMPSTATE, "co.myrt.ajam").unwrap();
+        assert_eq!(dump.target, "gui/501/co.myrt.ajam");
+        assert_eq!(dump.endpoints, vec!["co.myrt.ajam.xpc"]);
+        assert_eq!(dump.dependencies, vec!["co.myrt.other"]);
+        assert_eq!(
+            dump.last_spawn_error.as_deref(),
+            Some("78: Function not implemented")
+        );
+        assert_eq!(dump.info.state(), Some("not running"));
+
+        assert!(ServiceDump::find(DUMPSTATE, "co.myrt.missing").is_none());
+    }
+}
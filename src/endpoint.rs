@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use crate::agent::LaunchAgent;
+use crate::info::ServiceInfo;
+use crate::LaunchctlResult;
+
+/// Kind of endpoint launchd holds on behalf of a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EndpointKind {
+    /// Mach service from the `MachServices` key, listed under `endpoints`.
+    Mach,
+    /// Socket from the `Sockets` key, listed under `sockets`.
+    Socket,
+}
+
+impl EndpointKind {
+    /// Name of the `launchctl print` block listing endpoints of the kind.
+    fn block(self) -> &'static str {
+        match self {
+            Self::Mach => "endpoints",
+            Self::Socket => "sockets",
+        }
+    }
+}
+
+/// Endpoint of a loaded service, as shown by `launchctl print`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    /// Mach service name or socket key.
+    pub name: String,
+    pub kind: EndpointKind,
+    /// Values of the endpoint block, e.g. `port` or `active`.
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Endpoint {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    /// Returns whether a client currently holds the endpoint.
+    pub fn is_active(&self) -> bool {
+        self.get("active") == Some("1")
+    }
+}
+
+/// Difference between the endpoints an agent declares and those launchd
+/// registered for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointMismatch {
+    /// Declared in the plist but not registered. Clients looking it up fail
+    /// without an error from the agent.
+    Missing(EndpointKind, String),
+    /// Registered but not declared, usually left over from an older plist.
+    Undeclared(EndpointKind, String),
+}
+
+impl ServiceInfo {
+    /// Returns the Mach services and sockets registered for the service.
+    pub fn endpoints(&self) -> Vec<Endpoint> {
+        [EndpointKind::Mach, EndpointKind::Socket]
+            .into_iter()
+            .flat_map(|kind| {
+                self.block_entries(kind.block())
+                    .iter()
+                    .map(move |entry| Endpoint {
+                        name: entry.name.clone(),
+                        kind,
+                        fields: entry.fields.clone(),
+                    })
+            })
+            .collect()
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the endpoints launchd registered for the agent, empty if it is
+    /// not loaded.
+    pub fn endpoints(&self) -> LaunchctlResult<Vec<Endpoint>> {
+        Ok(self
+            .info()?
+            .map(|info| info.endpoints())
+            .unwrap_or_default())
+    }
+
+    /// Compare the `MachServices` and `Sockets` the agent declares with the
+    /// registered `endpoints`.
+    pub fn endpoint_mismatches(
+        &self,
+        endpoints: &[Endpoint],
+    ) -> Vec<EndpointMismatch> {
+        let mut declared: Vec<_> = self
+            .mach_service_names()
+            .into_iter()
+            .map(|name| (EndpointKind::Mach, name))
+            .collect();
+        declared.extend(
+            self.sockets
+                .keys()
+                .map(|name| (EndpointKind::Socket, name.clone())),
+        );
+
+        let registered = |kind: EndpointKind, name: &str| {
+            endpoints
+                .iter()
+                .any(|endpoint| endpoint.kind == kind && endpoint.name == name)
+        };
+        let mut mismatches: Vec<_> = declared
+            .iter()
+            .filter(|(kind, name)| !registered(*kind, name))
+            .map(|(kind, name)| EndpointMismatch::Missing(*kind, name.clone()))
+            .collect();
+        mismatches.extend(
+            endpoints
+                .iter()
+                .filter(|endpoint| {
+                    !declared.iter().any(|(kind, name)| {
+                        *kind == endpoint.kind && *name == endpoint.name
+                    })
+                })
+                .map(|endpoint| {
+                    EndpointMismatch::Undeclared(
+                        endpoint.kind,
+                        endpoint.name.clone(),
+                    )
+                }),
+        );
+        mismatches
+    }
+
+    /// Returns how the registered endpoints differ from the declared ones,
+    /// empty if they match or the agent is not loaded.
+    pub fn verify_endpoints(&self) -> LaunchctlResult<Vec<EndpointMismatch>> {
+        match self.info()? {
+            Some(info) => Ok(self.endpoint_mismatches(&info.endpoints())),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::SocketConfig;
+
+    const PRINT_OUTPUT: &str = "gui/501/co.myrt.ajam = {
+	state = waiting
+	endpoints = {
+		\"co.myrt.ajam.xpc\" = {
+			port = 0x2a103
+			active = 1
+			managed = 1
+		}
+		\"co.myrt.ajam.old\" = {
+			port = 0x2a207
+			active = 0
+		}
+	}
+	sockets = {
+		\"Listeners\" = {
+			type = stream
+			path = /tmp/ajam.sock
+		}
+	}
+}
+";
+
+    #[test]
+    fn test_parse_endpoints() {
+        let endpoints = ServiceInfo::parse(PRINT_OUTPUT).endpoints();
+        assert_eq!(endpoints.len(), 3);
+        assert_eq!(endpoints[0].name, "co.myrt.ajam.xpc");
+        assert_eq!(endpoints[0].kind, EndpointKind::Mach);
+        assert_eq!(endpoints[0].get("port"), Some("0x2a103"));
+        assert!(endpoints[0].is_active());
+        assert!(!endpoints[1].is_active());
+        assert_eq!(endpoints[2].name, "Listeners");
+        assert_eq!(endpoints[2].kind, EndpointKind::Socket);
+        assert_eq!(endpoints[2].get("path"), Some("/tmp/ajam.sock"));
+    }
+
+    #[test]
+    fn test_endpoint_mismatches() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        let mut services = plist::Dictionary::new();
+        services.insert("co.myrt.ajam.xpc".to_string(), true.into());
+        services.insert("co.myrt.ajam.new".to_string(), true.into());
+        agent
+            .extra
+            .insert("MachServices".to_string(), services.into());
+        agent
+            .sockets
+            .insert("Listeners".to_string(), SocketConfig::default());
+
+        let endpoints = ServiceInfo::parse(PRINT_OUTPUT).endpoints();
+        assert_eq!(
+            agent.endpoint_mismatches(&endpoints),
+            [
+                EndpointMismatch::Missing(
+                    EndpointKind::Mach,
+                    "co.myrt.ajam.new".to_string()
+                ),
+                EndpointMismatch::Undeclared(
+                    EndpointKind::Mach,
+                    "co.myrt.ajam.old".to_string()
+                ),
+            ]
+        );
+    }
+}
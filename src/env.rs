@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Placeholder shown instead of secret values.
+pub const REDACTED: &str = "<redacted>";
+
+/// Contents of the `EnvironmentVariables` dictionary.
+///
+/// Values can be marked as secret. Secrets are written to the plist like any
+/// other value, but `Debug` output and [`redacted`](Self::redacted) copies
+/// show [`REDACTED`] instead, so tokens do not end up in logs or audit
+/// records. Whether a value is secret is not stored in the plist; mark values
+/// again with [`mark_secret`](Self::mark_secret) after loading. Equality
+/// compares the variables only.
+#[derive(Clone, Default)]
+pub struct Environment {
+    variables: BTreeMap<String, String>,
+    secrets: Vec<String>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a variable.
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.variables.insert(key.to_string(), value.to_string());
+    }
+
+    /// Set a variable whose value must not be shown.
+    pub fn set_secret(&mut self, key: &str, value: &str) {
+        self.set(key, value);
+        self.mark_secret(key);
+    }
+
+    /// Treat the value of an existing or future variable as secret.
+    pub fn mark_secret(&mut self, key: &str) {
+        if !self.is_secret(key) {
+            self.secrets.push(key.to_string());
+        }
+    }
+
+    pub fn is_secret(&self, key: &str) -> bool {
+        self.secrets.iter().any(|secret| secret == key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.variables.get(key).map(String::as_str)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.secrets.retain(|secret| secret != key);
+        self.variables.remove(key)
+    }
+
+    /// Iterate over variables in key order, secrets included.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.variables
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.variables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+    }
+
+    /// Returns a copy with secret values replaced by [`REDACTED`], safe to
+    /// log, diff or store.
+    #[must_use]
+    pub fn redacted(&self) -> Self {
+        let variables = self
+            .variables
+            .iter()
+            .map(|(key, value)| {
+                let value = if self.is_secret(key) { REDACTED } else { value };
+                (key.clone(), value.to_string())
+            })
+            .collect();
+        Self {
+            variables,
+            secrets: self.secrets.clone(),
+        }
+    }
+}
+
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        self.variables == other.variables
+    }
+}
+
+impl Eq for Environment {}
+
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.redacted().variables).finish()
+    }
+}
+
+impl Serialize for Environment {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.variables.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Environment {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Self {
+            variables: BTreeMap::deserialize(deserializer)?,
+            secrets: Vec::new(),
+        })
+    }
+}
+
+impl<K: AsRef<str>, V: AsRef<str>> FromIterator<(K, V)> for Environment {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut environment = Self::new();
+        for (key, value) in iter {
+            environment.set(key.as_ref(), value.as_ref());
+        }
+        environment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{LaunchAgent, LaunchAgentBuilder};
+
+    #[test]
+    fn test_secrets_are_redacted_in_debug() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .env("PATH", "/usr/bin")
+            .env_secret("API_TOKEN", "hunter2")
+            .build()
+            .unwrap();
+
+        let debug = format!("{agent:?}");
+        assert!(debug.contains("/usr/bin"));
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains(REDACTED));
+        assert_eq!(
+            agent.redacted().environment_variables.get("API_TOKEN"),
+            Some(REDACTED)
+        );
+    }
+
+    #[test]
+    fn test_secrets_are_written() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent
+            .environment_variables
+            .set_secret("API_TOKEN", "hunter2");
+
+        let mut buf = Vec::new();
+        plist::to_writer_xml(&mut buf, &agent).unwrap();
+        let written = String::from_utf8(buf.clone()).unwrap();
+        assert!(written.contains("<string>hunter2</string>"));
+
+        let parsed: LaunchAgent = plist::from_bytes(&buf).unwrap();
+        assert_eq!(
+            parsed.environment_variables.get("API_TOKEN"),
+            Some("hunter2")
+        );
+        assert!(!parsed.environment_variables.is_secret("API_TOKEN"));
+    }
+}
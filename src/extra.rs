@@ -0,0 +1,176 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use plist::{Date, Dictionary, Value};
+
+use crate::agent::LaunchAgent;
+use crate::LaunchctlResult;
+
+/// Typed access to plist dictionaries, such as the keys of
+/// [`LaunchAgent::extra`](crate::LaunchAgent::extra) this crate does not
+/// model.
+///
+/// Getters return `None` if the key is missing or holds another type.
+pub trait ExtraKeys {
+    fn get_bool(&self, key: &str) -> Option<bool>;
+    fn get_string(&self, key: &str) -> Option<&str>;
+    /// Returns the integer if it fits into `i64`.
+    fn get_integer(&self, key: &str) -> Option<i64>;
+    /// Returns the real, or the integer converted to `f64`.
+    fn get_real(&self, key: &str) -> Option<f64>;
+    fn get_date(&self, key: &str) -> Option<SystemTime>;
+    fn get_data(&self, key: &str) -> Option<&[u8]>;
+    fn get_array(&self, key: &str) -> Option<&Vec<Value>>;
+    fn get_dictionary(&self, key: &str) -> Option<&Dictionary>;
+
+    fn set_bool(&mut self, key: &str, value: bool);
+    fn set_string(&mut self, key: &str, value: &str);
+    fn set_integer(&mut self, key: &str, value: i64);
+    fn set_real(&mut self, key: &str, value: f64);
+    fn set_date(&mut self, key: &str, value: SystemTime);
+    fn set_data(&mut self, key: &str, value: &[u8]);
+    fn set_array(&mut self, key: &str, value: Vec<Value>);
+    fn set_dictionary(&mut self, key: &str, value: Dictionary);
+}
+
+impl ExtraKeys for Dictionary {
+    fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key)?.as_boolean()
+    }
+
+    fn get_string(&self, key: &str) -> Option<&str> {
+        self.get(key)?.as_string()
+    }
+
+    fn get_integer(&self, key: &str) -> Option<i64> {
+        self.get(key)?.as_signed_integer()
+    }
+
+    fn get_real(&self, key: &str) -> Option<f64> {
+        match self.get(key)? {
+            Value::Real(real) => Some(*real),
+            #[allow(clippy::cast_precision_loss)]
+            Value::Integer(integer) => integer.as_signed().map(|i| i as f64),
+            _ => None,
+        }
+    }
+
+    fn get_date(&self, key: &str) -> Option<SystemTime> {
+        self.get(key)?.as_date().map(SystemTime::from)
+    }
+
+    fn get_data(&self, key: &str) -> Option<&[u8]> {
+        self.get(key)?.as_data()
+    }
+
+    fn get_array(&self, key: &str) -> Option<&Vec<Value>> {
+        self.get(key)?.as_array()
+    }
+
+    fn get_dictionary(&self, key: &str) -> Option<&Dictionary> {
+        self.get(key)?.as_dictionary()
+    }
+
+    fn set_bool(&mut self, key: &str, value: bool) {
+        self.insert(key.to_string(), Value::Boolean(value));
+    }
+
+    fn set_string(&mut self, key: &str, value: &str) {
+        self.insert(key.to_string(), Value::String(value.to_string()));
+    }
+
+    fn set_integer(&mut self, key: &str, value: i64) {
+        self.insert(key.to_string(), Value::Integer(value.into()));
+    }
+
+    fn set_real(&mut self, key: &str, value: f64) {
+        self.insert(key.to_string(), Value::Real(value));
+    }
+
+    fn set_date(&mut self, key: &str, value: SystemTime) {
+        self.insert(key.to_string(), Value::Date(Date::from(value)));
+    }
+
+    fn set_data(&mut self, key: &str, value: &[u8]) {
+        self.insert(key.to_string(), Value::Data(value.to_vec()));
+    }
+
+    fn set_array(&mut self, key: &str, value: Vec<Value>) {
+        self.insert(key.to_string(), Value::Array(value));
+    }
+
+    fn set_dictionary(&mut self, key: &str, value: Dictionary) {
+        self.insert(key.to_string(), Value::Dictionary(value));
+    }
+}
+
+impl LaunchAgent {
+    /// Reads an agent from a plist file, see
+    /// [`from_plist_value`](Self::from_plist_value).
+    pub(crate) fn read_plist(path: &Path) -> LaunchctlResult<Self> {
+        Self::from_plist_value(Value::from_file(path)?)
+    }
+
+    /// Parses an agent from plist bytes, see
+    /// [`from_plist_value`](Self::from_plist_value).
+    pub(crate) fn parse_plist(bytes: &[u8]) -> LaunchctlResult<Self> {
+        Self::from_plist_value(plist::from_bytes(bytes)?)
+    }
+
+    /// Converts a parsed plist into an agent.
+    ///
+    /// Deserializing the agent directly turns dates in extra keys into
+    /// strings, because serde buffers flattened fields without their plist
+    /// types. The extra keys are taken from the parsed plist instead.
+    pub(crate) fn from_plist_value(value: Value) -> LaunchctlResult<Self> {
+        let mut agent: Self = plist::from_value(&value)?;
+        if let Value::Dictionary(mut original) = value {
+            for (key, extra) in &mut agent.extra {
+                if let Some(value) = original.remove(key) {
+                    *extra = value;
+                }
+            }
+        }
+        Ok(agent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_typed_extra_keys_round_trip() {
+        let date = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut nested = Dictionary::new();
+        nested.set_real("Ratio", 0.5);
+        nested.set_data("Blob", &[0, 1, 2]);
+
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent.extra.set_bool("Flag", true);
+        agent.extra.set_integer("Count", -3);
+        agent.extra.set_real("Ratio", 1.25);
+        agent.extra.set_date("Since", date);
+        agent.extra.set_data("Blob", b"\xffdata");
+        agent.extra.set_array(
+            "List",
+            vec![Value::Array(vec![1.into(), "two".into()]), true.into()],
+        );
+        agent.extra.set_dictionary("Nested", nested.clone());
+
+        let bytes = agent.to_bytes().unwrap();
+        let read = LaunchAgent::parse_plist(&bytes).unwrap();
+        assert_eq!(read, agent);
+        assert_eq!(read.extra.get_bool("Flag"), Some(true));
+        assert_eq!(read.extra.get_integer("Count"), Some(-3));
+        assert_eq!(read.extra.get_real("Ratio"), Some(1.25));
+        assert_eq!(read.extra.get_real("Count"), Some(-3.0));
+        assert_eq!(read.extra.get_date("Since"), Some(date));
+        assert_eq!(read.extra.get_data("Blob"), Some(&b"\xffdata"[..]));
+        assert_eq!(read.extra.get_array("List").map(Vec::len), Some(2));
+        assert_eq!(read.extra.get_dictionary("Nested"), Some(&nested));
+        assert_eq!(read.extra.get_string("Flag"), None);
+    }
+}
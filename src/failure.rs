@@ -0,0 +1,230 @@
+use crate::context::ElevationFailure;
+use crate::LaunchAgentError;
+
+/// Classified reason of a failed launchctl command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchctlFailure {
+    /// The service is already loaded into the domain.
+    AlreadyLoaded,
+    /// The service is not loaded into the domain.
+    NotLoaded,
+    /// A disabled override prevents the service from being loaded.
+    Disabled,
+    /// The domain does not exist or does not support the action,
+    /// e.g. the GUI domain in an SSH session.
+    DomainUnavailable,
+    /// The caller is not allowed to manage the service.
+    PermissionDenied,
+    /// launchd refused the plist because of its owner or mode.
+    BadPermissions,
+    /// Generic bootstrap failure, usually a stale registration or a disabled
+    /// service on older macOS releases.
+    InputOutput,
+    /// Failure that does not match any known pattern.
+    Other,
+}
+
+impl LaunchctlFailure {
+    /// Classify a failure from the exit code and output of launchctl.
+    pub fn classify(code: i32, output: &str) -> Self {
+        let output = output.to_lowercase();
+        if output.contains("service is disabled") || code == 119 {
+            Self::Disabled
+        } else if output.contains("already in progress")
+            || output.contains("already loaded")
+            || code == 37
+        {
+            Self::AlreadyLoaded
+        } else if output.contains("domain does not support")
+            || output.contains("could not find domain")
+            || code == 125
+            || code == 112
+        {
+            Self::DomainUnavailable
+        } else if output.contains("could not find service")
+            || output.contains("no such process")
+            || code == 113
+        {
+            Self::NotLoaded
+        } else if output.contains("ownership") || code == 122 {
+            Self::BadPermissions
+        } else if output.contains("operation not permitted")
+            || output.contains("permission denied")
+            || output.contains("not privileged")
+        {
+            Self::PermissionDenied
+        } else if output.contains("input/output error") || code == 5 {
+            Self::InputOutput
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Remediation text for the failure.
+    pub fn suggestion(self) -> Option<&'static str> {
+        Some(match self {
+            Self::AlreadyLoaded => {
+                "service is already loaded; call boot_out() before bootstrapping it again"
+            }
+            Self::NotLoaded => "service is not loaded; call bootstrap() first",
+            Self::Disabled => "service is disabled; call enable() and retry",
+            Self::DomainUnavailable => {
+                "GUI domain unavailable over SSH; run from a logged-in session"
+            }
+            Self::PermissionDenied => {
+                "operation not permitted; run as the user owning the agent or as root"
+            }
+            Self::BadPermissions => {
+                "launchd rejected the plist; repair it with fix_permissions()"
+            }
+            Self::InputOutput => {
+                "bootstrap failed; boot out a stale registration, check that the \
+                 service is not disabled and that the plist is valid"
+            }
+            Self::Other => return None,
+        })
+    }
+}
+
+impl LaunchAgentError {
+    /// Returns the classified launchctl failure, if the error comes from one.
+    pub fn failure(&self) -> Option<LaunchctlFailure> {
+        match self {
+            Self::CommandFailed(code, output) => {
+                Some(LaunchctlFailure::classify(*code, output))
+            }
+            Self::NotPermitted(_) => Some(LaunchctlFailure::PermissionDenied),
+            _ => None,
+        }
+    }
+
+    /// Returns actionable remediation text that can be shown to end users.
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            Self::PListError(_) => {
+                Some("plist is malformed; check it with `plutil -lint <path>`")
+            }
+            Self::WriteError(_) => {
+                Some("check that ~/Library/LaunchAgents exists and is writable")
+            }
+            Self::CommandFailed(..) => {
+                self.failure().and_then(LaunchctlFailure::suggestion)
+            }
+            Self::InvalidBinary(..) => {
+                Some("point the agent at an absolute path of an executable file")
+            }
+            Self::UpdateRolledBack(_) => Some(
+                "new binary did not stay running; launch it manually to see why",
+            ),
+            Self::UpdateRollbackFailed(..) => Some(
+                "neither binary is running; install the agent again to recover",
+            ),
+            Self::LabelConflict(..) => Some(
+                "label is used elsewhere; remove the other definition or rename the agent",
+            ),
+            Self::BadPlistPermissions(..) => {
+                Some("repair the plist owner and mode with fix_permissions()")
+            }
+            Self::StaleRegistration(..) => {
+                Some("load the current plist with rebootstrap()")
+            }
+            Self::ElevationDeclined => {
+                Some("authorization was cancelled; run again and approve the prompt")
+            }
+            Self::ElevationFailed(ElevationFailure::PasswordRequired) => Some(
+                "sudo needs a password; use Elevation::SudoPrompt or run as root",
+            ),
+            Self::ElevationFailed(ElevationFailure::NotAllowed) => {
+                Some("user may not use sudo; ask an administrator to install it")
+            }
+            Self::SIPProtected(_) => Some(
+                "plists under /System ship with macOS; disable the service instead",
+            ),
+            Self::NoAquaSession(_) => Some(
+                "the user is not logged in; install at the next login with FirstRun::NextLogin",
+            ),
+            Self::PlistExists(_) => Some(
+                "another plist is in the way; remove it or choose another WritePolicy",
+            ),
+            Self::NotManaged(_) => Some(
+                "the plist was written by other software; leave it or remove it manually",
+            ),
+            Self::NotPermitted(_) => Some(
+                "daemons and other users' agents need root; use Elevation::Sudo or run as root",
+            ),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let cases = [
+            (
+                5,
+                "Bootstrap failed: 5: Input/output error",
+                LaunchctlFailure::InputOutput,
+            ),
+            (
+                37,
+                "Bootstrap failed: 37: Operation already in progress",
+                LaunchctlFailure::AlreadyLoaded,
+            ),
+            (
+                113,
+                "Could not find service \"x\" in domain for port",
+                LaunchctlFailure::NotLoaded,
+            ),
+            (
+                119,
+                "Bootstrap failed: 119: Service is disabled",
+                LaunchctlFailure::Disabled,
+            ),
+            (
+                125,
+                "Bootstrap failed: 125: Domain does not support specified action",
+                LaunchctlFailure::DomainUnavailable,
+            ),
+            (
+                1,
+                "Operation not permitted",
+                LaunchctlFailure::PermissionDenied,
+            ),
+            (
+                113,
+                "Could not find domain for port identifier: gui/501",
+                LaunchctlFailure::DomainUnavailable,
+            ),
+            (1, "Unrecognized target specifier.", LaunchctlFailure::Other),
+            (42, "Something else", LaunchctlFailure::Other),
+        ];
+        for (code, output, expected) in cases {
+            assert_eq!(
+                LaunchctlFailure::classify(code, output),
+                expected,
+                "{output}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_suggestion() {
+        let err = LaunchAgentError::CommandFailed(
+            119,
+            "Bootstrap failed: 119: Service is disabled".to_string(),
+        );
+        assert_eq!(err.failure(), Some(LaunchctlFailure::Disabled));
+        assert!(err.suggestion().unwrap().contains("enable()"));
+
+        let err = LaunchAgentError::CommandFailed(42, "Something else".to_string());
+        assert_eq!(err.suggestion(), None);
+
+        let err = LaunchAgentError::InvalidVersion("x".to_string());
+        assert_eq!(err.failure(), None);
+        assert_eq!(err.suggestion(), None);
+    }
+}
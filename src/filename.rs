@@ -0,0 +1,148 @@
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::agent::LaunchAgent;
+
+/// Returns the name in Unicode Normalization Form C.
+///
+/// HFS+ stores file names decomposed, APFS keeps them as written but
+/// compares them normalized, so `é` may be one code point or two depending
+/// on where a name came from. Names are composed before they become paths or
+/// are compared with directory entries.
+pub(crate) fn nfc(name: &str) -> String {
+    name.nfc().collect()
+}
+
+impl LaunchAgent {
+    /// Returns an existing plist whose name differs from the agent's only
+    /// by case or Unicode normalization, e.g. `Co.Myrt.Ajam.plist` for
+    /// `co.myrt.ajam`.
+    ///
+    /// APFS is case-insensitive by default, so such a file is the same file
+    /// to macOS and launchd while being a different name to this crate. On
+    /// case-sensitive volumes the two would be near-duplicates.
+    pub fn case_collision(&self) -> Option<PathBuf> {
+        let path = self.path().ok()?;
+        let (dir, name) = (path.parent()?, path.file_name()?.to_str()?);
+        std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .map(|entry| entry.file_name())
+            .find(|entry| {
+                entry.to_str().is_some_and(|entry| {
+                    entry != name && nfc(entry).eq_ignore_ascii_case(name)
+                })
+            })
+            .map(|entry| dir.join(entry))
+    }
+
+    /// Returns the plist colliding by case with the agent's, see
+    /// [`case_collision`](Self::case_collision), and the agent it defines,
+    /// if that is the same agent: its label equals ours ignoring case.
+    /// launchd labels are case-sensitive, so a plist with another label
+    /// belongs to another agent, e.g. of another vendor.
+    pub(crate) fn case_duplicate(&self) -> Option<(PathBuf, Self)> {
+        let collision = self.case_collision()?;
+        let other = Self::read_plist(&collision).ok()?;
+        nfc(&other.label)
+            .eq_ignore_ascii_case(&nfc(&self.label))
+            .then_some((collision, other))
+    }
+
+    /// Remove a plist colliding by case once the agent was written to
+    /// `written`, so it replaces the plist instead of leaving two spellings
+    /// of the same agent. On case-insensitive volumes both names may be the
+    /// written file, which is kept.
+    pub(crate) fn remove_case_collision(
+        &self,
+        collision: &Path,
+        written: &Path,
+    ) -> io::Result<()> {
+        if is_same_file(collision, written) {
+            return Ok(());
+        }
+        log::warn!(
+            target: "lunchctl",
+            "replacing {} with the plist of {}",
+            collision.display(),
+            self.label
+        );
+        std::fs::remove_file(collision)
+    }
+}
+
+/// Check if both paths name the same existing file.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => (a.dev(), a.ino()) == (b.dev(), b.ino()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::LaunchctlContext;
+
+    #[test]
+    fn test_case_collision() {
+        let id = rand::random_range(0..u32::MAX);
+        let agent = LaunchAgent::new(&format!("co.myrt.case.{id}"));
+        let shouted = LaunchAgent::new(&format!("CO.MYRT.CASE.{id}"));
+        assert_eq!(agent.case_collision(), None);
+
+        shouted.write().unwrap();
+        let case_sensitive = !agent.path().unwrap().exists();
+        assert_eq!(agent.case_collision(), Some(shouted.path().unwrap()));
+        assert!(agent
+            .diagnose()
+            .iter()
+            .any(|finding| finding.code == "case-collision"));
+
+        LaunchctlContext::idle().write(&agent).unwrap();
+        assert_eq!(agent.case_collision(), None);
+        assert_eq!(shouted.path().unwrap().exists(), !case_sensitive);
+        agent.remove().unwrap();
+    }
+
+    #[test]
+    fn test_case_collision_of_another_agent() {
+        let id = rand::random_range(0..u32::MAX);
+        let agent = LaunchAgent::new(&format!("co.myrt.vendor.{id}"));
+        let mut foreign = LaunchAgent::new(&format!("com.vendor.{id}"));
+        foreign.file_stem = Some(format!("CO.MYRT.VENDOR.{id}"));
+        foreign.write().unwrap();
+        let case_sensitive = !agent.path().unwrap().exists();
+        assert_eq!(agent.case_duplicate(), None);
+
+        LaunchctlContext::idle().write(&agent).unwrap();
+        assert!(foreign.path().unwrap().exists());
+        agent.remove().unwrap();
+        if case_sensitive {
+            foreign.remove().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_decomposed_label() {
+        let id = rand::random_range(0..u32::MAX);
+        let composed = LaunchAgent::new(&format!("co.myrt.caf\u{e9}.{id}"));
+        let decomposed = LaunchAgent::new(&format!("co.myrt.cafe\u{301}.{id}"));
+        assert_ne!(composed.label, decomposed.label);
+        assert_eq!(composed.path().unwrap(), decomposed.path().unwrap());
+
+        let dir = composed.path().unwrap().parent().unwrap().to_path_buf();
+        std::fs::create_dir_all(&dir).unwrap();
+        let stray = dir.join(format!("co.myrt.cafe\u{301}.{id}.plist"));
+        decomposed.write_plist_to(&stray).unwrap();
+        assert_eq!(composed.case_collision(), Some(stray));
+
+        LaunchctlContext::idle().write(&decomposed).unwrap();
+        assert!(LaunchAgent::exists(&composed.label));
+        assert_eq!(composed.case_collision(), None);
+        composed.remove().unwrap();
+    }
+}
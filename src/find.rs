@@ -0,0 +1,174 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::context::LaunchctlContext;
+use crate::domain::{DomainTarget, ServiceTarget};
+use crate::inspect::{DirectoryReport, Inspector};
+use crate::paths::LaunchDirectory;
+use crate::LaunchctlResult;
+
+/// Place where a label was found by [`find`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelLocation {
+    /// A plist in a standard directory defines the label.
+    Plist {
+        directory: LaunchDirectory,
+        path: PathBuf,
+    },
+    /// A plist outside the standard directories defines the label, e.g. in
+    /// the directory of a [`LaunchctlContext`].
+    OtherPlist(PathBuf),
+    /// A service with the label is loaded into the domain.
+    Loaded(DomainTarget),
+}
+
+impl LabelLocation {
+    /// Returns the location of the plist at `path`, in a standard directory
+    /// or not.
+    pub(crate) fn plist(path: PathBuf) -> Self {
+        match LaunchDirectory::for_path(&path) {
+            Some(directory) => Self::Plist { directory, path },
+            None => Self::OtherPlist(path),
+        }
+    }
+}
+
+impl fmt::Display for LabelLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Plist { path, .. } | Self::OtherPlist(path) => {
+                write!(f, "plist {}", path.display())
+            }
+            Self::Loaded(domain) => write!(f, "loaded in {domain}"),
+        }
+    }
+}
+
+/// Search for a label in the standard directories software installs into
+/// and in the current user's GUI and user domains as well as the system
+/// domain. The directories of macOS itself are not scanned; its services
+/// are still found when loaded.
+///
+/// A user agent and a system daemon sharing a label behave in confusing ways,
+/// so callers should check that only the expected location is returned.
+pub fn find(label: &str) -> LaunchctlResult<Vec<LabelLocation>> {
+    LaunchctlContext::default().find(label)
+}
+
+impl LaunchctlContext {
+    /// Search for a label like [`find`], printing the services with the
+    /// context's settings.
+    pub fn find(&self, label: &str) -> LaunchctlResult<Vec<LabelLocation>> {
+        let mut locations = plist_locations(&Inspector::new().inspect(), label);
+
+        let domains = [
+            DomainTarget::current_gui(),
+            DomainTarget::current_user(),
+            DomainTarget::System,
+        ];
+        for domain in domains {
+            let target = ServiceTarget::new(domain, label);
+            if self.print_target(&target)?.is_some() {
+                locations.push(LabelLocation::Loaded(domain));
+            }
+        }
+
+        Ok(locations)
+    }
+}
+
+/// Returns plists that are named after the label or define it.
+pub(crate) fn plist_locations(
+    reports: &[DirectoryReport],
+    label: &str,
+) -> Vec<LabelLocation> {
+    let file_name = format!("{label}.plist");
+    reports
+        .iter()
+        .flat_map(|report| {
+            report
+                .plists
+                .iter()
+                .filter(|plist| {
+                    plist
+                        .path
+                        .file_name()
+                        .is_some_and(|name| name == file_name.as_str())
+                        || plist
+                            .agent
+                            .as_ref()
+                            .is_ok_and(|agent| agent.label == label)
+                })
+                .map(|plist| LabelLocation::Plist {
+                    directory: report.directory,
+                    path: plist.path.clone(),
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgent;
+    use crate::inspect::InspectedPlist;
+    use crate::origin::AgentOrigin;
+
+    #[test]
+    fn test_plist_locations() {
+        let reports = vec![
+            DirectoryReport {
+                directory: LaunchDirectory::UserAgents,
+                path: PathBuf::from("/Users/me/Library/LaunchAgents"),
+                plists: vec![
+                    InspectedPlist {
+                        path: PathBuf::from(
+                            "/Users/me/Library/LaunchAgents/co.myrt.ajam.plist",
+                        ),
+                        agent: Ok(LaunchAgent::new("co.myrt.ajam")),
+                        requires_root: false,
+                        origin: AgentOrigin::HandWritten,
+                    },
+                    InspectedPlist {
+                        path: PathBuf::from(
+                            "/Users/me/Library/LaunchAgents/other.plist",
+                        ),
+                        agent: Ok(LaunchAgent::new("other")),
+                        requires_root: false,
+                        origin: AgentOrigin::HandWritten,
+                    },
+                ],
+                error: None,
+            },
+            DirectoryReport {
+                directory: LaunchDirectory::GlobalDaemons,
+                path: PathBuf::from("/Library/LaunchDaemons"),
+                plists: vec![InspectedPlist {
+                    path: PathBuf::from("/Library/LaunchDaemons/vendor.plist"),
+                    agent: Ok(LaunchAgent::new("co.myrt.ajam")),
+                    requires_root: false,
+                    origin: AgentOrigin::HandWritten,
+                }],
+                error: None,
+            },
+        ];
+
+        let locations = plist_locations(&reports, "co.myrt.ajam");
+        assert_eq!(
+            locations,
+            vec![
+                LabelLocation::Plist {
+                    directory: LaunchDirectory::UserAgents,
+                    path: PathBuf::from(
+                        "/Users/me/Library/LaunchAgents/co.myrt.ajam.plist"
+                    ),
+                },
+                LabelLocation::Plist {
+                    directory: LaunchDirectory::GlobalDaemons,
+                    path: PathBuf::from("/Library/LaunchDaemons/vendor.plist"),
+                },
+            ]
+        );
+        assert!(plist_locations(&reports, "missing").is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::diagnose::Finding;
+use crate::dirs::create_private_dir;
+use crate::LaunchctlResult;
+
+/// Remediation of a [`Finding`], applied with [`Finding::fix`].
+///
+/// Serialized as its kebab-case name, with the path for
+/// `create-directory`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fix {
+    /// Clear the disabled override, see
+    /// [`is_disabled_by_override`](LaunchAgent::is_disabled_by_override).
+    Enable,
+    /// See [`LaunchAgent::fix_permissions`].
+    FixPermissions,
+    /// See [`LaunchAgent::rebootstrap`].
+    Rebootstrap,
+    /// Create a missing directory, private to the user.
+    CreateDirectory(PathBuf),
+}
+
+impl Fix {
+    /// Apply the remediation to `agent`.
+    pub fn apply(&self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        LaunchctlContext::default().apply_fix(agent, self)
+    }
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Enable => f.write_str("enable the agent"),
+            Self::FixPermissions => f.write_str("fix the plist permissions"),
+            Self::Rebootstrap => f.write_str("bootstrap the agent again"),
+            Self::CreateDirectory(path) => write!(f, "create {}", path.display()),
+        }
+    }
+}
+
+impl Finding {
+    #[must_use]
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+
+    /// Apply the finding's remediation to `agent`, the agent it was found
+    /// in. Returns `false` if the finding has none.
+    pub fn fix(&self, agent: &LaunchAgent) -> LaunchctlResult<bool> {
+        let Some(fix) = &self.fix else {
+            return Ok(false);
+        };
+        fix.apply(agent)?;
+        Ok(true)
+    }
+}
+
+impl LaunchAgent {
+    /// Apply the remediations of the findings, e.g. of
+    /// [`diagnose_loaded`](Self::diagnose_loaded). Returns the findings that
+    /// were fixed; the first failing remediation stops the repair.
+    pub fn fix_findings<'a>(
+        &self,
+        findings: &'a [Finding],
+    ) -> LaunchctlResult<Vec<&'a Finding>> {
+        LaunchctlContext::default().fix_findings(self, findings)
+    }
+}
+
+impl LaunchctlContext {
+    /// Apply the remediation to `agent` with the context's settings, see
+    /// [`Fix::apply`]. Directories are not created in a dry run.
+    pub fn apply_fix(&self, agent: &LaunchAgent, fix: &Fix) -> LaunchctlResult<()> {
+        match fix {
+            Fix::Enable => self.enable(agent),
+            Fix::FixPermissions => self.fix_permissions(agent),
+            Fix::Rebootstrap => self.rebootstrap(agent).map(|_| ()),
+            Fix::CreateDirectory(_) if self.is_dry_run() => Ok(()),
+            Fix::CreateDirectory(path) => Ok(create_private_dir(path)?),
+        }
+    }
+
+    /// Apply the remediations of the findings with the context's settings,
+    /// see [`LaunchAgent::fix_findings`].
+    pub fn fix_findings<'a>(
+        &self,
+        agent: &LaunchAgent,
+        findings: &'a [Finding],
+    ) -> LaunchctlResult<Vec<&'a Finding>> {
+        let mut fixed = Vec::new();
+        for finding in findings {
+            if let Some(fix) = &finding.fix {
+                self.apply_fix(agent, fix)?;
+                fixed.push(finding);
+            }
+        }
+        Ok(fixed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::domain::DomainTarget;
+    use crate::lint;
+
+    #[test]
+    fn test_create_directory_fix() {
+        let label = format!("co.myrt.fix.{}", rand::random_range(0..u32::MAX));
+        let dir = std::env::temp_dir().join(&label);
+        let mut agent = LaunchAgent::new(&label);
+        agent.program_arguments = vec!["/bin/true".into()];
+        agent.standard_out_path = dir.join("out.log");
+
+        let findings = agent.diagnose();
+        let finding = findings
+            .iter()
+            .find(|f| f.code == "missing-log-directory")
+            .unwrap();
+        assert_eq!(finding.fix, Some(Fix::CreateDirectory(dir.clone())));
+        assert_eq!(agent.fix_findings(&findings).unwrap().len(), 1);
+        assert!(dir.is_dir());
+        assert!(agent.diagnose().is_empty());
+        std::fs::remove_dir(dir).unwrap();
+    }
+
+    #[test]
+    fn test_fixes_use_context() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let context = LaunchctlContext::new()
+            .directory("/Library/LaunchDaemons")
+            .domain(DomainTarget::System)
+            .dry_run(true)
+            .on_command(move |command| {
+                recorder.lock().unwrap().push(command.to_string());
+            });
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        let dir = std::env::temp_dir()
+            .join(format!("co.myrt.fix.{}", rand::random_range(0..u32::MAX)));
+        let findings = [
+            lint::DISABLED.warning("disabled").with_fix(Fix::Enable),
+            lint::STALE_REGISTRATION
+                .error("stale")
+                .with_fix(Fix::Rebootstrap),
+            lint::MISSING_LOG_DIRECTORY
+                .warning("missing")
+                .with_fix(Fix::CreateDirectory(dir.clone())),
+        ];
+
+        assert_eq!(context.fix_findings(&agent, &findings).unwrap().len(), 3);
+        assert!(!dir.exists());
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                "launchctl enable system/co.myrt.ajam",
+                "launchctl print system/co.myrt.ajam",
+                "launchctl bootstrap system /Library/LaunchDaemons/co.myrt.ajam.plist",
+            ]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use crate::agent::LaunchAgent;
+use crate::LaunchctlResult;
+
+/// Real-world plist for testing tools built on this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fixture {
+    /// Label of the job the fixture is modelled after.
+    pub name: &'static str,
+    /// Which kind of configuration the fixture represents.
+    pub description: &'static str,
+    pub xml: &'static str,
+}
+
+/// Representative plists as installed by Homebrew and common vendors.
+pub const FIXTURES: &[Fixture] = &[
+    Fixture {
+        name: "homebrew.mxcl.postgresql@14",
+        description: "Homebrew service kept alive with a working directory",
+        xml: r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>EnvironmentVariables</key>
+	<dict>
+		<key>LC_ALL</key>
+		<string>en_US.UTF-8</string>
+	</dict>
+	<key>KeepAlive</key>
+	<true/>
+	<key>Label</key>
+	<string>homebrew.mxcl.postgresql@14</string>
+	<key>LimitLoadToSessionType</key>
+	<array>
+		<string>Aqua</string>
+		<string>Background</string>
+		<string>LoginWindow</string>
+		<string>StandardIO</string>
+		<string>System</string>
+	</array>
+	<key>ProgramArguments</key>
+	<array>
+		<string>/opt/homebrew/opt/postgresql@14/bin/postgres</string>
+		<string>-D</string>
+		<string>/opt/homebrew/var/postgresql@14</string>
+	</array>
+	<key>RunAtLoad</key>
+	<true/>
+	<key>StandardErrorPath</key>
+	<string>/opt/homebrew/var/log/postgresql@14.log</string>
+	<key>StandardOutPath</key>
+	<string>/opt/homebrew/var/log/postgresql@14.log</string>
+	<key>WorkingDirectory</key>
+	<string>/opt/homebrew</string>
+</dict>
+</plist>
+"#,
+    },
+    Fixture {
+        name: "homebrew.mxcl.syncthing",
+        description: "Homebrew service restarted only after crashes",
+        xml: r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>KeepAlive</key>
+	<dict>
+		<key>SuccessfulExit</key>
+		<false/>
+	</dict>
+	<key>Label</key>
+	<string>homebrew.mxcl.syncthing</string>
+	<key>ProcessType</key>
+	<string>Background</string>
+	<key>ProgramArguments</key>
+	<array>
+		<string>/opt/homebrew/opt/syncthing/bin/syncthing</string>
+		<string>-no-browser</string>
+		<string>-no-restart</string>
+	</array>
+	<key>RunAtLoad</key>
+	<true/>
+	<key>StandardErrorPath</key>
+	<string>/opt/homebrew/var/log/syncthing.log</string>
+	<key>StandardOutPath</key>
+	<string>/opt/homebrew/var/log/syncthing.log</string>
+</dict>
+</plist>
+"#,
+    },
+    Fixture {
+        name: "com.google.keystone.agent",
+        description: "Vendor updater started on an interval in Aqua sessions",
+        xml: r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>Label</key>
+	<string>com.google.keystone.agent</string>
+	<key>LimitLoadToSessionType</key>
+	<string>Aqua</string>
+	<key>ProgramArguments</key>
+	<array>
+		<string>/Users/me/Library/Google/GoogleSoftwareUpdate/GoogleSoftwareUpdate.bundle/Contents/Resources/GoogleSoftwareUpdateAgent.app/Contents/MacOS/GoogleSoftwareUpdateAgent</string>
+		<string>-runMode</string>
+		<string>ifneeded</string>
+	</array>
+	<key>RunAtLoad</key>
+	<true/>
+	<key>StandardErrorPath</key>
+	<string>/dev/null</string>
+	<key>StandardOutPath</key>
+	<string>/dev/null</string>
+	<key>StartInterval</key>
+	<integer>3523</integer>
+</dict>
+</plist>
+"#,
+    },
+    Fixture {
+        name: "com.microsoft.update.agent",
+        description: "Vendor agent with a calendar schedule and Mach services",
+        xml: r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>Label</key>
+	<string>com.microsoft.update.agent</string>
+	<key>LimitLoadToSessionType</key>
+	<array>
+		<string>Aqua</string>
+	</array>
+	<key>MachServices</key>
+	<dict>
+		<key>com.microsoft.update.agent</key>
+		<true/>
+	</dict>
+	<key>ProgramArguments</key>
+	<array>
+		<string>/Library/Application Support/Microsoft/MAU2.0/Microsoft AutoUpdate.app/Contents/MacOS/Microsoft Update Assistant.app/Contents/MacOS/Microsoft Update Assistant</string>
+		<string>--launchByAgent</string>
+	</array>
+	<key>StartCalendarInterval</key>
+	<array>
+		<dict>
+			<key>Hour</key>
+			<integer>10</integer>
+			<key>Minute</key>
+			<integer>30</integer>
+		</dict>
+		<dict>
+			<key>Hour</key>
+			<integer>22</integer>
+			<key>Minute</key>
+			<integer>30</integer>
+		</dict>
+	</array>
+	<key>ThrottleInterval</key>
+	<integer>300</integer>
+</dict>
+</plist>
+"#,
+    },
+    Fixture {
+        name: "com.docker.socket",
+        description: "Socket-activated helper with an owned Unix socket",
+        xml: r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>Label</key>
+	<string>com.docker.socket</string>
+	<key>Program</key>
+	<string>/Library/PrivilegedHelperTools/com.docker.socket</string>
+	<key>ProgramArguments</key>
+	<array>
+		<string>/Library/PrivilegedHelperTools/com.docker.socket</string>
+		<string>/Users/me/.docker/run/docker.sock</string>
+		<string>/var/run/docker.sock</string>
+	</array>
+	<key>Sockets</key>
+	<dict>
+		<key>Listener</key>
+		<dict>
+			<key>SockPathMode</key>
+			<integer>438</integer>
+			<key>SockPathName</key>
+			<string>/var/run/com.docker.socket.sock</string>
+			<key>SockPathOwner</key>
+			<integer>0</integer>
+		</dict>
+	</dict>
+	<key>TimeOut</key>
+	<integer>30</integer>
+	<key>Version</key>
+	<string>4.30.0</string>
+</dict>
+</plist>
+"#,
+    },
+];
+
+/// Parse a plist as a [`LaunchAgent`], write it back and describe every key
+/// of the original that is missing or changed in the result.
+///
+/// Keys the crate adds with their default values, such as `RunAtLoad`, are
+/// not reported. An empty list means the round trip is lossless.
+pub fn round_trip_losses(xml: &[u8]) -> LaunchctlResult<Vec<String>> {
+    let original: plist::Value = plist::from_bytes(xml)?;
+    let agent = LaunchAgent::parse_plist(xml)?;
+    let mut written = Vec::new();
+    plist::to_writer_xml(&mut written, &agent)?;
+    let written: plist::Value = plist::from_bytes(&written)?;
+
+    let mut losses = Vec::new();
+    compare("", &original, &written, &mut losses);
+    Ok(losses)
+}
+
+/// Panic with the lost keys unless the plist survives a round trip through
+/// [`LaunchAgent`], see [`round_trip_losses`].
+pub fn assert_lossless(xml: &str) {
+    let losses = round_trip_losses(xml.as_bytes()).expect("plist is a valid agent");
+    assert!(losses.is_empty(), "round trip lost: {}", losses.join(", "));
+}
+
+fn compare(
+    path: &str,
+    original: &plist::Value,
+    written: &plist::Value,
+    losses: &mut Vec<String>,
+) {
+    match (original, written) {
+        (plist::Value::Dictionary(original), plist::Value::Dictionary(written)) => {
+            for (key, value) in original {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                match written.get(key) {
+                    Some(written) => compare(&path, value, written, losses),
+                    None => losses.push(format!("{path} is missing")),
+                }
+            }
+        }
+        (original, written) if original != written => {
+            losses.push(format!("{path} changed from {original:?} to {written:?}"));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixtures_round_trip() {
+        for fixture in FIXTURES {
+            assert_lossless(fixture.xml);
+        }
+    }
+
+    #[test]
+    fn test_losses_are_reported() {
+        let xml = FIXTURES[0].xml.replace(
+            "<key>RunAtLoad</key>\n\t<true/>",
+            "<key>RunAtLoad</key>\n\t<string>yes</string>",
+        );
+        assert!(round_trip_losses(xml.as_bytes()).is_err());
+
+        let original = plist::Value::from(plist::Dictionary::from_iter([(
+            "Sockets".to_string(),
+            plist::Value::from(1),
+        )]));
+        let mut losses = Vec::new();
+        compare(
+            "",
+            &original,
+            &plist::Value::Dictionary(plist::Dictionary::new()),
+            &mut losses,
+        );
+        assert_eq!(losses, ["Sockets is missing"]);
+    }
+}
@@ -0,0 +1,252 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::keepalive::KeepAlive;
+use crate::manifest::run_bounded;
+
+/// Number of agents queried at the same time by [`FleetStatus::collect`].
+const STATUS_CONCURRENCY: usize = 8;
+
+/// Status of a set of agents, e.g. for a status table or a JSON export.
+///
+/// `Display` renders a table with a row per agent. A description column is
+/// added if any agent has a description.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FleetStatus {
+    pub agents: Vec<AgentStatus>,
+}
+
+/// Status of one agent in a [`FleetStatus`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AgentStatus {
+    pub label: String,
+    /// State reported by launchd, `not loaded` if launchd does not know the
+    /// agent, or `unknown` if it could not be queried.
+    pub state: String,
+    pub pid: Option<u32>,
+    pub last_exit_code: Option<i32>,
+    /// When launchd starts the agent, e.g. `every 300s` or `keep alive`.
+    pub schedule: String,
+    /// Description stored in the configuration, see
+    /// [`LaunchAgent::description`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Why the agent could not be queried.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FleetStatus {
+    /// Query the status of every agent, several at a time.
+    pub fn collect(agents: &[LaunchAgent]) -> Self {
+        LaunchctlContext::default().fleet_status(agents)
+    }
+
+    /// Returns the status of the agent with the label.
+    pub fn get(&self, label: &str) -> Option<&AgentStatus> {
+        self.agents.iter().find(|status| status.label == label)
+    }
+
+    /// Returns the statuses of running agents.
+    pub fn running(&self) -> impl Iterator<Item = &AgentStatus> {
+        self.agents.iter().filter(|status| status.pid.is_some())
+    }
+}
+
+impl AgentStatus {
+    /// Query the status of the agent.
+    pub fn of(agent: &LaunchAgent) -> Self {
+        LaunchctlContext::default().agent_status(agent)
+    }
+}
+
+impl LaunchctlContext {
+    /// Query the status of every agent in the context's domain, see
+    /// [`FleetStatus::collect`].
+    pub fn fleet_status(&self, agents: &[LaunchAgent]) -> FleetStatus {
+        let agents = run_bounded(agents, STATUS_CONCURRENCY, |agent| {
+            self.agent_status(agent)
+        });
+        FleetStatus { agents }
+    }
+
+    /// Query the status of the agent in the context's domain.
+    pub fn agent_status(&self, agent: &LaunchAgent) -> AgentStatus {
+        let mut status = AgentStatus {
+            label: agent.label.clone(),
+            state: "not loaded".to_string(),
+            pid: None,
+            last_exit_code: None,
+            schedule: schedule(agent),
+            description: agent.description().map(ToString::to_string),
+            error: None,
+        };
+        match self.info(agent) {
+            Ok(Some(info)) => {
+                status.state = info.state().unwrap_or("unknown").to_string();
+                status.pid = info.pid();
+                status.last_exit_code = info.last_exit_code();
+            }
+            Ok(None) => {}
+            Err(e) => {
+                status.state = "unknown".to_string();
+                status.error = Some(e.to_string());
+            }
+        }
+        status
+    }
+}
+
+/// Describes when launchd starts the agent.
+fn schedule(agent: &LaunchAgent) -> String {
+    let mut parts = Vec::new();
+    if let Some(seconds) = agent
+        .extra
+        .get("StartInterval")
+        .and_then(plist::Value::as_signed_integer)
+    {
+        parts.push(format!("every {seconds}s"));
+    }
+    if agent.extra.contains_key("StartCalendarInterval") {
+        parts.push("calendar".to_string());
+    }
+    match &agent.keep_alive {
+        KeepAlive::Always(true) => parts.push("keep alive".to_string()),
+        KeepAlive::Conditions(_) => parts.push("keep alive if".to_string()),
+        KeepAlive::Always(false) => {}
+    }
+    if agent.run_at_load {
+        parts.push("at load".to_string());
+    }
+    if parts.is_empty() {
+        return "on demand".to_string();
+    }
+    parts.join(", ")
+}
+
+impl fmt::Display for FleetStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = |header: &str, column: fn(&AgentStatus) -> usize| {
+            self.agents
+                .iter()
+                .map(column)
+                .fold(header.len(), usize::max)
+        };
+        let label_width = width("LABEL", |s| s.label.len());
+        let state_width = width("STATE", |s| s.state.len());
+        let schedule_width = width("SCHEDULE", |s| s.schedule.len());
+        let described = self.agents.iter().any(|s| s.description.is_some());
+
+        let header = format!(
+            "{:label_width$}  {:state_width$}  {:>7}  {:>9}  {:schedule_width$}  DESCRIPTION",
+            "LABEL", "STATE", "PID", "LAST EXIT", "SCHEDULE"
+        );
+        writeln!(f, "{}", trim_row(&header, described))?;
+        for status in &self.agents {
+            let pid = status.pid.map_or("-".to_string(), |pid| pid.to_string());
+            let last_exit = status
+                .last_exit_code
+                .map_or("-".to_string(), |code| code.to_string());
+            let row = format!(
+                "{:label_width$}  {:state_width$}  {pid:>7}  {last_exit:>9}  {:schedule_width$}  {}",
+                status.label,
+                status.state,
+                status.schedule,
+                status.description.as_deref().unwrap_or("-"),
+            );
+            writeln!(f, "{}", trim_row(&row, described))?;
+        }
+        Ok(())
+    }
+}
+
+/// Drop the description column unless it is shown, and trailing padding.
+fn trim_row(row: &str, described: bool) -> &str {
+    let row = if described {
+        row
+    } else {
+        row.rsplit_once("  ").map_or(row, |(columns, _)| columns)
+    };
+    row.trim_end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+
+    #[test]
+    fn test_schedule() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        assert_eq!(schedule(&agent), "on demand");
+
+        agent.extra.insert("StartInterval".to_string(), 300.into());
+        agent.run_at_load = true;
+        assert_eq!(schedule(&agent), "every 300s, at load");
+
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .keep_alive(true)
+            .build()
+            .unwrap();
+        assert_eq!(schedule(&agent), "keep alive");
+    }
+
+    #[test]
+    fn test_display() {
+        let fleet = FleetStatus {
+            agents: vec![
+                AgentStatus {
+                    label: "co.myrt.ajam".to_string(),
+                    state: "running".to_string(),
+                    pid: Some(4312),
+                    last_exit_code: None,
+                    schedule: "keep alive".to_string(),
+                    description: None,
+                    error: None,
+                },
+                AgentStatus {
+                    label: "co.myrt.backup".to_string(),
+                    state: "not loaded".to_string(),
+                    pid: None,
+                    last_exit_code: Some(1),
+                    schedule: "calendar".to_string(),
+                    description: None,
+                    error: None,
+                },
+            ],
+        };
+        assert_eq!(
+            fleet.to_string(),
+            "\
+LABEL           STATE           PID  LAST EXIT  SCHEDULE
+co.myrt.ajam    running        4312          -  keep alive
+co.myrt.backup  not loaded        -          1  calendar
+"
+        );
+        assert_eq!(fleet.running().count(), 1);
+
+        let mut fleet = fleet;
+        fleet.agents[0].description = Some("Ajam sync".to_string());
+        assert_eq!(
+            fleet.to_string(),
+            "\
+LABEL           STATE           PID  LAST EXIT  SCHEDULE    DESCRIPTION
+co.myrt.ajam    running        4312          -  keep alive  Ajam sync
+co.myrt.backup  not loaded        -          1  calendar    -
+"
+        );
+    }
+
+    #[test]
+    fn test_collect() {
+        let agents = vec![LaunchAgent::new("co.myrt.ajam.fleet.missing")];
+        let fleet = LaunchctlContext::idle().fleet_status(&agents);
+        let status = fleet.get("co.myrt.ajam.fleet.missing").unwrap();
+        assert_eq!(status.state, "not loaded");
+        assert_eq!(status.pid, None);
+    }
+}
@@ -0,0 +1,304 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{write_atomically, LaunchAgent};
+use crate::context::LaunchctlContext;
+use crate::dirs::{crate_state_dir, create_private_dir};
+use crate::info::ServiceInfo;
+use crate::stats::{SpawnHistory, SpawnStats};
+use crate::LaunchctlResult;
+
+/// Events kept per agent, oldest are dropped first.
+const MAX_EVENTS: usize = 200;
+
+/// What was observed in a [`HistoryEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistoryEventKind {
+    Started,
+    Exited,
+}
+
+/// Start or exit of an agent, observed by
+/// [`LaunchAgent::record_history`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HistoryEvent {
+    /// Seconds since the Unix epoch when the event was observed.
+    pub timestamp: u64,
+    pub kind: HistoryEventKind,
+    /// Process of the run, `None` for runs that started and exited between
+    /// two observations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// Exit code of an exit, `None` if launchd did not report one, e.g.
+    /// because the agent was unloaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+/// Starts and exits of an agent, persisted across reboots.
+///
+/// launchd only reports the current process and the last exit code, so the
+/// history is built by observing the agent periodically, e.g. from a
+/// [`Supervisor`](crate::Supervisor) with
+/// [`SupervisionPolicy::record_history`](crate::SupervisionPolicy::record_history).
+/// Runs that start and exit between two observations are recorded as a
+/// single exit.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExitHistory {
+    pub events: Vec<HistoryEvent>,
+    /// Spawn counters taken at each observation of the loaded service.
+    #[serde(default)]
+    pub spawns: SpawnHistory,
+    /// Process seen by the last observation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_pid: Option<u32>,
+    /// Run counter seen by the last observation.
+    #[serde(default)]
+    last_runs: u32,
+}
+
+impl ExitHistory {
+    /// Compare the service's state with the last observation and record
+    /// what changed. `info` is `None` if the service is not loaded.
+    /// Returns the number of recorded events.
+    pub fn observe(&mut self, info: Option<&ServiceInfo>, timestamp: u64) -> usize {
+        let pid = info.and_then(ServiceInfo::pid);
+        let runs = info.and_then(ServiceInfo::runs).unwrap_or(0);
+        let exit_code = info.and_then(ServiceInfo::last_exit_code);
+        // A lower counter means the service was reloaded.
+        let new_runs = if runs < self.last_runs {
+            runs
+        } else {
+            runs - self.last_runs
+        };
+        let before = self.events.len();
+
+        if self.last_pid.is_some() && pid != self.last_pid {
+            self.push(
+                timestamp,
+                HistoryEventKind::Exited,
+                self.last_pid,
+                exit_code,
+            );
+        }
+        if pid.is_some() && pid != self.last_pid {
+            self.push(timestamp, HistoryEventKind::Started, pid, None);
+        } else if pid.is_none() && self.last_pid.is_none() && new_runs > 0 {
+            self.push(timestamp, HistoryEventKind::Exited, None, exit_code);
+        }
+
+        if let Some(info) = info {
+            self.spawns.push(SpawnStats::from_info(info, timestamp));
+        }
+        self.last_pid = pid;
+        self.last_runs = runs;
+        let recorded = self.events.len() - before;
+        if self.events.len() > MAX_EVENTS {
+            self.events.drain(..self.events.len() - MAX_EVENTS);
+        }
+        recorded
+    }
+
+    /// Returns the recorded exits, oldest first.
+    pub fn exits(&self) -> impl Iterator<Item = &HistoryEvent> {
+        self.events
+            .iter()
+            .filter(|event| event.kind == HistoryEventKind::Exited)
+    }
+
+    /// Returns the recorded starts, oldest first.
+    pub fn starts(&self) -> impl Iterator<Item = &HistoryEvent> {
+        self.events
+            .iter()
+            .filter(|event| event.kind == HistoryEventKind::Started)
+    }
+
+    /// Returns the most recent exit.
+    pub fn last_exit(&self) -> Option<&HistoryEvent> {
+        self.exits().last()
+    }
+
+    /// Returns the number of exits with a nonzero code since `since`,
+    /// in seconds since the Unix epoch.
+    pub fn failures_since(&self, since: u64) -> usize {
+        self.exits()
+            .filter(|event| event.timestamp >= since)
+            .filter(|event| event.exit_code.is_some_and(|code| code != 0))
+            .count()
+    }
+
+    fn push(
+        &mut self,
+        timestamp: u64,
+        kind: HistoryEventKind,
+        pid: Option<u32>,
+        exit_code: Option<i32>,
+    ) {
+        self.events.push(HistoryEvent {
+            timestamp,
+            kind,
+            pid,
+            exit_code,
+        });
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the file the agent's history is kept in,
+    /// `~/Library/Application Support/lunchctl/history/<label>.plist`.
+    /// Slashes in the label are escaped so the file stays in the directory.
+    pub fn history_path(&self) -> LaunchctlResult<PathBuf> {
+        let name = self.label.replace('%', "%25").replace('/', "%2F");
+        Ok(crate_state_dir()?
+            .join("history")
+            .join(format!("{name}.plist")))
+    }
+
+    /// Returns the recorded history, empty if nothing was recorded yet.
+    ///
+    /// A history that cannot be read, e.g. because it was damaged, is
+    /// logged and treated as empty, so recording starts over instead of
+    /// failing forever.
+    pub fn history(&self) -> ExitHistory {
+        let Some(path) = self.history_path().ok().filter(|path| path.exists())
+        else {
+            return ExitHistory::default();
+        };
+        plist::from_file(&path).unwrap_or_else(|e| {
+            log::warn!(
+                target: "lunchctl",
+                "discarding unreadable history {}: {e}",
+                path.display()
+            );
+            ExitHistory::default()
+        })
+    }
+
+    /// Observe the agent and add its starts and exits since the last call
+    /// to the persisted history. Returns the updated history.
+    ///
+    /// The history is updated under the agent's [`lock`](Self::lock) and
+    /// replaced atomically.
+    pub fn record_history(&self) -> LaunchctlResult<ExitHistory> {
+        LaunchctlContext::default().record_history(self)
+    }
+
+    /// Delete the recorded history. Returns `false` if there was none.
+    pub fn clear_history(&self) -> LaunchctlResult<bool> {
+        match std::fs::remove_file(self.history_path()?) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl LaunchctlContext {
+    /// Observe the agent in the context's domain and add its starts and
+    /// exits to its persisted history, see [`LaunchAgent::record_history`].
+    pub fn record_history(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<ExitHistory> {
+        let info = self.info(agent)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let _lock = self.lock(agent)?;
+        let mut history = agent.history();
+        if history.observe(info.as_ref(), timestamp) > 0 || info.is_some() {
+            let path = agent.history_path()?;
+            if let Some(dir) = path.parent() {
+                create_private_dir(dir)?;
+            }
+            write_atomically(&path, |file| {
+                Ok(plist::to_writer_xml(file, &history)?)
+            })?;
+        }
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(pid: Option<u32>, runs: u32, last_exit_code: i32) -> ServiceInfo {
+        let pid = pid
+            .map(|pid| format!("\tpid = {pid}\n"))
+            .unwrap_or_default();
+        ServiceInfo::parse(&format!(
+            "gui/501/co.myrt.ajam = {{\n\truns = {runs}\n\tlast exit code = {last_exit_code}\n{pid}}}"
+        ))
+    }
+
+    #[test]
+    fn test_observe() {
+        let mut history = ExitHistory::default();
+        assert_eq!(history.observe(Some(&info(Some(10), 1, 0)), 1), 1);
+        assert_eq!(history.observe(Some(&info(Some(10), 1, 0)), 2), 0);
+        // Exited with an error and was respawned.
+        assert_eq!(history.observe(Some(&info(Some(11), 2, 3)), 3), 2);
+        assert_eq!(history.observe(Some(&info(None, 2, 0)), 4), 1);
+        // Ran between observations.
+        assert_eq!(history.observe(Some(&info(None, 3, 1)), 5), 1);
+        assert_eq!(history.observe(None, 6), 0);
+
+        assert_eq!(history.starts().count(), 2);
+        let exits: Vec<_> = history.exits().map(|e| (e.pid, e.exit_code)).collect();
+        assert_eq!(
+            exits,
+            vec![(Some(10), Some(3)), (Some(11), Some(0)), (None, Some(1))]
+        );
+        assert_eq!(history.failures_since(0), 2);
+        assert_eq!(history.failures_since(4), 1);
+        assert_eq!(history.last_exit().unwrap().timestamp, 5);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = ExitHistory::default();
+        for pid in 1..=300 {
+            history.observe(Some(&info(Some(pid), pid, 0)), u64::from(pid));
+        }
+        assert_eq!(history.events.len(), MAX_EVENTS);
+        assert_eq!(history.events.last().unwrap().pid, Some(300));
+    }
+
+    #[test]
+    fn test_history_persistence() {
+        let label = format!("co.myrt.history.{}", rand::random_range(0..u32::MAX));
+        let agent = LaunchAgent::new(&label);
+        assert_eq!(agent.history(), ExitHistory::default());
+
+        let mut history = ExitHistory::default();
+        history.observe(Some(&info(Some(10), 1, 0)), 1);
+        let path = agent.history_path().unwrap();
+        create_private_dir(path.parent().unwrap()).unwrap();
+        plist::to_file_xml(&path, &history).unwrap();
+
+        assert_eq!(agent.history(), history);
+        assert_eq!(history.spawns.latest().unwrap().runs, 1);
+
+        std::fs::write(&path, "damaged").unwrap();
+        assert_eq!(agent.history(), ExitHistory::default());
+        assert!(agent.clear_history().unwrap());
+        assert!(!agent.clear_history().unwrap());
+    }
+
+    #[test]
+    fn test_history_path_stays_in_directory() {
+        let agent = LaunchAgent::new("../../co.myrt/ajam");
+        let path = agent.history_path().unwrap();
+        assert_eq!(
+            path.parent(),
+            Some(crate_state_dir().unwrap().join("history").as_path())
+        );
+        assert_eq!(path.file_name().unwrap(), "..%2F..%2Fco.myrt%2Fajam.plist");
+    }
+}
@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use crate::agent::LaunchAgent;
+use crate::control::LaunchControllable;
+use crate::install::{FirstRun, InstallState};
+use crate::namespace::LabelNamespace;
+use crate::LaunchctlResult;
+
+/// Label prefix of agents generated by `brew services`.
+pub const HOMEBREW_NAMESPACE: &str = "homebrew.mxcl";
+
+/// Default Homebrew prefixes on Apple silicon and Intel Macs.
+const HOMEBREW_PREFIXES: [&str; 2] = ["/opt/homebrew", "/usr/local"];
+
+/// `brew services` subcommand, see [`LaunchAgent::brew_services`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrewServicesCommand {
+    /// Start the service now and at every login.
+    Start,
+    /// Stop the service and remove its plist, so it doesn't start at login.
+    Stop,
+    /// Start the service now without registering it to start at login.
+    Run,
+    /// Stop the service if it runs, then start it.
+    Restart,
+}
+
+impl LabelNamespace {
+    /// Namespace of the agents generated by `brew services`.
+    pub fn homebrew() -> Self {
+        Self::new(HOMEBREW_NAMESPACE)
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the formula of an agent generated by `brew services`, e.g.
+    /// `postgresql@16` for `homebrew.mxcl.postgresql@16`.
+    pub fn homebrew_formula(&self) -> Option<&str> {
+        LabelNamespace::homebrew().child_name(&self.label)
+    }
+
+    /// Returns the service of a formula, from `~/Library/LaunchAgents` if it
+    /// was started, or else from the plist Homebrew ships in the formula's
+    /// `opt` directory. `None` if the formula has no service.
+    ///
+    /// The prefix is taken from `$HOMEBREW_PREFIX`, falling back to the
+    /// default locations.
+    pub fn homebrew_service(formula: &str) -> LaunchctlResult<Option<Self>> {
+        let agent = LabelNamespace::homebrew().agent(formula);
+        if let Some(installed) = agent.read_installed()? {
+            return Ok(Some(installed));
+        }
+        let file_name = format!("{}.plist", agent.label);
+        let shipped = homebrew_prefixes()
+            .into_iter()
+            .map(|prefix| prefix.join("opt").join(formula).join(&file_name))
+            .find(|path| path.is_file());
+        match shipped {
+            Some(path) => Ok(Some(LaunchAgent::read_plist(&path)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Act on the agent like `brew services <command>` does, so agents
+    /// generated by Homebrew and custom ones can be managed alike.
+    ///
+    /// `Run` writes and bootstraps the plist, then removes it: launchd keeps
+    /// the service loaded until logout without starting it at the next
+    /// login, and no disabled override is left behind.
+    pub fn brew_services(
+        &self,
+        command: BrewServicesCommand,
+    ) -> LaunchctlResult<InstallState> {
+        match command {
+            BrewServicesCommand::Start => self.install_with(FirstRun::NowAndAtLogin),
+            BrewServicesCommand::Stop => {
+                self.uninstall()?;
+                Ok(InstallState {
+                    bootstrap: None,
+                    started: false,
+                    loads_at_login: false,
+                })
+            }
+            BrewServicesCommand::Run => {
+                let bootstrap = self.install()?;
+                self.kickstart(false)?;
+                let _lock = self.lock()?;
+                self.remove_plist()?;
+                Ok(InstallState {
+                    bootstrap: Some(bootstrap),
+                    started: true,
+                    loads_at_login: false,
+                })
+            }
+            BrewServicesCommand::Restart => {
+                self.uninstall()?;
+                self.install_with(FirstRun::NowAndAtLogin)
+            }
+        }
+    }
+}
+
+/// Returns the services generated by `brew services` in
+/// `~/Library/LaunchAgents`, i.e. the started ones.
+pub fn homebrew_services() -> LaunchctlResult<Vec<LaunchAgent>> {
+    LabelNamespace::homebrew().installed()
+}
+
+fn homebrew_prefixes() -> Vec<PathBuf> {
+    std::env::var_os("HOMEBREW_PREFIX")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(HOMEBREW_PREFIXES.iter().map(PathBuf::from))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_homebrew_formula() {
+        let agent = LabelNamespace::homebrew().agent("postgresql@16");
+        assert_eq!(agent.label, "homebrew.mxcl.postgresql@16");
+        assert_eq!(agent.homebrew_formula(), Some("postgresql@16"));
+        assert_eq!(LaunchAgent::new("co.myrt.ajam").homebrew_formula(), None);
+        assert_eq!(LaunchAgent::new("homebrew.mxcl").homebrew_formula(), None);
+    }
+
+    #[test]
+    fn test_homebrew_service() {
+        let formula = format!("lunchctl-test-{}", rand::random_range(0..u32::MAX));
+        assert!(LaunchAgent::homebrew_service(&formula).unwrap().is_none());
+
+        let mut agent = LabelNamespace::homebrew().agent(&formula);
+        agent.program_arguments = vec!["/opt/homebrew/bin/redis-server".into()];
+        agent.write().unwrap();
+        assert_eq!(
+            LaunchAgent::homebrew_service(&formula).unwrap(),
+            Some(agent.stamped().into_owned())
+        );
+        assert!(homebrew_services()
+            .unwrap()
+            .iter()
+            .any(|service| service.label == agent.label));
+        agent.remove().unwrap();
+    }
+}
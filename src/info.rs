@@ -0,0 +1,662 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::LaunchctlResult;
+
+/// Parsed `launchctl print` output of a service.
+///
+/// Top-level `key = value` pairs are parsed as fields and nested blocks such
+/// as `arguments = { ... }` as lists of their lines. The output format
+/// changes between macOS releases, so parsing never fails: lines that do not
+/// fit are kept in [`unparsed`](Self::unparsed) and the original text is
+/// available through [`raw`](Self::raw).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceInfo {
+    /// Top-level scalar values by key, e.g. `state` or `last exit code`.
+    pub fields: BTreeMap<String, String>,
+    blocks: BTreeMap<String, Vec<String>>,
+    entries: BTreeMap<String, Vec<BlockEntry>>,
+    unparsed: Vec<String>,
+    raw: String,
+}
+
+/// Nested `<name> = { key = value ... }` entry of a top-level block, e.g. a
+/// Mach service listed under `endpoints`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockEntry {
+    /// Name of the entry, without quotes.
+    pub name: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Flag from the `properties` line of `launchctl print` output, showing how
+/// launchd registered the service.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ServiceProperty {
+    KeepAlive,
+    RunAtLoad,
+    LaunchOnlyOnce,
+    AbandonProcessGroup,
+    InetdCompatible,
+    LowPriorityIO,
+    SystemService,
+    SupportsTransactions,
+    /// launchd derived the program from the first program argument.
+    InferredProgram,
+    /// Flag this crate does not know about, as printed.
+    Other(String),
+}
+
+impl ServiceProperty {
+    fn parse(name: &str) -> Self {
+        match name {
+            "keepalive" => Self::KeepAlive,
+            "runatload" => Self::RunAtLoad,
+            "launch only once" => Self::LaunchOnlyOnce,
+            "abandon process group" => Self::AbandonProcessGroup,
+            "inetd compatible" => Self::InetdCompatible,
+            "low priority i/o" => Self::LowPriorityIO,
+            "system service" => Self::SystemService,
+            "supports transactions" => Self::SupportsTransactions,
+            "inferred program" => Self::InferredProgram,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// Parsed `launchctl blame` output, the reason the service was last started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blame {
+    pub reason: String,
+    raw: String,
+}
+
+/// Service loaded into the current user's domain, as shown by
+/// `launchctl list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    pub label: String,
+    /// Process ID, `None` if the service is not running.
+    pub pid: Option<u32>,
+    /// Last exit status, negative if the process was killed by a signal.
+    pub status: i32,
+}
+
+/// Parsed `launchctl list` output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceList {
+    pub entries: Vec<ListEntry>,
+    raw: String,
+}
+
+impl ServiceInfo {
+    /// Parse `launchctl print` output.
+    ///
+    /// Output without the enclosing `<service target> = {` block is parsed
+    /// as if it had one.
+    pub fn parse(output: &str) -> Self {
+        let mut fields = BTreeMap::new();
+        let mut blocks: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut entries: BTreeMap<String, Vec<BlockEntry>> = BTreeMap::new();
+        let mut unparsed = Vec::new();
+
+        let lines = output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+        let wrapped = lines.clone().next().is_some_and(|line| line.ends_with('{'));
+        let mut depth = usize::from(!wrapped);
+        let mut block: Option<String> = None;
+
+        for line in lines {
+            if line.starts_with('}') {
+                depth = depth.saturating_sub(1);
+                if depth <= 1 {
+                    block = None;
+                }
+                continue;
+            }
+            let opens_block = line.ends_with('{');
+            match depth {
+                1 if opens_block => {
+                    let name = block_name(line);
+                    blocks.entry(name.clone()).or_default();
+                    block = Some(name);
+                }
+                1 => match split_field(line) {
+                    Some((key, value)) => {
+                        fields.insert(key.to_string(), value.to_string());
+                    }
+                    None => {
+                        warn_unrecognized("print", line);
+                        unparsed.push(line.to_string());
+                    }
+                },
+                2 => {
+                    if let Some(lines) =
+                        block.as_ref().and_then(|name| blocks.get_mut(name))
+                    {
+                        lines.push(block_name(line));
+                    }
+                    if let Some(name) = block.as_ref().filter(|_| opens_block) {
+                        entries.entry(name.clone()).or_default().push(BlockEntry {
+                            name: block_name(line).trim_matches('"').to_string(),
+                            fields: BTreeMap::new(),
+                        });
+                    }
+                }
+                3 => {
+                    let entry = block
+                        .as_ref()
+                        .and_then(|name| entries.get_mut(name))
+                        .and_then(|entries| entries.last_mut());
+                    if let (Some(entry), Some((key, value))) =
+                        (entry, split_field(line))
+                    {
+                        entry.fields.insert(key.to_string(), value.to_string());
+                    }
+                }
+                _ => {}
+            }
+            if opens_block {
+                depth += 1;
+            }
+        }
+
+        Self {
+            fields,
+            blocks,
+            entries,
+            unparsed,
+            raw: output.to_string(),
+        }
+    }
+
+    /// Returns the lines of a top-level block, such as the program arguments
+    /// for `arguments`. Entries that open a block of their own are returned
+    /// without the opening brace.
+    pub fn block(&self, name: &str) -> Option<&[String]> {
+        self.blocks.get(name).map(Vec::as_slice)
+    }
+
+    /// Returns the nested entries of a top-level block with their fields,
+    /// empty if the block has none.
+    pub fn block_entries(&self, name: &str) -> &[BlockEntry] {
+        self.entries.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the top-level lines that are neither fields nor blocks.
+    pub fn unparsed(&self) -> &[String] {
+        &self.unparsed
+    }
+
+    /// Returns a top-level value by key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    /// Returns the service state, e.g. `running` or `not running`.
+    pub fn state(&self) -> Option<&str> {
+        self.get("state")
+    }
+
+    /// Check if launchd holds the next spawn back because the service
+    /// exited sooner than its `ThrottleInterval` allows. Such services are
+    /// put in the `penalty box` or wait in the `spawn scheduled` state.
+    pub fn is_throttled(&self) -> bool {
+        self.state() == Some("spawn scheduled")
+            || self.has_property(&ServiceProperty::Other("penalty box".to_string()))
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.get("pid")?.parse().ok()
+    }
+
+    /// Returns the plist the service was registered from.
+    pub fn path(&self) -> Option<&str> {
+        self.get("path")
+    }
+
+    pub fn program(&self) -> Option<&str> {
+        self.get("program")
+    }
+
+    /// Returns the last exit code, `None` if the service never exited.
+    pub fn last_exit_code(&self) -> Option<i32> {
+        self.get("last exit code")?.parse().ok()
+    }
+
+    /// Returns the flags of the `properties` line.
+    pub fn properties(&self) -> BTreeSet<ServiceProperty> {
+        self.get("properties")
+            .into_iter()
+            .flat_map(|line| line.split('|'))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(ServiceProperty::parse)
+            .collect()
+    }
+
+    /// Check if launchd registered the service with the flag.
+    pub fn has_property(&self, property: &ServiceProperty) -> bool {
+        self.properties().contains(property)
+    }
+
+    /// Returns how many times launchd has spawned the service since it was
+    /// loaded.
+    pub fn runs(&self) -> Option<u32> {
+        self.get("runs")?.parse().ok()
+    }
+
+    /// Returns the unparsed `launchctl print` output.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+}
+
+impl Blame {
+    /// Parse `launchctl blame` output.
+    pub fn parse(output: &str) -> Self {
+        Self {
+            reason: output.trim().to_string(),
+            raw: output.to_string(),
+        }
+    }
+
+    /// Returns the unparsed `launchctl blame` output.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+}
+
+impl ServiceList {
+    /// Parse `launchctl list` output.
+    pub fn parse(output: &str) -> Self {
+        let entries = output
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with("PID"))
+            .filter_map(|line| {
+                let entry = parse_list_line(line);
+                if entry.is_none() {
+                    warn_unrecognized("list", line);
+                }
+                entry
+            })
+            .collect();
+
+        Self {
+            entries,
+            raw: output.to_string(),
+        }
+    }
+
+    /// Returns the entry with the label.
+    pub fn get(&self, label: &str) -> Option<&ListEntry> {
+        self.entries.iter().find(|entry| entry.label == label)
+    }
+
+    /// Returns the unparsed `launchctl list` output.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the agent's state as shown by `launchctl print`,
+    /// `None` if the agent is not loaded, see [`LaunchctlContext::info`].
+    pub fn info(&self) -> LaunchctlResult<Option<ServiceInfo>> {
+        LaunchctlContext::default().info(self)
+    }
+
+    /// Returns the plist launchd registered the agent from, `None` if the
+    /// agent is not loaded. Unlike [`ServiceInfo::path`], paths that are not
+    /// valid UTF-8 are returned unchanged.
+    pub fn registered_path(&self) -> LaunchctlResult<Option<PathBuf>> {
+        LaunchctlContext::default().registered_path(self)
+    }
+
+    /// Returns why launchd last started the agent.
+    pub fn blame(&self) -> LaunchctlResult<Blame> {
+        LaunchctlContext::default().blame(self)
+    }
+}
+
+impl LaunchctlContext {
+    /// Returns the plist launchd registered the agent from, see
+    /// [`LaunchAgent::registered_path`].
+    pub fn registered_path(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<Option<PathBuf>> {
+        let target = self.service_target(agent);
+        let output = self.run_on_target(&target, &target.print_command())?;
+        if !output.success() {
+            return Ok(None);
+        }
+        Ok(output.stdout_value("path").map(PathBuf::from))
+    }
+
+    /// Returns why launchd last started the agent, see
+    /// [`LaunchAgent::blame`].
+    pub fn blame(&self, agent: &LaunchAgent) -> LaunchctlResult<Blame> {
+        let target = self.service_target(agent);
+        let output =
+            self.run(&self.command(["blame".to_string(), target.to_string()]))?;
+        Ok(Blame::parse(&output.stdout_lossy()))
+    }
+
+    /// Returns the services loaded into the context's domain, see [`list`].
+    pub fn list(&self) -> LaunchctlResult<ServiceList> {
+        let output = self.run(&self.command(["list"]))?;
+        Ok(ServiceList::parse(&output.stdout_lossy()))
+    }
+}
+
+/// Returns the services loaded into the current user's domain.
+pub fn list() -> LaunchctlResult<ServiceList> {
+    LaunchctlContext::default().list()
+}
+
+fn parse_list_line(line: &str) -> Option<ListEntry> {
+    let mut columns = line.split('\t');
+    let pid = columns.next()?.trim();
+    let status = columns.next()?.trim().parse().ok()?;
+    let label = columns.next()?.trim();
+    if label.is_empty() {
+        return None;
+    }
+    let pid = match pid {
+        "-" => None,
+        pid => Some(pid.parse().ok()?),
+    };
+    Some(ListEntry {
+        label: label.to_string(),
+        pid,
+        status,
+    })
+}
+
+/// Split a `key = value` line. Some blocks use `=>` instead of `=`.
+fn split_field(line: &str) -> Option<(&str, &str)> {
+    line.split_once(" = ")
+        .or_else(|| line.split_once(" => "))
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+/// Returns the line without a trailing ` = {` or `{`.
+fn block_name(line: &str) -> String {
+    line.trim_end_matches('{')
+        .trim_end()
+        .trim_end_matches('=')
+        .trim_end()
+        .to_string()
+}
+
+fn warn_unrecognized(command: &str, line: &str) {
+    log::warn!(
+        target: "lunchctl",
+        "unrecognized line in launchctl {command} output: {line:?}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRINT_OUTPUT: &str = "gui/501/co.myrt.ajam = {
+	active count = 1
+	runs = 3
+	path = /Users/me/Library/LaunchAgents/co.myrt.ajam.plist
+	state = running
+
+	program = /usr/bin/tail
+	arguments = {
+		/usr/bin/tail
+		-f
+	}
+
+	pid = 4312
+	properties = keepalive | runatload | inferred program | penalty box
+	last exit code = (never exited)
+	something unexpected
+}
+";
+
+    /// `launchctl print` output of the same agent on macOS 12 to 15.
+    const PRINT_BY_RELEASE: [(&str, &str); 4] = [
+        (
+            "12",
+            "gui/501/co.myrt.ajam = {
+	active count = 1
+	path = /Users/me/Library/LaunchAgents/co.myrt.ajam.plist
+	state = running
+
+	program = /usr/local/bin/ajam
+	arguments = {
+		/usr/local/bin/ajam
+		--serve
+	}
+
+	default environment = {
+		PATH => /usr/bin:/bin:/usr/sbin:/sbin
+	}
+
+	domain = com.apple.xpc.launchd.user.domain.501.100006.Aqua
+	minimum runtime = 10
+	exit timeout = 5
+	runs = 2
+	pid = 812
+	immediate reason = speculative
+	forks = 0
+	execs = 1
+	last exit code = 0
+
+	spawn type = daemon (3)
+	properties = keepalive | runatload | inferred program
+}
+",
+        ),
+        (
+            "13",
+            "gui/501/co.myrt.ajam = {
+	active count = 1
+	path = /Users/me/Library/LaunchAgents/co.myrt.ajam.plist
+	type = LaunchAgent
+	state = running
+
+	program = /usr/local/bin/ajam
+	arguments = {
+		/usr/local/bin/ajam
+		--serve
+	}
+
+	inherited environment = {
+		SSH_AUTH_SOCK => /private/tmp/com.apple.launchd.x/Listeners
+	}
+
+	runs = 2
+	pid = 812
+	immediate reason = speculative
+	last exit code = 0
+
+	resource coalition = {
+		ID = 1234
+	}
+
+	jetsam priority = 40
+	properties = keepalive | runatload | inferred program
+}
+",
+        ),
+        (
+            "14",
+            "gui/501/co.myrt.ajam = {
+	active count = 1
+	path = /Users/me/Library/LaunchAgents/co.myrt.ajam.plist
+	type = LaunchAgent
+	state = running
+
+	program = /usr/local/bin/ajam
+	arguments = {
+		/usr/local/bin/ajam
+		--serve
+	}
+
+	runs = 2
+	pid = 812
+	immediate reason = speculative
+	last exit code = 0
+	last exit reason = exited normally
+
+	event triggers = {
+	}
+
+	spawn role = interactive (2)
+	properties = keepalive | runatload | inferred program | managed LWCR
+}
+",
+        ),
+        (
+            "15",
+            "gui/501/co.myrt.ajam = {
+	active count = 1
+	path = /Users/me/Library/LaunchAgents/co.myrt.ajam.plist
+	type = LaunchAgent
+	state = running
+
+	program = /usr/local/bin/ajam
+	arguments = {
+		/usr/local/bin/ajam
+		--serve
+	}
+
+	runs = 2
+	pid = 812
+	immediate reason = speculative
+	last exit code = 0
+
+	endpoints = {
+		\"co.myrt.ajam.xpc\" = {
+			port = 0x1234
+			active = 1
+		}
+	}
+
+	job state = running
+	unexpected token without separator
+	properties = keepalive | runatload | inferred program | managed LWCR
+}
+",
+        ),
+    ];
+
+    #[test]
+    fn test_parse_service_info() {
+        let info = ServiceInfo::parse(PRINT_OUTPUT);
+        assert_eq!(info.state(), Some("running"));
+        assert_eq!(info.pid(), Some(4312));
+        assert_eq!(info.program(), Some("/usr/bin/tail"));
+        assert_eq!(
+            info.path(),
+            Some("/Users/me/Library/LaunchAgents/co.myrt.ajam.plist")
+        );
+        assert_eq!(info.last_exit_code(), None);
+        assert_eq!(info.runs(), Some(3));
+        assert_eq!(
+            info.properties(),
+            BTreeSet::from([
+                ServiceProperty::KeepAlive,
+                ServiceProperty::RunAtLoad,
+                ServiceProperty::InferredProgram,
+                ServiceProperty::Other("penalty box".to_string()),
+            ])
+        );
+        assert!(info.has_property(&ServiceProperty::KeepAlive));
+        assert!(!info.has_property(&ServiceProperty::LaunchOnlyOnce));
+        assert!(info.is_throttled());
+        assert!(!ServiceInfo::parse("state = running\n").is_throttled());
+        assert!(ServiceInfo::parse("state = spawn scheduled\n").is_throttled());
+        assert_eq!(info.get("active count"), Some("1"));
+        assert_eq!(info.get("-f"), None);
+        assert_eq!(info.unparsed(), ["something unexpected"]);
+        assert_eq!(info.raw(), PRINT_OUTPUT);
+    }
+
+    #[test]
+    fn test_parse_service_list() {
+        let output = "PID\tStatus\tLabel
+4312\t0\tco.myrt.ajam
+-\t-9\tco.myrt.other
+garbage
+";
+        let list = ServiceList::parse(output);
+        assert_eq!(
+            list.entries,
+            vec![
+                ListEntry {
+                    label: "co.myrt.ajam".to_string(),
+                    pid: Some(4312),
+                    status: 0,
+                },
+                ListEntry {
+                    label: "co.myrt.other".to_string(),
+                    pid: None,
+                    status: -9,
+                },
+            ]
+        );
+        assert_eq!(list.get("co.myrt.other").unwrap().status, -9);
+        assert_eq!(list.raw(), output);
+    }
+
+    #[test]
+    fn test_parse_blame() {
+        let blame = Blame::parse("speculative\n");
+        assert_eq!(blame.reason, "speculative");
+        assert_eq!(blame.raw(), "speculative\n");
+    }
+
+    #[test]
+    fn test_parse_print_output_of_each_release() {
+        for (release, output) in PRINT_BY_RELEASE {
+            let info = ServiceInfo::parse(output);
+            assert_eq!(info.state(), Some("running"), "macOS {release}");
+            assert_eq!(info.pid(), Some(812), "macOS {release}");
+            assert_eq!(info.runs(), Some(2), "macOS {release}");
+            assert_eq!(info.last_exit_code(), Some(0), "macOS {release}");
+            assert_eq!(info.program(), Some("/usr/local/bin/ajam"));
+            assert_eq!(
+                info.path(),
+                Some("/Users/me/Library/LaunchAgents/co.myrt.ajam.plist")
+            );
+            assert_eq!(
+                info.block("arguments").unwrap(),
+                ["/usr/local/bin/ajam", "--serve"],
+                "macOS {release}"
+            );
+            assert!(info.has_property(&ServiceProperty::KeepAlive));
+        }
+
+        let info = ServiceInfo::parse(PRINT_BY_RELEASE[3].1);
+        assert_eq!(info.unparsed(), ["unexpected token without separator"]);
+        assert_eq!(info.block("endpoints").unwrap(), ["\"co.myrt.ajam.xpc\""]);
+        assert_eq!(info.block("event triggers"), None);
+        let info = ServiceInfo::parse(PRINT_BY_RELEASE[2].1);
+        assert_eq!(info.block("event triggers"), Some(&[][..]));
+    }
+
+    #[test]
+    fn test_parse_partial_print_output() {
+        let info = ServiceInfo::parse("state = running\r\npid = 7\r\n}\n}\n");
+        assert_eq!(info.state(), Some("running"));
+        assert_eq!(info.pid(), Some(7));
+
+        let info = ServiceInfo::parse(
+            "gui/501/co.myrt.ajam = {\n\tstate = running\n\targuments = {\n",
+        );
+        assert_eq!(info.state(), Some("running"));
+        assert_eq!(info.block("arguments"), Some(&[][..]));
+    }
+}
@@ -0,0 +1,198 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::agent::LaunchAgent;
+use crate::origin::AgentOrigin;
+use crate::paths::LaunchDirectory;
+use crate::LaunchctlResult;
+
+/// Single plist found by the [`Inspector`].
+pub struct InspectedPlist {
+    pub path: PathBuf,
+    /// Parsed configuration, or the reason the file could not be read.
+    pub agent: LaunchctlResult<LaunchAgent>,
+    /// Set when the file could not be read for lack of permissions. Some
+    /// daemon plists are only readable by root.
+    pub requires_root: bool,
+    /// Probable origin of the plist.
+    pub origin: AgentOrigin,
+}
+
+/// Contents of one inspected directory.
+pub struct DirectoryReport {
+    pub directory: LaunchDirectory,
+    pub path: PathBuf,
+    pub plists: Vec<InspectedPlist>,
+    /// Set when the directory itself could not be listed, e.g. for lack of
+    /// permissions. A missing directory is reported as empty.
+    pub error: Option<io::Error>,
+}
+
+/// Read-only inventory of launchd job definitions.
+///
+/// The inspector only reads plist files. It never calls launchctl and never
+/// writes, so it is safe to use from unprivileged audit tools.
+pub struct Inspector {
+    directories: Vec<(LaunchDirectory, PathBuf)>,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inspector {
+    /// Create an inspector for the standard directories software installs
+    /// into. The SIP-protected directories of macOS itself are left out, see
+    /// [`all`](Self::all). `~/Library/LaunchAgents` is left out if the home
+    /// directory is unknown.
+    pub fn new() -> Self {
+        Self::with_directories(
+            LaunchDirectory::ALL
+                .into_iter()
+                .filter(|dir| dir.is_managed())
+                .filter_map(|dir| Some((dir, dir.path().ok()?))),
+        )
+    }
+
+    /// Create an inspector for all standard directories, including those
+    /// holding the agents and daemons shipped with macOS.
+    pub fn all() -> Self {
+        Self::with_directories(
+            LaunchDirectory::ALL
+                .into_iter()
+                .filter_map(|dir| Some((dir, dir.path().ok()?))),
+        )
+    }
+
+    /// Create an inspector for `/Library/LaunchDaemons` only.
+    ///
+    /// Daemons use the same plist format as agents and are parsed into
+    /// [`LaunchDaemon`](crate::LaunchDaemon).
+    pub fn daemons() -> Self {
+        let directory = LaunchDirectory::GlobalDaemons;
+        Self::with_directories(directory.path().map(|path| (directory, path)))
+    }
+
+    /// Create an inspector for the given directories only.
+    pub fn with_directories<I>(directories: I) -> Self
+    where
+        I: IntoIterator<Item = (LaunchDirectory, PathBuf)>,
+    {
+        Self {
+            directories: directories.into_iter().collect(),
+        }
+    }
+
+    /// Enumerate and parse every plist in the inspected directories.
+    pub fn inspect(&self) -> Vec<DirectoryReport> {
+        self.directories
+            .iter()
+            .map(|(directory, path)| Self::inspect_directory(*directory, path))
+            .collect()
+    }
+
+    fn inspect_directory(
+        directory: LaunchDirectory,
+        path: &Path,
+    ) -> DirectoryReport {
+        let mut report = DirectoryReport {
+            directory,
+            path: path.to_path_buf(),
+            plists: Vec::new(),
+            error: None,
+        };
+
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return report,
+            Err(e) => {
+                report.error = Some(e);
+                return report;
+            }
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "plist"))
+            .collect();
+        paths.sort();
+
+        report.plists = paths
+            .into_iter()
+            .map(|path| Self::inspect_plist(directory, path))
+            .collect();
+        report
+    }
+
+    fn inspect_plist(directory: LaunchDirectory, path: PathBuf) -> InspectedPlist {
+        let (agent, requires_root) = match std::fs::read(&path) {
+            Ok(bytes) => (LaunchAgent::parse_plist(&bytes), false),
+            Err(e) => {
+                let requires_root = e.kind() == io::ErrorKind::PermissionDenied;
+                (Err(e.into()), requires_root)
+            }
+        };
+        let origin = AgentOrigin::classify(directory, &path, agent.as_ref().ok());
+        InspectedPlist {
+            path,
+            agent,
+            requires_root,
+            origin,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_directory() {
+        let dir = std::env::temp_dir()
+            .join(format!("lunchctl.test.{}", rand::random_range(0..u32::MAX)));
+        std::fs::create_dir(&dir).unwrap();
+
+        let mut buf = Vec::new();
+        plist::to_writer_xml(&mut buf, &LaunchAgent::new("co.myrt.valid")).unwrap();
+        std::fs::write(dir.join("co.myrt.valid.plist"), buf).unwrap();
+        std::fs::write(dir.join("co.myrt.broken.plist"), "not a plist").unwrap();
+        std::fs::write(dir.join("README.txt"), "ignored").unwrap();
+
+        let inspector = Inspector::with_directories([
+            (LaunchDirectory::UserAgents, dir.clone()),
+            (LaunchDirectory::GlobalAgents, dir.join("missing")),
+        ]);
+        let reports = inspector.inspect();
+
+        assert_eq!(reports.len(), 2);
+        let plists = &reports[0].plists;
+        assert!(reports[0].error.is_none());
+        assert_eq!(plists.len(), 2);
+        assert!(plists[0].agent.is_err());
+        assert!(!plists[0].requires_root);
+        assert_eq!(plists[1].agent.as_ref().unwrap().label, "co.myrt.valid");
+
+        assert!(reports[1].error.is_none());
+        assert!(reports[1].plists.is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_directories() {
+        let directories = |inspector: Inspector| -> Vec<_> {
+            inspector
+                .directories
+                .into_iter()
+                .map(|(dir, _)| dir)
+                .collect()
+        };
+        assert!(directories(Inspector::new())
+            .iter()
+            .all(|dir| dir.is_managed()));
+        assert_eq!(directories(Inspector::all()), LaunchDirectory::ALL);
+    }
+}
@@ -0,0 +1,422 @@
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::control::BootstrapOutcome;
+use crate::domain::DomainTarget;
+use crate::LaunchctlResult;
+
+/// Progress step of a high-level operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallEvent {
+    /// The plist is being written to disk.
+    WritingPlist,
+    /// A previous registration is being booted out.
+    BootingOut,
+    /// The agent is being bootstrapped into launchd.
+    Bootstrapping,
+    /// The agent is being enabled or disabled.
+    Enabling,
+    /// The agent is being started.
+    Starting,
+    /// The running agent is being restarted.
+    Restarting,
+    /// Waiting for the agent to reach running state.
+    WaitingForRunning,
+    /// The operation failed and the previous configuration was restored.
+    RolledBack,
+    /// The plist and related files are being deleted.
+    RemovingFiles,
+    /// The installed agent already matches the configuration.
+    UpToDate,
+}
+
+/// When a newly installed agent first runs.
+///
+/// launchd loads every enabled plist in `~/Library/LaunchAgents` at login,
+/// so the choice is between starting now, at the next login, or both.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FirstRun {
+    /// Bootstrap and start the agent now, then disable it so it is not
+    /// loaded again at the next login. It stays loaded until then.
+    Now,
+    /// Write and enable the agent without bootstrapping it. launchd starts
+    /// it at the next login. A loaded registration is left as is.
+    NextLogin,
+    /// Bootstrap and start the agent now and at every login.
+    #[default]
+    NowAndAtLogin,
+}
+
+/// State of the agent after [`LaunchAgent::install_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstallState {
+    /// What bootstrapping changed, `None` if the agent was not bootstrapped.
+    pub bootstrap: Option<BootstrapOutcome>,
+    /// The agent was started now.
+    pub started: bool,
+    /// launchd loads the agent at the next login.
+    pub loads_at_login: bool,
+}
+
+/// Receives progress events from high-level operations such as
+/// `install()`, `uninstall()` and `ensure_installed()`.
+///
+/// Implemented for closures taking the agent label and the event, and as a
+/// no-op for `()`.
+pub trait InstallObserver {
+    fn on_event(&self, label: &str, event: InstallEvent);
+}
+
+impl InstallObserver for () {
+    fn on_event(&self, _label: &str, _event: InstallEvent) {}
+}
+
+impl<F> InstallObserver for F
+where
+    F: Fn(&str, InstallEvent),
+{
+    fn on_event(&self, label: &str, event: InstallEvent) {
+        self(label, event);
+    }
+}
+
+impl LaunchAgent {
+    /// Write the plist and bootstrap the agent, replacing a loaded
+    /// registration of the same label.
+    ///
+    /// Returns [`BootstrapOutcome::AlreadyLoaded`] without reloading if the
+    /// plist was already installed as is and launchd serves the agent from
+    /// it, and [`BootstrapOutcome::ReplacedStale`] if a registration of an
+    /// older plist, another path or another program was replaced. Fails with
+    /// [`LabelConflict`](crate::LaunchAgentError::LabelConflict) if another
+    /// plist or domain already uses the label, and with
+    /// [`NoAquaSession`](crate::LaunchAgentError::NoAquaSession) before
+    /// writing anything if the agent needs a login session that is missing.
+    pub fn install(&self) -> LaunchctlResult<BootstrapOutcome> {
+        self.install_observed(&())
+    }
+
+    /// Same as [`install`](Self::install), reporting progress to `observer`.
+    pub fn install_observed(
+        &self,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<BootstrapOutcome> {
+        LaunchctlContext::default().install_observed(self, observer)
+    }
+
+    /// Install the agent, choosing when it first runs, e.g. to honor a
+    /// "start now" checkbox of an installer.
+    pub fn install_with(
+        &self,
+        first_run: FirstRun,
+    ) -> LaunchctlResult<InstallState> {
+        self.install_with_observed(first_run, &())
+    }
+
+    /// Same as [`install_with`](Self::install_with), reporting progress to
+    /// `observer`.
+    pub fn install_with_observed(
+        &self,
+        first_run: FirstRun,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<InstallState> {
+        LaunchctlContext::default().install_with_observed(self, first_run, observer)
+    }
+
+    /// Install the agent unless the same configuration is already installed
+    /// and loaded. Returns [`BootstrapOutcome::AlreadyLoaded`] if nothing
+    /// was changed.
+    pub fn ensure_installed(&self) -> LaunchctlResult<BootstrapOutcome> {
+        self.ensure_installed_observed(&())
+    }
+
+    /// Same as [`ensure_installed`](Self::ensure_installed), reporting progress
+    /// to `observer`.
+    pub fn ensure_installed_observed(
+        &self,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<BootstrapOutcome> {
+        LaunchctlContext::default().ensure_installed_observed(self, observer)
+    }
+}
+
+impl LaunchctlContext {
+    /// Install the agent into the context's directory and domain, see
+    /// [`LaunchAgent::install`].
+    pub fn install(&self, agent: &LaunchAgent) -> LaunchctlResult<BootstrapOutcome> {
+        self.install_observed(agent, &())
+    }
+
+    /// Same as [`install`](Self::install), reporting progress to `observer`.
+    pub fn install_observed(
+        &self,
+        agent: &LaunchAgent,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<BootstrapOutcome> {
+        if self.domain == DomainTarget::current_gui() {
+            self.check_session(agent)?;
+        }
+        let _lock = self.lock(agent)?;
+        self.check_conflicts(agent)?;
+
+        let unchanged = self.is_installed_as_is(agent)?;
+        observer.on_event(&agent.label, InstallEvent::WritingPlist);
+        self.write_plist(agent)?;
+
+        let replaced = match self.info(agent)? {
+            Some(info) => {
+                let path = self.path(agent)?;
+                if unchanged
+                    && agent.registration_mismatch_at(&path, &info).is_none()
+                {
+                    observer.on_event(&agent.label, InstallEvent::UpToDate);
+                    return Ok(BootstrapOutcome::AlreadyLoaded);
+                }
+                observer.on_event(&agent.label, InstallEvent::BootingOut);
+                self.boot_out(agent)?;
+                true
+            }
+            None => false,
+        };
+
+        observer.on_event(&agent.label, InstallEvent::Bootstrapping);
+        let outcome = self.bootstrap(agent)?;
+        Ok(if replaced {
+            BootstrapOutcome::ReplacedStale
+        } else {
+            outcome
+        })
+    }
+
+    /// Install the agent, choosing when it first runs, see
+    /// [`LaunchAgent::install_with`].
+    pub fn install_with(
+        &self,
+        agent: &LaunchAgent,
+        first_run: FirstRun,
+    ) -> LaunchctlResult<InstallState> {
+        self.install_with_observed(agent, first_run, &())
+    }
+
+    /// Same as [`install_with`](Self::install_with), reporting progress to
+    /// `observer`.
+    pub fn install_with_observed(
+        &self,
+        agent: &LaunchAgent,
+        first_run: FirstRun,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<InstallState> {
+        if first_run == FirstRun::NextLogin {
+            let _lock = self.lock(agent)?;
+            self.check_conflicts(agent)?;
+            observer.on_event(&agent.label, InstallEvent::WritingPlist);
+            self.write_plist(agent)?;
+            observer.on_event(&agent.label, InstallEvent::Enabling);
+            self.enable(agent)?;
+            return Ok(InstallState {
+                bootstrap: None,
+                started: false,
+                loads_at_login: true,
+            });
+        }
+
+        observer.on_event(&agent.label, InstallEvent::Enabling);
+        self.enable(agent)?;
+        let bootstrap = self.install_observed(agent, observer)?;
+        observer.on_event(&agent.label, InstallEvent::Starting);
+        self.kickstart(agent, false)?;
+
+        let loads_at_login = first_run == FirstRun::NowAndAtLogin;
+        if !loads_at_login {
+            observer.on_event(&agent.label, InstallEvent::Enabling);
+            self.disable(agent)?;
+        }
+        Ok(InstallState {
+            bootstrap: Some(bootstrap),
+            started: true,
+            loads_at_login,
+        })
+    }
+
+    /// Install the agent unless it is already installed and loaded as is,
+    /// see [`LaunchAgent::ensure_installed`].
+    pub fn ensure_installed(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<BootstrapOutcome> {
+        self.ensure_installed_observed(agent, &())
+    }
+
+    /// Same as [`ensure_installed`](Self::ensure_installed), reporting
+    /// progress to `observer`.
+    pub fn ensure_installed_observed(
+        &self,
+        agent: &LaunchAgent,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<BootstrapOutcome> {
+        if self.is_installed_as_is(agent)? && self.is_loaded(agent)? {
+            observer.on_event(&agent.label, InstallEvent::UpToDate);
+            return Ok(BootstrapOutcome::AlreadyLoaded);
+        }
+        self.install_observed(agent, observer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::command::{CommandOutput, CommandSpec};
+    use crate::context::CommandRunner;
+
+    /// Runner answering `print` as if the agent were loaded from `path`.
+    struct LoadedRunner {
+        path: PathBuf,
+        failing_bootstrap: bool,
+    }
+
+    impl CommandRunner for LoadedRunner {
+        fn run(&self, command: &CommandSpec) -> LaunchctlResult<CommandOutput> {
+            let mut output = CommandOutput {
+                code: 0,
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            };
+            let domain = format!("{}/", DomainTarget::Gui(u32::MAX));
+            if command.args[0] == "print"
+                && !command.args[1].to_string_lossy().starts_with(&domain)
+            {
+                // Only the test domain has the service loaded.
+                output.code = 113;
+                output.stderr = b"Could not find service".to_vec();
+            } else if command.args[0] == "print" {
+                output.stdout =
+                    format!("agent = {{\n\tpath = {}\n}}\n", self.path.display())
+                        .into_bytes();
+            } else if command.args[0] == "bootstrap" && self.failing_bootstrap {
+                output.code = 5;
+                output.stderr = b"Bootstrap failed: 5: Input/output error".to_vec();
+            }
+            Ok(output)
+        }
+    }
+
+    fn loaded_from(
+        directory: &Path,
+        path: &Path,
+        failing_bootstrap: bool,
+    ) -> (LaunchctlContext, Arc<Mutex<Vec<String>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let context = LaunchctlContext::new()
+            .directory(directory)
+            .domain(DomainTarget::Gui(u32::MAX))
+            .runner(LoadedRunner {
+                path: path.to_path_buf(),
+                failing_bootstrap,
+            })
+            .on_command(move |command| {
+                let verb = command.args[0].to_string_lossy().into_owned();
+                recorder.lock().unwrap().push(verb);
+            });
+        (context, seen)
+    }
+
+    #[test]
+    fn test_install_outcomes() {
+        let directory = std::env::temp_dir().join(format!(
+            "lunchctl-install-{}",
+            rand::random_range(0..u32::MAX)
+        ));
+        std::fs::create_dir(&directory).unwrap();
+        let label =
+            format!("co.myrt.ajam.install.{}", rand::random_range(0..u32::MAX));
+        let agent = LaunchAgent::new(&label);
+        let path = directory.join(format!("{label}.plist"));
+
+        // launchd still serves an older version of the plist.
+        let (context, seen) = loaded_from(&directory, &path, false);
+        assert_eq!(
+            context.install(&agent).unwrap(),
+            BootstrapOutcome::ReplacedStale
+        );
+        // The conflict check prints the label in the three domains it
+        // searches first.
+        assert_eq!(
+            *seen.lock().unwrap(),
+            ["print", "print", "print", "print", "bootout", "bootstrap"]
+        );
+
+        seen.lock().unwrap().clear();
+        assert_eq!(
+            context.install(&agent).unwrap(),
+            BootstrapOutcome::AlreadyLoaded
+        );
+        assert_eq!(
+            context.ensure_installed(&agent).unwrap(),
+            BootstrapOutcome::AlreadyLoaded
+        );
+        assert!(!seen.lock().unwrap().iter().any(|verb| verb == "bootstrap"));
+
+        let (moved, _) =
+            loaded_from(&directory, Path::new("/tmp/ajam.plist"), false);
+        assert_eq!(
+            moved.install(&agent).unwrap(),
+            BootstrapOutcome::ReplacedStale
+        );
+
+        let (failing, _) = loaded_from(&directory, &path, true);
+        assert_eq!(
+            failing.bootstrap(&agent).unwrap(),
+            BootstrapOutcome::AlreadyLoaded
+        );
+        let (failing, _) =
+            loaded_from(&directory, Path::new("/tmp/ajam.plist"), true);
+        assert!(failing.bootstrap(&agent).is_err());
+
+        let idle = LaunchctlContext::idle()
+            .directory(&directory)
+            .domain(DomainTarget::Gui(u32::MAX));
+        assert_eq!(idle.install(&agent).unwrap(), BootstrapOutcome::NewlyLoaded);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_closure_observer() {
+        let events = RefCell::new(Vec::new());
+        let observer = |label: &str, event: InstallEvent| {
+            events.borrow_mut().push((label.to_string(), event));
+        };
+
+        let dyn_observer: &dyn InstallObserver = &observer;
+        dyn_observer.on_event("co.myrt.ajam", InstallEvent::WritingPlist);
+        dyn_observer.on_event("co.myrt.ajam", InstallEvent::Bootstrapping);
+
+        assert_eq!(
+            events.into_inner(),
+            vec![
+                ("co.myrt.ajam".to_string(), InstallEvent::WritingPlist),
+                ("co.myrt.ajam".to_string(), InstallEvent::Bootstrapping),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_installed_as_is() {
+        let label = format!("co.myrt.ajam.test.{}", rand::random_range(0.0..=1e9));
+        let context = LaunchctlContext::default();
+        let mut agent = LaunchAgent::new(&label);
+        assert!(!context.is_installed_as_is(&agent).unwrap());
+
+        agent.write().unwrap();
+        assert!(context.is_installed_as_is(&agent).unwrap());
+
+        agent.run_at_load = true;
+        assert!(!context.is_installed_as_is(&agent).unwrap());
+
+        agent.remove().unwrap();
+    }
+}
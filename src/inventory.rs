@@ -0,0 +1,156 @@
+use std::path::PathBuf;
+
+use crate::context::LaunchctlContext;
+use crate::domain::{DomainTarget, ServiceTarget};
+use crate::info::ServiceInfo;
+use crate::LaunchctlResult;
+
+/// Service registered in a launchd domain, as listed by
+/// `launchctl print <domain>`.
+///
+/// Covers every service of the domain, including Apple's and those of other
+/// apps, not only agents written by this crate. The listing has no plist
+/// paths, so [`path`](Self::path) prints each service on its own: one
+/// `launchctl` run per service, only worth it for the few services of
+/// interest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainService {
+    pub domain: DomainTarget,
+    pub label: String,
+    /// `running` or `not running`, the states `launchctl print` shows for
+    /// the service that the listing can tell apart. Finer states such as
+    /// `waiting` are only in [`info`](Self::info).
+    pub state: String,
+    /// Process ID, `None` if the service is not running.
+    pub pid: Option<u32>,
+    /// Last exit status, negative if the process was killed by a signal.
+    /// `None` if the service never exited.
+    pub last_exit_code: Option<i32>,
+}
+
+impl DomainService {
+    /// Returns the `<domain>/<label>` target of the service.
+    pub fn target(&self) -> ServiceTarget {
+        ServiceTarget::new(self.domain, &self.label)
+    }
+
+    /// Check if the service has a running process.
+    pub fn is_running(&self) -> bool {
+        self.pid.is_some()
+    }
+
+    /// Returns the service's state as shown by `launchctl print`, `None` if
+    /// it is no longer loaded. Runs `launchctl` once per call.
+    pub fn info(&self) -> LaunchctlResult<Option<ServiceInfo>> {
+        LaunchctlContext::default().print_target(&self.target())
+    }
+
+    /// Returns the plist the service was registered from, `None` for
+    /// services without one, such as those of XPC bundles. Runs `launchctl`
+    /// once per call, see [`info`](Self::info).
+    pub fn path(&self) -> LaunchctlResult<Option<PathBuf>> {
+        Ok(self.info()?.and_then(|info| info.path().map(PathBuf::from)))
+    }
+}
+
+impl DomainTarget {
+    /// Returns every service registered in the domain, e.g.
+    /// `DomainTarget::Gui(501).print()`.
+    ///
+    /// The domain listing has no plist paths; see [`DomainService::path`].
+    pub fn print(self) -> LaunchctlResult<Vec<DomainService>> {
+        LaunchctlContext::default().print_domain(self)
+    }
+}
+
+impl LaunchctlContext {
+    /// Returns every service registered in the domain, see
+    /// [`DomainTarget::print`].
+    pub fn print_domain(
+        &self,
+        domain: DomainTarget,
+    ) -> LaunchctlResult<Vec<DomainService>> {
+        let output =
+            self.run(&self.command(["print".to_string(), domain.to_string()]))?;
+        Ok(parse_services(domain, &output.stdout_lossy()))
+    }
+}
+
+/// Parse the `services` block of `launchctl print <domain>` output, lines
+/// of `<pid> <last exit status> <label>`. A pid of 0 and a status of `-`
+/// mean not running and never exited.
+fn parse_services(domain: DomainTarget, output: &str) -> Vec<DomainService> {
+    ServiceInfo::parse(output)
+        .block("services")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let pid = columns.next()?.parse::<u32>().ok()?;
+            let status = columns.next()?;
+            let label = columns.next()?;
+            let state = if pid == 0 { "not running" } else { "running" };
+            Some(DomainService {
+                domain,
+                label: label.to_string(),
+                state: state.to_string(),
+                pid: (pid != 0).then_some(pid),
+                last_exit_code: status.parse().ok(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_services() {
+        let output = "gui/501 = {
+	type = login
+	services = {
+		       0      -       co.myrt.ajam
+		     412      0       com.apple.Finder
+		       0     -9       com.example.helper
+	}
+	endpoints = {
+		   0x1bf03    M   A   com.apple.tsm.portname
+	}
+}
+";
+        let services = parse_services(DomainTarget::Gui(501), output);
+        assert_eq!(
+            services,
+            [
+                DomainService {
+                    domain: DomainTarget::Gui(501),
+                    label: "co.myrt.ajam".to_string(),
+                    state: "not running".to_string(),
+                    pid: None,
+                    last_exit_code: None,
+                },
+                DomainService {
+                    domain: DomainTarget::Gui(501),
+                    label: "com.apple.Finder".to_string(),
+                    state: "running".to_string(),
+                    pid: Some(412),
+                    last_exit_code: Some(0),
+                },
+                DomainService {
+                    domain: DomainTarget::Gui(501),
+                    label: "com.example.helper".to_string(),
+                    state: "not running".to_string(),
+                    pid: None,
+                    last_exit_code: Some(-9),
+                },
+            ]
+        );
+        assert!(services[1].is_running());
+        assert_eq!(
+            services[2].target().to_string(),
+            "gui/501/com.example.helper"
+        );
+        assert!(parse_services(DomainTarget::System, "").is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Value of the `KeepAlive` key.
+///
+/// launchd accepts either a boolean or a dictionary of conditions under which
+/// the job is kept running.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeepAlive {
+    Always(bool),
+    Conditions(KeepAliveConditions),
+}
+
+/// Conditions of a `KeepAlive` dictionary. The job is restarted whenever any
+/// of them holds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct KeepAliveConditions {
+    /// Restart after a zero exit status if `true`, after a non-zero one
+    /// if `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub successful_exit: Option<bool>,
+
+    /// Restart after a crash if `true`, after a clean exit if `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crashed: Option<bool>,
+
+    /// Keep running while the path exists (`true`) or does not exist
+    /// (`false`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub path_state: BTreeMap<PathBuf, bool>,
+
+    /// Keep running while the job with the label is loaded (`true`) or not
+    /// loaded (`false`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub other_job_enabled: BTreeMap<String, bool>,
+
+    /// Keep running while a network interface is up.
+    ///
+    /// Deprecated: modern macOS almost always reports the network as up, so
+    /// this does not wait for connectivity. See [`diagnose`].
+    ///
+    /// [`diagnose`]: crate::LaunchAgent::diagnose
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_state: Option<bool>,
+
+    /// Apply the conditions only after the job was started once on demand.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_initial_demand: Option<bool>,
+}
+
+impl KeepAlive {
+    /// Check if launchd restarts the job in any circumstances.
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            Self::Always(enabled) => *enabled,
+            Self::Conditions(_) => true,
+        }
+    }
+
+    /// Returns the conditions, turning the value into an empty dictionary
+    /// first if it is a boolean.
+    pub fn conditions_mut(&mut self) -> &mut KeepAliveConditions {
+        if let Self::Always(_) = self {
+            *self = Self::Conditions(KeepAliveConditions::default());
+        }
+        match self {
+            Self::Conditions(conditions) => conditions,
+            Self::Always(_) => unreachable!(),
+        }
+    }
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self::Always(false)
+    }
+}
+
+impl From<bool> for KeepAlive {
+    fn from(enabled: bool) -> Self {
+        Self::Always(enabled)
+    }
+}
+
+impl From<KeepAliveConditions> for KeepAlive {
+    fn from(conditions: KeepAliveConditions) -> Self {
+        Self::Conditions(conditions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{LaunchAgent, LaunchAgentBuilder};
+
+    #[test]
+    fn test_path_state_helpers() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .keep_alive_while_path_exists("/tmp/ajam.enabled")
+            .keep_alive_until_path_exists("/tmp/ajam.stop")
+            .build()
+            .unwrap();
+
+        let KeepAlive::Conditions(conditions) = &agent.keep_alive else {
+            panic!("expected KeepAlive conditions");
+        };
+        assert_eq!(
+            conditions.path_state,
+            BTreeMap::from([
+                (PathBuf::from("/tmp/ajam.enabled"), true),
+                (PathBuf::from("/tmp/ajam.stop"), false),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent
+            .keep_alive
+            .conditions_mut()
+            .path_state
+            .insert(PathBuf::from("/tmp/ajam.enabled"), true);
+        agent.keep_alive.conditions_mut().successful_exit = Some(false);
+
+        let mut buf = Vec::new();
+        plist::to_writer_xml(&mut buf, &agent).unwrap();
+        let written = String::from_utf8(buf.clone()).unwrap();
+        assert!(written.contains("<key>PathState</key>"));
+        assert!(!written.contains("<key>Crashed</key>"));
+
+        let parsed: LaunchAgent = plist::from_bytes(&buf).unwrap();
+        assert_eq!(parsed.keep_alive, agent.keep_alive);
+
+        let enabled = LaunchAgent {
+            keep_alive: true.into(),
+            ..LaunchAgent::new("co.myrt.ajam")
+        };
+        let mut buf = Vec::new();
+        plist::to_writer_xml(&mut buf, &enabled).unwrap();
+        let parsed: LaunchAgent = plist::from_bytes(&buf).unwrap();
+        assert_eq!(parsed.keep_alive, KeepAlive::Always(true));
+    }
+}
@@ -1,11 +1,171 @@
 use thiserror::Error;
 
 mod control;
+mod context;
+mod activation;
 mod agent;
+mod alert;
+mod bundle;
+mod cache;
+mod calendar;
+mod command;
+mod conflict;
+mod diagnose;
+mod dirs;
+mod disabled;
+mod domain;
+mod dumpstate;
+mod endpoint;
+mod env;
+mod extra;
+mod failure;
+mod filename;
+mod find;
+mod fix;
+#[cfg(any(test, feature = "fixtures"))]
+mod fixtures;
+mod fleet;
+mod history;
+mod homebrew;
+mod info;
+mod inspect;
+mod install;
+mod inventory;
+mod keepalive;
+mod lint;
+mod lock;
+mod mach;
+mod manifest;
+mod metadata;
+mod mobileconfig;
+mod namespace;
+#[cfg(feature = "notifications")]
+mod notify;
+mod origin;
 mod os;
+mod ownership;
+mod oneshot;
+mod patch;
+mod paths;
+mod permissions;
+mod ping;
+mod poll;
+mod power;
+mod process;
+mod procinfo;
+mod project;
+mod qos;
+mod recovery;
+mod registration;
+mod relaunch;
+mod relocation;
+mod report;
+mod schedule;
+mod script;
+mod security;
+mod session;
+mod shell;
+mod snapshot;
+mod socket;
+mod spawned;
+mod splay;
+#[cfg(feature = "spotlight")]
+mod spotlight;
+mod stats;
+mod status;
+mod supervisor;
+mod tcc;
+mod telemetry;
+mod template;
+mod uninstall;
+mod unreliable;
+mod update;
+mod wake;
+mod watch;
+mod write_policy;
+mod wrapper;
 
-pub use control::LaunchControllable;
-pub use agent::{LaunchAgent, LaunchAgentBuilder, ProcessType};
+pub use control::{BootstrapOutcome, LaunchControllable};
+pub use context::{
+    CommandRunner, Elevation, ElevationFailure, LaunchctlContext, ShellRunner,
+};
+pub use activation::{ActivationProfile, LoadTime, Trigger};
+pub use alert::{Alert, AlertKind, AlertSink, LogSink};
+pub use bundle::{current_app_bundle, ProgramLocation, BUNDLE_PROGRAM_KEY};
+pub use cache::CachedInfo;
+pub use calendar::CalendarInterval;
+pub use command::{CommandOutput, CommandSpec, COMMAND_PATH};
+pub use agent::{
+    LaunchAgent, LaunchAgentBuilder, LaunchDaemon, ProcessType, SocketConfig,
+};
+pub use diagnose::{Finding, Severity};
+pub use disabled::disabled_overrides;
+pub use domain::{DomainTarget, ServiceTarget};
+pub use dumpstate::{dumpstate_for, ServiceDump};
+pub use endpoint::{Endpoint, EndpointKind, EndpointMismatch};
+pub use env::{Environment, REDACTED};
+pub use extra::ExtraKeys;
+pub use failure::LaunchctlFailure;
+pub use find::{find, LabelLocation};
+pub use fix::Fix;
+#[cfg(any(test, feature = "fixtures"))]
+pub use fixtures::{assert_lossless, round_trip_losses, Fixture, FIXTURES};
+pub use fleet::{AgentStatus, FleetStatus};
+pub use history::{ExitHistory, HistoryEvent, HistoryEventKind};
+pub use homebrew::{homebrew_services, BrewServicesCommand, HOMEBREW_NAMESPACE};
+pub use info::{
+    list, Blame, BlockEntry, ListEntry, ServiceInfo, ServiceList, ServiceProperty,
+};
+pub use inspect::{DirectoryReport, InspectedPlist, Inspector};
+pub use install::{FirstRun, InstallEvent, InstallObserver, InstallState};
+pub use inventory::DomainService;
+pub use keepalive::{KeepAlive, KeepAliveConditions};
+pub use lint::{rule, LintConfig, Rule, RULES};
+pub use lock::{AgentLock, DEFAULT_LOCK_TIMEOUT};
+pub use mach::mach_service_exists;
+pub use manifest::{AgentResult, Manifest, ManifestReport};
+pub use mobileconfig::MobileConfig;
+pub use namespace::LabelNamespace;
+#[cfg(feature = "notifications")]
+pub use notify::NotificationSink;
+pub use metadata::{AgentMetadata, Version, DESCRIPTION_KEY, METADATA_KEY};
+pub use oneshot::{cleanup_one_shots, CleanupPolicy, ONE_SHOT_KEY};
+pub use origin::AgentOrigin;
+pub use ownership::{manager, set_manager, DEFAULT_MANAGER, MANAGED_BY_KEY};
+pub use paths::LaunchDirectory;
+pub use permissions::{PlistPermissions, PLIST_MODE};
+pub use ping::PingResponder;
+pub use poll::{poll, poll_for, CancellationToken, PollOptions};
+pub use power::on_ac_power;
+pub use process::Termination;
+pub use procinfo::ProcessEntry;
+pub use project::{ProjectAgents, PROJECT_DIRECTORY_KEY};
+pub use recovery::{RecoveryAction, RecoveryPolicy};
+pub use relaunch::Relaunch;
+pub use relocation::{verify_program_paths, Relocation};
+pub use report::WriteReport;
+pub use schedule::{Schedule, ScheduleWarning, TimeOfDay, Weekday};
+pub use script::SCRIPT_DIGEST_KEY;
+pub use session::{current_session, SessionType};
+pub use shell::DEFAULT_SHELL;
+pub use snapshot::{Snapshot, SnapshotEntry};
+pub use socket::{activate_socket, Connection, SocketServer};
+pub use spawned::{is_interactive, is_launchd_spawned, launchd_job_label, needs_as_user};
+pub use splay::SPLAY_KEY;
+#[cfg(feature = "spotlight")]
+pub use spotlight::app_path_for_bundle_id;
+pub use status::{StatusItem, StatusProvider};
+pub use supervisor::{RestartReason, SupervisionPolicy, Supervisor, SupervisorEvent};
+pub use tcc::FULL_DISK_ACCESS_SERVICE;
+#[cfg(feature = "metrics")]
+pub use telemetry::describe_metrics;
+pub use telemetry::DEFAULT_SLOW_CALL_THRESHOLD;
+pub use stats::{CrashLoop, CrashLoopAdvice, SpawnHistory, SpawnStats};
+pub use wake::{NOTIFYD_EVENT_STREAM, POWER_STATE_NOTIFICATION};
+pub use watch::{PlistWatcher, WatchEvent, WatchHandle};
+pub use write_policy::WritePolicy;
+pub use uninstall::{UninstallOptions, UninstallReport};
+pub use unreliable::{PathHazard, UnreliablePath};
 
 /// Error types for Launch Agent configuration.
 #[derive(Error, Debug)]
@@ -18,6 +178,87 @@ pub enum LaunchAgentError {
 
     #[error("Failed to run launchctl command. Exit code: {0}, Output: {1}")]
     CommandFailed(i32, String),
+
+    #[error("Failed to activate socket '{0}'")]
+    SocketActivationFailed(String, #[source] std::io::Error),
+
+    #[error("Socket operation failed")]
+    SocketError(#[source] std::io::Error),
+
+    #[error("Invalid program binary '{0}': {1}")]
+    InvalidBinary(std::path::PathBuf, String),
+
+    #[error("Agent '{0}' did not start after update, previous version restored")]
+    UpdateRolledBack(String),
+
+    #[error(
+        "Agent '{0}' did not start after update and the previous version could not be restored"
+    )]
+    UpdateRollbackFailed(String, #[source] Box<LaunchAgentError>),
+
+    #[error("Invalid version: '{0}'")]
+    InvalidVersion(String),
+
+    #[error(
+        "Label '{0}' is already in use: {locations}",
+        locations = join_locations(.1)
+    )]
+    LabelConflict(String, Vec<LabelLocation>),
+
+    #[error("Agent plist is locked by another process: {0}")]
+    Locked(std::path::PathBuf),
+
+    #[error("Timed out after {1:?} waiting for the agent plist lock: {0}")]
+    LockTimeout(std::path::PathBuf, std::time::Duration),
+
+    #[error("Plist has permissions launchd rejects: {0}: {1}")]
+    BadPlistPermissions(std::path::PathBuf, String),
+
+    #[error("Agent {0} is registered from a stale plist: {1}")]
+    StaleRegistration(String, String),
+
+    #[error("Label '{0}' is outside the namespace '{1}'")]
+    OutsideNamespace(String, String),
+
+    #[error("Template variable '{0}' has no value")]
+    UndefinedVariable(String),
+
+    #[error("Operation was cancelled")]
+    Cancelled,
+
+    #[error("Elevation was declined by the user")]
+    ElevationDeclined,
+
+    #[error("Failed to elevate command: {0}")]
+    ElevationFailed(ElevationFailure),
+
+    #[error("Plist is protected by System Integrity Protection: {0}")]
+    SIPProtected(std::path::PathBuf),
+
+    #[error("User {0} has no Aqua session to load the agent into")]
+    NoAquaSession(u32),
+
+    #[error("Plist already exists: {0}")]
+    PlistExists(std::path::PathBuf),
+
+    #[error("Plist is not managed by this app: {0}")]
+    NotManaged(std::path::PathBuf),
+
+    #[error("Not permitted to manage service {0}")]
+    NotPermitted(String),
+
+    #[error(
+        "Program {0} is relative to the app bundle, but the process is not in one"
+    )]
+    NoAppBundle(std::path::PathBuf),
+}
+
+fn join_locations(locations: &[LabelLocation]) -> String {
+    locations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 /// Result type for launchctl operations.
@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::diagnose::{Finding, Severity};
+use crate::LaunchctlResult;
+
+/// Check run by [`LaunchAgent::lint`], reporting findings with its `id` as
+/// their code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Rule {
+    pub id: &'static str,
+    /// Highest severity the rule reports with unless configured otherwise.
+    pub severity: Severity,
+    pub description: &'static str,
+}
+
+pub(crate) const MISSING_PROGRAM: Rule = Rule {
+    id: "missing-program",
+    severity: Severity::Error,
+    description: "neither Program nor ProgramArguments is set",
+};
+
+pub(crate) const NETWORK_STATE: Rule = Rule {
+    id: "network-state",
+    severity: Severity::Warning,
+    description: "KeepAlive depends on the deprecated NetworkState",
+};
+
+pub(crate) const QOS_CONFLICT: Rule = Rule {
+    id: "qos-conflict",
+    severity: Severity::Warning,
+    description: "scheduling keys contradict each other",
+};
+
+pub(crate) const CASE_COLLISION: Rule = Rule {
+    id: "case-collision",
+    severity: Severity::Warning,
+    description: "another plist name differs only by case",
+};
+
+pub(crate) const UNRELIABLE_PATH: Rule = Rule {
+    id: "unreliable-path",
+    severity: Severity::Warning,
+    description: "a path is on cloud storage, a network mount or evicted",
+};
+
+pub(crate) const MISSING_LOG_DIRECTORY: Rule = Rule {
+    id: "missing-log-directory",
+    severity: Severity::Error,
+    description: "the directory of an output log does not exist",
+};
+
+pub(crate) const PLIST_PERMISSIONS: Rule = Rule {
+    id: "plist-permissions",
+    severity: Severity::Error,
+    description: "launchd rejects the owner or mode of the plist",
+};
+
+pub(crate) const PROGRAM_IN_TMP: Rule = Rule {
+    id: "program-in-tmp",
+    severity: Severity::Error,
+    description: "the program is in a shared temporary directory",
+};
+
+pub(crate) const PROGRAM_WRITABLE: Rule = Rule {
+    id: "program-writable",
+    severity: Severity::Error,
+    description: "other users can modify the program",
+};
+
+pub(crate) const PLIST_WRITABLE: Rule = Rule {
+    id: "plist-writable",
+    severity: Severity::Error,
+    description: "other users can modify the plist",
+};
+
+pub(crate) const CREDENTIAL_IN_ENVIRONMENT: Rule = Rule {
+    id: "credential-in-environment",
+    severity: Severity::Warning,
+    description: "an environment variable holds a credential in plain text",
+};
+
+pub(crate) const DISABLED: Rule = Rule {
+    id: "disabled",
+    severity: Severity::Error,
+    description: "a disabled override keeps the agent from loading",
+};
+
+pub(crate) const STALE_REGISTRATION: Rule = Rule {
+    id: "stale-registration",
+    severity: Severity::Error,
+    description: "the loaded service comes from another plist or program",
+};
+
+pub(crate) const FULL_DISK_ACCESS: Rule = Rule {
+    id: "full-disk-access",
+    severity: Severity::Warning,
+    description: "the loaded agent failed for lack of Full Disk Access",
+};
+
+/// Every rule of [`LaunchAgent::diagnose`], [`LaunchAgent::security_audit`]
+/// and [`LaunchAgent::diagnose_loaded`].
+pub const RULES: &[Rule] = &[
+    MISSING_PROGRAM,
+    NETWORK_STATE,
+    QOS_CONFLICT,
+    CASE_COLLISION,
+    UNRELIABLE_PATH,
+    MISSING_LOG_DIRECTORY,
+    PLIST_PERMISSIONS,
+    PROGRAM_IN_TMP,
+    PROGRAM_WRITABLE,
+    PLIST_WRITABLE,
+    CREDENTIAL_IN_ENVIRONMENT,
+    DISABLED,
+    STALE_REGISTRATION,
+    FULL_DISK_ACCESS,
+];
+
+/// Returns the rule with the id.
+pub fn rule(id: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|rule| rule.id == id)
+}
+
+impl Rule {
+    /// Returns a warning of the rule.
+    pub(crate) fn warning(&self, message: &str) -> Finding {
+        Finding::warning(self.id, message)
+    }
+
+    /// Returns an error of the rule.
+    pub(crate) fn error(&self, message: &str) -> Finding {
+        Finding::error(self.id, message)
+    }
+}
+
+/// Which rules [`LaunchAgent::lint`] reports, with what severity, and which
+/// severity fails a check.
+///
+/// By default every rule is enabled with its own severity and only errors
+/// fail, so CI can reject broken agents while tolerating warnings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintConfig {
+    /// Severity overrides, `None` for disabled rules.
+    overrides: HashMap<String, Option<Severity>>,
+    fail_on: Severity,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            overrides: HashMap::new(),
+            fail_on: Severity::Error,
+        }
+    }
+}
+
+impl LintConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Disable the rule. Ids not in [`RULES`] are logged and ignored.
+    #[must_use]
+    pub fn allow(mut self, id: &str) -> Self {
+        if is_known(id) {
+            self.overrides.insert(id.to_string(), None);
+        }
+        self
+    }
+
+    /// Report the rule's findings with `severity`, enabling it if it was
+    /// disabled. Ids not in [`RULES`] are logged and ignored.
+    #[must_use]
+    pub fn severity(mut self, id: &str, severity: Severity) -> Self {
+        if is_known(id) {
+            self.overrides.insert(id.to_string(), Some(severity));
+        }
+        self
+    }
+
+    /// Fail on findings of `severity` or higher, e.g. `Severity::Warning`
+    /// to fail on every finding.
+    #[must_use]
+    pub fn fail_on(mut self, severity: Severity) -> Self {
+        self.fail_on = severity;
+        self
+    }
+
+    pub fn is_enabled(&self, id: &str) -> bool {
+        !matches!(self.overrides.get(id), Some(None))
+    }
+
+    /// Drop the findings of disabled rules and apply the severity overrides.
+    pub fn apply(&self, findings: Vec<Finding>) -> Vec<Finding> {
+        findings
+            .into_iter()
+            .filter_map(|mut finding| match self.overrides.get(finding.code) {
+                Some(None) => None,
+                Some(Some(severity)) => {
+                    finding.severity = *severity;
+                    Some(finding)
+                }
+                None => Some(finding),
+            })
+            .collect()
+    }
+
+    /// Check if any of the findings fails the check.
+    pub fn is_failure(&self, findings: &[Finding]) -> bool {
+        findings
+            .iter()
+            .any(|finding| finding.severity >= self.fail_on)
+    }
+}
+
+fn is_known(id: &str) -> bool {
+    let known = rule(id).is_some();
+    if !known {
+        log::warn!(target: "lunchctl", "ignoring unknown lint rule {id}");
+    }
+    known
+}
+
+impl LaunchAgent {
+    /// Run [`diagnose_loaded`](Self::diagnose_loaded) and
+    /// [`security_audit`](Self::security_audit) with the rules configured
+    /// in `config`.
+    pub fn lint(&self, config: &LintConfig) -> LaunchctlResult<Vec<Finding>> {
+        LaunchctlContext::default()
+            .lint_config(config.clone())
+            .lint(self)
+    }
+}
+
+impl LaunchctlContext {
+    /// Check the agent with the context's lint rules, see
+    /// [`LaunchAgent::lint`].
+    pub fn lint(&self, agent: &LaunchAgent) -> LaunchctlResult<Vec<Finding>> {
+        let mut findings = self.diagnose_loaded(agent)?;
+        findings.extend(agent.security_audit());
+        Ok(self.lint.apply(findings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::Permissions;
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+    use crate::agent::ProcessType;
+    use crate::command::{CommandOutput, CommandSpec};
+    use crate::context::CommandRunner;
+    use crate::os::home_dir;
+
+    #[test]
+    fn test_lint_config() {
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        let lint = |config: LintConfig| {
+            LaunchctlContext::idle()
+                .lint_config(config)
+                .lint(&agent)
+                .unwrap()
+        };
+        let findings = lint(LintConfig::new());
+        assert!(findings.iter().any(|f| f.code == MISSING_PROGRAM.id));
+        assert!(LintConfig::new().is_failure(&findings));
+        assert!(findings.iter().all(|f| rule(f.code).is_some()));
+
+        let config = LintConfig::new().allow("missing-program");
+        assert!(!config.is_enabled("missing-program"));
+        assert!(lint(config).is_empty());
+
+        let config =
+            LintConfig::new().severity("missing-program", Severity::Warning);
+        let findings = lint(config.clone());
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert!(!config.is_failure(&findings));
+        assert!(config.fail_on(Severity::Warning).is_failure(&findings));
+
+        assert_eq!(
+            LintConfig::new().allow("missing-programs"),
+            LintConfig::new()
+        );
+    }
+
+    /// Findings are created from the rule constants only, and every
+    /// constant is listed in `RULES`.
+    struct LoadedRunner {
+        label: String,
+    }
+
+    impl CommandRunner for LoadedRunner {
+        fn run(&self, command: &CommandSpec) -> LaunchctlResult<CommandOutput> {
+            let stdout = match command.args[0].to_str() {
+                Some("print-disabled") => format!(
+                    "disabled services = {{\n\t\"{}\" => disabled\n}}\n",
+                    self.label
+                ),
+                Some("print") => {
+                    "service = {\n\tpath = /tmp/other.plist\n\tlast exit code = 1\n}\n"
+                        .to_string()
+                }
+                _ => String::new(),
+            };
+            Ok(CommandOutput {
+                code: 0,
+                stdout: stdout.into_bytes(),
+                stderr: Vec::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_findings_come_from_rules() {
+        let label = format!("co.myrt.lint.{}", rand::random_range(0..u32::MAX));
+        let scratch = std::env::temp_dir().join(&label);
+        std::fs::create_dir(&scratch).unwrap();
+        let program = scratch.join("program");
+        std::fs::write(&program, "").unwrap();
+        std::fs::set_permissions(&program, Permissions::from_mode(0o777)).unwrap();
+        let error_log = scratch.join("error.log");
+        std::fs::write(&error_log, "open: Operation not permitted\n").unwrap();
+
+        // Missing program, with a world-writable plist colliding by case.
+        let bare = LaunchAgent::new(&label);
+        bare.write().unwrap();
+        let plist = bare.path().unwrap();
+        std::fs::set_permissions(&plist, Permissions::from_mode(0o666)).unwrap();
+        let collision =
+            plist.with_file_name(format!("{}.plist", label.to_uppercase()));
+        std::fs::write(&collision, "").unwrap();
+
+        let mut configured = LaunchAgent::new(&label);
+        configured.file_stem = Some(format!("{label}.configured"));
+        configured.program_arguments = vec![program.into()];
+        configured.keep_alive.conditions_mut().network_state = Some(true);
+        configured.process_type = ProcessType::Background;
+        configured.nice = Some(-5);
+        configured.standard_out_path = scratch.join("missing").join("out.log");
+        configured.standard_error_path.clone_from(&error_log);
+        configured.environment_variables.set("API_TOKEN", "secret");
+        let cloud = home_dir().unwrap().join("Library/Mobile Documents/ajam");
+        configured.extra.insert(
+            "WorkingDirectory".to_string(),
+            cloud.display().to_string().into(),
+        );
+
+        let mut in_tmp = LaunchAgent::new(&label);
+        in_tmp.program_arguments = vec!["/tmp/ajam".into()];
+
+        let loaded = LaunchctlContext::new().runner(LoadedRunner { label });
+        let mut findings = loaded.lint(&bare).unwrap();
+        findings.extend(loaded.lint(&configured).unwrap());
+        findings.extend(loaded.lint(&in_tmp).unwrap());
+
+        std::fs::remove_file(collision).unwrap();
+        std::fs::remove_file(plist).unwrap();
+        std::fs::remove_dir_all(scratch).unwrap();
+
+        for finding in &findings {
+            assert!(rule(finding.code).is_some(), "{finding}");
+        }
+        let ids: std::collections::HashSet<_> =
+            RULES.iter().map(|rule| rule.id).collect();
+        assert_eq!(ids.len(), RULES.len());
+        for rule in RULES {
+            assert!(
+                findings.iter().any(|finding| finding.code == rule.id),
+                "{} was not reported",
+                rule.id
+            );
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::agent::LaunchAgent;
+use crate::poll::{poll, PollOptions};
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// How long `write()`, `remove()` and `install()` wait for the lock.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Advisory lock on an agent's plist, released when dropped.
+///
+/// The lock is an `flock` on a sidecar file next to the plist, so it only
+/// coordinates processes that use this crate (e.g. an app and its updater).
+/// The sidecar is never deleted: a process waiting on a deleted file would
+/// lock it while others lock a new file at the same path.
+pub struct AgentLock {
+    _file: File,
+}
+
+impl AgentLock {
+    /// Returns the path of the sidecar file locking the plist at `path`.
+    pub(crate) fn path_for(path: &Path, label: &str) -> PathBuf {
+        path.with_file_name(format!(".{label}.plist.lock"))
+    }
+
+    /// Lock the file at `path`, creating it if needed. Fails with
+    /// [`LaunchAgentError::LockTimeout`] if the lock is not released within
+    /// `timeout`.
+    pub(crate) fn acquire(
+        path: PathBuf,
+        timeout: Duration,
+    ) -> LaunchctlResult<Self> {
+        let file = open_lock_file(&path)?;
+        let options = PollOptions::new(timeout).interval(RETRY_INTERVAL);
+        if poll(|| try_flock(&file), &options)? {
+            return Ok(Self { _file: file });
+        }
+        Err(LaunchAgentError::LockTimeout(path, timeout))
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the path of the sidecar file used for locking.
+    pub fn lock_path(&self) -> LaunchctlResult<PathBuf> {
+        Ok(AgentLock::path_for(&self.path()?, &self.label))
+    }
+
+    /// Lock the agent's plist, waiting up to [`DEFAULT_LOCK_TIMEOUT`].
+    pub fn lock(&self) -> LaunchctlResult<AgentLock> {
+        self.lock_timeout(DEFAULT_LOCK_TIMEOUT)
+    }
+
+    /// Lock the agent's plist or fail with [`LaunchAgentError::Locked`]
+    /// if another process holds the lock.
+    pub fn try_lock(&self) -> LaunchctlResult<AgentLock> {
+        let path = self.lock_path()?;
+        let file = open_lock_file(&path)?;
+        if try_flock(&file)? {
+            Ok(AgentLock { _file: file })
+        } else {
+            Err(LaunchAgentError::Locked(path))
+        }
+    }
+
+    /// Lock the agent's plist, failing with [`LaunchAgentError::LockTimeout`]
+    /// if the lock is not released within `timeout`.
+    pub fn lock_timeout(&self, timeout: Duration) -> LaunchctlResult<AgentLock> {
+        AgentLock::acquire(self.lock_path()?, timeout)
+    }
+}
+
+fn open_lock_file(path: &Path) -> LaunchctlResult<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    Ok(file)
+}
+
+/// Returns `false` if the lock is held by someone else.
+fn try_flock(file: &File) -> LaunchctlResult<bool> {
+    let code =
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if code == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.kind() == io::ErrorKind::WouldBlock {
+        return Ok(false);
+    }
+    Err(err.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock() {
+        let label = format!("co.myrt.ajam.test.{}", rand::random_range(0.0..=1e9));
+        let agent = LaunchAgent::new(&label);
+
+        let lock = agent.try_lock().unwrap();
+        assert!(matches!(agent.try_lock(), Err(LaunchAgentError::Locked(_))));
+        assert!(matches!(
+            agent.lock_timeout(Duration::from_millis(100)),
+            Err(LaunchAgentError::LockTimeout(..))
+        ));
+
+        drop(lock);
+        agent.write().unwrap();
+        let path = agent.lock_path().unwrap();
+        assert!(path.exists());
+        agent.remove().unwrap();
+        assert!(path.exists());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::domain::DomainTarget;
+use crate::info::ServiceInfo;
+use crate::LaunchctlResult;
+
+/// Returns whether the Mach service is registered in the current user's GUI
+/// domain.
+pub fn mach_service_exists(name: &str) -> LaunchctlResult<bool> {
+    DomainTarget::current_gui().mach_service_exists(name)
+}
+
+impl DomainTarget {
+    /// Returns the names of the Mach services registered in the domain, as
+    /// listed by `launchctl print <domain>`.
+    pub fn mach_services(self) -> LaunchctlResult<Vec<String>> {
+        LaunchctlContext::default().mach_services(self)
+    }
+
+    /// Returns whether the Mach service is registered in the domain.
+    ///
+    /// A registered service can be looked up by clients even while the
+    /// process providing it is not running; launchd starts it on demand.
+    pub fn mach_service_exists(self, name: &str) -> LaunchctlResult<bool> {
+        Ok(self.mach_services()?.iter().any(|service| service == name))
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the names of the `MachServices` the agent declares.
+    pub fn mach_service_names(&self) -> Vec<String> {
+        self.extra
+            .get("MachServices")
+            .and_then(plist::Value::as_dictionary)
+            .map(|services| services.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the declared `MachServices` that are not registered in the
+    /// agent's domain, empty if clients can reach all of them.
+    pub fn unregistered_mach_services(&self) -> LaunchctlResult<Vec<String>> {
+        LaunchctlContext::default().unregistered_mach_services(self)
+    }
+}
+
+impl LaunchctlContext {
+    /// Returns the names of the Mach services registered in the domain, see
+    /// [`DomainTarget::mach_services`].
+    pub fn mach_services(
+        &self,
+        domain: DomainTarget,
+    ) -> LaunchctlResult<Vec<String>> {
+        let output =
+            self.run(&self.command(["print".to_string(), domain.to_string()]))?;
+        Ok(endpoint_names(&output.stdout_lossy()))
+    }
+
+    /// Returns the declared `MachServices` that are not registered in the
+    /// context's domain, see [`LaunchAgent::unregistered_mach_services`].
+    pub fn unregistered_mach_services(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<Vec<String>> {
+        let declared = agent.mach_service_names();
+        if declared.is_empty() {
+            return Ok(declared);
+        }
+        let registered = self.mach_services(self.domain)?;
+        Ok(declared
+            .into_iter()
+            .filter(|name| !registered.contains(name))
+            .collect())
+    }
+}
+
+/// Returns the service names of the `endpoints` block of `launchctl print`
+/// output. Domains list endpoints as `<port> <flags> <name>` lines, services
+/// as quoted names opening a block of their own.
+fn endpoint_names(output: &str) -> Vec<String> {
+    ServiceInfo::parse(output)
+        .block("endpoints")
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| entry.split_whitespace().last())
+        .map(|name| name.trim_matches('"').to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_names() {
+        let domain = "gui/501 = {
+	type = login
+	endpoints = {
+		   0x1bf03    M   A   com.apple.tsm.portname
+		   0x2a103    M   D   co.myrt.ajam.xpc
+	}
+	services = {
+		       0      -       co.myrt.ajam
+	}
+}
+";
+        assert_eq!(
+            endpoint_names(domain),
+            ["com.apple.tsm.portname", "co.myrt.ajam.xpc"]
+        );
+
+        let service = "gui/501/co.myrt.ajam = {
+	state = waiting
+	endpoints = {
+		\"co.myrt.ajam.xpc\" = {
+			port = 0x2a103
+			active = 0
+			managed = 1
+		}
+	}
+}
+";
+        assert_eq!(endpoint_names(service), ["co.myrt.ajam.xpc"]);
+        assert!(endpoint_names("").is_empty());
+    }
+
+    #[test]
+    fn test_mach_service_names() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        assert!(agent.mach_service_names().is_empty());
+
+        let mut services = plist::Dictionary::new();
+        services.insert("co.myrt.ajam.xpc".to_string(), true.into());
+        agent
+            .extra
+            .insert("MachServices".to_string(), services.into());
+        assert_eq!(agent.mach_service_names(), ["co.myrt.ajam.xpc"]);
+    }
+}
@@ -0,0 +1,233 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::control::BootstrapOutcome;
+use crate::install::InstallObserver;
+use crate::poll::CancellationToken;
+use crate::LaunchctlResult;
+
+/// Default number of agents applied at the same time.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Set of agents that should be installed.
+///
+/// Applying the manifest brings every agent to its described configuration
+/// with `ensure_installed()`. Independent agents are applied in parallel.
+pub struct Manifest {
+    pub agents: Vec<LaunchAgent>,
+    context: LaunchctlContext,
+    concurrency: usize,
+    cancel: Option<CancellationToken>,
+    splay: Option<Duration>,
+}
+
+/// Outcome of applying a single manifest entry.
+pub struct AgentResult {
+    pub label: String,
+    /// `true` if the agent was (re)installed, `false` if it was up to date.
+    pub result: LaunchctlResult<bool>,
+}
+
+/// Per-agent outcomes of applying a manifest, in manifest order.
+pub struct ManifestReport {
+    pub results: Vec<AgentResult>,
+}
+
+impl ManifestReport {
+    /// Check if every agent was applied successfully.
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|r| r.result.is_ok())
+    }
+
+    /// Returns the labels of agents that were (re)installed.
+    pub fn changed(&self) -> Vec<&str> {
+        self.results
+            .iter()
+            .filter(|r| matches!(r.result, Ok(true)))
+            .map(|r| r.label.as_str())
+            .collect()
+    }
+
+    /// Returns the entries that failed to apply.
+    pub fn failures(&self) -> Vec<&AgentResult> {
+        self.results.iter().filter(|r| r.result.is_err()).collect()
+    }
+}
+
+impl Manifest {
+    pub fn new(agents: Vec<LaunchAgent>) -> Self {
+        Self {
+            agents,
+            context: LaunchctlContext::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            cancel: None,
+            splay: None,
+        }
+    }
+
+    /// Apply the agents with `context`, e.g. to install daemons or to
+    /// preview the commands with a dry run.
+    #[must_use]
+    pub fn context(mut self, context: LaunchctlContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Limit how many agents are applied at the same time.
+    #[must_use]
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    /// Stop applying agents when the token is cancelled. Agents that were
+    /// not started yet fail with
+    /// [`LaunchAgentError::Cancelled`](crate::LaunchAgentError::Cancelled).
+    #[must_use]
+    pub fn cancel_on(mut self, token: &CancellationToken) -> Self {
+        self.cancel = Some(token.clone());
+        self
+    }
+
+    /// Delay the schedules of the agents by a random offset of up to `max`,
+    /// see [`LaunchAgent::apply_splay`]. Installed agents keep their offset,
+    /// so applying the manifest again changes nothing.
+    #[must_use]
+    pub fn splay(mut self, max: Duration) -> Self {
+        self.splay = Some(max);
+        self
+    }
+
+    /// Returns a manifest of `agents` with the same settings.
+    pub(crate) fn with_agents(&self, agents: Vec<LaunchAgent>) -> Self {
+        Self {
+            agents,
+            context: self.context.clone(),
+            concurrency: self.concurrency,
+            cancel: self.cancel.clone(),
+            splay: self.splay,
+        }
+    }
+
+    /// Apply every agent of the manifest.
+    pub fn apply(&self) -> ManifestReport {
+        self.apply_observed(&())
+    }
+
+    /// Same as [`apply`](Self::apply), reporting progress to `observer`.
+    pub fn apply_observed(
+        &self,
+        observer: &(dyn InstallObserver + Sync),
+    ) -> ManifestReport {
+        let results =
+            run_bounded(&self.agents, self.concurrency, |agent| AgentResult {
+                label: agent.label.clone(),
+                result: self
+                    .cancel
+                    .as_ref()
+                    .map_or(Ok(()), CancellationToken::check)
+                    .and_then(|()| self.apply_agent(agent, observer)),
+            });
+        ManifestReport { results }
+    }
+
+    fn apply_agent(
+        &self,
+        agent: &LaunchAgent,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<bool> {
+        let outcome = match self.splay {
+            Some(max) => {
+                let mut agent = agent.clone();
+                agent.apply_splay(max)?;
+                self.context.ensure_installed_observed(&agent, observer)?
+            }
+            None => self.context.ensure_installed_observed(agent, observer)?,
+        };
+        Ok(outcome != BootstrapOutcome::AlreadyLoaded)
+    }
+}
+
+/// Run `f` over `items` on at most `limit` threads, keeping the input order.
+pub(crate) fn run_bounded<T, R, F>(items: &[T], limit: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let slots: Mutex<Vec<Option<R>>> =
+        Mutex::new(items.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..limit.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                slots.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    slots
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("every item is processed"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_run_bounded_keeps_order() {
+        let items: Vec<u64> = (0..20).collect();
+        let results = run_bounded(&items, 4, |i| {
+            thread::sleep(Duration::from_millis(20 - i));
+            i * 2
+        });
+        assert_eq!(results, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_run_bounded_respects_limit() {
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let items = vec![(); 12];
+
+        run_bounded(&items, 3, |()| {
+            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(10));
+            active.fetch_sub(1, Ordering::SeqCst);
+        });
+
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert!(run_bounded(&Vec::<()>::new(), 3, |()| ()).is_empty());
+    }
+
+    #[test]
+    fn test_cancelled_manifest_applies_nothing() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let agent = LaunchAgent::new("co.myrt.ajam.manifest.cancelled");
+        let report = Manifest::new(vec![agent.clone()]).cancel_on(&token).apply();
+
+        assert!(matches!(
+            report.results[0].result,
+            Err(crate::LaunchAgentError::Cancelled)
+        ));
+        assert!(!agent.path().unwrap().exists());
+    }
+}
@@ -0,0 +1,219 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use plist::{Dictionary, Value};
+
+use crate::agent::LaunchAgent;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Extra plist key holding the metadata written by this crate.
+pub const METADATA_KEY: &str = "co.myrt.lunchctl.Metadata";
+
+/// Extra plist key holding a human-readable description of the agent.
+///
+/// Generated plists have no place for comments, so the description is stored
+/// under this key. launchd ignores it.
+pub const DESCRIPTION_KEY: &str = "co.myrt.lunchctl.Description";
+
+const VERSION_KEY: &str = "Version";
+const INSTALLER_KEY: &str = "Installer";
+
+/// Application version in `major.minor.patch` form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for Version {
+    type Err = LaunchAgentError;
+
+    /// Parses `1`, `1.2` and `1.2.3`. Missing components are zero.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || LaunchAgentError::InvalidVersion(s.to_string());
+        let mut parts = s.trim().trim_start_matches('v').split('.');
+        let mut next = |required: bool| match parts.next() {
+            Some(part) => part.parse::<u64>().map_err(|_| invalid()),
+            None if required => Err(invalid()),
+            None => Ok(0),
+        };
+        let version = Self::new(next(true)?, next(false)?, next(false)?);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(version)
+    }
+}
+
+/// Application metadata stored in the agent plist.
+///
+/// Lets installers find out which release of their app wrote the agent
+/// currently on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentMetadata {
+    pub version: Version,
+    /// Identity of the installer, e.g. the bundle identifier of the app.
+    pub installer: Option<String>,
+}
+
+impl AgentMetadata {
+    pub fn new(version: Version) -> Self {
+        Self {
+            version,
+            installer: None,
+        }
+    }
+
+    fn to_dictionary(&self) -> Dictionary {
+        let mut dict = Dictionary::new();
+        dict.insert(VERSION_KEY.to_string(), self.version.to_string().into());
+        if let Some(installer) = &self.installer {
+            dict.insert(INSTALLER_KEY.to_string(), installer.clone().into());
+        }
+        dict
+    }
+
+    fn from_dictionary(dict: &Dictionary) -> Option<Self> {
+        let version = dict.get(VERSION_KEY)?.as_string()?.parse().ok()?;
+        let installer = dict
+            .get(INSTALLER_KEY)
+            .and_then(Value::as_string)
+            .map(ToString::to_string);
+        Some(Self { version, installer })
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the metadata embedded in the configuration, if any.
+    pub fn metadata(&self) -> Option<AgentMetadata> {
+        self.extra
+            .get(METADATA_KEY)
+            .and_then(Value::as_dictionary)
+            .and_then(AgentMetadata::from_dictionary)
+    }
+
+    /// Embeds the metadata into the configuration.
+    /// It is stored on disk with the next `write()`.
+    pub fn set_metadata(&mut self, metadata: &AgentMetadata) {
+        self.extra
+            .insert(METADATA_KEY.to_string(), metadata.to_dictionary().into());
+    }
+
+    /// Returns the description stored in the configuration, if any.
+    pub fn description(&self) -> Option<&str> {
+        self.extra.get(DESCRIPTION_KEY).and_then(Value::as_string)
+    }
+
+    /// Stores a human-readable description in the configuration.
+    /// It is stored on disk with the next `write()`.
+    pub fn set_description(&mut self, description: &str) {
+        self.extra
+            .insert(DESCRIPTION_KEY.to_string(), description.into());
+    }
+
+    /// Returns the version recorded in the agent plist currently on disk.
+    /// Returns `None` if the agent is not installed or has no metadata.
+    pub fn installed_version(&self) -> LaunchctlResult<Option<Version>> {
+        Ok(self
+            .read_installed()?
+            .and_then(|installed| installed.metadata().map(|m| m.version)))
+    }
+
+    /// Check if the agent on disk was written by a release older than `current`.
+    /// Agents that are not installed or lack metadata also need an upgrade.
+    pub fn needs_upgrade(&self, current: &Version) -> LaunchctlResult<bool> {
+        Ok(match self.installed_version()? {
+            Some(installed) => installed.cmp(current) == Ordering::Less,
+            None => true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!("1.2.3".parse::<Version>().unwrap(), Version::new(1, 2, 3));
+        assert_eq!("v2.0".parse::<Version>().unwrap(), Version::new(2, 0, 0));
+        assert_eq!("3".parse::<Version>().unwrap(), Version::new(3, 0, 0));
+        assert!("".parse::<Version>().is_err());
+        assert!("1.2.3.4".parse::<Version>().is_err());
+        assert!("1.x".parse::<Version>().is_err());
+        assert!(Version::new(1, 10, 0) > Version::new(1, 9, 9));
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        assert_eq!(agent.metadata(), None);
+
+        let metadata = AgentMetadata {
+            version: Version::new(1, 4, 0),
+            installer: Some("co.myrt.ajam.app".to_string()),
+        };
+        agent.set_metadata(&metadata);
+        assert_eq!(agent.metadata(), Some(metadata));
+    }
+
+    #[test]
+    fn test_description() {
+        let agent = crate::LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .description("Syncs Ajam projects in the background")
+            .build()
+            .unwrap();
+        assert_eq!(
+            agent.description(),
+            Some("Syncs Ajam projects in the background")
+        );
+
+        let mut buf = Vec::new();
+        plist::to_writer_xml(&mut buf, &agent).unwrap();
+        let parsed: LaunchAgent = plist::from_bytes(&buf).unwrap();
+        assert_eq!(parsed.description(), agent.description());
+    }
+
+    #[test]
+    fn test_needs_upgrade() {
+        let label = format!("co.myrt.ajam.test.{}", rand::random_range(0.0..=1e9));
+        let mut agent = LaunchAgent::new(&label);
+        let current = Version::new(1, 1, 0);
+
+        assert_eq!(agent.installed_version().unwrap(), None);
+        assert!(agent.needs_upgrade(&current).unwrap());
+
+        agent.set_metadata(&AgentMetadata::new(Version::new(1, 0, 5)));
+        agent.write().unwrap();
+        assert_eq!(
+            agent.installed_version().unwrap(),
+            Some(Version::new(1, 0, 5))
+        );
+        assert!(agent.needs_upgrade(&current).unwrap());
+
+        agent.set_metadata(&AgentMetadata::new(current));
+        agent.write().unwrap();
+        assert!(!agent.needs_upgrade(&current).unwrap());
+
+        agent.remove().unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use std::path::Path;
+
+use plist::{Dictionary, Value};
+
+use crate::agent::LaunchAgent;
+use crate::report::stable_hash;
+use crate::LaunchctlResult;
+
+/// Preference domain the agents are managed under.
+const LAUNCHD_DOMAIN: &str = "com.apple.launchd";
+
+/// Configuration profile (`.mobileconfig`) deploying agents through MDM.
+///
+/// Each agent becomes forced managed preferences of the `com.apple.launchd`
+/// domain, with a Service Management rule so macOS lists the agent as
+/// managed in Login Items instead of letting the user turn it off. The
+/// profile is plain plist, unsigned; MDM servers sign it on upload.
+///
+/// UUIDs are derived from the identifiers, so exporting the same agents
+/// again yields a profile that replaces the installed one.
+#[derive(Debug, Clone)]
+pub struct MobileConfig {
+    identifier: String,
+    display_name: Option<String>,
+    organization: Option<String>,
+    agents: Vec<LaunchAgent>,
+}
+
+impl MobileConfig {
+    /// Profile with the reverse-DNS `identifier`, e.g. `co.myrt.agents`.
+    pub fn new(identifier: &str) -> Self {
+        Self {
+            identifier: identifier.to_string(),
+            display_name: None,
+            organization: None,
+            agents: Vec::new(),
+        }
+    }
+
+    /// Set the name shown in System Settings. Defaults to the identifier.
+    #[must_use]
+    pub fn display_name(mut self, name: &str) -> Self {
+        self.display_name = Some(name.to_string());
+        self
+    }
+
+    /// Set the organization shown in System Settings.
+    #[must_use]
+    pub fn organization(mut self, organization: &str) -> Self {
+        self.organization = Some(organization.to_string());
+        self
+    }
+
+    /// Add an agent to the profile.
+    #[must_use]
+    pub fn agent(mut self, agent: &LaunchAgent) -> Self {
+        self.agents.push(agent.clone());
+        self
+    }
+
+    /// Returns the profile as a plist value.
+    pub fn to_value(&self) -> LaunchctlResult<Value> {
+        let mut settings = Dictionary::new();
+        for agent in &self.agents {
+            settings.insert(agent.label.clone(), plist::to_value(agent)?);
+        }
+        let mut forced = Dictionary::new();
+        forced.insert("mcx_preference_settings".to_string(), settings.into());
+        let mut domain = Dictionary::new();
+        domain.insert("Forced".to_string(), Value::Array(vec![forced.into()]));
+        let mut preferences = Dictionary::new();
+        preferences.insert(LAUNCHD_DOMAIN.to_string(), domain.into());
+
+        let mut managed = payload(
+            "com.apple.ManagedClient.preferences",
+            &format!("{}.{LAUNCHD_DOMAIN}", self.identifier),
+        );
+        managed.insert("PayloadContent".to_string(), preferences.into());
+
+        let rules = self
+            .agents
+            .iter()
+            .map(|agent| {
+                let mut rule = Dictionary::new();
+                rule.insert("RuleType".to_string(), "Label".into());
+                rule.insert("RuleValue".to_string(), agent.label.clone().into());
+                if let Some(description) = agent.description() {
+                    rule.insert("Comment".to_string(), description.into());
+                }
+                Value::from(rule)
+            })
+            .collect();
+        let mut login_items = payload(
+            "com.apple.servicemanagement",
+            &format!("{}.servicemanagement", self.identifier),
+        );
+        login_items.insert("Rules".to_string(), Value::Array(rules));
+
+        let mut profile = payload("Configuration", &self.identifier);
+        profile.insert(
+            "PayloadContent".to_string(),
+            Value::Array(vec![managed.into(), login_items.into()]),
+        );
+        profile.insert(
+            "PayloadDisplayName".to_string(),
+            self.display_name
+                .as_ref()
+                .unwrap_or(&self.identifier)
+                .clone()
+                .into(),
+        );
+        if let Some(organization) = &self.organization {
+            profile.insert(
+                "PayloadOrganization".to_string(),
+                organization.clone().into(),
+            );
+        }
+        profile.insert("PayloadScope".to_string(), "System".into());
+        Ok(profile.into())
+    }
+
+    /// Returns the profile as XML.
+    pub fn to_bytes(&self) -> LaunchctlResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.to_value()?.to_writer_xml(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Write the profile to `path`, conventionally with the `.mobileconfig`
+    /// extension.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> LaunchctlResult<()> {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+}
+
+/// Returns the keys every payload has.
+fn payload(kind: &str, identifier: &str) -> Dictionary {
+    let mut payload = Dictionary::new();
+    payload.insert("PayloadType".to_string(), kind.into());
+    payload.insert("PayloadIdentifier".to_string(), identifier.into());
+    payload.insert("PayloadUUID".to_string(), stable_uuid(identifier).into());
+    payload.insert("PayloadVersion".to_string(), 1.into());
+    payload
+}
+
+/// Returns a UUID-formatted digest of the name, the same for every export.
+fn stable_uuid(name: &str) -> String {
+    let hex = format!(
+        "{}{}",
+        stable_hash(name.as_bytes()),
+        stable_hash(name.as_bytes().repeat(2).as_slice())
+    );
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+    .to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mobileconfig() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent.program_arguments = vec!["/usr/local/bin/ajam".into()];
+        agent.set_description("Syncs Ajam projects");
+        let config = MobileConfig::new("co.myrt.profile")
+            .organization("Myrt")
+            .agent(&agent);
+
+        let value = config.to_value().unwrap();
+        let profile = value.as_dictionary().unwrap();
+        assert_eq!(profile["PayloadType"].as_string(), Some("Configuration"));
+        assert_eq!(
+            profile["PayloadDisplayName"].as_string(),
+            Some("co.myrt.profile")
+        );
+
+        let payloads = profile["PayloadContent"].as_array().unwrap();
+        let settings = payloads[0].as_dictionary().unwrap()["PayloadContent"]
+            .as_dictionary()
+            .unwrap()[LAUNCHD_DOMAIN]
+            .as_dictionary()
+            .unwrap()["Forced"]
+            .as_array()
+            .unwrap()[0]
+            .as_dictionary()
+            .unwrap()["mcx_preference_settings"]
+            .as_dictionary()
+            .unwrap();
+        let exported: LaunchAgent =
+            plist::from_value(&settings["co.myrt.ajam"]).unwrap();
+        assert_eq!(exported, agent);
+
+        let rules = payloads[1].as_dictionary().unwrap()["Rules"]
+            .as_array()
+            .unwrap();
+        assert_eq!(
+            rules[0].as_dictionary().unwrap()["RuleValue"].as_string(),
+            Some("co.myrt.ajam")
+        );
+
+        let uuid = stable_uuid("co.myrt.profile");
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(profile["PayloadUUID"].as_string(), Some(uuid.as_str()));
+        assert_eq!(config.to_bytes().unwrap(), config.to_bytes().unwrap());
+    }
+}
@@ -0,0 +1,261 @@
+use std::hash::{Hash, Hasher};
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::fleet::FleetStatus;
+use crate::inspect::Inspector;
+use crate::manifest::{AgentResult, Manifest, ManifestReport};
+use crate::paths::LaunchDirectory;
+use crate::uninstall::{UninstallOptions, UninstallReport};
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Reverse-DNS prefix shared by the labels of an app's agents, such as
+/// `co.myrt.ajam` for `co.myrt.ajam.worker` and `co.myrt.ajam.updater`.
+///
+/// Labels belong to the namespace if they equal the prefix or continue it
+/// after a dot, so `co.myrt.ajamx` is not part of `co.myrt.ajam`.
+/// Namespaces are equal if their prefixes are, whatever their contexts.
+#[derive(Debug, Clone)]
+pub struct LabelNamespace {
+    prefix: String,
+    pub(crate) context: LaunchctlContext,
+}
+
+impl PartialEq for LabelNamespace {
+    fn eq(&self, other: &Self) -> bool {
+        self.prefix == other.prefix
+    }
+}
+
+impl Eq for LabelNamespace {}
+
+impl Hash for LabelNamespace {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.prefix.hash(state);
+    }
+}
+
+impl LabelNamespace {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.trim_end_matches('.').to_string(),
+            context: LaunchctlContext::default(),
+        }
+    }
+
+    /// Find, install and uninstall the namespace's agents with `context`.
+    #[must_use]
+    pub fn context(mut self, context: LaunchctlContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns the label of the child, e.g. `co.myrt.ajam.worker` for
+    /// `worker`.
+    pub fn label(&self, name: &str) -> String {
+        format!("{}.{}", self.prefix, name.trim_start_matches('.'))
+    }
+
+    /// Returns an agent with the child's label.
+    pub fn agent(&self, name: &str) -> LaunchAgent {
+        LaunchAgent::new(&self.label(name))
+    }
+
+    /// Check if the label belongs to the namespace.
+    pub fn contains(&self, label: &str) -> bool {
+        self.child_name(label).is_some() || label == self.prefix
+    }
+
+    /// Returns the part of the label after the prefix, `None` for labels of
+    /// other namespaces and the prefix itself.
+    pub fn child_name<'a>(&self, label: &'a str) -> Option<&'a str> {
+        label
+            .strip_prefix(self.prefix.as_str())?
+            .strip_prefix('.')
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Returns the agents of the namespace in the context's directory,
+    /// `~/Library/LaunchAgents` by default, sorted by path. Unreadable
+    /// plists are skipped.
+    pub fn installed(&self) -> LaunchctlResult<Vec<LaunchAgent>> {
+        let reports = Inspector::with_directories([(
+            LaunchDirectory::UserAgents,
+            self.context.agents_directory()?,
+        )])
+        .inspect();
+        let mut agents = Vec::new();
+        for report in reports {
+            if let Some(error) = report.error {
+                return Err(error.into());
+            }
+            for plist in report.plists {
+                let Ok(mut agent) = plist.agent else {
+                    continue;
+                };
+                if !self.contains(&agent.label) {
+                    continue;
+                }
+                let stem = plist.path.file_stem().and_then(|stem| stem.to_str());
+                if stem.is_some_and(|stem| stem != agent.label) {
+                    agent.file_stem = stem.map(ToString::to_string);
+                }
+                agents.push(agent);
+            }
+        }
+        Ok(agents)
+    }
+
+    /// Returns the labels of the namespace's installed agents.
+    pub fn installed_labels(&self) -> LaunchctlResult<Vec<String>> {
+        Ok(self.installed()?.into_iter().map(|a| a.label).collect())
+    }
+
+    /// Query the status of every installed agent of the namespace.
+    pub fn status(&self) -> LaunchctlResult<FleetStatus> {
+        Ok(self.context.fleet_status(&self.installed()?))
+    }
+
+    /// Uninstall every installed agent of the namespace. Agents that are
+    /// not managed by this app are skipped if the context requires
+    /// ownership, see [`LaunchctlContext::require_ownership`].
+    pub fn uninstall_all(
+        &self,
+        options: &UninstallOptions,
+    ) -> LaunchctlResult<Vec<UninstallReport>> {
+        self.installed()?
+            .iter()
+            .filter(|agent| !self.context.require_ownership || agent.is_managed())
+            .map(|agent| self.context.uninstall_with(agent, options))
+            .collect()
+    }
+
+    /// Apply the manifest and uninstall agents of the namespace it no longer
+    /// lists, so the installed agents match the manifest exactly. If
+    /// ownership is required, agents not managed by this app are kept.
+    ///
+    /// Manifest entries outside the namespace are not applied and fail with
+    /// [`LaunchAgentError::OutsideNamespace`]. Uninstalled agents are
+    /// reported as changed. The manifest's agents are installed with its own
+    /// context, obsolete ones are uninstalled with the namespace's.
+    pub fn apply(&self, manifest: &Manifest) -> LaunchctlResult<ManifestReport> {
+        let (inside, outside): (Vec<_>, Vec<_>) = manifest
+            .agents
+            .iter()
+            .cloned()
+            .partition(|agent| self.contains(&agent.label));
+
+        let obsolete: Vec<_> = self
+            .installed()?
+            .into_iter()
+            .filter(|installed| !inside.iter().any(|a| a.label == installed.label))
+            .filter(|installed| {
+                !self.context.require_ownership || installed.is_managed()
+            })
+            .collect();
+
+        let mut report = manifest.with_agents(inside).apply();
+        report
+            .results
+            .extend(outside.into_iter().map(|agent| AgentResult {
+                result: Err(LaunchAgentError::OutsideNamespace(
+                    agent.label.clone(),
+                    self.prefix.clone(),
+                )),
+                label: agent.label,
+            }));
+        report
+            .results
+            .extend(obsolete.into_iter().map(|agent| AgentResult {
+                result: self.context.uninstall(&agent).map(|_| true),
+                label: agent.label,
+            }));
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels() {
+        let namespace = LabelNamespace::new("co.myrt.ajam.");
+        assert_eq!(namespace.prefix(), "co.myrt.ajam");
+        assert_eq!(namespace.label("worker"), "co.myrt.ajam.worker");
+        assert_eq!(namespace.agent(".updater").label, "co.myrt.ajam.updater");
+
+        assert!(namespace.contains("co.myrt.ajam"));
+        assert!(namespace.contains("co.myrt.ajam.worker"));
+        assert!(!namespace.contains("co.myrt.ajamx.worker"));
+        assert!(!namespace.contains("co.myrt.ajam."));
+        assert_eq!(
+            namespace.child_name("co.myrt.ajam.worker.1"),
+            Some("worker.1")
+        );
+        assert_eq!(namespace.child_name("co.myrt.ajam"), None);
+    }
+
+    #[test]
+    fn test_installed_and_apply() {
+        let prefix = format!("co.myrt.ajam.ns{}", rand::random_range(0..u32::MAX));
+        let context = LaunchctlContext::idle();
+        let namespace = LabelNamespace::new(&prefix).context(context.clone());
+        let worker = namespace.agent("worker");
+        let updater = namespace.agent("updater");
+        worker.write().unwrap();
+        updater.write().unwrap();
+        assert_eq!(
+            namespace.installed_labels().unwrap(),
+            [updater.label, worker.label]
+        );
+        let status = namespace.status().unwrap();
+        assert_eq!(status.agents.len(), 2);
+        assert!(status
+            .agents
+            .iter()
+            .all(|agent| agent.error.is_none() && agent.state == "not loaded"));
+
+        let outsider = LaunchAgent::new("co.myrt.other.worker");
+        let manifest = Manifest::new(vec![outsider]).context(context);
+        let report = namespace.apply(&manifest).unwrap();
+        assert!(matches!(
+            report.results[0].result,
+            Err(LaunchAgentError::OutsideNamespace(..))
+        ));
+        assert_eq!(report.changed().len(), 2);
+        assert!(namespace.installed().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_uninstall_all_skips_foreign_agents() {
+        let prefix = format!("co.myrt.ajam.ns{}", rand::random_range(0..u32::MAX));
+        let context = LaunchctlContext::idle().require_ownership(true);
+        let namespace = LabelNamespace::new(&prefix).context(context.clone());
+        let own = namespace.agent("own");
+        let foreign = namespace.agent("foreign");
+        own.write().unwrap();
+        foreign.write_plist_to(&foreign.path().unwrap()).unwrap();
+
+        let mut changed = foreign.clone();
+        changed.run_at_load = true;
+        assert!(matches!(
+            context.write(&changed),
+            Err(LaunchAgentError::NotManaged(_))
+        ));
+
+        let reports = namespace
+            .uninstall_all(&UninstallOptions::default())
+            .unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(
+            namespace.installed_labels().unwrap(),
+            [foreign.label.as_str()]
+        );
+        foreign.remove().unwrap();
+    }
+}
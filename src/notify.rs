@@ -0,0 +1,83 @@
+use std::collections::BTreeSet;
+
+use crate::alert::{Alert, AlertKind, AlertSink};
+use crate::command::CommandSpec;
+use crate::os::run_checked;
+
+/// Sink posting alerts as macOS user notifications.
+///
+/// Notifications are posted with `osascript`, so they appear under Script
+/// Editor in the notification settings. By default only alerts users can
+/// act on are posted: agents that stopped or were disabled in Login Items.
+#[derive(Debug, Clone)]
+pub struct NotificationSink {
+    title: String,
+    kinds: BTreeSet<AlertKind>,
+}
+
+impl NotificationSink {
+    /// Post notifications with `title`, usually the app name.
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            kinds: BTreeSet::from([AlertKind::Stopped, AlertKind::Disabled]),
+        }
+    }
+
+    /// Post notifications for alerts of `kinds` only.
+    #[must_use]
+    pub fn kinds(mut self, kinds: &[AlertKind]) -> Self {
+        self.kinds = kinds.iter().copied().collect();
+        self
+    }
+
+    /// Returns the `osascript` command posting the alert.
+    pub fn command(&self, alert: &Alert) -> CommandSpec {
+        let script = format!(
+            "display notification {} with title {}",
+            quote(&alert.to_string()),
+            quote(&self.title)
+        );
+        CommandSpec::new("osascript", ["-e".to_string(), script])
+    }
+}
+
+impl AlertSink for NotificationSink {
+    fn alert(&self, alert: &Alert) {
+        if !self.kinds.contains(&alert.kind) {
+            return;
+        }
+        if let Err(e) = run_checked(&self.command(alert)) {
+            log::warn!(target: "lunchctl", "failed to post notification: {e}");
+        }
+    }
+}
+
+/// Returns the text as a string literal for `osascript`.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+
+    #[test]
+    fn test_command() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .description("\"Ajam\" helper")
+            .build()
+            .unwrap();
+        let alert = Alert::new(AlertKind::Stopped, &agent);
+        let command = NotificationSink::new("Ajam").command(&alert);
+        assert_eq!(
+            command.args,
+            [
+                "-e",
+                r#"display notification "\"Ajam\" helper stopped" with title "Ajam""#
+            ]
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use std::ffi::OsStr;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::agent::LaunchAgent;
+use crate::command::CommandSpec;
+use crate::context::LaunchctlContext;
+use crate::control::BootstrapOutcome;
+use crate::domain::ServiceTarget;
+use crate::poll::{poll_for, PollOptions};
+use crate::find::LabelLocation;
+use crate::inspect::Inspector;
+use crate::paths::LaunchDirectory;
+use crate::wrapper::wrap;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Extra plist key marking agents installed with
+/// [`LaunchAgent::install_one_shot`].
+pub const ONE_SHOT_KEY: &str = "co.myrt.lunchctl.OneShot";
+
+/// Who removes a one-shot agent after its job completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CleanupPolicy {
+    /// The job boots itself out and deletes its plist after the program
+    /// exits, through a `/bin/sh` wrapper around the program.
+    #[default]
+    SelfRemoving,
+    /// The program runs as is. The app removes the agent later with
+    /// [`LaunchAgent::cleanup_one_shot`] or [`cleanup_one_shots`].
+    FollowUp,
+}
+
+/// Remove every finished one-shot agent of `~/Library/LaunchAgents`, see
+/// [`LaunchAgent::cleanup_one_shot`]. Returns the labels of the removed
+/// agents. Unreadable plists are skipped.
+pub fn cleanup_one_shots() -> LaunchctlResult<Vec<String>> {
+    LaunchctlContext::default().cleanup_one_shots()
+}
+
+impl LaunchAgent {
+    /// Install the agent to run its job once and be removed afterwards.
+    ///
+    /// The agent gets `LaunchOnlyOnce` and, unless something else starts
+    /// it, `RunAtLoad`. launchd forgets `LaunchOnlyOnce` on reboot, so
+    /// agents left behind would run again at the next login.
+    pub fn install_one_shot(
+        &self,
+        cleanup: CleanupPolicy,
+    ) -> LaunchctlResult<BootstrapOutcome> {
+        LaunchctlContext::default().install_one_shot(self, cleanup)
+    }
+
+    /// Returns the agent as [`install_one_shot`](Self::install_one_shot)
+    /// installs it. Fails if the home directory is unknown.
+    pub fn one_shot(&self, cleanup: CleanupPolicy) -> LaunchctlResult<Self> {
+        LaunchctlContext::default().one_shot(self, cleanup)
+    }
+
+    /// Check if the agent was installed with
+    /// [`install_one_shot`](Self::install_one_shot).
+    pub fn is_one_shot(&self) -> bool {
+        self.extra
+            .get(ONE_SHOT_KEY)
+            .and_then(plist::Value::as_boolean)
+            .unwrap_or(false)
+    }
+
+    /// Boot out the agent and delete its plist if its job has completed,
+    /// i.e. it is loaded, not running and has exited. A plist that is not
+    /// loaded, e.g. left behind by a failed bootstrap, is deleted too.
+    /// Returns `false` if the job has not completed yet or there is no
+    /// plist.
+    pub fn cleanup_one_shot(&self) -> LaunchctlResult<bool> {
+        LaunchctlContext::default().cleanup_one_shot(self)
+    }
+
+    /// Run the agent's program once inside launchd and wait for it to exit.
+    ///
+    /// The plist is written and bootstrapped, the job is started if it does
+    /// not run at load, and after it exits the agent is booted out and its
+    /// plist removed. This gives one-shot tasks launchd's environment, such
+    /// as access to the GUI session.
+    ///
+    /// Returns the exit code, or `None` if the job did not finish within
+    /// `timeout`, in which case it is booted out while still running. Fails
+    /// with [`LabelConflict`](LaunchAgentError::LabelConflict) if a plist with
+    /// the label is already installed.
+    pub fn run_once_and_wait(
+        &self,
+        timeout: Duration,
+    ) -> LaunchctlResult<Option<i32>> {
+        LaunchctlContext::default().run_once_and_wait(self, timeout)
+    }
+
+    /// Make the agent delete its plist at `path` and boot itself out of
+    /// `target` once its program exits, by running the program from a
+    /// `/bin/sh` wrapper.
+    fn remove_after_run(&mut self, path: &Path, target: &ServiceTarget) {
+        let remove =
+            CommandSpec::new("/bin/rm", [OsStr::new("-f"), path.as_os_str()]);
+        let boot_out = CommandSpec::new(
+            "/bin/launchctl",
+            ["bootout".to_string(), target.to_string()],
+        );
+        let script = format!("\"$0\" \"$@\"; {remove}; exec {boot_out}");
+        wrap(&mut self.program_arguments, Some(&mut self.extra), &script);
+    }
+}
+
+impl LaunchctlContext {
+    /// Install the agent to run its job once, see
+    /// [`LaunchAgent::install_one_shot`].
+    pub fn install_one_shot(
+        &self,
+        agent: &LaunchAgent,
+        cleanup: CleanupPolicy,
+    ) -> LaunchctlResult<BootstrapOutcome> {
+        self.install(&self.one_shot(agent, cleanup)?)
+    }
+
+    /// Returns the agent as [`install_one_shot`](Self::install_one_shot)
+    /// installs it.
+    pub fn one_shot(
+        &self,
+        agent: &LaunchAgent,
+        cleanup: CleanupPolicy,
+    ) -> LaunchctlResult<LaunchAgent> {
+        let path = self.path(agent)?;
+        let target = self.service_target(agent);
+        let mut agent = agent.clone();
+        if agent.activation_profile().is_manual() {
+            agent.run_at_load = true;
+        }
+        agent
+            .extra
+            .insert("LaunchOnlyOnce".to_string(), true.into());
+        agent.extra.insert(ONE_SHOT_KEY.to_string(), true.into());
+        if cleanup == CleanupPolicy::SelfRemoving {
+            agent.remove_after_run(&path, &target);
+        }
+        Ok(agent)
+    }
+
+    /// Remove every finished one-shot agent of the context's directory, see
+    /// [`cleanup_one_shots`].
+    pub fn cleanup_one_shots(&self) -> LaunchctlResult<Vec<String>> {
+        let reports = Inspector::with_directories([(
+            LaunchDirectory::UserAgents,
+            self.agents_directory()?,
+        )])
+        .inspect();
+        let mut removed = Vec::new();
+        for report in reports {
+            if let Some(error) = report.error {
+                return Err(error.into());
+            }
+            for plist in report.plists {
+                let Ok(agent) = plist.agent else {
+                    continue;
+                };
+                if agent.is_one_shot() && self.cleanup_one_shot(&agent)? {
+                    removed.push(agent.label);
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Remove the agent if its job has completed, see
+    /// [`LaunchAgent::cleanup_one_shot`].
+    pub fn cleanup_one_shot(&self, agent: &LaunchAgent) -> LaunchctlResult<bool> {
+        let _lock = self.lock(agent)?;
+        match self.info(agent)? {
+            Some(info) => {
+                if info.pid().is_some() || info.last_exit_code().is_none() {
+                    return Ok(false);
+                }
+                self.boot_out(agent)?;
+            }
+            None if !self.path(agent)?.exists() => return Ok(false),
+            None => {}
+        }
+        self.remove_plist(agent)?;
+        Ok(true)
+    }
+
+    /// Run the agent's program once inside launchd, see
+    /// [`LaunchAgent::run_once_and_wait`].
+    pub fn run_once_and_wait(
+        &self,
+        agent: &LaunchAgent,
+        timeout: Duration,
+    ) -> LaunchctlResult<Option<i32>> {
+        let _lock = self.lock(agent)?;
+        let path = self.path(agent)?;
+        if path.exists() {
+            return Err(LaunchAgentError::LabelConflict(
+                agent.label.clone(),
+                vec![LabelLocation::plist(path)],
+            ));
+        }
+        self.check_conflicts(agent)?;
+
+        self.write_plist(agent)?;
+        let result = self.run_registered(agent, timeout);
+
+        if self.is_loaded(agent)? {
+            self.boot_out(agent)?;
+        }
+        self.remove_plist(agent)?;
+        result
+    }
+
+    fn run_registered(
+        &self,
+        agent: &LaunchAgent,
+        timeout: Duration,
+    ) -> LaunchctlResult<Option<i32>> {
+        self.bootstrap(agent)?;
+        if !agent.run_at_load {
+            self.kickstart(agent, false)?;
+        }
+
+        let exit_code = || {
+            let Some(info) = self.info(agent)? else {
+                return Ok(None);
+            };
+            if LaunchAgent::check_is_running(info.raw()) {
+                return Ok(None);
+            }
+            Ok(info.last_exit_code())
+        };
+        poll_for(exit_code, &PollOptions::new(timeout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_shot() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam.once");
+        agent.program_arguments = vec!["/usr/bin/true".into()];
+        assert!(!agent.is_one_shot());
+
+        let follow_up = agent.one_shot(CleanupPolicy::FollowUp).unwrap();
+        assert!(follow_up.is_one_shot());
+        assert!(follow_up.run_at_load);
+        assert_eq!(follow_up.extra["LaunchOnlyOnce"].as_boolean(), Some(true));
+        assert_eq!(follow_up.program_arguments, agent.program_arguments);
+
+        agent.extra.insert("StartInterval".to_string(), 60.into());
+        let self_removing = agent.one_shot(CleanupPolicy::SelfRemoving).unwrap();
+        assert!(!self_removing.run_at_load);
+        assert_eq!(self_removing.program_arguments[..2], ["/bin/sh", "-c"]);
+        assert_eq!(self_removing.program_arguments[3], "/usr/bin/true");
+
+        // Not loaded and never written.
+        assert!(!LaunchctlContext::idle()
+            .cleanup_one_shot(&follow_up)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_cleanup_one_shot_not_loaded() {
+        let label = format!("co.myrt.ajam.once.{}", rand::random_range(0..u32::MAX));
+        let mut agent = LaunchAgent::new(&label);
+        agent.program_arguments = vec!["backup".into()];
+        agent
+            .extra
+            .insert("Program".to_string(), "/usr/local/bin/backup".into());
+        let agent = agent.one_shot(CleanupPolicy::SelfRemoving).unwrap();
+        assert!(!agent.extra.contains_key("Program"));
+        assert_eq!(agent.program_arguments[3], "/usr/local/bin/backup");
+        assert!(!agent.program_arguments[2]
+            .to_string_lossy()
+            .contains(".lock"));
+
+        let context = LaunchctlContext::idle();
+        context.write(&agent).unwrap();
+        assert!(context.cleanup_one_shot(&agent).unwrap());
+        assert!(!LaunchAgent::exists(&label));
+    }
+
+    #[test]
+    fn test_run_once_refuses_installed_label() {
+        let label = format!("co.myrt.ajam.once.{}", rand::random_range(0..u32::MAX));
+        let agent = LaunchAgent::new(&label);
+        agent.write().unwrap();
+
+        let result = agent.run_once_and_wait(Duration::from_millis(10));
+        assert!(matches!(result, Err(LaunchAgentError::LabelConflict(..))));
+        assert!(LaunchAgent::exists(&label));
+
+        agent.remove().unwrap();
+
+        let directory = std::env::temp_dir().join(&label);
+        std::fs::create_dir(&directory).unwrap();
+        let context = LaunchctlContext::idle().directory(&directory);
+        context.write(&agent).unwrap();
+        let Err(LaunchAgentError::LabelConflict(_, locations)) =
+            context.run_once_and_wait(&agent, Duration::from_millis(10))
+        else {
+            panic!("installed label was run");
+        };
+        assert_eq!(
+            locations,
+            [LabelLocation::OtherPlist(context.path(&agent).unwrap())]
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::agent::LaunchAgent;
+use crate::homebrew::HOMEBREW_NAMESPACE;
+use crate::paths::LaunchDirectory;
+
+/// Label prefixes of common MDM and device management agents.
+const MDM_PREFIXES: [&str; 8] = [
+    "com.jamf.",
+    "com.jamfsoftware.",
+    "io.kandji.",
+    "com.kandji.",
+    "com.mosyle.",
+    "com.addigy.",
+    "com.airwatch.",
+    "com.microsoft.intune.",
+];
+
+/// Probable origin of an inspected plist, guessed from its label, location
+/// and program path.
+///
+/// The guess lets cleanup tools leave agents of package managers, MDM and
+/// other apps alone. It is a heuristic: a hand-written plist can look like
+/// anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AgentOrigin {
+    /// Written by this crate.
+    Lunchctl,
+    /// Shipped with macOS.
+    Apple,
+    /// Generated by `brew services`.
+    Homebrew,
+    /// Installed by the `port` package manager.
+    MacPorts,
+    /// Installed by a device management agent.
+    Mdm,
+    /// Copied by an app, running a program from its bundle.
+    AppBundle,
+    /// None of the above, presumably written by hand.
+    HandWritten,
+}
+
+impl AgentOrigin {
+    /// Guess the origin of the plist at `path` in `directory`. Plists that
+    /// could not be parsed are guessed from their file name.
+    pub fn classify(
+        directory: LaunchDirectory,
+        path: &Path,
+        agent: Option<&LaunchAgent>,
+    ) -> Self {
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("");
+        let label = agent.map_or(stem, |agent| agent.label.as_str());
+        let program = agent.and_then(LaunchAgent::program_path);
+        let program = program.as_deref().unwrap_or(Path::new(""));
+
+        if agent.is_some_and(LaunchAgent::has_lunchctl_keys) {
+            Self::Lunchctl
+        } else if directory.is_protected() || label.starts_with("com.apple.") {
+            Self::Apple
+        } else if label.starts_with(&format!("{HOMEBREW_NAMESPACE}."))
+            || ["/opt/homebrew", "/usr/local/Cellar", "/usr/local/opt"]
+                .iter()
+                .any(|prefix| program.starts_with(prefix))
+        {
+            Self::Homebrew
+        } else if label.starts_with("org.macports.")
+            || program.starts_with("/opt/local")
+        {
+            Self::MacPorts
+        } else if MDM_PREFIXES.iter().any(|prefix| label.starts_with(prefix)) {
+            Self::Mdm
+        } else if program
+            .ancestors()
+            .any(|dir| dir.extension().is_some_and(|ext| ext == "app"))
+            || agent.is_some_and(|agent| {
+                agent.extra.contains_key("BundleProgram")
+                    || agent.extra.contains_key("AssociatedBundleIdentifiers")
+            })
+        {
+            Self::AppBundle
+        } else {
+            Self::HandWritten
+        }
+    }
+
+    /// Check if the plist was installed by other software, which cleanup
+    /// tools should not touch.
+    pub fn is_third_party(self) -> bool {
+        !matches!(self, Self::Lunchctl | Self::HandWritten)
+    }
+}
+
+impl fmt::Display for AgentOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Lunchctl => "lunchctl",
+            Self::Apple => "Apple",
+            Self::Homebrew => "Homebrew",
+            Self::MacPorts => "MacPorts",
+            Self::Mdm => "MDM",
+            Self::AppBundle => "app bundle",
+            Self::HandWritten => "hand-written",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::{AgentMetadata, Version};
+
+    fn classify(
+        directory: LaunchDirectory,
+        label: &str,
+        program: &str,
+    ) -> AgentOrigin {
+        let mut agent = LaunchAgent::new(label);
+        agent.program_arguments = vec![program.into()];
+        let path = directory.plist_path(label).unwrap();
+        AgentOrigin::classify(directory, &path, Some(&agent))
+    }
+
+    #[test]
+    fn test_classify() {
+        use LaunchDirectory::{GlobalDaemons, SystemAgents, UserAgents};
+
+        let cases = [
+            (
+                SystemAgents,
+                "com.example.x",
+                "/usr/libexec/x",
+                AgentOrigin::Apple,
+            ),
+            (
+                UserAgents,
+                "homebrew.mxcl.redis",
+                "/x",
+                AgentOrigin::Homebrew,
+            ),
+            (
+                UserAgents,
+                "redis",
+                "/opt/homebrew/opt/redis/bin/redis",
+                AgentOrigin::Homebrew,
+            ),
+            (
+                GlobalDaemons,
+                "org.macports.mysql",
+                "/x",
+                AgentOrigin::MacPorts,
+            ),
+            (
+                GlobalDaemons,
+                "com.jamf.management.daemon",
+                "/x",
+                AgentOrigin::Mdm,
+            ),
+            (
+                UserAgents,
+                "com.example.helper",
+                "/Applications/Example.app/Contents/MacOS/helper",
+                AgentOrigin::AppBundle,
+            ),
+            (
+                UserAgents,
+                "backup",
+                "/Users/me/bin/backup",
+                AgentOrigin::HandWritten,
+            ),
+        ];
+        for (directory, label, program, origin) in cases {
+            assert_eq!(classify(directory, label, program), origin, "{label}");
+        }
+
+        let mut agent = LaunchAgent::new("com.example.x");
+        agent.set_metadata(&AgentMetadata::new(Version::new(1, 0, 0)));
+        let path = UserAgents.plist_path("com.example.x").unwrap();
+        assert_eq!(
+            AgentOrigin::classify(UserAgents, &path, Some(&agent)),
+            AgentOrigin::Lunchctl
+        );
+        let broken = UserAgents.plist_path("homebrew.mxcl.x").unwrap();
+        assert_eq!(
+            AgentOrigin::classify(UserAgents, &broken, None),
+            AgentOrigin::Homebrew
+        );
+        assert!(AgentOrigin::Mdm.is_third_party());
+        assert!(!AgentOrigin::HandWritten.is_third_party());
+    }
+}
@@ -1,24 +1,127 @@
-use std::process::Command;
+use std::ffi::{CStr, OsStr};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
+use crate::command::{CommandOutput, CommandSpec};
+use crate::telemetry::{record_call, DEFAULT_SLOW_CALL_THRESHOLD};
 use crate::{LaunchAgentError, LaunchctlResult};
 
-/// Run a shell command.
-pub(crate) fn run_shell(command: &str) -> LaunchctlResult<String> {
-    let output =
-        Command::new("sh")
-            .arg("-c")
-            .arg(command)
-            .output()
-            .map_err(|e| {
-                LaunchAgentError::CommandFailed(
-                    e.raw_os_error().unwrap_or(1),
-                    e.to_string(),
-                )
-            })?;
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+/// Run a command and fail if it exits with a non-zero status.
+pub(crate) fn run_checked(command: &CommandSpec) -> LaunchctlResult<String> {
+    checked(&run_raw(command, DEFAULT_SLOW_CALL_THRESHOLD)?)
+}
+
+/// Run a command, keeping its output as bytes and recording how long
+/// launchctl invocations take, warning about ones slower than
+/// `slow_call_threshold`.
+pub(crate) fn run_raw(
+    command: &CommandSpec,
+    slow_call_threshold: Duration,
+) -> LaunchctlResult<CommandOutput> {
+    let started = Instant::now();
+    let result = spawn(command, false);
+    if command.program == "launchctl" {
+        let success = result.as_ref().is_ok_and(CommandOutput::success);
+        record_call(command, started.elapsed(), success, slow_call_threshold);
+    }
+    result
+}
+
+/// Returns stdout of a successful command, the error message of a failed one.
+pub(crate) fn checked(output: &CommandOutput) -> LaunchctlResult<String> {
+    if output.success() {
+        return Ok(output.stdout_lossy().into_owned());
+    }
+    Err(command_error(output))
+}
+
+/// Returns the error of a failed command, with its error output or, if it
+/// printed none, its output as the message.
+pub(crate) fn command_error(output: &CommandOutput) -> LaunchAgentError {
+    let stdout = output.stdout_lossy();
+    let stderr = output.stderr_lossy();
+    let message = if stderr.trim().is_empty() {
+        stdout.trim().to_string()
+    } else {
+        stderr.trim().to_string()
+    };
+    LaunchAgentError::CommandFailed(output.code, message)
+}
+
+/// Run the command without a shell, see [`CommandSpec::to_command`]. A
+/// program that does not exist exits with 127, as it would in a shell.
+pub(crate) fn spawn(
+    command: &CommandSpec,
+    inherit_environment: bool,
+) -> LaunchctlResult<CommandOutput> {
+    let output = match command.command(inherit_environment).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(CommandOutput {
+                code: 127,
+                stdout: Vec::new(),
+                stderr: format!("{}: command not found", command.program).into(),
+            });
+        }
+        Err(e) => {
+            return Err(LaunchAgentError::CommandFailed(
+                e.raw_os_error().unwrap_or(1),
+                e.to_string(),
+            ))
+        }
+    };
+    Ok(CommandOutput {
+        code: output.status.code().unwrap_or(-1),
+        stdout: output.stdout,
+        stderr: output.stderr,
+    })
 }
 
 /// Get the user ID.
 pub(crate) fn get_user_id() -> u32 {
     unsafe { libc::geteuid() }
 }
+
+/// Get the current user's home directory.
+///
+/// Processes spawned by launchd may run without `HOME`, so the user
+/// database is consulted if it is not set. Fails if neither knows it,
+/// instead of guessing a directory to write plists to.
+pub(crate) fn home_dir() -> io::Result<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| passwd_home(get_user_id()))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "could not determine the home directory",
+            )
+        })
+}
+
+/// Returns the home directory of the user from the user database.
+fn passwd_home(uid: u32) -> Option<PathBuf> {
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    // SAFETY: passwd is plain data, getpwuid_r fills it in.
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    // SAFETY: the buffers outlive the call and their sizes are passed.
+    let status = unsafe {
+        libc::getpwuid_r(
+            uid,
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if status != 0 || result.is_null() || passwd.pw_dir.is_null() {
+        return None;
+    }
+    // SAFETY: pw_dir points to a NUL-terminated string inside `buffer`.
+    let dir = unsafe { CStr::from_ptr(passwd.pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
@@ -0,0 +1,102 @@
+use std::borrow::Cow;
+use std::sync::{PoisonError, RwLock};
+
+use crate::agent::LaunchAgent;
+
+/// Extra plist keys written by this crate start with it.
+const LUNCHCTL_KEY_PREFIX: &str = "co.myrt.lunchctl.";
+
+/// Extra plist key naming the app that manages the agent.
+pub const MANAGED_BY_KEY: &str = "co.myrt.lunchctl.ManagedBy";
+
+/// Manager plists are stamped with while no app is set with
+/// [`set_manager`].
+pub const DEFAULT_MANAGER: &str = "co.myrt.lunchctl";
+
+static MANAGER: RwLock<Option<String>> = RwLock::new(None);
+
+/// Set the name, usually the bundle identifier, of the app managing the
+/// agents. Written plists are stamped with it under [`MANAGED_BY_KEY`],
+/// unless the agent names another manager. With `None` they are stamped
+/// with [`DEFAULT_MANAGER`].
+pub fn set_manager(name: Option<&str>) {
+    *MANAGER.write().unwrap_or_else(PoisonError::into_inner) =
+        name.map(String::from);
+}
+
+/// Returns the name set with [`set_manager`].
+pub fn manager() -> Option<String> {
+    MANAGER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+impl LaunchAgent {
+    /// Returns the name of the app managing the agent.
+    pub fn managed_by(&self) -> Option<&str> {
+        self.extra.get(MANAGED_BY_KEY)?.as_string()
+    }
+
+    /// Name the app managing the agent.
+    pub fn set_managed_by(&mut self, manager: &str) {
+        self.extra
+            .insert(MANAGED_BY_KEY.to_string(), manager.into());
+    }
+
+    /// Check if the agent is marked as managed by this app: by the name set
+    /// with [`set_manager`], or by any app if none is set, which includes
+    /// plists stamped with [`DEFAULT_MANAGER`].
+    pub fn is_managed(&self) -> bool {
+        self.managed_by().is_some_and(|managed_by| {
+            manager().map_or(true, |manager| managed_by == manager)
+        })
+    }
+
+    /// Returns the configuration as written: stamped with the manager, with
+    /// a missing bundle-relative program resolved against the current app
+    /// bundle.
+    pub(crate) fn stamped(&self) -> Cow<'_, Self> {
+        let resolved = self.resolved();
+        if resolved.managed_by().is_some() {
+            return resolved;
+        }
+        let mut agent = resolved.into_owned();
+        agent.set_managed_by(&manager().unwrap_or_else(|| DEFAULT_MANAGER.into()));
+        Cow::Owned(agent)
+    }
+
+    /// Check if the configuration carries a key written by this crate.
+    pub(crate) fn has_lunchctl_keys(&self) -> bool {
+        self.extra
+            .keys()
+            .any(|key| key.starts_with(LUNCHCTL_KEY_PREFIX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_managed_by() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        assert_eq!(agent.managed_by(), None);
+        assert!(!agent.is_managed());
+        assert!(!agent.has_lunchctl_keys());
+
+        agent.set_managed_by("co.myrt.ajam.app");
+        assert_eq!(agent.managed_by(), Some("co.myrt.ajam.app"));
+        assert!(agent.is_managed());
+        assert!(agent.has_lunchctl_keys());
+        assert!(matches!(agent.stamped(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_stamped_by_default() {
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        let stamped = agent.stamped();
+        assert_eq!(stamped.managed_by(), Some(DEFAULT_MANAGER));
+        assert!(stamped.is_managed());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::agent::{write_atomically, LaunchAgent};
+use crate::inspect::Inspector;
+use crate::paths::LaunchDirectory;
+use crate::LaunchctlResult;
+
+impl LaunchAgent {
+    /// Modify the plist of an installed agent in place, see
+    /// [`patch`](Self::patch). The plist is looked up by label, so it is
+    /// found even if its file is named differently.
+    pub fn patch_file<F>(label: &str, edit: F) -> LaunchctlResult<Self>
+    where
+        F: FnOnce(&mut Self),
+    {
+        Self::installed(label)?.patch(edit)
+    }
+
+    /// Modify the plist at the agent's path in place.
+    ///
+    /// The plist is loaded, `edit` is applied to it and only the keys it
+    /// changed are written back, atomically and under the agent's lock. Keys
+    /// the closure left alone, including defaults this crate would fill in,
+    /// stay exactly as they were, which makes it safe to tweak plists owned by
+    /// other software. The label cannot be changed this way.
+    ///
+    /// Returns the agent as written. Running agents pick up the change after
+    /// being booted out and bootstrapped again.
+    pub fn patch<F>(&self, edit: F) -> LaunchctlResult<Self>
+    where
+        F: FnOnce(&mut Self),
+    {
+        let _lock = self.lock()?;
+        let path = self.path()?;
+
+        let mut original: plist::Dictionary = plist::from_file(&path)?;
+        let mut agent =
+            Self::from_plist_value(plist::Value::Dictionary(original.clone()))?;
+        let label = agent.label.clone();
+        let before = to_dictionary(&agent)?;
+
+        edit(&mut agent);
+        agent.label = label;
+        agent.file_stem.clone_from(&self.file_stem);
+        let after = to_dictionary(&agent)?;
+
+        for (key, value) in &after {
+            if before.get(key) != Some(value) {
+                original.insert(key.clone(), value.clone());
+            }
+        }
+        for key in before.keys() {
+            if !after.contains_key(key) {
+                original.remove(key);
+            }
+        }
+
+        write_atomically(&path, |file| Ok(plist::to_writer_xml(file, &original)?))?;
+        Ok(agent)
+    }
+
+    /// Returns the user agent installed with the label, from the plist named
+    /// after it or else from the plist defining it.
+    fn installed(label: &str) -> LaunchctlResult<Self> {
+        if Self::path_for(label)?.exists() {
+            return Self::from_file(label);
+        }
+        let directory = LaunchDirectory::UserAgents;
+        let reports =
+            Inspector::with_directories([(directory, directory.path()?)]).inspect();
+        let stem = reports
+            .iter()
+            .flat_map(|report| &report.plists)
+            .find(|plist| plist.agent.as_ref().is_ok_and(|a| a.label == label))
+            .and_then(|plist| plist.path.file_stem())
+            .map(|stem| stem.to_string_lossy().into_owned());
+        match stem {
+            Some(stem) => Self::from_file_stem(&stem),
+            None => Self::from_file(label),
+        }
+    }
+}
+
+fn to_dictionary(agent: &LaunchAgent) -> LaunchctlResult<plist::Dictionary> {
+    match plist::to_value(agent)? {
+        plist::Value::Dictionary(dictionary) => Ok(dictionary),
+        _ => unreachable!("launch agents serialize to dictionaries"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch_file_keeps_untouched_keys() {
+        let label =
+            format!("co.myrt.ajam.patch.{}", rand::random_range(0..u32::MAX));
+        let agent = LaunchAgent::new(&label);
+        let mut contents = plist::Dictionary::new();
+        contents.insert("Label".to_string(), label.clone().into());
+        contents.insert("Program".to_string(), "/usr/bin/true".into());
+        plist::to_file_xml(agent.path().unwrap(), &contents).unwrap();
+
+        let patched = LaunchAgent::patch_file(&label, |agent| {
+            agent.run_at_load = true;
+            agent.label = "ignored".to_string();
+        })
+        .unwrap();
+        assert_eq!(patched.label, label);
+
+        let written: plist::Dictionary =
+            plist::from_file(agent.path().unwrap()).unwrap();
+        assert_eq!(written.get("RunAtLoad"), Some(&true.into()));
+        assert_eq!(written.get("Program"), Some(&"/usr/bin/true".into()));
+        assert_eq!(written.get("Label"), Some(&label.into()));
+        assert!(!written.contains_key("StandardOutPath"));
+        assert!(!written.contains_key("KeepAlive"));
+
+        agent.remove().unwrap();
+    }
+
+    #[test]
+    fn test_patch_file_named_differently() {
+        let label =
+            format!("co.myrt.ajam.patch.{}", rand::random_range(0..u32::MAX));
+        let mut agent = LaunchAgent::new(&label);
+        agent.file_stem = Some(format!("{label}.renamed"));
+        agent.write().unwrap();
+
+        let patched = LaunchAgent::patch_file(&label, |agent| {
+            agent.run_at_load = true;
+        })
+        .unwrap();
+        assert_eq!(patched.path().unwrap(), agent.path().unwrap());
+        assert!(!LaunchAgent::path_for(&label).unwrap().exists());
+        let written: plist::Dictionary =
+            plist::from_file(agent.path().unwrap()).unwrap();
+        assert_eq!(written.get("RunAtLoad"), Some(&true.into()));
+
+        agent.remove().unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+
+use crate::os::home_dir;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Standard directory launchd loads job definitions from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LaunchDirectory {
+    /// `~/Library/LaunchAgents`, per-user agents.
+    UserAgents,
+    /// `/Library/LaunchAgents`, agents for every user.
+    GlobalAgents,
+    /// `/Library/LaunchDaemons`, system-wide daemons.
+    GlobalDaemons,
+    /// `/System/Library/LaunchAgents`, agents shipped with macOS.
+    SystemAgents,
+    /// `/System/Library/LaunchDaemons`, daemons shipped with macOS.
+    SystemDaemons,
+}
+
+impl LaunchDirectory {
+    /// Every directory, in the order launchd documentation lists them.
+    pub const ALL: [Self; 5] = [
+        Self::UserAgents,
+        Self::GlobalAgents,
+        Self::GlobalDaemons,
+        Self::SystemAgents,
+        Self::SystemDaemons,
+    ];
+
+    /// Returns the location of the directory. Fails for
+    /// `~/Library/LaunchAgents` if the home directory is unknown.
+    pub fn path(self) -> io::Result<PathBuf> {
+        Ok(match self {
+            Self::UserAgents => home_dir()?.join("Library").join("LaunchAgents"),
+            Self::GlobalAgents => PathBuf::from("/Library/LaunchAgents"),
+            Self::GlobalDaemons => PathBuf::from("/Library/LaunchDaemons"),
+            Self::SystemAgents => PathBuf::from("/System/Library/LaunchAgents"),
+            Self::SystemDaemons => PathBuf::from("/System/Library/LaunchDaemons"),
+        })
+    }
+
+    /// Check if the directory is on the read-only system volume guarded by
+    /// System Integrity Protection. Its plists cannot be changed, even by root.
+    pub fn is_protected(self) -> bool {
+        matches!(self, Self::SystemAgents | Self::SystemDaemons)
+    }
+
+    /// Check if plists in the directory can be installed and removed, given
+    /// the necessary privileges.
+    pub fn is_managed(self) -> bool {
+        !self.is_protected()
+    }
+
+    /// Returns the directory containing the plist at `path`, if it is one of
+    /// the standard directories.
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let parent = path.parent()?;
+        Self::ALL
+            .into_iter()
+            .find(|dir| dir.path().is_ok_and(|path| path == parent))
+    }
+
+    /// Returns the path of the plist named `<file_stem>.plist` in the
+    /// directory.
+    pub fn plist_path(self, file_stem: &str) -> io::Result<PathBuf> {
+        Ok(self.path()?.join(format!("{file_stem}.plist")))
+    }
+
+    /// Check if the directory exists. `~/Library/LaunchAgents` is missing
+    /// on fresh accounts until something installs an agent.
+    pub fn exists(self) -> bool {
+        self.path().is_ok_and(|path| path.is_dir())
+    }
+
+    /// Check if the current process can create and remove plists in the
+    /// directory. Global directories are writable by root only.
+    pub fn is_writable(self) -> bool {
+        let Some(path) = self
+            .path()
+            .ok()
+            .and_then(|path| CString::new(path.into_os_string().into_vec()).ok())
+        else {
+            return false;
+        };
+        unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+    }
+
+    /// Create the directory if it does not exist. Fails for protected
+    /// directories, see [`is_protected`](Self::is_protected).
+    pub fn create(self) -> LaunchctlResult<PathBuf> {
+        let path = self.path()?;
+        if self.is_protected() {
+            return Err(LaunchAgentError::SIPProtected(path));
+        }
+        std::fs::create_dir_all(&path)?;
+        Ok(path)
+    }
+}
+
+/// Check if the path lies in the SIP-protected `/System/Library`. The rest of
+/// `/System` includes the writable data volume at `/System/Volumes/Data`.
+pub(crate) fn is_protected_path(path: &Path) -> bool {
+    path.starts_with("/System/Library")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protected_directories() {
+        let protected: Vec<_> = LaunchDirectory::ALL
+            .into_iter()
+            .filter(|dir| dir.is_protected())
+            .collect();
+        assert_eq!(
+            protected,
+            vec![
+                LaunchDirectory::SystemAgents,
+                LaunchDirectory::SystemDaemons
+            ]
+        );
+        assert!(LaunchDirectory::UserAgents.is_managed());
+        assert!(is_protected_path(Path::new(
+            "/System/Library/LaunchDaemons/com.apple.x.plist"
+        )));
+        assert!(!is_protected_path(Path::new(
+            "/Library/LaunchDaemons/x.plist"
+        )));
+        assert!(!is_protected_path(Path::new(
+            "/System/Volumes/Data/Library/LaunchDaemons/x.plist"
+        )));
+    }
+
+    #[test]
+    fn test_plist_paths() {
+        let path = LaunchDirectory::GlobalDaemons
+            .plist_path("co.myrt.ajam")
+            .unwrap();
+        assert_eq!(path, Path::new("/Library/LaunchDaemons/co.myrt.ajam.plist"));
+        assert_eq!(
+            LaunchDirectory::for_path(&path),
+            Some(LaunchDirectory::GlobalDaemons)
+        );
+        assert_eq!(
+            LaunchDirectory::for_path(
+                &LaunchDirectory::UserAgents.plist_path("x").unwrap()
+            ),
+            Some(LaunchDirectory::UserAgents)
+        );
+        assert_eq!(LaunchDirectory::for_path(Path::new("/tmp/x.plist")), None);
+        assert!(matches!(
+            LaunchDirectory::SystemAgents.create(),
+            Err(LaunchAgentError::SIPProtected(_))
+        ));
+    }
+}
@@ -0,0 +1,204 @@
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+
+use crate::agent::LaunchAgent;
+use crate::command::CommandSpec;
+use crate::context::LaunchctlContext;
+use crate::paths::LaunchDirectory;
+use crate::os::{checked, get_user_id};
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Mode launchd expects for job plists.
+pub const PLIST_MODE: u32 = 0o644;
+
+/// User and group IDs of `root:wheel`.
+const ROOT: (u32, u32) = (0, 0);
+
+/// Owner and mode of a plist on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlistPermissions {
+    pub uid: u32,
+    pub gid: u32,
+    /// Permission bits, without the file type.
+    pub mode: u32,
+}
+
+impl PlistPermissions {
+    /// Read the owner and mode of the file.
+    pub fn of(path: &Path) -> LaunchctlResult<Self> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Self {
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            mode: metadata.permissions().mode() & 0o7777,
+        })
+    }
+
+    /// Returns why launchd would refuse a plist with these permissions at
+    /// `path`, `None` if it would accept it.
+    ///
+    /// Plists in the system-wide directories must belong to `root:wheel`,
+    /// user agents to the current user. None of them may be writable by
+    /// group or others.
+    pub fn problem(&self, path: &Path) -> Option<String> {
+        if self.mode & 0o022 != 0 {
+            return Some(format!(
+                "mode {:o} is writable by group or others",
+                self.mode
+            ));
+        }
+        match expected_owner(path) {
+            Some((uid, gid)) if (self.uid, self.gid) != (uid, gid) => Some(format!(
+                "owned by {}:{} instead of root:wheel",
+                self.uid, self.gid
+            )),
+            None if self.uid != get_user_id() => Some(format!(
+                "owned by uid {} instead of the current user",
+                self.uid
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl LaunchAgent {
+    /// Check that launchd will accept the owner and mode of the plist on
+    /// disk, failing with [`LaunchAgentError::BadPlistPermissions`] if not.
+    pub fn verify_permissions(&self) -> LaunchctlResult<()> {
+        let path = self.path()?;
+        match PlistPermissions::of(&path)?.problem(&path) {
+            Some(problem) => {
+                Err(LaunchAgentError::BadPlistPermissions(path, problem))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Give the plist on disk the owner and mode launchd expects, repairing
+    /// "dubious ownership" failures. Changing the owner requires root.
+    pub fn fix_permissions(&self) -> LaunchctlResult<()> {
+        LaunchctlContext::default().fix_permissions(self)
+    }
+}
+
+impl LaunchctlContext {
+    /// Give the plist in the context's directory the owner and mode launchd
+    /// expects, see [`LaunchAgent::fix_permissions`]. With
+    /// [`elevation`](Self::elevation), `chmod` and `chown` run through the
+    /// runner.
+    pub fn fix_permissions(&self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        let path = self.path(agent)?;
+        let current = PlistPermissions::of(&path)?;
+        let (uid, gid) =
+            expected_owner(&path).unwrap_or((get_user_id(), current.gid));
+        if self.is_elevated() {
+            let chmod = CommandSpec::new(
+                "/bin/chmod",
+                [
+                    format!("{PLIST_MODE:o}").into(),
+                    path.clone().into_os_string(),
+                ],
+            );
+            checked(&self.run(&self.elevated(chmod))?)?;
+            if (current.uid, current.gid) != (uid, gid) {
+                let chown = CommandSpec::new(
+                    "/usr/sbin/chown",
+                    [format!("{uid}:{gid}").into(), path.into_os_string()],
+                );
+                checked(&self.run(&self.elevated(chown))?)?;
+            }
+            return Ok(());
+        }
+        if self.is_dry_run() {
+            return Ok(());
+        }
+        std::fs::set_permissions(
+            &path,
+            std::fs::Permissions::from_mode(PLIST_MODE),
+        )?;
+        if (current.uid, current.gid) != (uid, gid) {
+            std::os::unix::fs::chown(&path, Some(uid), Some(gid))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the owner required for plists in system-wide directories, `None`
+/// for plists that belong to the current user.
+fn expected_owner(path: &Path) -> Option<(u32, u32)> {
+    let system_wide = [
+        LaunchDirectory::GlobalAgents,
+        LaunchDirectory::GlobalDaemons,
+    ];
+    system_wide
+        .iter()
+        .any(|directory| {
+            directory
+                .path()
+                .is_ok_and(|directory| path.starts_with(directory))
+        })
+        .then_some(ROOT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problem() {
+        let user = PlistPermissions {
+            uid: get_user_id(),
+            gid: 20,
+            mode: 0o644,
+        };
+        let agent_path =
+            Path::new("/Users/me/Library/LaunchAgents/co.myrt.ajam.plist");
+        let daemon_path = Path::new("/Library/LaunchDaemons/co.myrt.ajam.plist");
+
+        assert_eq!(user.problem(agent_path), None);
+        assert!(PlistPermissions {
+            mode: 0o666,
+            ..user
+        }
+        .problem(agent_path)
+        .is_some());
+        assert_eq!(
+            PlistPermissions {
+                uid: 0,
+                gid: 0,
+                mode: 0o644
+            }
+            .problem(daemon_path),
+            None
+        );
+        if get_user_id() != 0 {
+            assert!(user.problem(daemon_path).is_some());
+        }
+    }
+
+    #[test]
+    fn test_written_plist_is_fixed_and_verified() {
+        let label = format!("co.myrt.ajam.perm.{}", rand::random_range(0..u32::MAX));
+        let agent = LaunchAgent::new(&label);
+        agent.write().unwrap();
+        assert_eq!(
+            PlistPermissions::of(&agent.path().unwrap()).unwrap().mode,
+            PLIST_MODE
+        );
+        agent.verify_permissions().unwrap();
+
+        std::fs::set_permissions(
+            agent.path().unwrap(),
+            std::fs::Permissions::from_mode(0o666),
+        )
+        .unwrap();
+        assert!(matches!(
+            agent.verify_permissions(),
+            Err(LaunchAgentError::BadPlistPermissions(..))
+        ));
+        agent.fix_permissions().unwrap();
+        agent.verify_permissions().unwrap();
+
+        agent.remove().unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::agent::LaunchAgent;
+use crate::dirs::create_owned_dir;
+use crate::os::get_user_id;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+const PING: &[u8] = b"ping\n";
+const PONG: &str = "pong";
+
+/// Size of `sun_path` on macOS, including the terminating zero.
+const SUN_PATH_LEN: usize = 104;
+
+/// How long the responder waits for a request and its answer to be sent,
+/// so a client that connects and stalls cannot block it.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often the responder checks for connections and whether it was
+/// stopped.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(20);
+
+impl LaunchAgent {
+    /// Returns the path of the Unix socket the agent answers liveness pings
+    /// on, in `/tmp/lunchctl-<uid>`. The directory is private to the user,
+    /// so other users cannot take the path over.
+    pub fn ping_socket_path(&self) -> PathBuf {
+        ping_dir().join(format!("{}.ping", self.label))
+    }
+
+    /// Check if the agent process is responsive, not only alive.
+    ///
+    /// Connects to the agent's ping socket and waits up to `timeout` for the
+    /// answer. Returns `false` if nobody listens or the answer does not arrive
+    /// in time.
+    pub fn ping(&self, timeout: Duration) -> LaunchctlResult<bool> {
+        let path = self.ping_socket_path();
+        check_socket_path(&path)?;
+        match Self::exchange_ping(&path, timeout) {
+            Ok(answered) => Ok(answered),
+            Err(e) => match e.kind() {
+                io::ErrorKind::NotFound
+                | io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::WouldBlock
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::ConnectionReset => Ok(false),
+                _ => Err(LaunchAgentError::SocketError(e)),
+            },
+        }
+    }
+
+    fn exchange_ping(path: &Path, timeout: Duration) -> io::Result<bool> {
+        let mut stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.write_all(PING)?;
+
+        let mut answer = String::new();
+        BufReader::new(stream).read_line(&mut answer)?;
+        Ok(answer.trim_end() == PONG)
+    }
+}
+
+/// Answers liveness pings from inside the agent process.
+///
+/// The responder listens on [`LaunchAgent::ping_socket_path`] in a background
+/// thread. Dropping it stops the thread and removes the socket file.
+pub struct PingResponder {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl PingResponder {
+    /// Start answering pings for the given agent.
+    pub fn start(agent: &LaunchAgent) -> LaunchctlResult<Self> {
+        let path = agent.ping_socket_path();
+        check_socket_path(&path)?;
+        create_owned_dir(&ping_dir())?;
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        let listener =
+            UnixListener::bind(&path).map_err(LaunchAgentError::SocketError)?;
+        // Accepting without blocking lets the thread notice it was stopped
+        // even if no connection wakes it up.
+        listener
+            .set_nonblocking(true)
+            .map_err(LaunchAgentError::SocketError)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let listener = thread::spawn(move || {
+            while !stopped.load(Ordering::Acquire) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let _ = Self::answer(stream);
+                    }
+                    Err(_) => thread::sleep(ACCEPT_INTERVAL),
+                }
+            }
+        });
+
+        Ok(Self {
+            path,
+            stop,
+            listener: Some(listener),
+        })
+    }
+
+    fn answer(stream: UnixStream) -> io::Result<()> {
+        // Accepted sockets inherit the listener's non-blocking mode on macOS.
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+        stream.set_write_timeout(Some(ANSWER_TIMEOUT))?;
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        reader.read_line(&mut request)?;
+        if request.as_bytes() == PING {
+            writeln!(reader.get_mut(), "{PONG}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PingResponder {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.join();
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Returns the directory of ping sockets, `/tmp/lunchctl-<uid>`. It is
+/// not `$TMPDIR`, which agents started by launchd may not share with apps.
+fn ping_dir() -> PathBuf {
+    PathBuf::from("/tmp").join(format!("lunchctl-{}", get_user_id()))
+}
+
+/// Fail if the path does not fit into a socket address.
+fn check_socket_path(path: &Path) -> LaunchctlResult<()> {
+    if path.as_os_str().as_bytes().len() < SUN_PATH_LEN {
+        return Ok(());
+    }
+    let message = format!("socket path is too long: {}", path.display());
+    Err(LaunchAgentError::SocketError(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        message,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ping() {
+        let label = format!("co.myrt.ajam.test.{}", rand::random_range(0..u32::MAX));
+        let agent = LaunchAgent::new(&label);
+        let timeout = Duration::from_millis(500);
+
+        assert!(!agent.ping(timeout).unwrap());
+
+        let responder = PingResponder::start(&agent).unwrap();
+        assert!(agent.ping(timeout).unwrap());
+
+        drop(responder);
+        assert!(!agent.ping_socket_path().exists());
+        assert!(!agent.ping(timeout).unwrap());
+    }
+
+    #[test]
+    fn test_stalled_client() {
+        let label = format!("co.myrt.ajam.test.{}", rand::random_range(0..u32::MAX));
+        let agent = LaunchAgent::new(&label);
+        let responder = PingResponder::start(&agent).unwrap();
+
+        let _stalled = UnixStream::connect(agent.ping_socket_path()).unwrap();
+        assert!(agent.ping(ANSWER_TIMEOUT * 3).unwrap());
+
+        // Stopping does not depend on connecting to the socket.
+        std::fs::remove_file(agent.ping_socket_path()).unwrap();
+        drop(responder);
+    }
+
+    #[test]
+    fn test_long_label() {
+        let agent = LaunchAgent::new(&"co.myrt.ajam".repeat(10));
+        assert!(matches!(
+            PingResponder::start(&agent),
+            Err(LaunchAgentError::SocketError(_))
+        ));
+        assert!(agent.ping(Duration::from_millis(10)).is_err());
+    }
+}
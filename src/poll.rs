@@ -0,0 +1,216 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Longest time a cancelled wait keeps sleeping before it notices.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Flag that aborts waits and batch operations it is passed to, e.g. when
+/// the user closes a progress window.
+///
+/// Clones share the flag. Cancellation is checked between launchctl calls,
+/// so a call that is already running is not interrupted.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask every operation observing the token to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Fail with [`LaunchAgentError::Cancelled`] if the token was cancelled.
+    pub fn check(&self) -> LaunchctlResult<()> {
+        if self.is_cancelled() {
+            return Err(LaunchAgentError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// How [`poll`] waits for a condition.
+#[derive(Debug, Clone)]
+pub struct PollOptions {
+    /// Delay before the first retry.
+    pub interval: Duration,
+    /// Factor the delay is multiplied by after every retry, `1.0` to poll at
+    /// a fixed interval.
+    pub backoff: f64,
+    /// Upper bound of the delay between retries.
+    pub max_interval: Duration,
+    /// How long to keep trying. The condition is always checked at least
+    /// once.
+    pub timeout: Duration,
+    /// Stop waiting with [`LaunchAgentError::Cancelled`] once cancelled.
+    pub cancel: Option<CancellationToken>,
+}
+
+impl PollOptions {
+    /// Check every 100 ms until `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            interval: Duration::from_millis(100),
+            backoff: 1.0,
+            max_interval: Duration::from_secs(1),
+            timeout,
+            cancel: None,
+        }
+    }
+
+    /// Set the delay before the first retry.
+    #[must_use]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Multiply the delay by `factor` after every retry, up to `max_interval`.
+    #[must_use]
+    pub fn backoff(mut self, factor: f64, max_interval: Duration) -> Self {
+        self.backoff = factor;
+        self.max_interval = max_interval;
+        self
+    }
+
+    /// Stop waiting when the token is cancelled.
+    #[must_use]
+    pub fn cancel_on(mut self, token: &CancellationToken) -> Self {
+        self.cancel = Some(token.clone());
+        self
+    }
+
+    fn check_cancelled(&self) -> LaunchctlResult<()> {
+        self.cancel
+            .as_ref()
+            .map_or(Ok(()), CancellationToken::check)
+    }
+
+    /// Sleep for `duration`, waking up early if cancelled.
+    fn sleep(&self, duration: Duration) -> LaunchctlResult<()> {
+        if self.cancel.is_none() {
+            thread::sleep(duration);
+            return Ok(());
+        }
+        let started = Instant::now();
+        while let Some(left) = duration.checked_sub(started.elapsed()) {
+            if left.is_zero() {
+                break;
+            }
+            self.check_cancelled()?;
+            thread::sleep(left.min(CANCEL_CHECK_INTERVAL));
+        }
+        self.check_cancelled()
+    }
+}
+
+/// Check `condition` until it holds or the timeout passes.
+/// Returns `false` on timeout; errors of the condition and cancellation end
+/// polling.
+pub fn poll<F>(condition: F, options: &PollOptions) -> LaunchctlResult<bool>
+where
+    F: FnMut() -> LaunchctlResult<bool>,
+{
+    let mut condition = condition;
+    let found = poll_for(|| Ok(condition()?.then_some(())), options)?;
+    Ok(found.is_some())
+}
+
+/// Call `check` until it returns a value or the timeout passes.
+/// Returns `None` on timeout; errors of the check and cancellation end
+/// polling.
+pub fn poll_for<T, F>(check: F, options: &PollOptions) -> LaunchctlResult<Option<T>>
+where
+    F: FnMut() -> LaunchctlResult<Option<T>>,
+{
+    let mut check = check;
+    let started = Instant::now();
+    let mut interval = options.interval;
+    loop {
+        options.check_cancelled()?;
+        if let Some(value) = check()? {
+            return Ok(Some(value));
+        }
+        let Some(remaining) = options.timeout.checked_sub(started.elapsed()) else {
+            return Ok(None);
+        };
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+        options.sleep(interval.min(remaining))?;
+        interval = interval
+            .mul_f64(options.backoff.max(1.0))
+            .min(options.max_interval.max(options.interval));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poll_until_condition() {
+        let mut calls = 0;
+        let options = PollOptions::new(Duration::from_secs(5))
+            .interval(Duration::from_millis(1))
+            .backoff(2.0, Duration::from_millis(4));
+        let found = poll(
+            || {
+                calls += 1;
+                Ok(calls == 5)
+            },
+            &options,
+        )
+        .unwrap();
+        assert!(found);
+        assert_eq!(calls, 5);
+    }
+
+    #[test]
+    fn test_poll_timeout() {
+        let options = PollOptions::new(Duration::from_millis(30))
+            .interval(Duration::from_millis(10));
+        let started = Instant::now();
+        assert_eq!(poll_for(|| Ok(None::<()>), &options).unwrap(), None);
+        assert!(started.elapsed() >= Duration::from_millis(30));
+
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        let options = PollOptions::new(Duration::from_secs(10))
+            .interval(Duration::from_secs(1))
+            .cancel_on(&token);
+        let started = Instant::now();
+        let result = poll(
+            || {
+                canceller.cancel();
+                Ok(false)
+            },
+            &options,
+        );
+        assert!(matches!(result, Err(LaunchAgentError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let mut calls = 0;
+        let options = PollOptions::new(Duration::ZERO);
+        let found = poll(
+            || {
+                calls += 1;
+                Ok(false)
+            },
+            &options,
+        )
+        .unwrap();
+        assert!(!found);
+        assert_eq!(calls, 1);
+    }
+}
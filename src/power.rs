@@ -0,0 +1,137 @@
+use std::ffi::OsString;
+
+use crate::agent::LaunchAgent;
+use crate::command::CommandSpec;
+use crate::os::run_checked;
+use crate::wrapper::{find_wrapper, unwrap, wrap};
+use crate::LaunchctlResult;
+
+/// Shell script running its arguments only on AC power. The wrapped program
+/// and its arguments are passed as `$0` and `$@`, so they need no quoting.
+/// Runs on battery exit with status 0 so launchd does not count them as
+/// failures.
+const AC_POWER_GUARD: &str =
+    "/usr/bin/pmset -g batt | /usr/bin/grep -q \"'AC Power'\" || exit 0; exec \"$0\" \"$@\"";
+
+/// Returns whether the Mac is running on AC power, as reported by
+/// `pmset -g batt`. Desktops without a battery always are.
+pub fn on_ac_power() -> LaunchctlResult<bool> {
+    let output = run_checked(&CommandSpec::new("pmset", ["-g", "batt"]))?;
+    Ok(is_ac_power(&output))
+}
+
+fn is_ac_power(pmset_output: &str) -> bool {
+    pmset_output
+        .lines()
+        .next()
+        .is_some_and(|line| line.contains("'AC Power'"))
+}
+
+impl LaunchAgent {
+    /// Skip runs of the agent while the Mac is on battery, e.g. for periodic
+    /// backups.
+    ///
+    /// The program arguments are wrapped in a `/bin/sh` script checking the
+    /// power source first, moving a `Program` key into the arguments.
+    /// Skipped runs exit with status 0. Calling it with `false` removes the
+    /// wrapper again, also from under a splay wrapper.
+    pub fn set_ac_power_only(&mut self, ac_power_only: bool) {
+        if ac_power_only {
+            wrap_ac_power(&mut self.program_arguments, Some(&mut self.extra));
+        } else {
+            unwrap(&mut self.program_arguments, is_guard);
+        }
+    }
+
+    /// Returns whether the program arguments are wrapped by
+    /// [`set_ac_power_only`](Self::set_ac_power_only).
+    pub fn is_ac_power_only(&self) -> bool {
+        is_wrapped(&self.program_arguments)
+    }
+}
+
+/// Wrap the arguments in the AC power check unless they already are.
+pub(crate) fn wrap_ac_power(
+    args: &mut Vec<OsString>,
+    extra: Option<&mut plist::Dictionary>,
+) {
+    if !is_wrapped(args) {
+        wrap(args, extra, AC_POWER_GUARD);
+    }
+}
+
+fn is_wrapped(args: &[OsString]) -> bool {
+    find_wrapper(args, is_guard).is_some()
+}
+
+fn is_guard(script: &str) -> bool {
+    script == AC_POWER_GUARD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+
+    #[test]
+    fn test_is_ac_power() {
+        assert!(is_ac_power(
+            "Now drawing from 'AC Power'\n -InternalBattery-0 (id=1)\t100%; charged;"
+        ));
+        assert!(!is_ac_power(
+            "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=1)\t80%;"
+        ));
+    }
+
+    #[test]
+    fn test_ac_power_only() {
+        let mut agent = LaunchAgentBuilder::default()
+            .label("co.myrt.backup")
+            .args(["/usr/local/bin/backup", "--full"])
+            .ac_power_only()
+            .arg("--quiet")
+            .build()
+            .unwrap();
+        assert!(agent.is_ac_power_only());
+        assert_eq!(agent.program_arguments.len(), 6);
+        assert_eq!(agent.program_arguments[3], "/usr/local/bin/backup");
+
+        agent.set_ac_power_only(true);
+        assert_eq!(agent.program_arguments.len(), 6);
+        agent.set_ac_power_only(false);
+        assert_eq!(
+            agent.program_arguments,
+            ["/usr/local/bin/backup", "--full", "--quiet"]
+        );
+    }
+
+    #[test]
+    fn test_ac_power_only_with_program_key() {
+        let mut agent = LaunchAgent::new("co.myrt.backup");
+        agent.program_arguments = vec!["backup".into(), "--full".into()];
+        agent
+            .extra
+            .insert("Program".to_string(), "/usr/local/bin/backup".into());
+        agent.set_ac_power_only(true);
+        assert!(!agent.extra.contains_key("Program"));
+        assert_eq!(
+            agent.program_arguments[2..],
+            [AC_POWER_GUARD, "/usr/local/bin/backup", "--full"]
+        );
+
+        // A splay wrapper stacked on top.
+        let sleep = "sleep 60; exec \"$0\" \"$@\"";
+        wrap(&mut agent.program_arguments, None, sleep);
+        assert_eq!(agent.program_arguments.len(), 8);
+        assert!(agent.is_ac_power_only());
+        agent.set_ac_power_only(true);
+        assert_eq!(agent.program_arguments.len(), 8);
+
+        agent.set_ac_power_only(false);
+        assert!(!agent.is_ac_power_only());
+        assert_eq!(
+            agent.program_arguments[2..],
+            [sleep, "/usr/local/bin/backup", "--full"]
+        );
+    }
+}
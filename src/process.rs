@@ -0,0 +1,195 @@
+use std::io;
+use std::time::Duration;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::poll::{poll, PollOptions};
+use crate::LaunchctlResult;
+
+/// How the process was brought down by [`LaunchAgent::stop_gracefully`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// The agent had no running process.
+    NotRunning,
+    /// The process exited after `SIGTERM` within the grace period.
+    Terminated,
+    /// The process ignored `SIGTERM` and was killed with `SIGKILL`.
+    Killed,
+    /// The process survived `SIGKILL` for another grace period.
+    StillRunning,
+}
+
+impl LaunchAgent {
+    /// Check if processes spawned by the agent outlive it.
+    ///
+    /// With `AbandonProcessGroup` set, launchd does not kill the agent's
+    /// process group on exit, so children keep running after `boot_out()`.
+    pub fn abandons_children(&self) -> bool {
+        self.abandon_process_group.unwrap_or(false)
+    }
+
+    /// Send a signal to every process in the agent's process group.
+    /// Returns `false` if the agent is not running.
+    pub fn kill_process_group(&self, signal: i32) -> LaunchctlResult<bool> {
+        LaunchctlContext::default().kill_process_group(self, signal)
+    }
+
+    /// Ask the running process to exit with `SIGTERM`, killing it with
+    /// `SIGKILL` if it is still alive after `grace`.
+    ///
+    /// The agent stays loaded, so launchd may start a new process according
+    /// to its `KeepAlive` settings. A new process ID is treated as the old
+    /// process having exited.
+    pub fn stop_gracefully(&self, grace: Duration) -> LaunchctlResult<Termination> {
+        LaunchctlContext::default().stop_gracefully(self, grace)
+    }
+
+    /// Boot out the agent and signal the processes it left behind.
+    ///
+    /// launchd only cleans up the process group of agents without
+    /// `AbandonProcessGroup`. For the others, the group is remembered before
+    /// booting out and sent `signal` afterwards, so children do not turn into
+    /// orphans. Returns `true` if leftover processes were signalled.
+    pub fn boot_out_with_children(&self, signal: i32) -> LaunchctlResult<bool> {
+        LaunchctlContext::default().boot_out_with_children(self, signal)
+    }
+}
+
+impl LaunchctlContext {
+    /// Send a signal to every process in the agent's process group, see
+    /// [`LaunchAgent::kill_process_group`].
+    pub fn kill_process_group(
+        &self,
+        agent: &LaunchAgent,
+        signal: i32,
+    ) -> LaunchctlResult<bool> {
+        let Some(pid) = self.pid(agent)? else {
+            return Ok(false);
+        };
+        let group = process_group(pid)?;
+        signal_group(group, signal)?;
+        Ok(true)
+    }
+
+    /// Stop the agent's running process, see
+    /// [`LaunchAgent::stop_gracefully`].
+    pub fn stop_gracefully(
+        &self,
+        agent: &LaunchAgent,
+        grace: Duration,
+    ) -> LaunchctlResult<Termination> {
+        let Some(pid) = self.pid(agent)? else {
+            return Ok(Termination::NotRunning);
+        };
+
+        self.kill(agent, libc::SIGTERM)?;
+        if self.wait_for_exit(agent, pid, grace)? {
+            return Ok(Termination::Terminated);
+        }
+
+        self.kill(agent, libc::SIGKILL)?;
+        if self.wait_for_exit(agent, pid, grace)? {
+            return Ok(Termination::Killed);
+        }
+        Ok(Termination::StillRunning)
+    }
+
+    /// Wait until the process `pid` is no longer the agent's process.
+    /// Returns `false` if it is still running after `timeout`.
+    fn wait_for_exit(
+        &self,
+        agent: &LaunchAgent,
+        pid: u32,
+        timeout: Duration,
+    ) -> LaunchctlResult<bool> {
+        poll(
+            || Ok(self.pid(agent)? != Some(pid)),
+            &PollOptions::new(timeout),
+        )
+    }
+
+    /// Boot out the agent and signal the processes it left behind, see
+    /// [`LaunchAgent::boot_out_with_children`].
+    pub fn boot_out_with_children(
+        &self,
+        agent: &LaunchAgent,
+        signal: i32,
+    ) -> LaunchctlResult<bool> {
+        let group = match self.pid(agent)? {
+            Some(pid) if agent.abandons_children() => Some(process_group(pid)?),
+            _ => None,
+        };
+
+        self.boot_out(agent)?;
+
+        match group {
+            Some(group) => signal_group(group, signal),
+            None => Ok(false),
+        }
+    }
+}
+
+fn process_group(pid: u32) -> LaunchctlResult<libc::pid_t> {
+    let pid = libc::pid_t::try_from(pid)
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let group = unsafe { libc::getpgid(pid) };
+    if group < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(group)
+}
+
+/// Returns `false` if the group has no processes left.
+fn signal_group(group: libc::pid_t, signal: i32) -> LaunchctlResult<bool> {
+    if unsafe { libc::killpg(group, signal) } == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::ESRCH) {
+        return Ok(false);
+    }
+    Err(err.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    use super::*;
+
+    #[test]
+    fn test_abandons_children() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        assert!(!agent.abandons_children());
+
+        agent.abandon_process_group = Some(true);
+        assert!(agent.abandons_children());
+    }
+
+    #[test]
+    fn test_stop_gracefully_not_running() {
+        let agent = LaunchAgent::new("co.myrt.ajam.missing");
+        assert_eq!(
+            LaunchctlContext::idle()
+                .stop_gracefully(&agent, Duration::from_millis(10))
+                .unwrap(),
+            Termination::NotRunning
+        );
+    }
+
+    #[test]
+    fn test_signal_group() {
+        let mut child = Command::new("sleep")
+            .arg("10")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let group = process_group(child.id()).unwrap();
+        assert_eq!(u32::try_from(group).unwrap(), child.id());
+
+        assert!(signal_group(group, libc::SIGKILL).unwrap());
+        child.wait().unwrap();
+        assert!(!signal_group(group, libc::SIGKILL).unwrap());
+    }
+}
@@ -0,0 +1,303 @@
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::agent::LaunchAgent;
+use crate::control::LaunchControllable;
+use crate::LaunchctlResult;
+
+/// Process in the tree of an agent, see [`LaunchAgent::process_tree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    /// Process ID of the parent.
+    pub parent: u32,
+    /// Short name of the executable, as shown by `ps -c`.
+    pub name: String,
+}
+
+impl LaunchAgent {
+    /// Returns the agent's running process followed by every process it
+    /// spawned, directly or not, parents before their children. Empty if the
+    /// agent is not running.
+    ///
+    /// Descendants are found by their parent process ID, so processes that
+    /// were reparented after their parent exited are not included.
+    pub fn process_tree(&self) -> LaunchctlResult<Vec<ProcessEntry>> {
+        match self.pid()? {
+            Some(pid) => Ok(descendants(pid, &processes()?)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Send a signal to the processes spawned by the agent, not to the agent
+    /// itself. Returns the number of processes signalled.
+    pub fn kill_children(&self, signal: i32) -> LaunchctlResult<usize> {
+        let mut signalled = 0;
+        for child in self.process_tree()?.iter().skip(1) {
+            if signal_process(child.pid, signal)? {
+                signalled += 1;
+            }
+        }
+        Ok(signalled)
+    }
+
+    /// Returns when the agent's running process was started, `None` if the
+    /// agent is not running.
+    pub fn started_at(&self) -> LaunchctlResult<Option<SystemTime>> {
+        match self.pid()? {
+            Some(pid) => process_start_time(pid),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns how long the agent's process has been running, `None` if the
+    /// agent is not running.
+    pub fn uptime(&self) -> LaunchctlResult<Option<Duration>> {
+        Ok(self
+            .started_at()?
+            .map(|started| started.elapsed().unwrap_or_default()))
+    }
+}
+
+/// Returns `false` if the process no longer exists.
+fn signal_process(pid: u32, signal: i32) -> LaunchctlResult<bool> {
+    let pid = libc::pid_t::try_from(pid)
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    if unsafe { libc::kill(pid, signal) } == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::ESRCH) {
+        return Ok(false);
+    }
+    Err(err.into())
+}
+
+/// Returns `root` and its descendants among `processes`, parents first.
+fn descendants(root: u32, processes: &[ProcessEntry]) -> Vec<ProcessEntry> {
+    let Some(root) = processes.iter().find(|process| process.pid == root) else {
+        return Vec::new();
+    };
+    let mut tree = vec![root.clone()];
+    let mut next = 0;
+    while let Some(parent) = tree.get(next).map(|process| process.pid) {
+        tree.extend(
+            processes
+                .iter()
+                .filter(|process| process.parent == parent && process.pid != parent)
+                .cloned(),
+        );
+        next += 1;
+    }
+    tree
+}
+
+/// Returns every process visible to the current user.
+#[cfg(target_os = "macos")]
+fn processes() -> LaunchctlResult<Vec<ProcessEntry>> {
+    let count = unsafe { libc::proc_listallpids(std::ptr::null_mut(), 0) };
+    let count = usize::try_from(count).map_err(|_| io::Error::last_os_error())?;
+    // Leave room for processes started in between.
+    let mut pids: Vec<libc::c_int> = vec![0; count + 64];
+    let size =
+        libc::c_int::try_from(pids.len() * std::mem::size_of::<libc::c_int>())
+            .unwrap_or(libc::c_int::MAX);
+    let count = unsafe { libc::proc_listallpids(pids.as_mut_ptr().cast(), size) };
+    let count = usize::try_from(count).map_err(|_| io::Error::last_os_error())?;
+    pids.truncate(count);
+
+    let mut processes = Vec::new();
+    for pid in pids {
+        let Ok(pid) = u32::try_from(pid) else {
+            continue;
+        };
+        // Processes exit or deny access while being listed.
+        if let Ok(Some(info)) = bsd_info(pid) {
+            let name = if info.pbi_name[0] == 0 {
+                &info.pbi_comm[..]
+            } else {
+                &info.pbi_name[..]
+            };
+            let name: Vec<u8> = name
+                .iter()
+                .take_while(|&&c| c != 0)
+                .map(|&c| c.to_ne_bytes()[0])
+                .collect();
+            processes.push(ProcessEntry {
+                pid,
+                parent: info.pbi_ppid,
+                name: String::from_utf8_lossy(&name).into_owned(),
+            });
+        }
+    }
+    Ok(processes)
+}
+
+/// Returns the start time of the process, `None` if there is no such
+/// process.
+#[cfg(target_os = "macos")]
+pub(crate) fn process_start_time(pid: u32) -> LaunchctlResult<Option<SystemTime>> {
+    let Some(info) = bsd_info(pid)? else {
+        return Ok(None);
+    };
+    let started = Duration::from_secs(info.pbi_start_tvsec)
+        + Duration::from_micros(info.pbi_start_tvusec);
+    Ok(Some(UNIX_EPOCH + started))
+}
+
+/// Returns the BSD process info of `proc_pidinfo`, `None` if there is no
+/// such process.
+#[cfg(target_os = "macos")]
+fn bsd_info(pid: u32) -> LaunchctlResult<Option<libc::proc_bsdinfo>> {
+    let pid = libc::c_int::try_from(pid)
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let mut info: libc::proc_bsdinfo = unsafe { std::mem::zeroed() };
+    let size = libc::c_int::try_from(std::mem::size_of::<libc::proc_bsdinfo>())
+        .unwrap_or(libc::c_int::MAX);
+    let written = unsafe {
+        libc::proc_pidinfo(
+            pid,
+            libc::PROC_PIDTBSDINFO,
+            0,
+            std::ptr::addr_of_mut!(info).cast(),
+            size,
+        )
+    };
+    if written == size {
+        return Ok(Some(info));
+    }
+    let err = io::Error::last_os_error();
+    if written == 0 && err.raw_os_error() == Some(libc::ESRCH) {
+        return Ok(None);
+    }
+    Err(err.into())
+}
+
+/// Returns the start time of the process, `None` if there is no such
+/// process. Reads `/proc`, for development on Linux.
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn process_start_time(pid: u32) -> LaunchctlResult<Option<SystemTime>> {
+    let stat = match std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+        Ok(stat) => stat,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    // Fields after the command name, which may contain spaces, start with
+    // the third; the start time in clock ticks after boot is the 22nd.
+    let ticks: u64 = stat
+        .rsplit_once(')')
+        .and_then(|(_, fields)| fields.split_whitespace().nth(19))
+        .and_then(|ticks| ticks.parse().ok())
+        .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+    let boot_time: u64 = std::fs::read_to_string("/proc/stat")?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|seconds| seconds.trim().parse().ok())
+        .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+    let ticks_per_second =
+        u64::try_from(unsafe { libc::sysconf(libc::_SC_CLK_TCK) })
+            .unwrap_or(100)
+            .max(1);
+
+    let started = Duration::from_secs(boot_time)
+        + Duration::from_millis(ticks * 1000 / ticks_per_second);
+    Ok(Some(UNIX_EPOCH + started))
+}
+
+/// Returns every process visible to the current user, from `/proc`.
+#[cfg(not(target_os = "macos"))]
+fn processes() -> LaunchctlResult<Vec<ProcessEntry>> {
+    let mut processes = Vec::new();
+    for entry in std::fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse().ok())
+        else {
+            continue;
+        };
+        // Processes exit while being listed.
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        let Some((name, fields)) = stat
+            .split_once('(')
+            .and_then(|(_, rest)| rest.rsplit_once(')'))
+        else {
+            continue;
+        };
+        let Some(parent) = fields
+            .split_whitespace()
+            .nth(1)
+            .and_then(|p| p.parse().ok())
+        else {
+            continue;
+        };
+        processes.push(ProcessEntry {
+            pid,
+            parent,
+            name: name.to_string(),
+        });
+    }
+    Ok(processes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    #[test]
+    fn test_process_start_time() {
+        let before = SystemTime::now() - Duration::from_secs(1);
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let started = process_start_time(child.id()).unwrap().unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        assert!(started >= before);
+        assert!(started <= SystemTime::now());
+        assert_eq!(process_start_time(u32::MAX >> 1).unwrap(), None);
+    }
+
+    fn entry(pid: u32, parent: u32, name: &str) -> ProcessEntry {
+        ProcessEntry {
+            pid,
+            parent,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_descendants() {
+        let processes = [
+            entry(1, 0, "launchd"),
+            entry(10, 1, "ajam"),
+            entry(11, 10, "worker"),
+            entry(12, 11, "helper"),
+            entry(13, 1, "other"),
+            entry(14, 10, "worker"),
+        ];
+        let pids: Vec<_> = descendants(10, &processes)
+            .iter()
+            .map(|process| process.pid)
+            .collect();
+        assert_eq!(pids, [10, 11, 14, 12]);
+        assert!(descendants(99, &processes).is_empty());
+    }
+
+    #[test]
+    fn test_processes() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let processes = processes().unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let tree = descendants(std::process::id(), &processes);
+        let sleep = tree
+            .iter()
+            .find(|process| process.pid == child.id())
+            .unwrap();
+        assert_eq!(sleep.name, "sleep");
+        assert_eq!(sleep.parent, std::process::id());
+    }
+}
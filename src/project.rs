@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::agent::LaunchAgent;
+use crate::namespace::LabelNamespace;
+use crate::report::stable_hash;
+use crate::LaunchctlResult;
+
+/// Extra plist key holding the project directory of a per-project agent.
+pub const PROJECT_DIRECTORY_KEY: &str = "co.myrt.lunchctl.ProjectDirectory";
+
+/// One agent per project directory, created from a template.
+///
+/// Each project gets the label `<namespace>.<id>`, where the id is derived
+/// from the project path, so reopening a project finds its agent again. The
+/// template is instantiated with the variables `PROJECT_DIR`, `PROJECT_NAME`
+/// and `PROJECT_ID`, see [`LaunchAgent::instantiate`]. Agents are installed
+/// and uninstalled with the namespace's context, see
+/// [`LabelNamespace::context`].
+#[derive(Debug, Clone)]
+pub struct ProjectAgents {
+    pub namespace: LabelNamespace,
+    pub template: LaunchAgent,
+}
+
+impl ProjectAgents {
+    pub fn new(namespace: LabelNamespace, template: LaunchAgent) -> Self {
+        Self {
+            namespace,
+            template,
+        }
+    }
+
+    /// Returns the label of the project's agent.
+    pub fn label_for(&self, project: &Path) -> String {
+        self.namespace.label(&project_id(project))
+    }
+
+    /// Returns the project's agent without installing it.
+    pub fn agent_for(&self, project: &Path) -> LaunchctlResult<LaunchAgent> {
+        let id = project_id(project);
+        let name = project
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let vars = BTreeMap::from([
+            ("PROJECT_DIR".to_string(), project.display().to_string()),
+            ("PROJECT_NAME".to_string(), name),
+            ("PROJECT_ID".to_string(), id.clone()),
+        ]);
+
+        let mut agent = LaunchAgent::instantiate(&self.template, &vars)?;
+        agent.label = self.namespace.label(&id);
+        agent.file_stem = None;
+        agent.extra.insert(
+            PROJECT_DIRECTORY_KEY.to_string(),
+            project.display().to_string().into(),
+        );
+        Ok(agent)
+    }
+
+    /// Install and start the project's agent unless it is already installed
+    /// as configured. Returns the agent.
+    pub fn open(&self, project: &Path) -> LaunchctlResult<LaunchAgent> {
+        let agent = self.agent_for(project)?;
+        self.namespace.context.ensure_installed(&agent)?;
+        Ok(agent)
+    }
+
+    /// Uninstall the project's agent.
+    /// Returns `false` if the project had no agent.
+    pub fn forget(&self, project: &Path) -> LaunchctlResult<bool> {
+        let agent = LaunchAgent::new(&self.label_for(project));
+        let context = &self.namespace.context;
+        if !context.path(&agent)?.exists() {
+            return Ok(false);
+        }
+        context.uninstall(&agent)?;
+        Ok(true)
+    }
+
+    /// Returns the installed project agents with their project directories.
+    pub fn instances(&self) -> LaunchctlResult<Vec<(PathBuf, LaunchAgent)>> {
+        Ok(self
+            .namespace
+            .installed()?
+            .into_iter()
+            .filter_map(|agent| {
+                let project = agent
+                    .extra
+                    .get(PROJECT_DIRECTORY_KEY)
+                    .and_then(plist::Value::as_string)?;
+                Some((PathBuf::from(project), agent))
+            })
+            .collect())
+    }
+}
+
+/// Returns a stable identifier of the project path, the [`stable_hash`]
+/// of its bytes. A hash that changed between releases would orphan installed
+/// agents.
+fn project_id(project: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    stable_hash(project.as_os_str().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+    use crate::context::LaunchctlContext;
+
+    fn projects(prefix: &str) -> ProjectAgents {
+        let template = LaunchAgentBuilder::default()
+            .label("unused")
+            .args(["/usr/local/bin/ajam", "watch", "${PROJECT_DIR}"])
+            .env("AJAM_PROJECT", "${PROJECT_NAME}")
+            .build()
+            .unwrap();
+        ProjectAgents::new(
+            LabelNamespace::new(prefix).context(LaunchctlContext::idle()),
+            template,
+        )
+    }
+
+    #[test]
+    fn test_project_id_is_stable() {
+        assert_eq!(project_id(Path::new("")), "cbf29ce484222325");
+        assert_eq!(project_id(Path::new("a")), "af63dc4c8601ec8c");
+        assert_ne!(
+            project_id(Path::new("/Users/me/src/site")),
+            project_id(Path::new("/Users/me/src/blog"))
+        );
+    }
+
+    #[test]
+    fn test_instances() {
+        let prefix = format!("co.myrt.ajam.p{}", rand::random_range(0..u32::MAX));
+        let projects = projects(&prefix);
+        let site = Path::new("/Users/me/src/site");
+
+        let agent = projects.agent_for(site).unwrap();
+        assert_eq!(agent.label, projects.label_for(site));
+        assert_eq!(agent.program_arguments[2], "/Users/me/src/site");
+        assert_eq!(
+            agent.environment_variables.get("AJAM_PROJECT"),
+            Some("site")
+        );
+
+        agent.write().unwrap();
+        let instances = projects.instances().unwrap();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].0, site);
+
+        assert!(projects.forget(site).unwrap());
+        assert!(!projects.forget(site).unwrap());
+        assert!(projects.instances().unwrap().is_empty());
+    }
+}
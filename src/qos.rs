@@ -0,0 +1,148 @@
+use crate::agent::{LaunchAgent, LaunchAgentBuilder, ProcessType};
+
+/// Restart interval of background presets, in seconds.
+const BACKGROUND_THROTTLE_INTERVAL: u32 = 60;
+
+/// Nice value of background presets.
+const BACKGROUND_NICE: i32 = 10;
+
+/// Presets replace each other: keys a preset does not set are unset.
+impl LaunchAgentBuilder {
+    /// Configure the job as background work the user did not ask for:
+    /// background process type, low priority I/O, lowered CPU priority and
+    /// less frequent restarts.
+    pub fn as_background_task(&mut self) -> &mut Self {
+        self.clear_priority()
+            .process_type(ProcessType::Background)
+            .low_priority_io(true)
+            .nice(BACKGROUND_NICE)
+            .throttle_interval(BACKGROUND_THROTTLE_INTERVAL)
+    }
+
+    /// Configure the job as one the user waits on, with no resource limits.
+    pub fn as_interactive(&mut self) -> &mut Self {
+        self.clear_priority()
+            .process_type(ProcessType::Interactive)
+            .low_priority_io(false)
+            .nice(0)
+    }
+
+    /// Configure the job to follow the priority of its XPC clients.
+    /// Fixed priority keys are reset so they do not fight the adaptation.
+    pub fn as_adaptive(&mut self) -> &mut Self {
+        self.clear_priority()
+            .process_type(ProcessType::Adaptive)
+            .low_priority_io(false)
+            .nice(0)
+    }
+
+    /// Configure the job with the default resource limits.
+    pub fn as_standard(&mut self) -> &mut Self {
+        self.clear_priority()
+            .process_type(ProcessType::Standard)
+            .low_priority_io(false)
+            .nice(0)
+    }
+}
+
+impl LaunchAgent {
+    /// Returns descriptions of priority keys that contradict the process type.
+    ///
+    /// `ProcessType`, `Nice` and `LowPriorityIO` are applied independently by
+    /// launchd, so an interactive job with low priority I/O is accepted but
+    /// feels sluggish, and a background job with a negative nice value
+    /// competes with the apps the user is working in.
+    pub fn qos_conflicts(&self) -> Vec<&'static str> {
+        let mut conflicts = Vec::new();
+        let nice = self.nice.unwrap_or(0);
+        let low_priority_io = self.low_priority_io.unwrap_or(false);
+
+        match self.process_type {
+            ProcessType::Background => {
+                if nice < 0 {
+                    conflicts
+                        .push("background job raises its CPU priority with Nice");
+                }
+            }
+            ProcessType::Interactive => {
+                if low_priority_io {
+                    conflicts.push("interactive job has LowPriorityIO set");
+                }
+                if nice > 0 {
+                    conflicts
+                        .push("interactive job lowers its CPU priority with Nice");
+                }
+            }
+            ProcessType::Adaptive => {
+                if low_priority_io || nice != 0 {
+                    conflicts.push(
+                        "adaptive job has fixed priority keys that override adaptation",
+                    );
+                }
+            }
+            ProcessType::Standard => {}
+        }
+
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_preset() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .as_background_task()
+            .build()
+            .unwrap();
+
+        assert_eq!(agent.process_type, ProcessType::Background);
+        assert_eq!(agent.low_priority_io, Some(true));
+        assert_eq!(agent.nice, Some(BACKGROUND_NICE));
+        assert_eq!(agent.throttle_interval, Some(BACKGROUND_THROTTLE_INTERVAL));
+        assert!(agent.qos_conflicts().is_empty());
+    }
+
+    #[test]
+    fn test_switching_presets() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .as_background_task()
+            .as_interactive()
+            .build()
+            .unwrap();
+
+        assert_eq!(agent.process_type, ProcessType::Interactive);
+        assert_eq!(agent.throttle_interval, None);
+        assert_eq!(agent.low_priority_io, Some(false));
+    }
+
+    #[test]
+    fn test_qos_conflicts() {
+        let mut agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .as_interactive()
+            .build()
+            .unwrap();
+        assert!(agent.qos_conflicts().is_empty());
+
+        agent.low_priority_io = Some(true);
+        agent.nice = Some(5);
+        assert_eq!(agent.qos_conflicts().len(), 2);
+    }
+
+    #[test]
+    fn test_low_priority_io_key() {
+        let agent = LaunchAgent {
+            low_priority_io: Some(true),
+            ..LaunchAgent::new("co.myrt.ajam")
+        };
+        let mut buf = Vec::new();
+        plist::to_writer_xml(&mut buf, &agent).unwrap();
+        let plist = String::from_utf8(buf).unwrap();
+        assert!(plist.contains("<key>LowPriorityIO</key>"));
+    }
+}
@@ -0,0 +1,156 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::failure::LaunchctlFailure;
+use crate::LaunchctlResult;
+
+/// Which recovery steps [`LaunchAgent::bootstrap_with_recovery`] may take
+/// when bootstrapping fails with a known, recoverable error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryPolicy {
+    /// Boot out a service already registered under the label, e.g. from an
+    /// old plist path.
+    pub boot_out_stale: bool,
+    /// Clear a disabled override that keeps the service from loading.
+    pub enable_if_disabled: bool,
+    /// How often bootstrap is retried after recovering.
+    pub retries: u32,
+    /// Delay before each retry, giving launchd time to finish the boot out.
+    pub retry_delay: Duration,
+}
+
+impl Default for RecoveryPolicy {
+    /// Take every recovery step and retry once.
+    fn default() -> Self {
+        Self {
+            boot_out_stale: true,
+            enable_if_disabled: true,
+            retries: 1,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Step taken by [`LaunchAgent::bootstrap_with_recovery`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryAction {
+    /// A stale registration of the label was booted out.
+    BootedOut,
+    /// The disabled override was cleared.
+    Enabled,
+    /// Bootstrap was tried again.
+    Retried,
+}
+
+impl RecoveryPolicy {
+    /// Never recover, bootstrap once.
+    pub fn none() -> Self {
+        Self {
+            boot_out_stale: false,
+            enable_if_disabled: false,
+            retries: 0,
+            retry_delay: Duration::ZERO,
+        }
+    }
+
+    /// Returns the steps to take before retrying after `failure`. Failures
+    /// that recovery cannot fix return no steps.
+    fn plan(&self, failure: LaunchctlFailure) -> Vec<RecoveryAction> {
+        let mut plan = Vec::new();
+        let (stale, disabled) = match failure {
+            LaunchctlFailure::AlreadyLoaded => (true, false),
+            LaunchctlFailure::Disabled => (false, true),
+            // Error 5 means either on older releases.
+            LaunchctlFailure::InputOutput => (true, true),
+            _ => (false, false),
+        };
+        if stale && self.boot_out_stale {
+            plan.push(RecoveryAction::BootedOut);
+        }
+        if disabled && self.enable_if_disabled {
+            plan.push(RecoveryAction::Enabled);
+        }
+        plan
+    }
+}
+
+impl LaunchAgent {
+    /// Bootstrap the agent, recovering from "already loaded", "service is
+    /// disabled" and I/O error 5 failures as allowed by `policy`: boot out
+    /// the stale registration, clear the disabled override and retry.
+    ///
+    /// Returns the steps taken, empty if the first bootstrap succeeded. The
+    /// error of the last attempt is returned if recovery does not help.
+    pub fn bootstrap_with_recovery(
+        &self,
+        policy: &RecoveryPolicy,
+    ) -> LaunchctlResult<Vec<RecoveryAction>> {
+        LaunchctlContext::default().bootstrap_with_recovery(self, policy)
+    }
+}
+
+impl LaunchctlContext {
+    /// Bootstrap the agent, recovering from known failures as allowed by
+    /// `policy`, see [`LaunchAgent::bootstrap_with_recovery`].
+    pub fn bootstrap_with_recovery(
+        &self,
+        agent: &LaunchAgent,
+        policy: &RecoveryPolicy,
+    ) -> LaunchctlResult<Vec<RecoveryAction>> {
+        let mut actions = Vec::new();
+        let mut attempts = 0;
+        loop {
+            let Err(error) = self.bootstrap(agent) else {
+                return Ok(actions);
+            };
+            let plan = error.failure().map(|f| policy.plan(f)).unwrap_or_default();
+            if plan.is_empty() || attempts >= policy.retries {
+                return Err(error);
+            }
+
+            for action in plan {
+                let taken = match action {
+                    RecoveryAction::BootedOut => self.boot_out_registered(agent)?,
+                    RecoveryAction::Enabled => self.enable(agent).map(|()| true)?,
+                    RecoveryAction::Retried => false,
+                };
+                if taken && !actions.contains(&action) {
+                    actions.push(action);
+                }
+            }
+            thread::sleep(policy.retry_delay);
+            attempts += 1;
+            actions.push(RecoveryAction::Retried);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        let policy = RecoveryPolicy::default();
+        assert_eq!(
+            policy.plan(LaunchctlFailure::InputOutput),
+            [RecoveryAction::BootedOut, RecoveryAction::Enabled]
+        );
+        assert_eq!(
+            policy.plan(LaunchctlFailure::AlreadyLoaded),
+            [RecoveryAction::BootedOut]
+        );
+        assert!(policy.plan(LaunchctlFailure::PermissionDenied).is_empty());
+
+        let policy = RecoveryPolicy {
+            enable_if_disabled: false,
+            ..RecoveryPolicy::default()
+        };
+        assert!(policy.plan(LaunchctlFailure::Disabled).is_empty());
+        assert!(RecoveryPolicy::none()
+            .plan(LaunchctlFailure::InputOutput)
+            .is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+use std::path::Path;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::control::BootstrapOutcome;
+use crate::info::ServiceInfo;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+impl LaunchAgent {
+    /// Bootstrap the agent and check that launchd registered the plist that
+    /// was just written, see [`verify_registration`](Self::verify_registration).
+    pub fn bootstrap_verified(&self) -> LaunchctlResult<BootstrapOutcome> {
+        LaunchctlContext::default().bootstrap_verified(self)
+    }
+
+    /// Check that the loaded service was registered from this agent's plist
+    /// and runs its program.
+    ///
+    /// launchd keeps serving a label from the plist it was loaded from, so
+    /// after an app bundle moves the service may still point to the old
+    /// location. Such registrations fail with
+    /// [`LaunchAgentError::StaleRegistration`], fixed by
+    /// [`rebootstrap`](Self::rebootstrap). Agents that are not loaded pass.
+    pub fn verify_registration(&self) -> LaunchctlResult<()> {
+        LaunchctlContext::default().verify_registration(self)
+    }
+
+    /// Boot out whatever is registered under the label and bootstrap the
+    /// plist on disk.
+    pub fn rebootstrap(&self) -> LaunchctlResult<BootstrapOutcome> {
+        LaunchctlContext::default().rebootstrap(self)
+    }
+
+    /// Describes how the registration differs from the agent installed at
+    /// `path`, `None` if it matches.
+    pub(crate) fn registration_mismatch_at(
+        &self,
+        path: &Path,
+        info: &ServiceInfo,
+    ) -> Option<String> {
+        if let Some(registered) = info.path() {
+            if Path::new(registered) != path {
+                return Some(format!(
+                    "registered from {registered} instead of {}",
+                    path.display()
+                ));
+            }
+        }
+        if let (Some(registered), Some(program)) =
+            (info.program(), self.program_path())
+        {
+            if Path::new(registered) != program {
+                return Some(format!(
+                    "runs {registered} instead of {}",
+                    program.display()
+                ));
+            }
+        }
+        None
+    }
+}
+
+impl LaunchctlContext {
+    /// Bootstrap the agent and check that launchd registered the plist that
+    /// was just written, see [`LaunchAgent::bootstrap_verified`].
+    pub fn bootstrap_verified(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<BootstrapOutcome> {
+        let outcome = self.bootstrap(agent)?;
+        self.verify_registration(agent)?;
+        Ok(outcome)
+    }
+
+    /// Check that the service loaded in the context's domain was registered
+    /// from the agent's plist in the context's directory, see
+    /// [`LaunchAgent::verify_registration`].
+    pub fn verify_registration(&self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        let Some(info) = self.info(agent)? else {
+            return Ok(());
+        };
+        match agent.registration_mismatch_at(&self.path(agent)?, &info) {
+            Some(mismatch) => Err(LaunchAgentError::StaleRegistration(
+                agent.label.clone(),
+                mismatch,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Boot out whatever is registered under the label and bootstrap the
+    /// plist on disk, see [`LaunchAgent::rebootstrap`].
+    pub fn rebootstrap(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<BootstrapOutcome> {
+        let replaced = self.boot_out_registered(agent)?;
+        self.bootstrap(agent)?;
+        Ok(if replaced {
+            BootstrapOutcome::ReplacedStale
+        } else {
+            BootstrapOutcome::NewlyLoaded
+        })
+    }
+
+    /// Boot out the service registered under the label, whichever plist it
+    /// came from. Returns `false` if nothing was loaded.
+    pub(crate) fn boot_out_registered(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<bool> {
+        if !self.is_loaded(agent)? {
+            return Ok(false);
+        }
+        self.boot_out_target(agent)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+    use crate::command::{CommandOutput, CommandSpec};
+    use crate::context::CommandRunner;
+    use crate::domain::DomainTarget;
+
+    fn print_output(path: &Path, program: &str) -> String {
+        format!(
+            "gui/501/co.myrt.ajam = {{\n\tpath = {}\n\tprogram = {program}\n}}\n",
+            path.display()
+        )
+    }
+
+    #[test]
+    fn test_registration_mismatch() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .arg("/Applications/Ajam.app/Contents/MacOS/ajam")
+            .build()
+            .unwrap();
+
+        let path = agent.path().unwrap();
+        let current = ServiceInfo::parse(&print_output(
+            &path,
+            "/Applications/Ajam.app/Contents/MacOS/ajam",
+        ));
+        assert_eq!(agent.registration_mismatch_at(&path, &current), None);
+
+        let moved = ServiceInfo::parse(&print_output(
+            &path,
+            "/Users/me/Downloads/Ajam.app/Contents/MacOS/ajam",
+        ));
+        assert!(agent
+            .registration_mismatch_at(&path, &moved)
+            .unwrap()
+            .starts_with("runs /Users/me/Downloads/Ajam.app"));
+
+        let other_plist = ServiceInfo::parse(&print_output(
+            Path::new("/Library/LaunchAgents/co.myrt.ajam.plist"),
+            "/Applications/Ajam.app/Contents/MacOS/ajam",
+        ));
+        assert!(agent
+            .registration_mismatch_at(&path, &other_plist)
+            .is_some());
+    }
+
+    struct StaleRunner;
+
+    impl CommandRunner for StaleRunner {
+        fn run(&self, command: &CommandSpec) -> LaunchctlResult<CommandOutput> {
+            let stdout = if command.args[0] == "print" {
+                print_output(Path::new("/tmp/ajam.plist"), "/tmp/ajam")
+            } else {
+                String::new()
+            };
+            Ok(CommandOutput {
+                code: 0,
+                stdout: stdout.into_bytes(),
+                stderr: Vec::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_rebootstrap_uses_context() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let context = LaunchctlContext::new()
+            .directory("/Library/LaunchDaemons")
+            .domain(DomainTarget::System)
+            .runner(StaleRunner)
+            .on_command(move |command| {
+                recorder.lock().unwrap().push(command.to_string());
+            });
+        let agent = LaunchAgent::new("co.myrt.ajam");
+
+        assert!(matches!(
+            context.verify_registration(&agent),
+            Err(LaunchAgentError::StaleRegistration(..))
+        ));
+        seen.lock().unwrap().clear();
+        assert_eq!(
+            context.rebootstrap(&agent).unwrap(),
+            BootstrapOutcome::ReplacedStale
+        );
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                "launchctl print system/co.myrt.ajam",
+                "launchctl bootout system/co.myrt.ajam",
+                "launchctl bootstrap system /Library/LaunchDaemons/co.myrt.ajam.plist",
+            ]
+        );
+    }
+}
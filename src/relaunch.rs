@@ -0,0 +1,104 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use crate::agent::LaunchAgent;
+use crate::control::BootstrapOutcome;
+use crate::oneshot::CleanupPolicy;
+use crate::LaunchctlResult;
+
+/// Agent that relaunches an app once, e.g. after an updater replaced it,
+/// and removes itself afterwards.
+///
+/// The agent runs its command at load with `LaunchOnlyOnce`, optionally
+/// after waiting for the old process to exit, then deletes its plist and
+/// boots itself out. Processes the command starts are kept running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relaunch {
+    label: String,
+    arguments: Vec<OsString>,
+    after_pid: Option<u32>,
+}
+
+impl Relaunch {
+    /// Open the app bundle at `app` with `/usr/bin/open`.
+    pub fn app<P: AsRef<Path>>(label: &str, app: P) -> Self {
+        Self::command(
+            label,
+            [OsString::from("/usr/bin/open"), app.as_ref().into()],
+        )
+    }
+
+    /// Run the command. It should return once the app is started, since
+    /// the agent is removed only after it exits.
+    pub fn command<I, S>(label: &str, arguments: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        Self {
+            label: label.to_string(),
+            arguments: arguments.into_iter().map(Into::into).collect(),
+            after_pid: None,
+        }
+    }
+
+    /// Wait for the process with `pid`, usually the app calling this, to
+    /// exit before relaunching.
+    #[must_use]
+    pub fn after_exit_of(mut self, pid: u32) -> Self {
+        self.after_pid = Some(pid);
+        self
+    }
+
+    /// Returns the self-removing agent. Fails if the home directory is
+    /// unknown.
+    pub fn agent(&self) -> LaunchctlResult<LaunchAgent> {
+        let mut agent = LaunchAgent::new(&self.label);
+        agent.program_arguments.clone_from(&self.arguments);
+        if let Some(pid) = self.after_pid {
+            let script = format!(
+                "while /bin/kill -0 {pid} 2>/dev/null; do sleep 0.2; done; \
+                 exec \"$0\" \"$@\""
+            );
+            let wrapper = ["/bin/sh", "-c", &script].map(OsString::from);
+            agent.program_arguments.splice(..0, wrapper);
+        }
+        agent.abandon_process_group = Some(true);
+        agent.one_shot(CleanupPolicy::SelfRemoving)
+    }
+
+    /// Install the agent, which relaunches the app right away or once the
+    /// process passed to [`after_exit_of`](Self::after_exit_of) exits.
+    pub fn install(&self) -> LaunchctlResult<BootstrapOutcome> {
+        self.agent()?.install()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relaunch_agent() {
+        let agent = Relaunch::app("co.myrt.ajam.relaunch", "/Applications/Ajam.app")
+            .after_exit_of(42)
+            .agent()
+            .unwrap();
+        assert!(agent.run_at_load);
+        assert_eq!(agent.extra["LaunchOnlyOnce"].as_boolean(), Some(true));
+
+        let arguments: Vec<_> = agent
+            .program_arguments
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(arguments[..2], ["/bin/sh", "-c"]);
+        assert!(arguments[2].contains("/bin/rm -f"));
+        assert!(arguments[2].contains("co.myrt.ajam.relaunch.plist"));
+        let boot_out = format!("/bin/launchctl bootout {}", agent.service_target());
+        assert!(arguments[2].ends_with(&boot_out));
+        assert_eq!(arguments[3..5], ["/bin/sh", "-c"]);
+        assert!(arguments[5].starts_with("while /bin/kill -0 42 "));
+        assert_eq!(arguments[6..], ["/usr/bin/open", "/Applications/Ajam.app"]);
+    }
+}
@@ -0,0 +1,235 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::agent::LaunchAgent;
+use crate::bundle::{app_bundle_of, current_app_bundle};
+use crate::context::LaunchctlContext;
+use crate::inspect::Inspector;
+use crate::ownership::DEFAULT_MANAGER;
+use crate::paths::LaunchDirectory;
+#[cfg(feature = "spotlight")]
+use crate::spotlight::app_paths_for_bundle_id;
+use crate::LaunchctlResult;
+
+/// Agent that was pointed at the new location of its app bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    pub label: String,
+    /// Bundle the plist referred to.
+    pub from: PathBuf,
+    /// Bundle the plist refers to now.
+    pub to: PathBuf,
+}
+
+/// Check the program of every user agent written by this crate, and repair
+/// the agents whose app was moved. Agents stamped by another app, see
+/// [`LaunchAgent::is_managed`], are left alone. Returns the repaired agents.
+pub fn verify_program_paths() -> LaunchctlResult<Vec<Relocation>> {
+    let directory = LaunchDirectory::UserAgents;
+    let reports =
+        Inspector::with_directories([(directory, directory.path()?)]).inspect();
+    let mut relocations = Vec::new();
+    for report in reports {
+        if let Some(error) = report.error {
+            return Err(error.into());
+        }
+        for plist in report.plists {
+            let Ok(agent) = plist.agent else {
+                continue;
+            };
+            if !is_repairable(&agent) {
+                continue;
+            }
+            if let Some(relocation) = agent.repair_program_path()? {
+                relocations.push(relocation);
+            }
+        }
+    }
+    Ok(relocations)
+}
+
+/// Check if the agent was written by this crate, e.g. with a
+/// [`BUNDLE_PROGRAM_KEY`](crate::BUNDLE_PROGRAM_KEY), and no other app
+/// manages it.
+fn is_repairable(agent: &LaunchAgent) -> bool {
+    agent.has_lunchctl_keys()
+        && agent
+            .managed_by()
+            .map_or(true, |by| by == DEFAULT_MANAGER || agent.is_managed())
+}
+
+impl LaunchAgent {
+    /// If the program is missing because its app bundle was moved, rewrite
+    /// the plist to the new location and bootstrap the agent again if it was
+    /// loaded. Returns `None` if the program exists or the app was not found.
+    ///
+    /// The running app is looked at first, so agents are repaired by the app
+    /// that installed them on its next launch. With the `spotlight` feature,
+    /// apps with the agent's `AssociatedBundleIdentifiers` are searched too.
+    pub fn repair_program_path(&self) -> LaunchctlResult<Option<Relocation>> {
+        LaunchctlContext::default().repair_program_path(self)
+    }
+
+    /// Returns the bundle identifiers of the apps the agent belongs to.
+    #[cfg(feature = "spotlight")]
+    fn associated_bundle_ids(&self) -> Vec<String> {
+        match self.extra.get("AssociatedBundleIdentifiers") {
+            Some(plist::Value::String(id)) => vec![id.clone()],
+            Some(plist::Value::Array(ids)) => ids
+                .iter()
+                .filter_map(plist::Value::as_string)
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the agent pointed at the first candidate bundle that has the
+    /// missing program.
+    fn relocate<F>(&self, candidates: F) -> Option<(Relocation, Self)>
+    where
+        F: FnOnce(&Path) -> Vec<PathBuf>,
+    {
+        let program = self.program_path()?;
+        if program.exists() {
+            return None;
+        }
+        let from = app_bundle_of(&program)?;
+        let relative = program.strip_prefix(&from).ok()?;
+        let to = candidates(&from)
+            .into_iter()
+            .find(|bundle| *bundle != from && bundle.join(relative).exists())?;
+
+        let mut agent = self.clone();
+        for argument in &mut agent.program_arguments {
+            if let Some(moved) = moved_path(Path::new(argument), &from, &to) {
+                *argument = OsString::from(moved);
+            }
+        }
+        if let Some(plist::Value::String(program)) = agent.extra.get_mut("Program") {
+            if let Some(moved) = moved_path(Path::new(program), &from, &to) {
+                *program = moved.display().to_string();
+            }
+        }
+        let relocation = Relocation {
+            label: self.label.clone(),
+            from,
+            to,
+        };
+        Some((relocation, agent))
+    }
+}
+
+impl LaunchctlContext {
+    /// Repair the agent's program path like
+    /// [`LaunchAgent::repair_program_path`], rewriting the plist in the
+    /// context's directory and bootstrapping it into the context's domain.
+    pub fn repair_program_path(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<Option<Relocation>> {
+        let Some((relocation, repaired)) = agent.relocate(|from| {
+            #[cfg_attr(not(feature = "spotlight"), allow(unused_mut))]
+            let mut candidates: Vec<PathBuf> = current_app_bundle()
+                .filter(|bundle| {
+                    agent.bundle_relative_program().is_some()
+                        || bundle.file_name() == from.file_name()
+                })
+                .into_iter()
+                .collect();
+            #[cfg(feature = "spotlight")]
+            for bundle_id in agent.associated_bundle_ids() {
+                match app_paths_for_bundle_id(&bundle_id) {
+                    Ok(paths) => candidates.extend(paths),
+                    Err(e) => log::warn!(
+                        target: "lunchctl",
+                        "failed to look up {bundle_id}: {e}"
+                    ),
+                }
+            }
+            candidates
+        }) else {
+            return Ok(None);
+        };
+        let _lock = self.lock(agent)?;
+        let loaded = self.is_loaded(agent)?;
+        self.write_plist(&repaired)?;
+        if loaded {
+            self.rebootstrap(&repaired)?;
+        }
+        log::info!(
+            target: "lunchctl",
+            "Agent {} moved from {} to {}",
+            relocation.label,
+            relocation.from.display(),
+            relocation.to.display()
+        );
+        Ok(Some(relocation))
+    }
+}
+
+/// Returns `path` moved from the `from` bundle to `to`, `None` if it is
+/// outside `from`.
+fn moved_path(path: &Path, from: &Path, to: &Path) -> Option<PathBuf> {
+    path.strip_prefix(from)
+        .ok()
+        .map(|relative| to.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relocate() {
+        let root = std::env::temp_dir().join(format!(
+            "lunchctl-relocation-{}",
+            rand::random_range(0..u32::MAX)
+        ));
+        let bundle = root.join("Applications/Ajam.app");
+        std::fs::create_dir_all(bundle.join("Contents/MacOS")).unwrap();
+        std::fs::write(bundle.join("Contents/MacOS/helper"), "").unwrap();
+
+        let old = root.join("Downloads/Ajam.app");
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent.program_arguments = vec![
+            old.join("Contents/MacOS/helper").into(),
+            old.join("Contents/Resources/config.toml").into(),
+            "--verbose".into(),
+        ];
+
+        let (relocation, moved) = agent
+            .relocate(|_| vec![old.clone(), bundle.clone()])
+            .unwrap();
+        assert_eq!(relocation.from, old);
+        assert_eq!(relocation.to, bundle);
+        assert_eq!(
+            moved.program_arguments,
+            [
+                bundle.join("Contents/MacOS/helper").into_os_string(),
+                bundle
+                    .join("Contents/Resources/config.toml")
+                    .into_os_string(),
+                "--verbose".into(),
+            ]
+        );
+        assert!(moved.relocate(|_| vec![bundle.clone()]).is_none());
+        assert!(agent.relocate(|_| vec![root.join("Ajam.app")]).is_none());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_is_repairable() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        assert!(!is_repairable(&agent));
+        agent.set_managed_by(DEFAULT_MANAGER);
+        assert!(is_repairable(&agent));
+        agent.extra.clear();
+        agent.extra.insert(
+            crate::BUNDLE_PROGRAM_KEY.to_string(),
+            "Contents/MacOS/helper".into(),
+        );
+        assert!(is_repairable(&agent));
+    }
+}
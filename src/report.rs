@@ -0,0 +1,109 @@
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::LaunchctlResult;
+
+/// What [`LaunchAgent::write_report`] wrote.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WriteReport {
+    pub path: PathBuf,
+    /// Size of the serialized plist in bytes.
+    pub len: usize,
+    /// SHA-256 digest of the serialized plist in hex.
+    pub digest: String,
+}
+
+impl WriteReport {
+    pub(crate) fn new(path: &Path, bytes: &[u8]) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            len: bytes.len(),
+            digest: digest(bytes),
+        }
+    }
+
+    /// Check that the file still holds what was written. Returns `false` if
+    /// it was changed or removed since.
+    pub fn verify(&self) -> LaunchctlResult<bool> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => {
+                Ok(bytes.len() == self.len && digest(&bytes) == self.digest)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl LaunchAgent {
+    /// Same as [`write`](Self::write), returning the path, size and digest of
+    /// the written plist for logging and verification.
+    pub fn write_report(&self) -> LaunchctlResult<WriteReport> {
+        LaunchctlContext::default().write(self)
+    }
+
+    /// Returns the digest the agent's plist has when written, to compare
+    /// configurations without writing them.
+    pub fn digest(&self) -> LaunchctlResult<String> {
+        Ok(digest(&self.stamped().to_bytes()?))
+    }
+}
+
+/// Returns the SHA-256 digest of the bytes in hex, to verify contents.
+pub(crate) fn digest(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Returns the 64-bit FNV-1a hash of the bytes in hex.
+///
+/// It is not collision-resistant, so it only derives identifiers from
+/// names; contents are verified with [`digest`]. Unlike `std` hashers it
+/// does not change between Rust releases, so identifiers stay stable.
+pub(crate) fn stable_hash(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(
+            digest(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            digest(b"a"),
+            "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb"
+        );
+        assert_eq!(stable_hash(b""), "cbf29ce484222325");
+        assert_eq!(stable_hash(b"a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn test_write_report() {
+        let label = format!("co.myrt.ajam.test.{}", rand::random_range(0..u32::MAX));
+        let mut agent = LaunchAgent::new(&label);
+
+        let report = agent.write_report().unwrap();
+        assert_eq!(report.path, agent.path().unwrap());
+        assert_eq!(report.len, std::fs::read(&report.path).unwrap().len());
+        assert_eq!(report.digest, agent.digest().unwrap());
+        assert!(report.verify().unwrap());
+
+        agent.run_at_load = true;
+        agent.write().unwrap();
+        assert!(!report.verify().unwrap());
+        agent.remove().unwrap();
+        assert!(!report.verify().unwrap());
+    }
+}
@@ -0,0 +1,505 @@
+use std::fmt;
+
+use plist::{Dictionary, Value};
+
+use crate::agent::LaunchAgent;
+use crate::calendar::{dst_anomaly, CalendarInterval};
+
+/// Day of the week.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Weekday {
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+}
+
+impl Weekday {
+    /// Returns the `Weekday` value of `StartCalendarInterval`, 0 for Sunday.
+    pub fn number(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the weekday of a `Weekday` value, 0 or 7 for Sunday.
+    pub fn from_number(number: u8) -> Self {
+        const DAYS: [Weekday; 7] = [
+            Weekday::Sunday,
+            Weekday::Monday,
+            Weekday::Tuesday,
+            Weekday::Wednesday,
+            Weekday::Thursday,
+            Weekday::Friday,
+            Weekday::Saturday,
+        ];
+        DAYS[usize::from(number % 7)]
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::Weekday> for Weekday {
+    fn from(weekday: chrono::Weekday) -> Self {
+        Self::from_number(weekday.num_days_from_sunday().try_into().unwrap_or(0))
+    }
+}
+
+/// Local time of day a schedule entry fires at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+    /// launchd schedules at minute resolution, seconds are dropped with a
+    /// [`ScheduleWarning::SecondsIgnored`].
+    pub second: u8,
+}
+
+impl TimeOfDay {
+    pub fn new(hour: u8, minute: u8) -> Self {
+        Self {
+            hour,
+            minute,
+            second: 0,
+        }
+    }
+}
+
+impl From<(u8, u8)> for TimeOfDay {
+    fn from((hour, minute): (u8, u8)) -> Self {
+        Self::new(hour, minute)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::NaiveTime> for TimeOfDay {
+    fn from(time: chrono::NaiveTime) -> Self {
+        use chrono::Timelike;
+
+        let field = |value: u32| u8::try_from(value).unwrap_or(u8::MAX);
+        Self {
+            hour: field(time.hour()),
+            minute: field(time.minute()),
+            second: field(time.second()),
+        }
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)?;
+        if self.second != 0 {
+            write!(f, ":{:02}", self.second)?;
+        }
+        Ok(())
+    }
+}
+
+/// Schedule that can't be expressed exactly by `StartCalendarInterval`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleWarning {
+    /// The time is not a valid time of day, the entry was dropped.
+    InvalidTime(TimeOfDay),
+    /// The day is not a valid day of the month, the entry was dropped.
+    InvalidDay(u8),
+    /// launchd schedules at minute resolution, the seconds were dropped.
+    SecondsIgnored(TimeOfDay),
+    /// Months without this day are skipped.
+    ShortMonths(u8),
+    /// The local time does not exist on the day clocks go forward, so the
+    /// run on that day is shifted or skipped.
+    SkippedByDst(TimeOfDay),
+    /// The local time occurs twice on the day clocks go back, launchd runs
+    /// the job once.
+    RepeatedByDst(TimeOfDay),
+    /// The time was converted from another time zone whose offset to local
+    /// time changes during the year, so runs drift by the difference.
+    ZoneOffsetChanges(TimeOfDay),
+}
+
+impl fmt::Display for ScheduleWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidTime(time) => write!(f, "{time} is not a valid time"),
+            Self::InvalidDay(day) => {
+                write!(f, "{day} is not a valid day of the month")
+            }
+            Self::SecondsIgnored(time) => {
+                write!(f, "{time}: seconds are ignored by launchd")
+            }
+            Self::ShortMonths(day) => {
+                write!(f, "months without day {day} are skipped")
+            }
+            Self::SkippedByDst(time) => {
+                write!(f, "{time} does not exist when clocks go forward")
+            }
+            Self::RepeatedByDst(time) => {
+                write!(f, "{time} occurs twice when clocks go back")
+            }
+            Self::ZoneOffsetChanges(time) => write!(
+                f,
+                "{time} drifts when the time zone offset to local time changes"
+            ),
+        }
+    }
+}
+
+/// Builder of `StartCalendarInterval` schedules.
+///
+/// launchd fires calendar entries in the local time of the machine, at
+/// minute resolution. Entries are checked against the local time zone when
+/// added, and anything that won't run exactly as requested over the next
+/// year is reported in [`warnings`](Self::warnings).
+///
+/// ```
+/// use lunchctl::{Schedule, Weekday};
+///
+/// let schedule = Schedule::new()
+///     .daily((3, 30))
+///     .weekly(Weekday::Monday, (9, 0));
+/// assert_eq!(schedule.intervals().len(), 2);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schedule {
+    intervals: Vec<CalendarInterval>,
+    warnings: Vec<ScheduleWarning>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run every day at `time`.
+    #[must_use]
+    pub fn daily(self, time: impl Into<TimeOfDay>) -> Self {
+        self.push(time.into(), CalendarInterval::default())
+    }
+
+    /// Run every week on `weekday` at `time`.
+    #[must_use]
+    pub fn weekly(
+        self,
+        weekday: impl Into<Weekday>,
+        time: impl Into<TimeOfDay>,
+    ) -> Self {
+        let interval = CalendarInterval {
+            weekday: Some(weekday.into().number()),
+            ..CalendarInterval::default()
+        };
+        self.push(time.into(), interval)
+    }
+
+    /// Run every month on `day` at `time`.
+    #[must_use]
+    pub fn monthly(mut self, day: u8, time: impl Into<TimeOfDay>) -> Self {
+        if !(1..=31).contains(&day) {
+            self.warnings.push(ScheduleWarning::InvalidDay(day));
+            return self;
+        }
+        if day > 28 {
+            self.warnings.push(ScheduleWarning::ShortMonths(day));
+        }
+        let interval = CalendarInterval {
+            day: Some(day),
+            ..CalendarInterval::default()
+        };
+        self.push(time.into(), interval)
+    }
+
+    /// Add a raw entry. It is not checked.
+    #[must_use]
+    pub fn interval(mut self, interval: CalendarInterval) -> Self {
+        self.intervals.push(interval);
+        self
+    }
+
+    /// Run every day at `time` in the time zone `zone`, converted to local
+    /// time with the current offset.
+    #[cfg(feature = "chrono")]
+    #[must_use]
+    pub fn daily_in<Tz: chrono::TimeZone>(
+        self,
+        time: chrono::NaiveTime,
+        zone: &Tz,
+    ) -> Self {
+        self.push_in(time, zone, None)
+    }
+
+    /// Run every week on `weekday` at `time` in the time zone `zone`,
+    /// converted to local time with the current offset. The weekday moves
+    /// with the time if the conversion crosses midnight.
+    #[cfg(feature = "chrono")]
+    #[must_use]
+    pub fn weekly_in<Tz: chrono::TimeZone>(
+        self,
+        weekday: chrono::Weekday,
+        time: chrono::NaiveTime,
+        zone: &Tz,
+    ) -> Self {
+        self.push_in(time, zone, Some(weekday.into()))
+    }
+
+    /// Returns the entries of the schedule.
+    pub fn intervals(&self) -> &[CalendarInterval] {
+        &self.intervals
+    }
+
+    /// Returns the ways the schedule differs from what was requested.
+    pub fn warnings(&self) -> &[ScheduleWarning] {
+        &self.warnings
+    }
+
+    /// Returns the `StartCalendarInterval` value, a dictionary for a single
+    /// entry and an array otherwise.
+    pub fn to_value(&self) -> Value {
+        match self.intervals.as_slice() {
+            [interval] => interval.to_dictionary().into(),
+            intervals => Value::Array(
+                intervals
+                    .iter()
+                    .map(|interval| interval.to_dictionary().into())
+                    .collect(),
+            ),
+        }
+    }
+
+    fn push(mut self, time: TimeOfDay, interval: CalendarInterval) -> Self {
+        if time.hour > 23 || time.minute > 59 || time.second > 59 {
+            self.warnings.push(ScheduleWarning::InvalidTime(time));
+            return self;
+        }
+        if time.second != 0 {
+            self.warnings.push(ScheduleWarning::SecondsIgnored(time));
+        }
+        let anomaly = dst_anomaly(time.hour, time.minute);
+        if anomaly.skipped {
+            self.warnings.push(ScheduleWarning::SkippedByDst(time));
+        }
+        if anomaly.repeated {
+            self.warnings.push(ScheduleWarning::RepeatedByDst(time));
+        }
+        self.intervals.push(CalendarInterval {
+            hour: Some(time.hour),
+            minute: Some(time.minute),
+            ..interval
+        });
+        self
+    }
+
+    #[cfg(feature = "chrono")]
+    fn push_in<Tz: chrono::TimeZone>(
+        mut self,
+        time: chrono::NaiveTime,
+        zone: &Tz,
+        weekday: Option<Weekday>,
+    ) -> Self {
+        let Some((local, days, varies)) = to_local(time, zone) else {
+            self.warnings
+                .push(ScheduleWarning::InvalidTime(time.into()));
+            return self;
+        };
+        if varies {
+            self.warnings
+                .push(ScheduleWarning::ZoneOffsetChanges(time.into()));
+        }
+        match weekday {
+            Some(weekday) => {
+                let number = (i64::from(weekday.number()) + days).rem_euclid(7);
+                let weekday = Weekday::from_number(number.try_into().unwrap_or(0));
+                self.weekly(weekday, local)
+            }
+            None => self.daily(local),
+        }
+    }
+}
+
+/// Convert `time` in `zone` to local time for today. Returns the local
+/// time, the days it moved by, and whether the conversion differs on any
+/// day of the next year.
+#[cfg(feature = "chrono")]
+fn to_local<Tz: chrono::TimeZone>(
+    time: chrono::NaiveTime,
+    zone: &Tz,
+) -> Option<(chrono::NaiveTime, i64, bool)> {
+    use chrono::{Days, Local, NaiveDate};
+
+    let convert = |date: NaiveDate| {
+        let at = zone.from_local_datetime(&date.and_time(time)).earliest()?;
+        let local = at.with_timezone(&Local).naive_local();
+        Some((local.time(), (local.date() - date).num_days()))
+    };
+    let today = Local::now().date_naive();
+    let (local, days) = convert(today)?;
+    let varies = (1..366)
+        .filter_map(|offset| today.checked_add_days(Days::new(offset)))
+        .filter_map(convert)
+        .any(|converted| converted != (local, days));
+    Some((local, days, varies))
+}
+
+/// Append the entries of `schedule` to `StartCalendarInterval` in
+/// `extra`, logging its warnings.
+pub(crate) fn append_schedule(
+    extra: &mut Dictionary,
+    label: &str,
+    schedule: &Schedule,
+) {
+    for warning in schedule.warnings() {
+        log::warn!(target: "lunchctl", "{label}: {warning}");
+    }
+    let mut entries = match extra.remove("StartCalendarInterval") {
+        Some(Value::Array(entries)) => entries,
+        Some(entry) => vec![entry],
+        None => Vec::new(),
+    };
+    entries.extend(
+        schedule
+            .intervals()
+            .iter()
+            .map(|interval| interval.to_dictionary().into()),
+    );
+    match entries.len() {
+        0 => {}
+        1 => {
+            extra.insert("StartCalendarInterval".to_string(), entries.remove(0));
+        }
+        _ => {
+            extra.insert("StartCalendarInterval".to_string(), Value::Array(entries));
+        }
+    }
+}
+
+impl LaunchAgent {
+    /// Replace `StartCalendarInterval` with the schedule, logging its
+    /// warnings. An empty schedule removes the key.
+    pub fn set_schedule(&mut self, schedule: &Schedule) {
+        self.extra.remove("StartCalendarInterval");
+        append_schedule(&mut self.extra, &self.label, schedule);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule_entries() {
+        let schedule = Schedule::new()
+            .daily((12, 15))
+            .weekly(Weekday::Friday, (13, 0))
+            .monthly(15, (12, 0));
+        assert!(schedule.warnings().is_empty());
+        assert_eq!(
+            schedule.intervals(),
+            [
+                CalendarInterval {
+                    hour: Some(12),
+                    minute: Some(15),
+                    ..CalendarInterval::default()
+                },
+                CalendarInterval {
+                    hour: Some(13),
+                    minute: Some(0),
+                    weekday: Some(5),
+                    ..CalendarInterval::default()
+                },
+                CalendarInterval {
+                    hour: Some(12),
+                    minute: Some(0),
+                    day: Some(15),
+                    ..CalendarInterval::default()
+                },
+            ]
+        );
+
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent.set_schedule(&Schedule::new().daily((12, 15)));
+        assert_eq!(agent.calendar_intervals(), &schedule.intervals()[..1]);
+        agent.set_schedule(&Schedule::new());
+        assert!(!agent.extra.contains_key("StartCalendarInterval"));
+    }
+
+    #[test]
+    fn test_schedule_warnings() {
+        let schedule = Schedule::new()
+            .daily(TimeOfDay {
+                hour: 12,
+                minute: 0,
+                second: 30,
+            })
+            .daily((24, 0))
+            .monthly(0, (12, 0))
+            .monthly(31, (12, 0));
+        assert_eq!(
+            schedule.warnings(),
+            [
+                ScheduleWarning::SecondsIgnored(TimeOfDay {
+                    hour: 12,
+                    minute: 0,
+                    second: 30
+                }),
+                ScheduleWarning::InvalidTime(TimeOfDay::new(24, 0)),
+                ScheduleWarning::InvalidDay(0),
+                ScheduleWarning::ShortMonths(31),
+            ]
+        );
+        assert_eq!(schedule.intervals().len(), 2);
+    }
+
+    #[test]
+    fn test_builder_helpers() {
+        let agent = crate::LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .daily_at(&[9, 17], 0)
+            .weekly_on(Weekday::Saturday, (10, 30))
+            .monthly_on(1, (12, 0))
+            .build()
+            .unwrap();
+        let entry = |hour, minute| CalendarInterval {
+            hour: Some(hour),
+            minute: Some(minute),
+            ..CalendarInterval::default()
+        };
+        assert_eq!(
+            agent.calendar_intervals(),
+            [
+                entry(9, 0),
+                entry(17, 0),
+                CalendarInterval {
+                    weekday: Some(6),
+                    ..entry(10, 30)
+                },
+                CalendarInterval {
+                    day: Some(1),
+                    ..entry(12, 0)
+                },
+            ]
+        );
+
+        let single = crate::LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .daily_at(&[3], 15)
+            .build()
+            .unwrap();
+        assert!(single.extra["StartCalendarInterval"]
+            .as_dictionary()
+            .is_some());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_schedule_in_zone() {
+        use chrono::{Local, NaiveTime};
+
+        let noon = NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+        let schedule = Schedule::new().daily_in(noon, &Local).weekly_in(
+            chrono::Weekday::Sun,
+            noon,
+            &Local,
+        );
+        assert!(schedule.warnings().is_empty());
+        assert_eq!(schedule.intervals()[0].hour, Some(12));
+        assert_eq!(schedule.intervals()[1].weekday, Some(0));
+    }
+}
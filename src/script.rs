@@ -0,0 +1,121 @@
+use std::fs::{File, Permissions};
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use plist::Value;
+
+use crate::agent::LaunchAgent;
+use crate::report::digest;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Extra plist key holding the digest of the agent's script, see
+/// [`LaunchAgent::write_script`].
+pub const SCRIPT_DIGEST_KEY: &str = "co.myrt.lunchctl.ScriptDigest";
+
+/// Mode of installed scripts, readable and executable by the owner only.
+const SCRIPT_MODE: u32 = 0o700;
+
+impl LaunchAgent {
+    /// Returns the path a script named `name` is installed to, in the
+    /// agent's [state directory](Self::state_dir).
+    pub fn script_path(&self, name: &str) -> LaunchctlResult<PathBuf> {
+        Ok(self.state_dir()?.join(name))
+    }
+
+    /// Install a script run by `interpreter` as the agent's program.
+    ///
+    /// Scripts referenced from Downloads, iCloud Drive or an app bundle
+    /// that moves break at login, so the script is copied into the state
+    /// directory, private to the user and without a quarantine attribute,
+    /// and referenced by its absolute path. Its digest is stored under
+    /// [`SCRIPT_DIGEST_KEY`], so the plist changes with the script and
+    /// `ensure_installed()` reinstalls the agent.
+    ///
+    /// Replaces the program arguments and returns the script path. The
+    /// plist is stored on disk with the next `write()`.
+    pub fn write_script<P: AsRef<Path>>(
+        &mut self,
+        interpreter: P,
+        name: &str,
+        contents: &[u8],
+    ) -> LaunchctlResult<PathBuf> {
+        let interpreter = interpreter.as_ref();
+        Self::validate_binary(interpreter)?;
+        let path = self.script_path(name)?;
+        if name.is_empty() || name.contains('/') || name.starts_with('.') {
+            return Err(LaunchAgentError::InvalidBinary(
+                path,
+                "script name is not a plain file name".to_string(),
+            ));
+        }
+
+        self.create_state_dir()?;
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.set_permissions(Permissions::from_mode(SCRIPT_MODE))?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, &path)?;
+        clear_quarantine(&path);
+
+        self.program_arguments = vec![interpreter.into(), path.clone().into()];
+        self.extra
+            .insert(SCRIPT_DIGEST_KEY.to_string(), digest(contents).into());
+        Ok(path)
+    }
+
+    /// Returns the digest of the installed script stored in the
+    /// configuration, if any.
+    pub fn script_digest(&self) -> Option<&str> {
+        self.extra.get(SCRIPT_DIGEST_KEY).and_then(Value::as_string)
+    }
+}
+
+/// Remove the quarantine attribute the file inherits from a quarantined
+/// process, which would make Gatekeeper block it when launchd runs it.
+#[cfg(target_os = "macos")]
+fn clear_quarantine(path: &Path) {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return;
+    };
+    unsafe {
+        libc::removexattr(path.as_ptr(), c"com.apple.quarantine".as_ptr(), 0);
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn clear_quarantine(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_script() {
+        let label = format!("co.myrt.script.{}", rand::random_range(0..u32::MAX));
+        let mut agent = LaunchAgent::new(&label);
+
+        let path = agent
+            .write_script("/bin/sh", "run.sh", b"echo one")
+            .unwrap();
+        assert_eq!(path, agent.state_dir().unwrap().join("run.sh"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"echo one");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, SCRIPT_MODE);
+        assert_eq!(agent.program_arguments, ["/bin/sh", path.to_str().unwrap()]);
+
+        let first = agent.clone();
+        agent
+            .write_script("/bin/sh", "run.sh", b"echo two")
+            .unwrap();
+        assert_ne!(agent.script_digest(), first.script_digest());
+        assert_ne!(agent, first);
+
+        assert!(agent.write_script("/bin/sh", "../run.sh", b"").is_err());
+        assert!(agent.write_script("sh", "run.sh", b"").is_err());
+        agent.remove_state_dir().unwrap();
+    }
+}
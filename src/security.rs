@@ -0,0 +1,176 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::agent::LaunchAgent;
+use crate::diagnose::Finding;
+use crate::fix::Fix;
+use crate::lint::{
+    Rule, CREDENTIAL_IN_ENVIRONMENT, PLIST_WRITABLE, PROGRAM_IN_TMP,
+    PROGRAM_WRITABLE,
+};
+use crate::wrapper::program_index;
+
+/// Directories anyone can write to. Programs there can be swapped by other
+/// users.
+const SHARED_TEMP_DIRS: [&str; 4] =
+    ["/tmp", "/private/tmp", "/var/tmp", "/private/var/tmp"];
+
+/// Parts of environment variable names that suggest a credential.
+const CREDENTIAL_MARKERS: [&str; 8] = [
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "API_KEY",
+    "ACCESS_KEY",
+    "PRIVATE_KEY",
+    "CREDENTIAL",
+];
+
+impl LaunchAgent {
+    /// Check the agent for configurations other local users or software
+    /// could abuse: writable program binaries and plists, programs in shared
+    /// temporary directories and credentials in the environment.
+    ///
+    /// The program and plist are inspected on disk if they exist.
+    pub fn security_audit(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        if let Some(program) = self.program_path() {
+            if is_in_shared_temp_dir(&program) {
+                let message = format!(
+                    "program is in a shared temporary directory: {}",
+                    program.display()
+                );
+                findings.push(PROGRAM_IN_TMP.error(&message).with_suggestion(
+                    "install the program where only its owner can write",
+                ));
+            }
+            findings.extend(writable_finding(
+                &program,
+                &PROGRAM_WRITABLE,
+                "program",
+            ));
+        }
+
+        if let Ok(path) = self.path() {
+            findings.extend(
+                writable_finding(&path, &PLIST_WRITABLE, "plist")
+                    .map(|finding| finding.with_fix(Fix::FixPermissions)),
+            );
+        }
+
+        for (key, _) in self.environment_variables.iter() {
+            if looks_like_credential(key)
+                && !self.environment_variables.is_secret(key)
+            {
+                let message = format!(
+                    "environment variable {key} looks like a credential in plain text"
+                );
+                findings.push(
+                    CREDENTIAL_IN_ENVIRONMENT.warning(&message).with_suggestion(
+                        "read it from the Keychain, or mark it with env_secret()",
+                    ),
+                );
+            }
+        }
+
+        findings
+    }
+
+    /// Returns the executable launchd runs, from `Program` or the first
+    /// program argument past the `/bin/sh` wrappers of this crate.
+    pub(crate) fn program_path(&self) -> Option<PathBuf> {
+        if let Some(program) =
+            self.extra.get("Program").and_then(plist::Value::as_string)
+        {
+            return Some(PathBuf::from(program));
+        }
+        self.program_arguments
+            .get(program_index(&self.program_arguments))
+            .map(PathBuf::from)
+    }
+}
+
+/// Returns a finding if the file is writable by its group or by everyone.
+fn writable_finding(path: &Path, rule: &Rule, what: &str) -> Option<Finding> {
+    let mode = std::fs::metadata(path).ok()?.permissions().mode();
+    if mode & 0o002 != 0 {
+        return Some(
+            rule.error(&format!("{what} is world-writable: {}", path.display()))
+                .with_suggestion("chmod o-w the file"),
+        );
+    }
+    if mode & 0o020 != 0 {
+        return Some(
+            rule.warning(&format!("{what} is group-writable: {}", path.display()))
+                .with_suggestion("chmod g-w the file"),
+        );
+    }
+    None
+}
+
+fn is_in_shared_temp_dir(path: &Path) -> bool {
+    SHARED_TEMP_DIRS.iter().any(|dir| path.starts_with(dir))
+}
+
+fn looks_like_credential(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    CREDENTIAL_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+
+    fn codes(findings: &[Finding]) -> Vec<&'static str> {
+        findings.iter().map(|finding| finding.code).collect()
+    }
+
+    #[test]
+    fn test_program_in_tmp() {
+        let dir = std::env::temp_dir()
+            .join(format!("lunchctl.test.{}", rand::random_range(0..u32::MAX)));
+        std::fs::create_dir(&dir).unwrap();
+        let program = dir.join("run.sh");
+        std::fs::write(&program, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o777))
+            .unwrap();
+
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam.audit")
+            .arg(program.as_os_str())
+            .build()
+            .unwrap();
+        let findings = agent.security_audit();
+        assert!(codes(&findings).contains(&"program-writable"));
+        assert_eq!(
+            is_in_shared_temp_dir(&program),
+            codes(&findings).contains(&"program-in-tmp")
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_credentials_in_environment() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam.audit")
+            .arg("/usr/bin/true")
+            .env("GITHUB_TOKEN", "ghp_x")
+            .env_secret("DB_PASSWORD", "x")
+            .env("PATH", "/usr/bin")
+            .build()
+            .unwrap();
+        let findings = agent.security_audit();
+        assert_eq!(codes(&findings), vec!["credential-in-environment"]);
+        assert!(findings[0].message.contains("GITHUB_TOKEN"));
+    }
+
+    #[test]
+    fn test_shared_temp_dirs() {
+        assert!(is_in_shared_temp_dir(Path::new("/private/tmp/x")));
+        assert!(!is_in_shared_temp_dir(Path::new("/tmpfs/x")));
+    }
+}
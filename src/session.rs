@@ -0,0 +1,157 @@
+use plist::Value;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::domain::DomainTarget;
+use crate::failure::LaunchctlFailure;
+use crate::os::{command_error, get_user_id};
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Kind of session the calling process runs in, as reported by
+/// `launchctl managername`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SessionType {
+    /// Logged-in GUI session.
+    Aqua,
+    /// Session without GUI access, e.g. over SSH or from a background job.
+    Background,
+    /// The login window, before a user logs in.
+    LoginWindow,
+    /// Session of a terminal without a GUI login.
+    StandardIO,
+    /// The system domain, e.g. MDM scripts and daemons running as root.
+    System,
+    /// Session this crate does not know about, as printed.
+    Other(String),
+}
+
+impl SessionType {
+    fn parse(name: &str) -> Self {
+        match name {
+            "Aqua" => Self::Aqua,
+            "Background" => Self::Background,
+            "LoginWindow" => Self::LoginWindow,
+            "StandardIO" => Self::StandardIO,
+            "System" => Self::System,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// Returns the session type of the calling process.
+pub fn current_session() -> LaunchctlResult<SessionType> {
+    LaunchctlContext::default().current_session()
+}
+
+impl DomainTarget {
+    /// Check if the domain exists. GUI domains only exist while the user is
+    /// logged in, whatever session the caller runs in.
+    ///
+    /// Fails if `launchctl print` fails for any other reason than a missing
+    /// domain.
+    pub fn is_available(self) -> LaunchctlResult<bool> {
+        LaunchctlContext::default().is_domain_available(self)
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the `LimitLoadToSessionType` values, empty if the key is not
+    /// set.
+    pub fn session_types(&self) -> Vec<&str> {
+        match self.extra.get("LimitLoadToSessionType") {
+            Some(Value::String(session)) => vec![session.as_str()],
+            Some(Value::Array(sessions)) => {
+                sessions.iter().filter_map(Value::as_string).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Check if the agent is loaded in Aqua sessions only, which is the
+    /// default for agents without `LimitLoadToSessionType`. Such agents can
+    /// only be bootstrapped while the user is logged in.
+    pub fn requires_aqua_session(&self) -> bool {
+        let sessions = self.session_types();
+        sessions.is_empty() || sessions == ["Aqua"]
+    }
+
+    /// Fail with [`LaunchAgentError::NoAquaSession`] if the agent requires
+    /// an Aqua session and the user is not logged in, e.g. when an MDM script
+    /// or SSH login installs it. Installing would write the plist but fail to
+    /// bootstrap it.
+    ///
+    /// Callers can defer the install to the next login with
+    /// [`FirstRun::NextLogin`](crate::FirstRun::NextLogin) instead.
+    pub fn check_session(&self) -> LaunchctlResult<()> {
+        LaunchctlContext::default().check_session(self)
+    }
+}
+
+impl LaunchctlContext {
+    /// Returns the session type launchctl runs in with the context's
+    /// settings, see [`current_session`].
+    pub fn current_session(&self) -> LaunchctlResult<SessionType> {
+        let output = self.run(&self.command(["managername"]))?;
+        Ok(SessionType::parse(output.stdout_lossy().trim()))
+    }
+
+    /// Check if the domain exists, see [`DomainTarget::is_available`].
+    pub fn is_domain_available(
+        &self,
+        domain: DomainTarget,
+    ) -> LaunchctlResult<bool> {
+        let output =
+            self.run(&self.command(["print".to_string(), domain.to_string()]))?;
+        if output.success() {
+            return Ok(true);
+        }
+        let error = command_error(&output);
+        match error.failure() {
+            Some(LaunchctlFailure::DomainUnavailable) => Ok(false),
+            _ => Err(error),
+        }
+    }
+
+    /// Fail with [`LaunchAgentError::NoAquaSession`] if the agent requires
+    /// an Aqua session and the user is not logged in, see
+    /// [`LaunchAgent::check_session`].
+    pub fn check_session(&self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        let uid = get_user_id();
+        if agent.requires_aqua_session()
+            && !self.is_domain_available(DomainTarget::Gui(uid))?
+        {
+            return Err(LaunchAgentError::NoAquaSession(uid));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requires_aqua_session() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        assert!(agent.session_types().is_empty());
+        assert!(agent.requires_aqua_session());
+
+        agent
+            .extra
+            .insert("LimitLoadToSessionType".to_string(), "Aqua".into());
+        assert!(agent.requires_aqua_session());
+
+        agent.extra.insert(
+            "LimitLoadToSessionType".to_string(),
+            Value::Array(vec!["Aqua".into(), "Background".into()]),
+        );
+        assert_eq!(agent.session_types(), ["Aqua", "Background"]);
+        assert!(!agent.requires_aqua_session());
+
+        assert_eq!(SessionType::parse("Aqua"), SessionType::Aqua);
+        assert_eq!(
+            SessionType::parse("Unknown"),
+            SessionType::Other("Unknown".to_string())
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use crate::agent::LaunchAgentBuilder;
+
+/// Shell used by [`LaunchAgentBuilder::shell_command`], the default for user
+/// accounts since macOS Catalina.
+pub const DEFAULT_SHELL: &str = "/bin/zsh";
+
+impl LaunchAgentBuilder {
+    /// Run a shell one-liner, such as `brew update && brew upgrade`, through
+    /// a login zsh.
+    ///
+    /// The command is passed to the shell as a single argument, so it needs
+    /// no extra quoting. A login shell reads the user's profile, making
+    /// `PATH` additions such as Homebrew's available.
+    pub fn shell_command(&mut self, command: &str) -> &mut Self {
+        self.shell_command_with(DEFAULT_SHELL, true, command)
+    }
+
+    /// Run a shell one-liner through the given shell, as a login shell if
+    /// `login` is set.
+    pub fn shell_command_with(
+        &mut self,
+        shell: &str,
+        login: bool,
+        command: &str,
+    ) -> &mut Self {
+        let flags = if login { "-lc" } else { "-c" };
+        self.program_arguments(vec![shell.into(), flags.into(), command.into()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_command() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .shell_command("brew update && echo 'done'")
+            .build()
+            .unwrap();
+        assert_eq!(
+            agent.program_arguments,
+            vec!["/bin/zsh", "-lc", "brew update && echo 'done'"]
+        );
+
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .shell_command_with("/bin/sh", false, "true")
+            .build()
+            .unwrap();
+        assert_eq!(agent.program_arguments, vec!["/bin/sh", "-c", "true"]);
+    }
+
+    #[test]
+    fn test_shell_command_runs() {
+        let agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .shell_command_with("/bin/sh", false, "printf '%s' \"it's\"")
+            .build()
+            .unwrap();
+        let output = std::process::Command::new(&agent.program_arguments[0])
+            .args(&agent.program_arguments[1..])
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, b"it's");
+    }
+}
@@ -0,0 +1,314 @@
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::write_policy::WritePolicy;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Saved state of the current user's agents whose label starts with a prefix.
+///
+/// Plists are stored byte for byte together with their loaded and disabled
+/// state, so a configuration can be tried out and reverted if it breaks
+/// login, or tests can run against a clean `LaunchAgents` directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Snapshot {
+    pub prefix: String,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+/// Single agent captured in a [`Snapshot`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnapshotEntry {
+    pub label: String,
+    pub file_name: String,
+    pub contents: plist::Data,
+    pub loaded: bool,
+    /// State of the disabled override, `None` if there was no override.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<bool>,
+}
+
+impl Snapshot {
+    /// Capture every agent in `~/Library/LaunchAgents` whose label starts
+    /// with `prefix`.
+    pub fn capture(prefix: &str) -> LaunchctlResult<Self> {
+        LaunchctlContext::default().capture_snapshot(prefix)
+    }
+
+    /// Bring the agents matching the prefix back to the captured state.
+    ///
+    /// Agents created after the capture are booted out and removed, captured
+    /// plists are written back, disabled overrides are reapplied and agents
+    /// that were loaded are bootstrapped again.
+    ///
+    /// Fails before changing anything if an entry's file name is not a plain
+    /// `.plist` file name, so a snapshot cannot write outside the directory.
+    pub fn restore(&self) -> LaunchctlResult<()> {
+        LaunchctlContext::default().restore_snapshot(self)
+    }
+
+    /// Write the snapshot to a single plist file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> LaunchctlResult<()> {
+        plist::to_file_xml(path, self)?;
+        Ok(())
+    }
+
+    /// Read a snapshot saved with [`save`](Self::save).
+    pub fn load<P: AsRef<Path>>(path: P) -> LaunchctlResult<Self> {
+        Ok(plist::from_file(path)?)
+    }
+
+    /// Returns labels and paths of the plists in `directory` matching the
+    /// prefix.
+    fn matching_plists(
+        directory: &Path,
+        prefix: &str,
+    ) -> LaunchctlResult<Vec<(String, PathBuf)>> {
+        let mut plists = Vec::new();
+
+        for entry in std::fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != "plist") {
+                continue;
+            }
+            let label = match plist::from_file::<_, LaunchAgent>(&path) {
+                Ok(agent) => agent.label,
+                Err(_) => file_name(&path).trim_end_matches(".plist").to_string(),
+            };
+            if label.starts_with(prefix) {
+                plists.push((label, path));
+            }
+        }
+
+        plists.sort();
+        Ok(plists)
+    }
+}
+
+impl LaunchctlContext {
+    /// Capture every agent in the context's directory whose label starts
+    /// with `prefix`, see [`Snapshot::capture`].
+    pub fn capture_snapshot(&self, prefix: &str) -> LaunchctlResult<Snapshot> {
+        let overrides = self.disabled_overrides()?;
+        let mut entries = Vec::new();
+
+        let directory = self.agents_directory()?;
+        for (label, path) in Snapshot::matching_plists(&directory, prefix)? {
+            let agent = LaunchAgent::new(&label);
+            entries.push(SnapshotEntry {
+                file_name: file_name(&path),
+                contents: std::fs::read(&path)?.into(),
+                loaded: self.is_loaded(&agent)?,
+                disabled: overrides.get(&label).copied(),
+                label,
+            });
+        }
+
+        Ok(Snapshot {
+            prefix: prefix.to_string(),
+            entries,
+        })
+    }
+
+    /// Bring the agents matching the snapshot's prefix back to the captured
+    /// state, see [`Snapshot::restore`].
+    pub fn restore_snapshot(&self, snapshot: &Snapshot) -> LaunchctlResult<()> {
+        for entry in &snapshot.entries {
+            self.check_restorable(&entry.agent()?, entry.contents.as_ref())?;
+        }
+        let directory = self.agents_directory()?;
+        for (label, path) in Snapshot::matching_plists(&directory, &snapshot.prefix)?
+        {
+            if snapshot.entries.iter().any(|e| e.label == label) {
+                continue;
+            }
+            let mut agent = LaunchAgent::new(&label);
+            agent.file_stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+            let _lock = self.lock(&agent)?;
+            if self.is_loaded(&agent)? {
+                self.boot_out(&agent)?;
+            }
+            self.remove_plist(&agent)?;
+        }
+
+        for entry in &snapshot.entries {
+            let agent = entry.agent()?;
+            let _lock = self.lock(&agent)?;
+            if self.is_loaded(&agent)? {
+                self.boot_out(&agent)?;
+            }
+            self.write_plist_bytes(&agent, entry.contents.as_ref())?;
+
+            match entry.disabled {
+                Some(true) => self.disable(&agent)?,
+                Some(false) => self.enable(&agent)?,
+                None => {}
+            }
+            if entry.loaded {
+                self.bootstrap(&agent)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fail if the ownership requirement or the write policy keeps the
+    /// agent's plist from being replaced with `contents`.
+    fn check_restorable(
+        &self,
+        agent: &LaunchAgent,
+        contents: &[u8],
+    ) -> LaunchctlResult<()> {
+        self.check_ownership(agent)?;
+        let path = self.path(agent)?;
+        match std::fs::read(&path) {
+            Ok(installed) if installed != contents => {}
+            _ => return Ok(()),
+        }
+        match self.write_policy {
+            WritePolicy::ErrorIfExists => Err(LaunchAgentError::PlistExists(path)),
+            WritePolicy::OverwriteIfManagedByUs => {
+                match agent.read_installed_at(&path)? {
+                    Some(installed) if !installed.is_managed() => {
+                        Err(LaunchAgentError::NotManaged(path))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            WritePolicy::OverwriteAlways
+            | WritePolicy::MergePreservingUnknownKeys => Ok(()),
+        }
+    }
+}
+
+impl SnapshotEntry {
+    /// Returns the captured agent, named after the captured file. Fails if
+    /// the file name is not a plain `.plist` file name.
+    fn agent(&self) -> LaunchctlResult<LaunchAgent> {
+        let mut agent = LaunchAgent::new(&self.label);
+        agent.file_stem = Some(plist_stem(&self.file_name)?.to_string());
+        Ok(agent)
+    }
+}
+
+/// Returns the stem of a plist file name, failing if it is not a single
+/// path component ending in `.plist`.
+fn plist_stem(file_name: &str) -> LaunchctlResult<&str> {
+    let mut components = Path::new(file_name).components();
+    let plain = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(name)), None) if name == file_name
+    );
+    match file_name.strip_suffix(".plist") {
+        Some(stem) if plain && !stem.is_empty() => Ok(stem),
+        _ => {
+            let message =
+                format!("invalid plist file name in snapshot: {file_name}");
+            Err(io::Error::new(io::ErrorKind::InvalidData, message).into())
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_and_restore() {
+        let prefix =
+            format!("co.myrt.ajam.snapshot.{}", rand::random_range(0..u32::MAX));
+        let kept = LaunchAgent::new(&format!("{prefix}.kept"));
+        kept.write().unwrap();
+
+        let context = LaunchctlContext::idle();
+        let snapshot = context.capture_snapshot(&prefix).unwrap();
+        assert_eq!(snapshot.entries.len(), 1);
+        assert_eq!(snapshot.entries[0].label, kept.label);
+
+        let mut added = LaunchAgent::new(&format!("{prefix}.added"));
+        added.file_stem = Some(format!("{prefix}.renamed"));
+        added.write().unwrap();
+        kept.remove().unwrap();
+
+        context
+            .clone()
+            .dry_run(true)
+            .restore_snapshot(&snapshot)
+            .unwrap();
+        assert!(!LaunchAgent::exists(&kept.label));
+        assert!(added.path().unwrap().exists());
+
+        context.restore_snapshot(&snapshot).unwrap();
+        assert!(LaunchAgent::exists(&kept.label));
+        assert!(!added.path().unwrap().exists());
+        assert_eq!(
+            LaunchAgent::from_file(&kept.label).unwrap(),
+            *kept.stamped()
+        );
+
+        kept.remove().unwrap();
+    }
+
+    #[test]
+    fn test_plist_stem() {
+        assert_eq!(plist_stem("co.myrt.ajam.plist").unwrap(), "co.myrt.ajam");
+        for name in [
+            "../co.myrt.ajam.plist",
+            "/tmp/co.myrt.ajam.plist",
+            "Agents/co.myrt.ajam.plist",
+            "co.myrt.ajam.plist/",
+            "..",
+            ".plist",
+            "co.myrt.ajam",
+        ] {
+            assert!(plist_stem(name).is_err(), "{name}");
+        }
+
+        let snapshot = Snapshot {
+            prefix: "co.myrt".to_string(),
+            entries: vec![SnapshotEntry {
+                label: "co.myrt.ajam".to_string(),
+                file_name: "../../.zshrc.plist".to_string(),
+                contents: b"<plist/>".to_vec().into(),
+                loaded: false,
+                disabled: None,
+            }],
+        };
+        assert!(snapshot.restore().is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let snapshot = Snapshot {
+            prefix: "co.myrt".to_string(),
+            entries: vec![SnapshotEntry {
+                label: "co.myrt.ajam".to_string(),
+                file_name: "co.myrt.ajam.plist".to_string(),
+                contents: b"<plist/>".to_vec().into(),
+                loaded: true,
+                disabled: Some(false),
+            }],
+        };
+        let path = std::env::temp_dir().join(format!(
+            "lunchctl.snapshot.{}.plist",
+            rand::random_range(0..u32::MAX)
+        ));
+
+        snapshot.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), snapshot);
+        std::fs::remove_file(path).unwrap();
+    }
+}
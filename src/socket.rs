@@ -0,0 +1,246 @@
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+use crate::agent::LaunchAgent;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    fn launch_activate_socket(
+        name: *const libc::c_char,
+        fds: *mut *mut libc::c_int,
+        cnt: *mut libc::size_t,
+    ) -> libc::c_int;
+}
+
+/// Check in with launchd and take ownership of the descriptors for the socket
+/// `name` from the job's `Sockets` dictionary.
+///
+/// Must be called from the process launched by launchd.
+#[cfg(target_os = "macos")]
+pub fn activate_socket(name: &str) -> LaunchctlResult<Vec<OwnedFd>> {
+    let c_name = std::ffi::CString::new(name).map_err(|e| {
+        LaunchAgentError::SocketActivationFailed(name.to_string(), e.into())
+    })?;
+    let mut fds: *mut libc::c_int = std::ptr::null_mut();
+    let mut cnt: libc::size_t = 0;
+
+    let code =
+        unsafe { launch_activate_socket(c_name.as_ptr(), &mut fds, &mut cnt) };
+    if code != 0 {
+        return Err(LaunchAgentError::SocketActivationFailed(
+            name.to_string(),
+            io::Error::from_raw_os_error(code),
+        ));
+    }
+
+    let owned = (0..cnt)
+        .map(|i| unsafe { OwnedFd::from_raw_fd(*fds.add(i)) })
+        .collect();
+    unsafe { libc::free(fds.cast()) };
+
+    Ok(owned)
+}
+
+/// Check in with launchd and take ownership of the descriptors for the socket
+/// `name` from the job's `Sockets` dictionary.
+///
+/// Socket activation is only available on macOS.
+#[cfg(not(target_os = "macos"))]
+pub fn activate_socket(name: &str) -> LaunchctlResult<Vec<OwnedFd>> {
+    Err(LaunchAgentError::SocketActivationFailed(
+        name.to_string(),
+        io::ErrorKind::Unsupported.into(),
+    ))
+}
+
+/// Connection accepted from a launchd-provided socket.
+pub enum Connection {
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Connection {
+    /// Wrap an accepted descriptor according to its address family.
+    fn from_fd(fd: OwnedFd) -> Self {
+        let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        let mut len = libc::socklen_t::try_from(std::mem::size_of_val(&addr))
+            .unwrap_or(libc::socklen_t::MAX);
+        let code = unsafe {
+            libc::getsockname(
+                fd.as_raw_fd(),
+                std::ptr::from_mut(&mut addr).cast(),
+                &mut len,
+            )
+        };
+        if code == 0 && i32::from(addr.ss_family) == libc::AF_UNIX {
+            Self::Unix(UnixStream::from(fd))
+        } else {
+            Self::Tcp(TcpStream::from(fd))
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Unix(stream) => stream.read(buf),
+            Self::Tcp(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Unix(stream) => stream.write(buf),
+            Self::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Unix(stream) => stream.flush(),
+            Self::Tcp(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Minimal accept loop for socket-activated services.
+///
+/// The server accepts connections from the listening sockets handed over by
+/// launchd and returns once no connection arrived for the idle timeout, so the
+/// process can exit and launchd starts it again on the next connection.
+pub struct SocketServer {
+    listeners: Vec<OwnedFd>,
+    idle_timeout: Option<Duration>,
+}
+
+impl SocketServer {
+    /// Create a server from already listening sockets.
+    pub fn from_fds(listeners: Vec<OwnedFd>) -> Self {
+        Self {
+            listeners,
+            idle_timeout: None,
+        }
+    }
+
+    /// Create a server from the launchd socket `name`.
+    pub fn activate(name: &str) -> LaunchctlResult<Self> {
+        Ok(Self::from_fds(activate_socket(name)?))
+    }
+
+    /// Create a server from the launchd socket `name`, using the agent's
+    /// `TimeOut` as the idle timeout.
+    pub fn for_agent(agent: &LaunchAgent, name: &str) -> LaunchctlResult<Self> {
+        let server = Self::activate(name)?;
+        Ok(match agent.time_out {
+            Some(secs) => server.idle_timeout(Duration::from_secs(secs.into())),
+            None => server,
+        })
+    }
+
+    /// Stop accepting connections after being idle for `timeout`.
+    #[must_use]
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Accept connections and pass them to `handler` until the idle timeout
+    /// expires. Without an idle timeout the loop runs forever.
+    pub fn run<F>(&self, mut handler: F) -> LaunchctlResult<()>
+    where
+        F: FnMut(Connection),
+    {
+        let mut poll_fds: Vec<libc::pollfd> = self
+            .listeners
+            .iter()
+            .map(|fd| libc::pollfd {
+                fd: fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        let mut last_activity = Instant::now();
+
+        loop {
+            let timeout = match self.idle_timeout {
+                Some(idle) => {
+                    let left = idle.saturating_sub(last_activity.elapsed());
+                    if left.is_zero() {
+                        return Ok(());
+                    }
+                    i32::try_from(left.as_millis()).unwrap_or(i32::MAX).max(1)
+                }
+                None => -1,
+            };
+
+            let nfds = libc::nfds_t::try_from(poll_fds.len()).unwrap_or(0);
+            let ready = unsafe { libc::poll(poll_fds.as_mut_ptr(), nfds, timeout) };
+            if ready < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(LaunchAgentError::SocketError(err));
+            }
+
+            for poll_fd in poll_fds.iter_mut().filter(|p| p.revents != 0) {
+                poll_fd.revents = 0;
+                let fd = unsafe {
+                    libc::accept(
+                        poll_fd.fd,
+                        std::ptr::null_mut(),
+                        std::ptr::null_mut(),
+                    )
+                };
+                if fd < 0 {
+                    continue;
+                }
+                last_activity = Instant::now();
+                handler(Connection::from_fd(unsafe { OwnedFd::from_raw_fd(fd) }));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_server_accepts_and_exits_when_idle() {
+        let path = std::env::temp_dir().join(format!(
+            "lunchctl.test.{}.sock",
+            rand::random_range(0..u32::MAX)
+        ));
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = SocketServer::from_fds(vec![listener.into()])
+            .idle_timeout(Duration::from_millis(200));
+
+        let client_path = path.clone();
+        let client = thread::spawn(move || {
+            let mut stream = UnixStream::connect(client_path).unwrap();
+            stream.write_all(b"ping").unwrap();
+        });
+
+        let mut received = Vec::new();
+        server
+            .run(|mut conn| {
+                assert!(matches!(conn, Connection::Unix(_)));
+                conn.read_to_end(&mut received).unwrap();
+            })
+            .unwrap();
+
+        client.join().unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(received, b"ping");
+    }
+}
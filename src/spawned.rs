@@ -0,0 +1,68 @@
+use std::io::IsTerminal;
+
+use crate::domain::DomainTarget;
+use crate::os::get_user_id;
+use crate::session::{current_session, SessionType};
+use crate::LaunchctlResult;
+
+/// Check if the calling process was started by launchd, e.g. because it
+/// runs as a launch agent itself.
+///
+/// launchd passes the job's label in `XPC_SERVICE_NAME`. Processes it
+/// spawns without one are recognized by having launchd as their parent and
+/// no terminal.
+pub fn is_launchd_spawned() -> bool {
+    if launchd_job_label().is_some() {
+        return true;
+    }
+    // SAFETY: getppid has no preconditions.
+    let parent = unsafe { libc::getppid() };
+    parent == 1 && !is_interactive()
+}
+
+/// Returns the label of the launchd job the calling process runs as.
+pub fn launchd_job_label() -> Option<String> {
+    std::env::var("XPC_SERVICE_NAME")
+        .ok()
+        .filter(|name| !name.is_empty() && name != "0")
+}
+
+/// Check if the calling process has a terminal to prompt for a password
+/// on. Launch agents do not.
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal()
+}
+
+/// Check if launchctl has to run commands for `domain` through
+/// `launchctl asuser`, because the calling process is not in the domain's
+/// user session.
+///
+/// That is the case for daemons and other users' processes managing a
+/// user's agents, and for the user's own jobs outside the Aqua session,
+/// such as agents limited to the `Background` session. `asuser` itself
+/// needs root, see
+/// [`LaunchctlContext::adapt_to_session`](crate::LaunchctlContext::adapt_to_session).
+pub fn needs_as_user(domain: DomainTarget) -> LaunchctlResult<bool> {
+    let uid = match domain {
+        DomainTarget::Gui(uid) | DomainTarget::User(uid) => uid,
+        DomainTarget::System => return Ok(false),
+    };
+    if uid != get_user_id() {
+        return Ok(true);
+    }
+    Ok(matches!(domain, DomainTarget::Gui(_))
+        && !matches!(current_session()?, SessionType::Aqua))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_as_user() {
+        assert!(!needs_as_user(DomainTarget::System).unwrap());
+        assert!(!needs_as_user(DomainTarget::current_user()).unwrap());
+        let other = get_user_id().wrapping_add(1);
+        assert!(needs_as_user(DomainTarget::Gui(other)).unwrap());
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+use plist::{Dictionary, Value};
+
+use crate::agent::LaunchAgent;
+use crate::wrapper::wrap;
+use crate::LaunchctlResult;
+
+/// Extra plist key holding the splay offset in seconds, see
+/// [`LaunchAgent::apply_splay`].
+pub const SPLAY_KEY: &str = "co.myrt.lunchctl.Splay";
+
+impl LaunchAgent {
+    /// Returns the splay offset applied to the schedule, if any.
+    pub fn splay_offset(&self) -> Option<Duration> {
+        self.extra
+            .get(SPLAY_KEY)
+            .and_then(Value::as_unsigned_integer)
+            .map(Duration::from_secs)
+    }
+
+    /// Delay the agent's schedule by a random offset of up to `max`, so a
+    /// fleet of machines does not run it at the same moment.
+    ///
+    /// `StartCalendarInterval` entries with a fixed minute are shifted by
+    /// whole minutes. Past midnight a `Weekday` moves to the next day,
+    /// while entries with a `Day` or `Month` stop at 23:59. `StartInterval`
+    /// jobs count from when they were loaded, so they get a `/bin/sh`
+    /// wrapper sleeping for the offset, modulo the interval, before each
+    /// run.
+    ///
+    /// The offset is stored in the plist under [`SPLAY_KEY`]. The offset of
+    /// the installed plist is reused, so reinstalling keeps the schedule, and
+    /// agents that already carry one are left as they are. Call it on the
+    /// configuration with the unshifted schedule. Returns the offset.
+    pub fn apply_splay(&mut self, max: Duration) -> LaunchctlResult<Duration> {
+        if let Some(offset) = self.splay_offset() {
+            return Ok(offset);
+        }
+        let installed = self
+            .read_installed()?
+            .and_then(|agent| agent.splay_offset());
+        let max = max.as_secs().max(1);
+        let offset = Duration::from_secs(installed.map_or_else(
+            || RandomState::new().hash_one(&self.label) % max,
+            |offset| offset.as_secs() % max,
+        ));
+
+        self.shift_schedule(offset);
+        self.extra
+            .insert(SPLAY_KEY.to_string(), Value::from(offset.as_secs()));
+        Ok(offset)
+    }
+
+    fn shift_schedule(&mut self, offset: Duration) {
+        let minutes = offset.as_secs() / 60;
+        match self.extra.get_mut("StartCalendarInterval") {
+            Some(Value::Dictionary(entry)) => shift_entry(entry, minutes),
+            Some(Value::Array(entries)) => {
+                for entry in entries.iter_mut().filter_map(Value::as_dictionary_mut)
+                {
+                    shift_entry(entry, minutes);
+                }
+            }
+            _ => {}
+        }
+
+        let interval = self
+            .extra
+            .get("StartInterval")
+            .and_then(Value::as_unsigned_integer)
+            .filter(|interval| *interval > 0);
+        let delay = interval.map_or(0, |interval| offset.as_secs() % interval);
+        if delay > 0 {
+            let script = format!("sleep {delay}; exec \"$0\" \"$@\"");
+            wrap(&mut self.program_arguments, Some(&mut self.extra), &script);
+        }
+    }
+}
+
+/// Shift a calendar entry with a fixed minute by `minutes`.
+///
+/// A shift past midnight moves a `Weekday` to the next day. Entries with a
+/// `Day` or `Month` cannot be moved to the next day reliably, so they stop
+/// at 23:59 instead.
+fn shift_entry(entry: &mut Dictionary, minutes: u64) {
+    const DAY: u64 = 24 * 60;
+    let field = |entry: &Dictionary, key: &str| {
+        entry.get(key).and_then(Value::as_unsigned_integer)
+    };
+    let Some(minute) = field(entry, "Minute") else {
+        return;
+    };
+    let Some(hour) = field(entry, "Hour") else {
+        entry.insert("Minute".to_string(), Value::from((minute + minutes) % 60));
+        return;
+    };
+    let mut total = hour * 60 + minute + minutes;
+    if entry.contains_key("Day") || entry.contains_key("Month") {
+        total = total.min(DAY - 1);
+    } else if let Some(weekday) = field(entry, "Weekday").filter(|_| total >= DAY) {
+        entry.insert(
+            "Weekday".to_string(),
+            Value::from((weekday + total / DAY) % 7),
+        );
+    }
+    entry.insert("Minute".to_string(), Value::from(total % 60));
+    entry.insert("Hour".to_string(), Value::from(total / 60 % 24));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar_entry(hour: u64, minute: u64) -> Value {
+        let mut entry = Dictionary::new();
+        entry.insert("Hour".to_string(), hour.into());
+        entry.insert("Minute".to_string(), minute.into());
+        entry.into()
+    }
+
+    #[test]
+    fn test_shift_schedule() {
+        let mut agent = LaunchAgent::new("co.myrt.backup");
+        agent.program_arguments = vec!["/usr/local/bin/backup".into()];
+        agent.extra.insert(
+            "StartCalendarInterval".to_string(),
+            Value::Array(vec![calendar_entry(3, 0), calendar_entry(23, 50)]),
+        );
+        agent
+            .extra
+            .insert("StartInterval".to_string(), Value::from(3600_u64));
+
+        agent.shift_schedule(Duration::from_secs(25 * 60));
+        assert_eq!(
+            agent.extra["StartCalendarInterval"],
+            Value::Array(vec![calendar_entry(3, 25), calendar_entry(0, 15)])
+        );
+
+        let with = |hour, minute, key: &str, value: u64| {
+            let mut entry = calendar_entry(hour, minute).into_dictionary().unwrap();
+            entry.insert(key.to_string(), value.into());
+            entry
+        };
+        let mut saturday = with(23, 50, "Weekday", 6);
+        shift_entry(&mut saturday, 25);
+        assert_eq!(saturday, with(0, 15, "Weekday", 0));
+        let mut first = with(23, 50, "Day", 1);
+        shift_entry(&mut first, 25);
+        assert_eq!(first, with(23, 59, "Day", 1));
+
+        assert_eq!(
+            agent.program_arguments,
+            [
+                "/bin/sh",
+                "-c",
+                "sleep 1500; exec \"$0\" \"$@\"",
+                "/usr/local/bin/backup"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_splay_is_stable() {
+        let label = format!("co.myrt.splay.{}", rand::random_range(0..u32::MAX));
+        let mut agent = LaunchAgent::new(&label);
+        agent
+            .extra
+            .insert("StartCalendarInterval".to_string(), calendar_entry(3, 0));
+        let template = agent.clone();
+
+        let max = Duration::from_secs(3600);
+        let offset = agent.apply_splay(max).unwrap();
+        assert!(offset < max);
+        assert_eq!(agent.splay_offset(), Some(offset));
+        assert_eq!(agent.apply_splay(max).unwrap(), offset);
+
+        agent.write().unwrap();
+        let mut reinstalled = template;
+        assert_eq!(reinstalled.apply_splay(max).unwrap(), offset);
+        assert_eq!(reinstalled, agent);
+        agent.remove().unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::command::CommandSpec;
+use crate::LaunchctlResult;
+
+/// Returns the current path of the app with `bundle_id`, e.g. to point an
+/// agent at a binary inside it. Returns `None` if Spotlight knows no such
+/// app, for example because its volume is not indexed.
+///
+/// Apps in the Trash are skipped. If several copies exist, the one
+/// Spotlight lists first wins.
+pub fn app_path_for_bundle_id(bundle_id: &str) -> LaunchctlResult<Option<PathBuf>> {
+    Ok(app_paths_for_bundle_id(bundle_id)?
+        .into_iter()
+        .find(|path| path.is_dir()))
+}
+
+/// Returns the paths of every app with `bundle_id` Spotlight knows about.
+pub(crate) fn app_paths_for_bundle_id(
+    bundle_id: &str,
+) -> LaunchctlResult<Vec<PathBuf>> {
+    let Some(command) = bundle_id_query(bundle_id) else {
+        return Ok(Vec::new());
+    };
+    let output = command.output()?;
+    Ok(parse_app_paths(&output.stdout))
+}
+
+/// Returns the `mdfind` command searching for apps with `bundle_id`, `None`
+/// if it is not a valid bundle identifier. Bundle identifiers are checked
+/// instead of escaped, so they cannot alter the query.
+fn bundle_id_query(bundle_id: &str) -> Option<CommandSpec> {
+    let valid = !bundle_id.is_empty()
+        && bundle_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+    valid.then(|| {
+        CommandSpec::new(
+            "mdfind",
+            [format!(
+                "kMDItemContentType == 'com.apple.application-bundle' \
+                 && kMDItemCFBundleIdentifier == '{bundle_id}'"
+            )],
+        )
+    })
+}
+
+fn parse_app_paths(stdout: &[u8]) -> Vec<PathBuf> {
+    stdout
+        .split(|&byte| byte == b'\n')
+        .map(|line| Path::new(OsStr::from_bytes(line)))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "app"))
+        .filter(|path| !path.components().any(|part| part.as_os_str() == ".Trash"))
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_id_query() {
+        let command = bundle_id_query("co.myrt.Ajam-Helper").unwrap();
+        assert_eq!(command.program, "mdfind");
+        assert!(command.args[0]
+            .to_str()
+            .unwrap()
+            .ends_with("kMDItemCFBundleIdentifier == 'co.myrt.Ajam-Helper'"));
+        assert!(bundle_id_query("co.myrt.ajam' || true").is_none());
+        assert!(bundle_id_query("").is_none());
+    }
+
+    #[test]
+    fn test_parse_app_paths() {
+        let stdout = b"/Applications/Ajam.app\n\
+            /Users/me/.Trash/Ajam.app\n\
+            /Volumes/Ajam/Ajam.app\n\
+            /Users/me/Library/Caches/ajam.plist\n";
+        assert_eq!(
+            parse_app_paths(stdout),
+            [
+                PathBuf::from("/Applications/Ajam.app"),
+                PathBuf::from("/Volumes/Ajam/Ajam.app"),
+            ]
+        );
+    }
+}
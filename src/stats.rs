@@ -0,0 +1,307 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::info::ServiceInfo;
+use crate::telemetry::record_crash_loop;
+use crate::LaunchctlResult;
+
+/// Spawn counters of a loaded agent at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SpawnStats {
+    /// Seconds since the Unix epoch when the stats were taken.
+    pub timestamp: u64,
+    /// Number of times launchd spawned the agent since it was loaded.
+    pub runs: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_exit_code: Option<i32>,
+    pub running: bool,
+    /// launchd postponed the next spawn because the agent exited too soon.
+    #[serde(default)]
+    pub throttled: bool,
+}
+
+/// Report of an agent that keeps exiting and being respawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrashLoop {
+    /// Spawns during the inspected window.
+    pub spawns: u32,
+    /// Spawns during the window that ended with an error.
+    pub failures: u32,
+    /// launchd is currently throttling the agent.
+    pub throttled: bool,
+    pub advice: CrashLoopAdvice,
+}
+
+/// Recommended reaction to a [`CrashLoop`].
+///
+/// Restarting a flapping agent from a supervisor only fights launchd's own
+/// throttling, so neither advice involves a restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrashLoopAdvice {
+    /// The program exits with errors. Check its logs and the binary.
+    FixBinary,
+    /// The program exits cleanly but too often for `KeepAlive`. Make it stay
+    /// running or raise `ThrottleInterval`.
+    IncreaseThrottleInterval,
+}
+
+/// Number of samples a [`SpawnHistory`] keeps.
+const MAX_SAMPLES: usize = 200;
+
+/// Series of [`SpawnStats`] taken over time.
+///
+/// launchd only reports counters, so spawns and failures within a time window
+/// are derived by comparing samples. The samples are persisted as part of an
+/// agent's [`ExitHistory`](crate::ExitHistory).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SpawnHistory {
+    pub samples: Vec<SpawnStats>,
+}
+
+impl SpawnStats {
+    /// Extract the counters from `launchctl print` output.
+    pub fn from_info(info: &ServiceInfo, timestamp: u64) -> Self {
+        Self {
+            timestamp,
+            runs: info.runs().unwrap_or(0),
+            last_exit_code: info.last_exit_code(),
+            running: info.state() == Some("running"),
+            throttled: info.is_throttled(),
+        }
+    }
+
+    /// Check if the agent is down after its last run exited with an error.
+    pub fn last_spawn_failed(&self) -> bool {
+        !self.running && self.last_exit_code.is_some_and(|code| code != 0)
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the agent's current spawn counters, `None` if it is not loaded.
+    pub fn spawn_stats(&self) -> LaunchctlResult<Option<SpawnStats>> {
+        LaunchctlContext::default().spawn_stats(self)
+    }
+}
+
+impl LaunchctlContext {
+    /// Returns the agent's current spawn counters, `None` if it is not loaded.
+    pub fn spawn_stats(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<Option<SpawnStats>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Ok(self
+            .info(agent)?
+            .map(|info| SpawnStats::from_info(&info, timestamp)))
+    }
+}
+
+impl LaunchAgent {
+    /// Record the agent's current stats into its persisted history, see
+    /// [`record_history`](Self::record_history), and check it for a crash
+    /// loop, see [`SpawnHistory::crash_loop`].
+    ///
+    /// Call it periodically, e.g. from a supervisor loop or a scheduled job,
+    /// since the window is evaluated over the recorded samples.
+    pub fn is_crash_looping(
+        &self,
+        window: Duration,
+        threshold: u32,
+    ) -> LaunchctlResult<Option<CrashLoop>> {
+        let history = self.record_history()?;
+        let crash_loop = history.spawns.crash_loop(window, threshold);
+        record_crash_loop(&self.label, crash_loop.is_some());
+        Ok(crash_loop)
+    }
+}
+
+impl SpawnHistory {
+    /// Take the agent's current stats and add them to the history.
+    /// Nothing is recorded if the agent is not loaded.
+    pub fn record(&mut self, agent: &LaunchAgent) -> LaunchctlResult<()> {
+        if let Some(stats) = agent.spawn_stats()? {
+            self.push(stats);
+        }
+        Ok(())
+    }
+
+    /// Add stats to the history, keeping the latest 200 samples.
+    ///
+    /// A drop in the run counter means the agent was reloaded, so earlier
+    /// samples no longer compare and are discarded.
+    pub fn push(&mut self, stats: SpawnStats) {
+        if self
+            .samples
+            .last()
+            .is_some_and(|last| last.runs > stats.runs)
+        {
+            self.samples.clear();
+        }
+        self.samples.push(stats);
+        let excess = self.samples.len().saturating_sub(MAX_SAMPLES);
+        self.samples.drain(..excess);
+    }
+
+    /// Returns the most recent sample.
+    pub fn latest(&self) -> Option<&SpawnStats> {
+        self.samples.last()
+    }
+
+    /// Returns how many times the agent was spawned during the last
+    /// `window`, as far as the recorded samples tell. A counter that went
+    /// down was reset by a reload, so the runs after it are counted from
+    /// zero.
+    pub fn spawns_within(&self, window: Duration) -> u32 {
+        self.samples_within(window)
+            .windows(2)
+            .map(|pair| match pair[1].runs.checked_sub(pair[0].runs) {
+                Some(spawns) => spawns,
+                None => pair[1].runs,
+            })
+            .sum()
+    }
+
+    /// Returns how many samples during the last `window` show a failed spawn
+    /// that was not seen in the previous sample.
+    pub fn failures_within(&self, window: Duration) -> u32 {
+        let recent = self.samples_within(window);
+        let failures = recent
+            .windows(2)
+            .filter(|pair| {
+                pair[1].runs > pair[0].runs && pair[1].last_spawn_failed()
+            })
+            .count();
+        u32::try_from(failures).unwrap_or(u32::MAX)
+    }
+
+    /// Check if the agent spawned at least `threshold` times during the last
+    /// `window` while failing or being throttled by launchd.
+    pub fn crash_loop(&self, window: Duration, threshold: u32) -> Option<CrashLoop> {
+        let latest = self.latest()?;
+        let spawns = self.spawns_within(window);
+        let failures = self.failures_within(window);
+        let flapping =
+            failures >= threshold || (latest.throttled && spawns >= threshold);
+        if !flapping {
+            return None;
+        }
+
+        let advice = if latest.last_exit_code.is_some_and(|code| code != 0) {
+            CrashLoopAdvice::FixBinary
+        } else {
+            CrashLoopAdvice::IncreaseThrottleInterval
+        };
+        Some(CrashLoop {
+            spawns,
+            failures,
+            throttled: latest.throttled,
+            advice,
+        })
+    }
+
+    fn samples_within(&self, window: Duration) -> &[SpawnStats] {
+        let Some(latest) = self.latest() else {
+            return &[];
+        };
+        let since = latest.timestamp.saturating_sub(window.as_secs());
+        let start = self
+            .samples
+            .iter()
+            .position(|stats| stats.timestamp >= since)
+            .unwrap_or(self.samples.len());
+        &self.samples[start..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(timestamp: u64, runs: u32, last_exit_code: i32) -> SpawnStats {
+        SpawnStats {
+            timestamp,
+            runs,
+            last_exit_code: Some(last_exit_code),
+            running: false,
+            throttled: false,
+        }
+    }
+
+    #[test]
+    fn test_spawns_and_failures_within() {
+        let mut history = SpawnHistory::default();
+        history.push(stats(0, 1, 0));
+        history.push(stats(100, 2, 0));
+        history.push(stats(110, 4, 1));
+        history.push(stats(120, 6, 1));
+
+        let window = Duration::from_secs(30);
+        assert_eq!(history.spawns_within(window), 4);
+        assert_eq!(history.failures_within(window), 2);
+        assert_eq!(history.spawns_within(Duration::from_secs(1000)), 5);
+    }
+
+    #[test]
+    fn test_crash_loop() {
+        let mut history = SpawnHistory::default();
+        history.push(stats(0, 1, 0));
+        history.push(stats(10, 2, 0));
+        let window = Duration::from_secs(60);
+        assert_eq!(history.crash_loop(window, 1), None);
+
+        history.push(stats(20, 3, 1));
+        history.push(stats(30, 4, 1));
+        assert_eq!(
+            history.crash_loop(window, 2),
+            Some(CrashLoop {
+                spawns: 3,
+                failures: 2,
+                throttled: false,
+                advice: CrashLoopAdvice::FixBinary,
+            })
+        );
+
+        let mut history = SpawnHistory::default();
+        history.push(stats(0, 1, 0));
+        history.push(SpawnStats {
+            throttled: true,
+            ..stats(10, 5, 0)
+        });
+        let crash_loop = history.crash_loop(window, 3).unwrap();
+        assert_eq!(crash_loop.advice, CrashLoopAdvice::IncreaseThrottleInterval);
+    }
+
+    #[test]
+    fn test_reload_resets_history() {
+        let mut history = SpawnHistory::default();
+        history.push(stats(0, 5, 1));
+        history.push(stats(10, 1, 0));
+        assert_eq!(history.samples.len(), 1);
+    }
+
+    #[test]
+    fn test_counter_reset_in_samples() {
+        let history = SpawnHistory {
+            samples: vec![stats(0, 5, 0), stats(10, 2, 0), stats(20, 3, 0)],
+        };
+        assert_eq!(history.spawns_within(Duration::from_secs(60)), 3);
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut history = SpawnHistory::default();
+        for i in 0..=u32::try_from(MAX_SAMPLES).unwrap() {
+            history.push(stats(u64::from(i), i, 0));
+        }
+        assert_eq!(history.samples.len(), MAX_SAMPLES);
+        assert_eq!(history.samples[0].runs, 1);
+    }
+}
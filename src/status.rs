@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::procinfo::process_start_time;
+use crate::LaunchctlResult;
+
+/// Default time a [`StatusProvider`] snapshot is reused.
+const DEFAULT_TTL: Duration = Duration::from_secs(2);
+
+/// Compact status of an agent for a status item or menu.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusItem {
+    pub label: String,
+    pub running: bool,
+    pub pid: Option<u32>,
+    /// How long the current process has been running.
+    pub uptime: Option<Duration>,
+    /// Exit status of the last run, `None` if the agent is not loaded.
+    pub last_exit_code: Option<i32>,
+    /// When launchd next starts the agent by its schedule, if it has one and
+    /// the time is known.
+    pub next_run: Option<SystemTime>,
+}
+
+/// Status of a set of agents that is cheap to poll, e.g. every few seconds
+/// from a menu bar app.
+///
+/// A refresh runs `launchctl list` once for all agents instead of printing
+/// each of them, and its result is reused until it is older than the TTL.
+pub struct StatusProvider {
+    agents: Vec<LaunchAgent>,
+    context: LaunchctlContext,
+    ttl: Duration,
+    cached: Option<(Instant, Vec<StatusItem>)>,
+    /// Process ID of each running agent and when it was started.
+    started: BTreeMap<String, (u32, SystemTime)>,
+    /// When each agent was last seen starting, kept after it exits.
+    last_start: BTreeMap<String, SystemTime>,
+}
+
+impl StatusProvider {
+    pub fn new(agents: Vec<LaunchAgent>) -> Self {
+        Self {
+            agents,
+            context: LaunchctlContext::default(),
+            ttl: DEFAULT_TTL,
+            cached: None,
+            started: BTreeMap::new(),
+            last_start: BTreeMap::new(),
+        }
+    }
+
+    /// List the agents with `context`.
+    #[must_use]
+    pub fn context(mut self, context: LaunchctlContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Reuse snapshots for `ttl`, 2 seconds by default.
+    #[must_use]
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Returns the status of every agent, in the order they were given.
+    /// launchctl runs only if the cached snapshot is missing or expired.
+    pub fn snapshot(&mut self) -> LaunchctlResult<&[StatusItem]> {
+        let expired = self
+            .cached
+            .as_ref()
+            .map_or(true, |(taken, _)| taken.elapsed() >= self.ttl);
+        if expired {
+            self.refresh()?;
+        }
+        Ok(self
+            .cached
+            .as_ref()
+            .map_or(&[], |(_, items)| items.as_slice()))
+    }
+
+    /// Returns the status of the agent with the label.
+    pub fn get(&mut self, label: &str) -> LaunchctlResult<Option<&StatusItem>> {
+        Ok(self.snapshot()?.iter().find(|item| item.label == label))
+    }
+
+    /// Take a snapshot now, replacing the cached one.
+    pub fn refresh(&mut self) -> LaunchctlResult<()> {
+        let services = self.context.list()?;
+        let now = SystemTime::now();
+        let items = self
+            .agents
+            .iter()
+            .map(|agent| {
+                let entry = services.get(&agent.label);
+                let pid = entry.and_then(|entry| entry.pid);
+                let started =
+                    observe_start(&mut self.started, &agent.label, pid, now);
+                if let Some(started) = started {
+                    self.last_start.insert(agent.label.clone(), started);
+                }
+                let last_start = self.last_start.get(&agent.label).copied();
+                StatusItem {
+                    label: agent.label.clone(),
+                    running: pid.is_some(),
+                    pid,
+                    uptime: started.map(|started| {
+                        now.duration_since(started).unwrap_or_default()
+                    }),
+                    last_exit_code: entry.map(|entry| entry.status),
+                    next_run: next_run(
+                        agent,
+                        || last_start.or_else(|| recorded_last_run(agent)),
+                        now,
+                    ),
+                }
+            })
+            .collect();
+        self.cached = Some((Instant::now(), items));
+        Ok(())
+    }
+
+    /// Drop the cached snapshot so the next query runs launchctl.
+    pub fn invalidate(&mut self) {
+        self.cached = None;
+    }
+}
+
+/// Remember when the process was started, looking it up only for new
+/// processes. Processes whose start time cannot be read count from when they
+/// were first seen. Returns the start time, `None` if the agent is not
+/// running.
+fn observe_start(
+    started: &mut BTreeMap<String, (u32, SystemTime)>,
+    label: &str,
+    pid: Option<u32>,
+    now: SystemTime,
+) -> Option<SystemTime> {
+    let Some(pid) = pid else {
+        started.remove(label);
+        return None;
+    };
+    let start_time = || process_start_time(pid).ok().flatten().unwrap_or(now);
+    let (seen, since) = started
+        .entry(label.to_string())
+        .or_insert_with(|| (pid, start_time()));
+    if *seen != pid {
+        *seen = pid;
+        *since = start_time();
+    }
+    Some(*since)
+}
+
+/// Returns when the agent last started, or else exited, according to its
+/// recorded history, see [`LaunchAgent::history`].
+fn recorded_last_run(agent: &LaunchAgent) -> Option<SystemTime> {
+    let history = agent.history();
+    let event = history.starts().last().or_else(|| history.last_exit())?;
+    Some(UNIX_EPOCH + Duration::from_secs(event.timestamp))
+}
+
+/// Returns the next scheduled start of the agent: by its calendar, or every
+/// `StartInterval` seconds counted from its last run, which `last_run`
+/// looks up only for agents with an interval.
+fn next_run<F>(
+    agent: &LaunchAgent,
+    last_run: F,
+    now: SystemTime,
+) -> Option<SystemTime>
+where
+    F: FnOnce() -> Option<SystemTime>,
+{
+    let by_interval = agent
+        .extra
+        .get("StartInterval")
+        .and_then(plist::Value::as_unsigned_integer)
+        .filter(|interval| *interval > 0)
+        .and_then(|interval| Some(next_tick(last_run()?, interval, now)));
+    let by_calendar = agent.next_calendar_run(now);
+    by_interval.into_iter().chain(by_calendar).min()
+}
+
+/// Returns the first of `last + n * interval` seconds, `n >= 1`, that is
+/// not before `now`.
+fn next_tick(last: SystemTime, interval: u64, now: SystemTime) -> SystemTime {
+    let elapsed = now.duration_since(last).unwrap_or_default().as_secs();
+    let ticks = elapsed.div_ceil(interval).max(1);
+    last + Duration::from_secs(interval.saturating_mul(ticks))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn test_observe_start() {
+        let mut started = BTreeMap::new();
+        let t0 = SystemTime::now();
+        let t1 = t0 + Duration::from_secs(60);
+        // Process IDs that do not exist count from when they were seen.
+        let (a, b) = (u32::MAX >> 1, (u32::MAX >> 1) - 1);
+
+        assert_eq!(observe_start(&mut started, "a", Some(a), t0), Some(t0));
+        assert_eq!(observe_start(&mut started, "a", Some(a), t1), Some(t0));
+        assert_eq!(observe_start(&mut started, "a", Some(b), t1), Some(t1));
+        assert_eq!(observe_start(&mut started, "a", None, t1), None);
+        assert_eq!(observe_start(&mut started, "a", Some(b), t1), Some(t1));
+    }
+
+    #[test]
+    fn test_next_run_by_interval() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        let t0 = SystemTime::now();
+        assert_eq!(next_run(&agent, || Some(t0), t0), None);
+
+        agent.extra.insert("StartInterval".to_string(), 60.into());
+        let at = |secs| t0 + Duration::from_secs(secs);
+        assert_eq!(next_run(&agent, || Some(t0), at(10)), Some(at(60)));
+        assert_eq!(next_run(&agent, || Some(t0), at(90)), Some(at(120)));
+        assert_eq!(next_run(&agent, || Some(t0), at(120)), Some(at(120)));
+        assert_eq!(next_run(&agent, || None, at(10)), None);
+    }
+
+    #[test]
+    fn test_snapshot_of_unloaded_agents() {
+        let label = format!("co.myrt.status.{}", rand::random_range(0..u32::MAX));
+        let mut provider = StatusProvider::new(vec![LaunchAgent::new(&label)]);
+
+        let items = provider.snapshot().unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(!items[0].running);
+        assert_eq!(items[0].uptime, None);
+        assert_eq!(items[0].last_exit_code, None);
+        assert!(provider.get(&label).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_snapshot_uses_context() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let context = LaunchctlContext::new()
+            .launchctl("/usr/local/bin/launchctl")
+            .dry_run(true)
+            .on_command(move |command| {
+                recorder.lock().unwrap().push(command.to_string());
+            });
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        let mut provider = StatusProvider::new(vec![agent]).context(context);
+
+        provider.snapshot().unwrap();
+        provider.snapshot().unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            ["/usr/local/bin/launchctl list".to_string()]
+        );
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::agent::LaunchAgent;
+use crate::alert::{Alert, AlertKind, AlertSink};
+use crate::context::LaunchctlContext;
+use crate::poll::{poll, CancellationToken, PollOptions};
+use crate::stats::SpawnHistory;
+use crate::telemetry::record_crash_loop;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// Default interval between reconcile passes of a [`Supervisor`].
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+
+type HealthCheck = Arc<dyn Fn(&LaunchAgent) -> LaunchctlResult<bool> + Send + Sync>;
+type EventHook = Arc<dyn Fn(&SupervisorEvent) + Send + Sync>;
+
+/// How a [`Supervisor`] keeps an agent alive.
+#[derive(Clone)]
+pub struct SupervisionPolicy {
+    /// Start the agent when it is not running.
+    pub ensure_running: bool,
+    /// Restart the agent when the check returns `false`.
+    health_check: Option<HealthCheck>,
+    /// Restarts allowed within [`restart_window`](Self::restart_window)
+    /// before the supervisor gives up on the agent.
+    pub max_restarts: u32,
+    pub restart_window: Duration,
+    /// Spawns within [`crash_loop_window`](Self::crash_loop_window) that
+    /// count as a crash loop, `0` to not check. Crash looping agents are not
+    /// restarted, see [`CrashLoopAdvice`](crate::CrashLoopAdvice).
+    pub crash_loop_threshold: u32,
+    pub crash_loop_window: Duration,
+    /// Persist the agent's starts and exits, see
+    /// [`LaunchAgent::record_history`].
+    pub record_history: bool,
+}
+
+impl Default for SupervisionPolicy {
+    /// Keep the agent running, restarting it at most 5 times in 10 minutes.
+    fn default() -> Self {
+        Self {
+            ensure_running: true,
+            health_check: None,
+            max_restarts: 5,
+            restart_window: Duration::from_secs(600),
+            crash_loop_threshold: 5,
+            crash_loop_window: Duration::from_secs(300),
+            record_history: false,
+        }
+    }
+}
+
+impl fmt::Debug for SupervisionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SupervisionPolicy")
+            .field("ensure_running", &self.ensure_running)
+            .field("health_check", &self.health_check.is_some())
+            .field("max_restarts", &self.max_restarts)
+            .field("restart_window", &self.restart_window)
+            .field("crash_loop_threshold", &self.crash_loop_threshold)
+            .field("crash_loop_window", &self.crash_loop_window)
+            .field("record_history", &self.record_history)
+            .finish()
+    }
+}
+
+impl SupervisionPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn ensure_running(mut self, ensure_running: bool) -> Self {
+        self.ensure_running = ensure_running;
+        self
+    }
+
+    /// Restart the running agent when `check` returns `false`.
+    #[must_use]
+    pub fn health_check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&LaunchAgent) -> LaunchctlResult<bool> + Send + Sync + 'static,
+    {
+        self.health_check = Some(Arc::new(check));
+        self
+    }
+
+    /// Restart the running agent when it does not answer a
+    /// [`ping`](LaunchAgent::ping) within `timeout`.
+    #[must_use]
+    pub fn ping(self, timeout: Duration) -> Self {
+        self.health_check(move |agent| agent.ping(timeout))
+    }
+
+    /// Give up on the agent after `max_restarts` restarts within `window`.
+    #[must_use]
+    pub fn max_restarts(mut self, max_restarts: u32, window: Duration) -> Self {
+        self.max_restarts = max_restarts;
+        self.restart_window = window;
+        self
+    }
+
+    /// Treat `threshold` spawns within `window` as a crash loop.
+    #[must_use]
+    pub fn crash_loop(mut self, threshold: u32, window: Duration) -> Self {
+        self.crash_loop_threshold = threshold;
+        self.crash_loop_window = window;
+        self
+    }
+
+    /// Persist the agent's starts and exits on every pass.
+    #[must_use]
+    pub fn record_history(mut self, record_history: bool) -> Self {
+        self.record_history = record_history;
+        self
+    }
+}
+
+/// Why a [`Supervisor`] restarted an agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartReason {
+    NotRunning,
+    HealthCheckFailed,
+}
+
+/// Something a [`Supervisor`] did or noticed during a reconcile pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SupervisorEvent {
+    Restarted {
+        label: String,
+        reason: RestartReason,
+    },
+    /// The agent needed a restart but used up its restarts within the
+    /// window. Reported once until the window allows restarts again.
+    RestartLimitReached { label: String, restarts: u32 },
+    /// Checking or restarting the agent failed.
+    Failed { label: String, error: String },
+}
+
+impl SupervisorEvent {
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Restarted { label, .. }
+            | Self::RestartLimitReached { label, .. }
+            | Self::Failed { label, .. } => label,
+        }
+    }
+}
+
+/// Restarts of an agent within its policy's window.
+#[derive(Debug, Default)]
+struct RestartBudget {
+    restarts: Vec<Instant>,
+    exhausted: bool,
+}
+
+/// What a [`Supervisor`] remembers about an agent between passes.
+#[derive(Debug, Default)]
+struct AgentState {
+    budget: RestartBudget,
+    history: SpawnHistory,
+    /// Alerts raised and not cleared yet.
+    raised: BTreeSet<AlertKind>,
+}
+
+impl AgentState {
+    /// Add `alert` to `alerts` unless it is already raised.
+    fn raise(&mut self, alert: Alert, alerts: &mut Vec<Alert>) {
+        if self.raised.insert(alert.kind) {
+            alerts.push(alert);
+        }
+    }
+
+    fn clear(&mut self, kind: AlertKind) {
+        self.raised.remove(&kind);
+    }
+}
+
+impl RestartBudget {
+    /// Take a restart at `now`. Returns `false` if the policy allows no more
+    /// restarts within the window.
+    fn take(&mut self, policy: &SupervisionPolicy, now: Instant) -> bool {
+        self.restarts
+            .retain(|at| now.duration_since(*at) < policy.restart_window);
+        if self.restarts.len() >= policy.max_restarts as usize {
+            return false;
+        }
+        self.restarts.push(now);
+        self.exhausted = false;
+        true
+    }
+}
+
+/// Periodically reconciles agents with their [`SupervisionPolicy`]: starts
+/// agents that stopped, restarts agents that fail their health check and
+/// gives up on agents that keep failing.
+///
+/// launchd already restarts agents with `KeepAlive`; the supervisor adds
+/// the checks launchd cannot do, such as whether the process still answers.
+/// Crash loops, failed health checks and agents disabled by the user are
+/// reported to the [`AlertSink`]s.
+#[derive(Default)]
+pub struct Supervisor {
+    agents: Vec<(LaunchAgent, SupervisionPolicy)>,
+    context: LaunchctlContext,
+    interval: Option<Duration>,
+    hooks: Vec<EventHook>,
+    sinks: Vec<Arc<dyn AlertSink>>,
+    states: BTreeMap<String, AgentState>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Supervise `agent` according to `policy`.
+    #[must_use]
+    pub fn supervise(
+        mut self,
+        agent: LaunchAgent,
+        policy: SupervisionPolicy,
+    ) -> Self {
+        self.agents.push((agent, policy));
+        self
+    }
+
+    /// Check and restart the agents with `context`.
+    #[must_use]
+    pub fn context(mut self, context: LaunchctlContext) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Set the interval between reconcile passes, 30 seconds by default.
+    #[must_use]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Call `hook` with every event, e.g. to log it.
+    #[must_use]
+    pub fn on_event<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&SupervisorEvent) + Send + Sync + 'static,
+    {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Report alerts to `sink`.
+    #[must_use]
+    pub fn alert_to<S: AlertSink + 'static>(mut self, sink: S) -> Self {
+        self.sinks.push(Arc::new(sink));
+        self
+    }
+
+    /// Check every agent once, restarting those that need it. Returns the
+    /// events of the pass after reporting them to the hooks and its alerts
+    /// to the sinks.
+    pub fn reconcile(&mut self) -> Vec<SupervisorEvent> {
+        let mut events = Vec::new();
+        let mut alerts = Vec::new();
+        for (agent, policy) in &self.agents {
+            let state = self.states.entry(agent.label.clone()).or_default();
+            let event = match reconcile_agent(
+                &self.context,
+                agent,
+                policy,
+                state,
+                &mut alerts,
+            ) {
+                Ok(event) => event,
+                Err(e) => Some(SupervisorEvent::Failed {
+                    label: agent.label.clone(),
+                    error: e.to_string(),
+                }),
+            };
+            events.extend(event);
+        }
+        for event in &events {
+            for hook in &self.hooks {
+                hook(event);
+            }
+        }
+        for alert in &alerts {
+            for sink in &self.sinks {
+                sink.alert(alert);
+            }
+        }
+        events
+    }
+
+    /// Reconcile at the configured interval until `cancel` is cancelled.
+    pub fn run(&mut self, cancel: &CancellationToken) {
+        let interval = self.interval.unwrap_or(DEFAULT_INTERVAL);
+        let wait = PollOptions::new(interval)
+            .interval(interval)
+            .cancel_on(cancel);
+        while !cancel.is_cancelled() {
+            self.reconcile();
+            if let Err(LaunchAgentError::Cancelled) = poll(|| Ok(false), &wait) {
+                break;
+            }
+        }
+    }
+}
+
+/// Restart the agent if its policy asks for it, adding the alerts it raises
+/// to `alerts`.
+fn reconcile_agent(
+    context: &LaunchctlContext,
+    agent: &LaunchAgent,
+    policy: &SupervisionPolicy,
+    state: &mut AgentState,
+    alerts: &mut Vec<Alert>,
+) -> LaunchctlResult<Option<SupervisorEvent>> {
+    let recorded = if policy.record_history {
+        context
+            .record_history(agent)
+            .inspect_err(|e| {
+                log::warn!(
+                    target: "lunchctl",
+                    "failed to record the history of {}: {e}",
+                    agent.label
+                );
+            })
+            .ok()
+    } else {
+        None
+    };
+    match recorded {
+        Some(history) => state.history = history.spawns,
+        None => {
+            if let Some(stats) = context.spawn_stats(agent)? {
+                state.history.push(stats);
+            }
+        }
+    }
+
+    // Restarting would fail or undo the user's choice.
+    if context.is_disabled(agent)? {
+        state.raise(Alert::new(AlertKind::Disabled, agent), alerts);
+        return Ok(None);
+    }
+    state.clear(AlertKind::Disabled);
+
+    if policy.crash_loop_threshold > 0 {
+        let crash_loop = state
+            .history
+            .crash_loop(policy.crash_loop_window, policy.crash_loop_threshold);
+        record_crash_loop(&agent.label, crash_loop.is_some());
+        if let Some(crash_loop) = crash_loop {
+            let mut alert = Alert::new(AlertKind::CrashLoop, agent);
+            alert.crash_loop = Some(crash_loop);
+            alert.last_exit_code = state
+                .history
+                .latest()
+                .and_then(|stats| stats.last_exit_code);
+            state.raise(alert, alerts);
+            return Ok(None);
+        }
+        state.clear(AlertKind::CrashLoop);
+    }
+
+    let running = context.is_running(agent)?;
+    if running {
+        state.clear(AlertKind::Stopped);
+    }
+    let reason = if running {
+        match &policy.health_check {
+            Some(check) if !check(agent)? => {
+                state.raise(Alert::new(AlertKind::HealthCheckFailed, agent), alerts);
+                RestartReason::HealthCheckFailed
+            }
+            _ => {
+                state.clear(AlertKind::HealthCheckFailed);
+                return Ok(None);
+            }
+        }
+    } else if policy.ensure_running {
+        let mut alert = Alert::new(AlertKind::Stopped, agent);
+        alert.last_exit_code = state
+            .history
+            .latest()
+            .and_then(|stats| stats.last_exit_code);
+        state.raise(alert, alerts);
+        RestartReason::NotRunning
+    } else {
+        return Ok(None);
+    };
+
+    let budget = &mut state.budget;
+    if !budget.take(policy, Instant::now()) {
+        if budget.exhausted {
+            return Ok(None);
+        }
+        budget.exhausted = true;
+        return Ok(Some(SupervisorEvent::RestartLimitReached {
+            label: agent.label.clone(),
+            restarts: policy.max_restarts,
+        }));
+    }
+
+    if running {
+        context.kickstart(agent, true)?;
+    } else if context.is_loaded(agent)? {
+        context.kickstart(agent, false)?;
+    } else {
+        context.bootstrap(agent)?;
+    }
+    Ok(Some(SupervisorEvent::Restarted {
+        label: agent.label.clone(),
+        reason,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_budget() {
+        let policy =
+            SupervisionPolicy::new().max_restarts(2, Duration::from_secs(60));
+        let mut budget = RestartBudget::default();
+        let start = Instant::now();
+
+        assert!(budget.take(&policy, start));
+        assert!(budget.take(&policy, start + Duration::from_secs(10)));
+        assert!(!budget.take(&policy, start + Duration::from_secs(20)));
+        assert!(budget.take(&policy, start + Duration::from_secs(61)));
+        assert!(!budget.take(&policy, start + Duration::from_secs(62)));
+    }
+
+    #[test]
+    fn test_alerts_are_raised_once() {
+        let agent = LaunchAgent::new("co.myrt.ajam");
+        let mut state = AgentState::default();
+        let mut alerts = Vec::new();
+
+        state.raise(Alert::new(AlertKind::Disabled, &agent), &mut alerts);
+        state.raise(Alert::new(AlertKind::Disabled, &agent), &mut alerts);
+        assert_eq!(alerts.len(), 1);
+        state.clear(AlertKind::Disabled);
+        state.raise(Alert::new(AlertKind::Disabled, &agent), &mut alerts);
+        assert_eq!(alerts.len(), 2);
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::agent::{LaunchAgent, DEV_NULL};
+use crate::diagnose::Finding;
+use crate::info::ServiceInfo;
+use crate::lint::FULL_DISK_ACCESS;
+
+/// TCC service of Full Disk Access, as passed to `tccutil reset`.
+pub const FULL_DISK_ACCESS_SERVICE: &str = "SystemPolicyAllFiles";
+
+/// Bytes read from the end of the error log.
+const LOG_TAIL: u64 = 16 * 1024;
+
+/// Messages of programs denied access to protected folders.
+const DENIAL_MARKERS: [&str; 2] = ["Operation not permitted", "EPERM"];
+
+impl LaunchAgent {
+    /// Returns the identifier TCC knows the program by: the bundle
+    /// identifier of the app containing it, or its path.
+    pub fn tcc_identifier(&self) -> Option<String> {
+        let program = self.program_path()?;
+        let bundle_id = program
+            .ancestors()
+            .find(|dir| dir.extension().is_some_and(|ext| ext == "app"))
+            .and_then(bundle_identifier);
+        Some(bundle_id.unwrap_or_else(|| program.display().to_string()))
+    }
+
+    /// Returns a hint to grant Full Disk Access if the agent last exited
+    /// with an error after logging "Operation not permitted" to its standard
+    /// error, as it was most likely denied access to a protected folder.
+    pub(crate) fn full_disk_access_finding(
+        &self,
+        info: &ServiceInfo,
+    ) -> Option<Finding> {
+        let failed = info.last_exit_code().is_some_and(|code| code != 0);
+        (failed && self.logged_denial()).then(|| self.full_disk_access_hint())
+    }
+
+    fn logged_denial(&self) -> bool {
+        let path = &self.standard_error_path;
+        path != Path::new(DEV_NULL)
+            && read_tail(path).is_some_and(|log| {
+                DENIAL_MARKERS.iter().any(|marker| log.contains(marker))
+            })
+    }
+
+    fn full_disk_access_hint(&self) -> Finding {
+        let identifier = self.tcc_identifier().unwrap_or_default();
+        let message = "the agent failed with \"Operation not permitted\", it may \
+                       lack Full Disk Access";
+        FULL_DISK_ACCESS.warning(message).with_suggestion(&format!(
+            "grant Full Disk Access to {identifier} in System Settings > Privacy & \
+             Security; `tccutil reset {FULL_DISK_ACCESS_SERVICE} {identifier}` clears \
+             a previous decision"
+        ))
+    }
+}
+
+/// Returns `CFBundleIdentifier` of the app bundle.
+fn bundle_identifier(app: &Path) -> Option<String> {
+    let info =
+        plist::Value::from_file(app.join("Contents").join("Info.plist")).ok()?;
+    info.as_dictionary()?
+        .get("CFBundleIdentifier")?
+        .as_string()
+        .map(ToString::to_string)
+}
+
+/// Returns the end of the log file.
+fn read_tail(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(LOG_TAIL)))
+        .ok()?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).ok()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tcc_identifier() {
+        let dir = std::env::temp_dir()
+            .join(format!("lunchctl-tcc-{}", rand::random_range(0..u32::MAX)));
+        let app = dir.join("Sync.app");
+        std::fs::create_dir_all(app.join("Contents/MacOS")).unwrap();
+        let mut info = plist::Dictionary::new();
+        info.insert("CFBundleIdentifier".to_string(), "co.myrt.sync".into());
+        plist::to_file_xml(app.join("Contents/Info.plist"), &info).unwrap();
+
+        let mut agent = LaunchAgent::new("co.myrt.sync");
+        assert_eq!(agent.tcc_identifier(), None);
+        agent.program_arguments = vec![app.join("Contents/MacOS/sync").into()];
+        assert_eq!(agent.tcc_identifier().as_deref(), Some("co.myrt.sync"));
+        agent.program_arguments = vec!["/usr/local/bin/sync".into()];
+        assert_eq!(
+            agent.tcc_identifier().as_deref(),
+            Some("/usr/local/bin/sync")
+        );
+
+        assert!(!agent.logged_denial());
+        let log = dir.join("sync.log");
+        std::fs::write(&log, "ls: Documents: Operation not permitted\n").unwrap();
+        agent.standard_error_path = log;
+        assert!(agent.logged_denial());
+        let info = |code| {
+            ServiceInfo::parse(&format!(
+                "gui/501/co.myrt.sync = {{\n\tlast exit code = {code}\n}}"
+            ))
+        };
+        assert_eq!(agent.full_disk_access_finding(&info(0)), None);
+        assert!(agent
+            .full_disk_access_finding(&info(1))
+            .unwrap()
+            .to_string()
+            .contains("tccutil reset SystemPolicyAllFiles /usr/local/bin/sync"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use crate::command::CommandSpec;
+use crate::fleet::FleetStatus;
+
+/// Default duration above which a launchctl invocation is logged as slow,
+/// see [`LaunchctlContext::slow_call_threshold`](crate::LaunchctlContext::slow_call_threshold).
+pub const DEFAULT_SLOW_CALL_THRESHOLD: Duration = Duration::from_millis(500);
+
+#[cfg(feature = "metrics")]
+const CALLS: &str = "lunchctl_launchctl_calls_total";
+#[cfg(feature = "metrics")]
+const FAILURES: &str = "lunchctl_launchctl_failures_total";
+#[cfg(feature = "metrics")]
+const DURATION: &str = "lunchctl_launchctl_call_duration_seconds";
+#[cfg(feature = "metrics")]
+const SLOW_CALLS: &str = "lunchctl_launchctl_slow_calls_total";
+#[cfg(feature = "metrics")]
+const MANAGED: &str = "lunchctl_agents_managed";
+#[cfg(feature = "metrics")]
+const RUNNING: &str = "lunchctl_agents_running";
+#[cfg(feature = "metrics")]
+const CRASH_LOOPING: &str = "lunchctl_agent_crash_looping";
+
+/// Register descriptions of the exported metrics with the installed
+/// recorder.
+#[cfg(feature = "metrics")]
+pub fn describe_metrics() {
+    metrics::describe_counter!(CALLS, "launchctl invocations by subcommand");
+    metrics::describe_counter!(
+        FAILURES,
+        "launchctl invocations that exited with an error, by subcommand"
+    );
+    metrics::describe_histogram!(
+        DURATION,
+        metrics::Unit::Seconds,
+        "Duration of launchctl invocations by subcommand"
+    );
+    metrics::describe_counter!(
+        SLOW_CALLS,
+        "launchctl invocations slower than the slow call threshold"
+    );
+    metrics::describe_gauge!(MANAGED, "Agents in the last exported fleet status");
+    metrics::describe_gauge!(RUNNING, "Running agents in the last fleet status");
+    metrics::describe_gauge!(CRASH_LOOPING, "1 if the agent is crash looping");
+}
+
+/// Log a finished launchctl invocation with its duration and record it in
+/// the metrics. Metrics are only recorded with the `metrics` feature.
+///
+/// Every invocation is logged at debug level with its duration, so the
+/// `lunchctl` log target shows where the time of an install goes. Ones
+/// slower than `threshold` are logged as warnings.
+pub(crate) fn record_call(
+    command: &CommandSpec,
+    elapsed: Duration,
+    success: bool,
+    threshold: Duration,
+) {
+    let slow = elapsed > threshold;
+    if slow {
+        log::warn!(target: "lunchctl", "slow launchctl call ({elapsed:?}): {command}");
+    } else {
+        log::debug!(target: "lunchctl", "{command} took {elapsed:?}");
+    }
+
+    #[cfg(feature = "metrics")]
+    {
+        let subcommand = command
+            .args
+            .first()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        metrics::counter!(CALLS, "subcommand" => subcommand.clone()).increment(1);
+        if !success {
+            metrics::counter!(FAILURES, "subcommand" => subcommand.clone())
+                .increment(1);
+        }
+        if slow {
+            metrics::counter!(SLOW_CALLS, "subcommand" => subcommand.clone())
+                .increment(1);
+        }
+        metrics::histogram!(DURATION, "subcommand" => subcommand)
+            .record(elapsed.as_secs_f64());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = success;
+}
+
+/// Record whether the agent is crash looping.
+pub(crate) fn record_crash_loop(label: &str, looping: bool) {
+    #[cfg(feature = "metrics")]
+    metrics::gauge!(CRASH_LOOPING, "label" => label.to_string())
+        .set(f64::from(u8::from(looping)));
+    #[cfg(not(feature = "metrics"))]
+    let _ = (label, looping);
+}
+
+impl FleetStatus {
+    /// Set the managed and running agent gauges from this status.
+    /// Does nothing without the `metrics` feature.
+    pub fn export_metrics(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            #[allow(clippy::cast_precision_loss)]
+            let count = |n: usize| n as f64;
+            metrics::gauge!(MANAGED).set(count(self.agents.len()));
+            metrics::gauge!(RUNNING).set(count(self.running().count()));
+        }
+    }
+}
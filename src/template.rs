@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use crate::agent::LaunchAgent;
+use crate::env::Environment;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+impl LaunchAgent {
+    /// Create an agent from a template whose strings contain `${VAR}`
+    /// placeholders, e.g. one agent per project from a single definition.
+    ///
+    /// Placeholders are replaced in the label, program arguments, output
+    /// paths, environment values and the string values of extra keys such as
+    /// `WorkingDirectory`. `$$` stands for a literal `$`. Placeholders
+    /// without a value fail with [`LaunchAgentError::UndefinedVariable`].
+    pub fn instantiate(
+        template: &LaunchAgent,
+        vars: &BTreeMap<String, String>,
+    ) -> LaunchctlResult<LaunchAgent> {
+        let mut agent = template.clone();
+        agent.label = substitute(&template.label, vars)?;
+        agent.program_arguments = template
+            .program_arguments
+            .iter()
+            .map(|arg| match arg.to_str() {
+                Some(arg) => substitute(arg, vars).map(OsString::from),
+                None => Ok(arg.clone()),
+            })
+            .collect::<LaunchctlResult<_>>()?;
+        agent.standard_out_path =
+            substitute_path(&template.standard_out_path, vars)?;
+        agent.standard_error_path =
+            substitute_path(&template.standard_error_path, vars)?;
+
+        let mut environment = Environment::new();
+        for (key, value) in template.environment_variables.iter() {
+            environment.set(key, &substitute(value, vars)?);
+            if template.environment_variables.is_secret(key) {
+                environment.mark_secret(key);
+            }
+        }
+        agent.environment_variables = environment;
+
+        for value in agent.extra.values_mut() {
+            substitute_value(value, vars)?;
+        }
+        Ok(agent)
+    }
+}
+
+/// Replace the placeholders of `template` with their values.
+fn substitute(
+    template: &str,
+    vars: &BTreeMap<String, String>,
+) -> LaunchctlResult<String> {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let Some(end) = after.find('}') else {
+                return Err(LaunchAgentError::UndefinedVariable(after.to_string()));
+            };
+            let name = &after[..end];
+            let value = vars.get(name).ok_or_else(|| {
+                LaunchAgentError::UndefinedVariable(name.to_string())
+            })?;
+            result.push_str(value);
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn substitute_path(
+    path: &std::path::Path,
+    vars: &BTreeMap<String, String>,
+) -> LaunchctlResult<PathBuf> {
+    match path.to_str() {
+        Some(path) => Ok(PathBuf::from(substitute(path, vars)?)),
+        None => Ok(path.to_path_buf()),
+    }
+}
+
+/// Replace placeholders in every string of a plist value.
+fn substitute_value(
+    value: &mut plist::Value,
+    vars: &BTreeMap<String, String>,
+) -> LaunchctlResult<()> {
+    match value {
+        plist::Value::String(string) => *string = substitute(string, vars)?,
+        plist::Value::Array(items) => {
+            for item in items {
+                substitute_value(item, vars)?;
+            }
+        }
+        plist::Value::Dictionary(dict) => {
+            for item in dict.values_mut() {
+                substitute_value(item, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+
+    fn vars() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("PROJECT".to_string(), "site".to_string()),
+            ("DIR".to_string(), "/Users/me/src/site".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_substitute() {
+        assert_eq!(
+            substitute("${DIR}/build", &vars()).unwrap(),
+            "/Users/me/src/site/build"
+        );
+        assert_eq!(
+            substitute("$$HOME costs $5", &vars()).unwrap(),
+            "$HOME costs $5"
+        );
+        assert!(matches!(
+            substitute("${MISSING}", &vars()),
+            Err(LaunchAgentError::UndefinedVariable(name)) if name == "MISSING"
+        ));
+        assert!(substitute("${DIR", &vars()).is_err());
+    }
+
+    #[test]
+    fn test_instantiate() {
+        let mut template = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam.watch.${PROJECT}")
+            .args(["/usr/local/bin/ajam", "watch", "${DIR}"])
+            .standard_out_path("${DIR}/.ajam/watch.log")
+            .env("AJAM_PROJECT", "${PROJECT}")
+            .env_secret("AJAM_TOKEN", "${PROJECT}-token")
+            .build()
+            .unwrap();
+        template
+            .extra
+            .insert("WorkingDirectory".to_string(), "${DIR}".into());
+
+        let agent = LaunchAgent::instantiate(&template, &vars()).unwrap();
+        assert_eq!(agent.label, "co.myrt.ajam.watch.site");
+        assert_eq!(agent.program_arguments[2], "/Users/me/src/site");
+        assert_eq!(
+            agent.standard_out_path,
+            PathBuf::from("/Users/me/src/site/.ajam/watch.log")
+        );
+        assert_eq!(
+            agent.environment_variables.get("AJAM_PROJECT"),
+            Some("site")
+        );
+        assert!(agent.environment_variables.is_secret("AJAM_TOKEN"));
+        assert_eq!(
+            agent
+                .extra
+                .get("WorkingDirectory")
+                .and_then(plist::Value::as_string),
+            Some("/Users/me/src/site")
+        );
+    }
+}
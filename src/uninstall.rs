@@ -0,0 +1,223 @@
+use std::path::PathBuf;
+
+use crate::agent::{LaunchAgent, DEV_NULL};
+use crate::context::LaunchctlContext;
+use crate::install::{InstallEvent, InstallObserver};
+use crate::LaunchctlResult;
+
+/// What `uninstall_with()` should clean up besides the plist itself.
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct UninstallOptions {
+    /// Remove the files the agent redirects its output to.
+    pub remove_logs: bool,
+    /// Remove the `~/Library/Application Support/<label>` directory and the
+    /// agent's recorded history.
+    pub remove_state: bool,
+    /// Remove the agent's cache and temporary directories.
+    pub remove_cache: bool,
+    /// Clear a disabled override left by `launchctl disable`.
+    pub clear_disabled_override: bool,
+}
+
+impl UninstallOptions {
+    /// Clean up everything the agent left behind.
+    pub fn all() -> Self {
+        Self {
+            remove_logs: true,
+            remove_state: true,
+            remove_cache: true,
+            clear_disabled_override: true,
+        }
+    }
+}
+
+/// Summary of what `uninstall_with()` did.
+#[derive(Debug, Clone, Default)]
+pub struct UninstallReport {
+    /// The agent was loaded and has been booted out.
+    pub booted_out: bool,
+    /// Files and directories that were deleted, the plist included.
+    pub removed: Vec<PathBuf>,
+    /// A disabled override existed and has been cleared.
+    pub override_cleared: bool,
+}
+
+impl LaunchAgent {
+    /// Boot out the agent if it is loaded and remove its plist.
+    pub fn uninstall(&self) -> LaunchctlResult<UninstallReport> {
+        self.uninstall_with(&UninstallOptions::default())
+    }
+
+    /// Boot out the agent if it is loaded, remove its plist and clean up
+    /// the residue selected in `options`.
+    pub fn uninstall_with(
+        &self,
+        options: &UninstallOptions,
+    ) -> LaunchctlResult<UninstallReport> {
+        self.uninstall_observed(options, &())
+    }
+
+    /// Same as [`uninstall_with`](Self::uninstall_with), reporting progress
+    /// to `observer`.
+    pub fn uninstall_observed(
+        &self,
+        options: &UninstallOptions,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<UninstallReport> {
+        LaunchctlContext::default().uninstall_observed(self, options, observer)
+    }
+
+    /// Remove the residue selected in `options`, adding the removed paths
+    /// to `report`.
+    fn remove_residue(
+        &self,
+        options: &UninstallOptions,
+        report: &mut UninstallReport,
+    ) -> LaunchctlResult<()> {
+        if options.remove_logs {
+            for log in self.log_paths() {
+                if log.is_file() {
+                    std::fs::remove_file(&log)?;
+                    report.removed.push(log);
+                }
+            }
+        }
+
+        if options.remove_state {
+            if self.remove_state_dir()? {
+                report.removed.push(self.state_dir()?);
+            }
+            if self.clear_history()? {
+                report.removed.push(self.history_path()?);
+            }
+        }
+
+        if options.remove_cache {
+            if self.remove_cache_dir()? {
+                report.removed.push(self.cache_dir()?);
+            }
+            if self.remove_temp_dir()? {
+                report.removed.push(self.temp_dir());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the output redirection targets, skipping the null device.
+    pub(crate) fn log_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for path in [&self.standard_out_path, &self.standard_error_path] {
+            if path.as_os_str() != DEV_NULL && !paths.contains(path) {
+                paths.push(path.clone());
+            }
+        }
+        paths
+    }
+}
+
+impl LaunchctlContext {
+    /// Boot out the agent if it is loaded and remove its plist from the
+    /// context's directory, see [`LaunchAgent::uninstall`].
+    pub fn uninstall(
+        &self,
+        agent: &LaunchAgent,
+    ) -> LaunchctlResult<UninstallReport> {
+        self.uninstall_with(agent, &UninstallOptions::default())
+    }
+
+    /// Same as [`uninstall`](Self::uninstall), cleaning up the residue
+    /// selected in `options`. Dry runs leave the residue in place.
+    pub fn uninstall_with(
+        &self,
+        agent: &LaunchAgent,
+        options: &UninstallOptions,
+    ) -> LaunchctlResult<UninstallReport> {
+        self.uninstall_observed(agent, options, &())
+    }
+
+    /// Same as [`uninstall_with`](Self::uninstall_with), reporting progress
+    /// to `observer`.
+    pub fn uninstall_observed(
+        &self,
+        agent: &LaunchAgent,
+        options: &UninstallOptions,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<UninstallReport> {
+        let _lock = self.lock(agent)?;
+        self.check_ownership(agent)?;
+        let mut report = UninstallReport::default();
+
+        if self.is_loaded(agent)? {
+            observer.on_event(&agent.label, InstallEvent::BootingOut);
+            self.boot_out(agent)?;
+            report.booted_out = true;
+        }
+
+        observer.on_event(&agent.label, InstallEvent::RemovingFiles);
+        let path = self.path(agent)?;
+        if path.exists() {
+            self.remove_plist(agent)?;
+            report.removed.push(path);
+        }
+        if !self.is_dry_run() {
+            agent.remove_residue(options, &mut report)?;
+        }
+
+        if options.clear_disabled_override && self.is_disabled(agent)? {
+            self.enable(agent)?;
+            report.override_cleared = true;
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LaunchAgentError;
+
+    #[test]
+    fn test_log_paths() {
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        assert!(agent.log_paths().is_empty());
+
+        agent.standard_out_path = PathBuf::from("/tmp/ajam.log");
+        agent.standard_error_path = PathBuf::from("/tmp/ajam.log");
+        assert_eq!(agent.log_paths(), vec![PathBuf::from("/tmp/ajam.log")]);
+
+        agent.standard_error_path = PathBuf::from("/tmp/ajam.err");
+        assert_eq!(agent.log_paths().len(), 2);
+    }
+
+    #[test]
+    fn test_uninstall_without_override() {
+        let label = format!("co.myrt.ajam.test.{}", rand::random_range(0..u32::MAX));
+        let agent = LaunchAgent::new(&label);
+        agent.write().unwrap();
+
+        let report = LaunchctlContext::idle().uninstall(&agent).unwrap();
+        assert!(!report.override_cleared);
+        assert_eq!(report.removed, vec![agent.path().unwrap()]);
+    }
+
+    #[test]
+    fn test_uninstall_requires_ownership() {
+        let id = rand::random_range(0..u32::MAX);
+        let context = LaunchctlContext::idle().require_ownership(true);
+        let foreign = LaunchAgent::new(&format!("co.myrt.ajam.foreign.{id}"));
+        foreign.write_plist_to(&foreign.path().unwrap()).unwrap();
+        assert!(matches!(
+            context.uninstall(&foreign),
+            Err(LaunchAgentError::NotManaged(_))
+        ));
+        assert!(foreign.path().unwrap().exists());
+        foreign.remove().unwrap();
+
+        let own = LaunchAgent::new(&format!("co.myrt.ajam.own.{id}"));
+        own.write().unwrap();
+        context.uninstall(&own).unwrap();
+        assert!(!own.path().unwrap().exists());
+    }
+}
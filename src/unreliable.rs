@@ -0,0 +1,195 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use plist::Value;
+
+use crate::agent::LaunchAgent;
+use crate::os::home_dir;
+
+/// Folders under `~/Library` synced by iCloud Drive and File Provider
+/// apps such as Dropbox.
+const CLOUD_STORAGE_DIRS: [&str; 2] = ["Mobile Documents", "CloudStorage"];
+
+/// Why launchd may fail to access a path at login.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathHazard {
+    /// The path is in iCloud Drive or another cloud storage folder, which
+    /// may not be available, or downloaded, when the agent starts.
+    CloudStorage,
+    /// The path is on a network file system mounted after login, if at all.
+    NetworkMount,
+    /// The file is dataless: its contents were evicted to the cloud and are
+    /// only fetched on access.
+    Dataless,
+}
+
+/// Path of the configuration launchd may fail to access, see
+/// [`LaunchAgent::unreliable_paths`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreliablePath {
+    pub path: PathBuf,
+    pub hazard: PathHazard,
+}
+
+impl fmt::Display for UnreliablePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.display();
+        match self.hazard {
+            PathHazard::CloudStorage => write!(f, "{path} is in cloud storage"),
+            PathHazard::NetworkMount => write!(f, "{path} is on a network mount"),
+            PathHazard::Dataless => write!(f, "{path} is a dataless file"),
+        }
+    }
+}
+
+impl LaunchAgent {
+    /// Returns the program, working directory and watched paths that live
+    /// in cloud storage, on network mounts or in dataless files.
+    ///
+    /// Paths are inspected on disk. Missing paths are checked by the
+    /// nearest existing parent directory.
+    pub fn unreliable_paths(&self) -> Vec<UnreliablePath> {
+        let cloud_dirs: Vec<PathBuf> = home_dir()
+            .map(|home| {
+                CLOUD_STORAGE_DIRS
+                    .iter()
+                    .map(|dir| home.join("Library").join(dir))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.referenced_paths()
+            .into_iter()
+            .filter_map(|path| {
+                let hazard = if cloud_dirs.iter().any(|dir| path.starts_with(dir)) {
+                    PathHazard::CloudStorage
+                } else if is_network_mount(&path) {
+                    PathHazard::NetworkMount
+                } else if is_dataless(&path) {
+                    PathHazard::Dataless
+                } else {
+                    return None;
+                };
+                Some(UnreliablePath { path, hazard })
+            })
+            .collect()
+    }
+
+    /// Returns the paths launchd accesses to start the agent.
+    fn referenced_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.program_path().into_iter().collect();
+        if let Some(dir) = self
+            .extra
+            .get("WorkingDirectory")
+            .and_then(Value::as_string)
+        {
+            paths.push(dir.into());
+        }
+        for key in ["WatchPaths", "QueueDirectories"] {
+            if let Some(Value::Array(values)) = self.extra.get(key) {
+                paths.extend(
+                    values
+                        .iter()
+                        .filter_map(Value::as_string)
+                        .map(PathBuf::from),
+                );
+            }
+        }
+        paths.retain(|path| path.is_absolute());
+        paths
+    }
+}
+
+/// Returns the path or its nearest existing ancestor.
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|ancestor| ancestor.exists())
+}
+
+#[cfg(target_os = "macos")]
+fn is_network_mount(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    /// `f_flags` bit of file systems stored locally.
+    const MNT_LOCAL: u32 = 0x1000;
+
+    let Some(path) = existing_ancestor(path)
+        .and_then(|path| std::ffi::CString::new(path.as_os_str().as_bytes()).ok())
+    else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    stat.f_flags & MNT_LOCAL == 0
+}
+
+#[cfg(not(target_os = "macos"))]
+fn is_network_mount(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    /// `f_type` of NFS, SMB, CIFS and SMB2 file systems.
+    const NETWORK_MAGICS: [i64; 4] = [0x6969, 0x517b, 0xff53_4d42, 0xfe53_4d42];
+
+    let Some(path) = existing_ancestor(path)
+        .and_then(|path| std::ffi::CString::new(path.as_os_str().as_bytes()).ok())
+    else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    // `f_type` is not an `i64` on every target.
+    #[allow(clippy::useless_conversion)]
+    i64::try_from(stat.f_type).is_ok_and(|kind| NETWORK_MAGICS.contains(&kind))
+}
+
+#[cfg(target_os = "macos")]
+fn is_dataless(path: &Path) -> bool {
+    use std::os::macos::fs::MetadataExt;
+
+    /// `st_flags` bit of files whose contents are not on disk.
+    const SF_DATALESS: u32 = 0x4000_0000;
+
+    std::fs::metadata(path)
+        .is_ok_and(|metadata| metadata.st_flags() & SF_DATALESS != 0)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn is_dataless(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unreliable_paths() {
+        let icloud = home_dir()
+            .unwrap()
+            .join("Library/Mobile Documents/com~apple~CloudDocs/bin/sync");
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent.program_arguments = vec![icloud.clone().into()];
+        agent.extra.insert(
+            "WatchPaths".to_string(),
+            Value::Array(vec!["/etc/hosts".into(), "relative".into()]),
+        );
+
+        assert_eq!(
+            agent.unreliable_paths(),
+            [UnreliablePath {
+                path: icloud,
+                hazard: PathHazard::CloudStorage,
+            }]
+        );
+        assert_eq!(
+            agent
+                .diagnose()
+                .iter()
+                .filter(|finding| finding.code == "unreliable-path")
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::agent::LaunchAgent;
+use crate::bundle::ProgramLocation;
+use crate::context::LaunchctlContext;
+use crate::install::{InstallEvent, InstallObserver};
+use crate::{LaunchAgentError, LaunchctlResult};
+
+impl LaunchAgent {
+    /// Point the agent at a new program binary and restart it.
+    ///
+    /// The new binary replaces the program behind any wrapper scripts, the
+    /// plist is rewritten and the running job is restarted with
+    /// `kickstart -k`. If the restart fails or the agent does not reach
+    /// running state within `timeout`, the previous configuration is
+    /// restored and restarted, and [`LaunchAgentError::UpdateRolledBack`] is
+    /// returned, or [`LaunchAgentError::UpdateRollbackFailed`] if restoring
+    /// fails too.
+    ///
+    /// The agent must already be bootstrapped.
+    pub fn replace_binary_and_restart<P: AsRef<Path>>(
+        &mut self,
+        new_path: P,
+        timeout: Duration,
+    ) -> LaunchctlResult<()> {
+        self.replace_binary_and_restart_observed(new_path, timeout, &())
+    }
+
+    /// Same as [`replace_binary_and_restart`](Self::replace_binary_and_restart),
+    /// reporting progress to `observer`.
+    pub fn replace_binary_and_restart_observed<P: AsRef<Path>>(
+        &mut self,
+        new_path: P,
+        timeout: Duration,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<()> {
+        LaunchctlContext::default()
+            .replace_binary_and_restart_observed(self, new_path, timeout, observer)
+    }
+
+    /// Check that the path points to an executable regular file.
+    pub(crate) fn validate_binary(path: &Path) -> LaunchctlResult<()> {
+        let invalid = |reason: &str| {
+            LaunchAgentError::InvalidBinary(path.to_path_buf(), reason.to_string())
+        };
+        if !path.is_absolute() {
+            return Err(invalid("path is not absolute"));
+        }
+        let metadata =
+            std::fs::metadata(path).map_err(|e| invalid(&e.to_string()))?;
+        if !metadata.is_file() {
+            return Err(invalid("not a regular file"));
+        }
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(invalid("file is not executable"));
+        }
+        Ok(())
+    }
+}
+
+impl LaunchctlContext {
+    /// Point the agent at a new program binary and restart it, see
+    /// [`LaunchAgent::replace_binary_and_restart`].
+    pub fn replace_binary_and_restart<P: AsRef<Path>>(
+        &self,
+        agent: &mut LaunchAgent,
+        new_path: P,
+        timeout: Duration,
+    ) -> LaunchctlResult<()> {
+        self.replace_binary_and_restart_observed(agent, new_path, timeout, &())
+    }
+
+    /// Same as [`replace_binary_and_restart`](Self::replace_binary_and_restart),
+    /// reporting progress to `observer`.
+    pub fn replace_binary_and_restart_observed<P: AsRef<Path>>(
+        &self,
+        agent: &mut LaunchAgent,
+        new_path: P,
+        timeout: Duration,
+        observer: &dyn InstallObserver,
+    ) -> LaunchctlResult<()> {
+        let new_path = new_path.as_ref();
+        LaunchAgent::validate_binary(new_path)?;
+        let _lock = self.lock(agent)?;
+
+        let previous = agent.clone();
+        agent.set_program(ProgramLocation::Absolute(new_path.to_path_buf()))?;
+
+        observer.on_event(&agent.label, InstallEvent::WritingPlist);
+        self.write_plist(agent)?;
+        observer.on_event(&agent.label, InstallEvent::Restarting);
+        let started = self.kickstart(agent, true).and_then(|()| {
+            observer.on_event(&agent.label, InstallEvent::WaitingForRunning);
+            self.wait_until_running(agent, timeout)
+        });
+        match started {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(e) => log::warn!(
+                target: "lunchctl",
+                "failed to restart {}: {e}",
+                agent.label
+            ),
+        }
+
+        *agent = previous;
+        let restored = self
+            .write_plist(agent)
+            .and_then(|_| self.kickstart(agent, true));
+        if let Err(e) = restored {
+            log::warn!(
+                target: "lunchctl",
+                "failed to restore the previous version of {}: {e}",
+                agent.label
+            );
+            return Err(LaunchAgentError::UpdateRollbackFailed(
+                agent.label.clone(),
+                Box::new(e),
+            ));
+        }
+        observer.on_event(&agent.label, InstallEvent::RolledBack);
+        Err(LaunchAgentError::UpdateRolledBack(agent.label.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{CommandOutput, CommandSpec};
+    use crate::context::CommandRunner;
+    use crate::wrapper::wrap;
+
+    #[test]
+    fn test_validate_binary() {
+        assert!(LaunchAgent::validate_binary(Path::new("/bin/sh")).is_ok());
+        assert!(LaunchAgent::validate_binary(Path::new("bin/sh")).is_err());
+        assert!(LaunchAgent::validate_binary(Path::new("/bin")).is_err());
+
+        let plain = std::env::temp_dir()
+            .join(format!("lunchctl.test.{}", rand::random_range(0..u32::MAX)));
+        std::fs::write(&plain, "").unwrap();
+        std::fs::set_permissions(&plain, std::fs::Permissions::from_mode(0o644))
+            .unwrap();
+        assert!(LaunchAgent::validate_binary(&plain).is_err());
+        std::fs::remove_file(plain).unwrap();
+        assert!(LaunchAgent::validate_binary(Path::new("/nonexistent/bin")).is_err());
+    }
+
+    struct RunningRunner;
+
+    impl CommandRunner for RunningRunner {
+        fn run(&self, command: &CommandSpec) -> LaunchctlResult<CommandOutput> {
+            let stdout = if command.args[0] == "print" {
+                b"state = running\n".to_vec()
+            } else {
+                Vec::new()
+            };
+            Ok(CommandOutput {
+                code: 0,
+                stdout,
+                stderr: Vec::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_replace_wrapped_program() {
+        let directory = std::env::temp_dir().join(format!(
+            "lunchctl-update-{}",
+            rand::random_range(0..u32::MAX)
+        ));
+        std::fs::create_dir(&directory).unwrap();
+        let context = LaunchctlContext::new()
+            .directory(&directory)
+            .runner(RunningRunner);
+        let script = "ulimit -n 4096; exec \"$0\" \"$@\"";
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent.program_arguments = vec!["ajam".into(), "--verbose".into()];
+        agent
+            .extra
+            .insert("Program".to_string(), "/usr/local/bin/ajam".into());
+        wrap(&mut agent.program_arguments, Some(&mut agent.extra), script);
+
+        context
+            .replace_binary_and_restart(&mut agent, "/bin/sh", Duration::ZERO)
+            .unwrap();
+        assert_eq!(
+            agent.program_arguments,
+            ["/bin/sh", "-c", script, "/bin/sh", "--verbose"]
+        );
+
+        agent.program_arguments = vec!["ajam".into()];
+        agent
+            .extra
+            .insert("Program".to_string(), "/usr/local/bin/ajam".into());
+        context
+            .replace_binary_and_restart(&mut agent, "/bin/sh", Duration::ZERO)
+            .unwrap();
+        assert_eq!(
+            agent.extra.get("Program").and_then(plist::Value::as_string),
+            Some("/bin/sh")
+        );
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    struct FailingKickstart;
+
+    impl CommandRunner for FailingKickstart {
+        fn run(&self, command: &CommandSpec) -> LaunchctlResult<CommandOutput> {
+            let failing = command.args[0] == "kickstart";
+            Ok(CommandOutput {
+                code: i32::from(failing),
+                stdout: Vec::new(),
+                stderr: if failing {
+                    b"Operation not permitted".to_vec()
+                } else {
+                    Vec::new()
+                },
+            })
+        }
+    }
+
+    #[test]
+    fn test_rollback() {
+        let directory = std::env::temp_dir().join(format!(
+            "lunchctl-update-{}",
+            rand::random_range(0..u32::MAX)
+        ));
+        std::fs::create_dir(&directory).unwrap();
+        let mut agent = LaunchAgent::new("co.myrt.ajam");
+        agent.program_arguments = vec!["/bin/sleep".into(), "60".into()];
+
+        let idle = LaunchctlContext::idle().directory(&directory);
+        let mut updated = agent.clone();
+        assert!(matches!(
+            idle.replace_binary_and_restart(&mut updated, "/bin/sh", Duration::ZERO),
+            Err(LaunchAgentError::UpdateRolledBack(_))
+        ));
+        assert_eq!(updated, agent);
+
+        let failing = LaunchctlContext::new()
+            .directory(&directory)
+            .runner(FailingKickstart);
+        assert!(matches!(
+            failing.replace_binary_and_restart(
+                &mut updated,
+                "/bin/sh",
+                Duration::ZERO
+            ),
+            Err(LaunchAgentError::UpdateRollbackFailed(..))
+        ));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
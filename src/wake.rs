@@ -0,0 +1,115 @@
+use plist::{Dictionary, Value};
+
+use crate::agent::LaunchAgent;
+
+/// `LaunchEvents` stream matching Darwin notifications.
+pub const NOTIFYD_EVENT_STREAM: &str = "com.apple.notifyd.matching";
+
+/// Darwin notification posted when the system power state changes.
+pub const POWER_STATE_NOTIFICATION: &str =
+    "com.apple.powermanagement.systempowerstate";
+
+/// Name of the event [`LaunchAgent::set_on_wake`] registers.
+const WAKE_EVENT: &str = "co.myrt.lunchctl.Wake";
+
+impl LaunchAgent {
+    /// Start the agent when the system wakes up.
+    ///
+    /// Registers a `LaunchEvents` entry matching the power state
+    /// notification on the `com.apple.notifyd.matching` stream. Two things
+    /// about these events are easy to miss:
+    ///
+    /// - The notification is posted on sleep as well as on wake, so the
+    ///   program should check the power state or tolerate an extra run.
+    /// - launchd keeps the event pending until the program consumes it with
+    ///   `xpc_set_event_stream_handler`. A program that does not will be
+    ///   started again as soon as it exits.
+    ///
+    /// Programs that cannot consume XPC events are better served by
+    /// `StartInterval` or `StartCalendarInterval`: launchd runs jobs whose
+    /// time passed during sleep once on wake, coalescing missed runs.
+    pub fn set_on_wake(&mut self, on_wake: bool) {
+        set_wake_event(&mut self.extra, on_wake);
+    }
+
+    /// Returns whether any `LaunchEvents` entry starts the agent on power
+    /// state changes, whether set by [`set_on_wake`](Self::set_on_wake) or
+    /// by hand.
+    pub fn runs_on_wake(&self) -> bool {
+        self.extra
+            .get("LaunchEvents")
+            .and_then(Value::as_dictionary)
+            .and_then(|streams| streams.get(NOTIFYD_EVENT_STREAM))
+            .and_then(Value::as_dictionary)
+            .is_some_and(|events| {
+                events.values().any(|event| {
+                    event
+                        .as_dictionary()
+                        .and_then(|event| event.get("Notification"))
+                        .and_then(Value::as_string)
+                        == Some(POWER_STATE_NOTIFICATION)
+                })
+            })
+    }
+}
+
+/// Add or remove the wake event in the `LaunchEvents` of `extra`, keeping
+/// other events.
+pub(crate) fn set_wake_event(extra: &mut Dictionary, on_wake: bool) {
+    let mut streams = extra
+        .get("LaunchEvents")
+        .and_then(Value::as_dictionary)
+        .cloned()
+        .unwrap_or_default();
+    let mut stream = streams
+        .get(NOTIFYD_EVENT_STREAM)
+        .and_then(Value::as_dictionary)
+        .cloned()
+        .unwrap_or_default();
+
+    if on_wake {
+        let mut event = Dictionary::new();
+        event.insert("Notification".to_string(), POWER_STATE_NOTIFICATION.into());
+        stream.insert(WAKE_EVENT.to_string(), event.into());
+    } else {
+        stream.remove(WAKE_EVENT);
+    }
+
+    if stream.is_empty() {
+        streams.remove(NOTIFYD_EVENT_STREAM);
+    } else {
+        streams.insert(NOTIFYD_EVENT_STREAM.to_string(), stream.into());
+    }
+    if streams.is_empty() {
+        extra.remove("LaunchEvents");
+    } else {
+        extra.insert("LaunchEvents".to_string(), streams.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::LaunchAgentBuilder;
+
+    #[test]
+    fn test_on_wake() {
+        let mut agent = LaunchAgentBuilder::default()
+            .label("co.myrt.ajam")
+            .on_wake()
+            .build()
+            .unwrap();
+        assert!(agent.runs_on_wake());
+
+        let mut buf = Vec::new();
+        plist::to_writer_xml(&mut buf, &agent).unwrap();
+        let xml = String::from_utf8(buf).unwrap();
+        assert!(xml.contains("<key>LaunchEvents</key>"));
+        assert!(xml.contains("<key>com.apple.notifyd.matching</key>"));
+        assert!(xml.contains(POWER_STATE_NOTIFICATION));
+
+        agent.set_on_wake(false);
+        assert!(!agent.runs_on_wake());
+        assert!(!agent.extra.contains_key("LaunchEvents"));
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use crate::paths::LaunchDirectory;
+use crate::LaunchctlResult;
+
+/// Interval between full rescans of the watched directory.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Change to a watched plist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    Added(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+}
+
+/// Watches a launchd directory for plists added, modified or removed by
+/// other software.
+///
+/// On macOS the directory is observed with kqueue, so changes are reported
+/// right away. Modifications that do not touch the directory itself, as well
+/// as every change on other platforms, are picked up by periodic rescans.
+pub struct PlistWatcher {
+    directory: Option<PathBuf>,
+    prefix: String,
+    interval: Duration,
+}
+
+/// Running watcher. Watching stops when the handle is dropped.
+pub struct WatchHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Modification time and size of a plist.
+type FileState = (Option<SystemTime>, u64);
+
+impl PlistWatcher {
+    /// Watch the current user's agents whose file name starts with `prefix`.
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            directory: None,
+            prefix: prefix.to_string(),
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Watch another directory instead of `~/Library/LaunchAgents`.
+    #[must_use]
+    pub fn directory<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Set the interval between full rescans.
+    #[must_use]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Start watching, calling `callback` from a background thread. Fails
+    /// if no directory was given and the home directory is unknown.
+    pub fn start<F>(self, mut callback: F) -> LaunchctlResult<WatchHandle>
+    where
+        F: FnMut(WatchEvent) + Send + 'static,
+    {
+        let directory = match &self.directory {
+            Some(directory) => directory.clone(),
+            None => LaunchDirectory::UserAgents.path()?,
+        };
+        let events = DirectoryEvents::new(&directory)?;
+        let mut known = self.scan(&directory);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_stop = Arc::clone(&stop);
+        let thread = thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                events.wait(self.interval);
+                let current = self.scan(&directory);
+                for event in diff(&known, &current) {
+                    callback(event);
+                }
+                known = current;
+            }
+        });
+
+        Ok(WatchHandle {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Start watching, delivering events through a channel.
+    pub fn channel(self) -> LaunchctlResult<(WatchHandle, Receiver<WatchEvent>)> {
+        let (sender, receiver) = mpsc::channel();
+        let handle = self.start(move |event| {
+            let _ = sender.send(event);
+        })?;
+        Ok((handle, receiver))
+    }
+
+    fn scan(&self, directory: &Path) -> BTreeMap<PathBuf, FileState> {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return BTreeMap::new();
+        };
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| self.matches(&entry.path()))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((entry.path(), (metadata.modified().ok(), metadata.len())))
+            })
+            .collect()
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        name.starts_with(&self.prefix)
+            && path.extension().is_some_and(|ext| ext == "plist")
+    }
+}
+
+impl WatchHandle {
+    /// Stop watching and wait for the background thread to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Returns the events turning `before` into `after`.
+fn diff(
+    before: &BTreeMap<PathBuf, FileState>,
+    after: &BTreeMap<PathBuf, FileState>,
+) -> Vec<WatchEvent> {
+    let mut events = Vec::new();
+    for (path, state) in after {
+        match before.get(path) {
+            None => events.push(WatchEvent::Added(path.clone())),
+            Some(old) if old != state => {
+                events.push(WatchEvent::Modified(path.clone()));
+            }
+            Some(_) => {}
+        }
+    }
+    for path in before.keys() {
+        if !after.contains_key(path) {
+            events.push(WatchEvent::Removed(path.clone()));
+        }
+    }
+    events
+}
+
+/// kqueue subscription to changes of the directory's entries.
+#[cfg(target_os = "macos")]
+struct DirectoryEvents {
+    queue: std::os::fd::OwnedFd,
+    _directory: std::fs::File,
+}
+
+#[cfg(target_os = "macos")]
+impl DirectoryEvents {
+    fn new(path: &Path) -> LaunchctlResult<Self> {
+        use std::os::fd::{AsRawFd, FromRawFd};
+
+        let directory = std::fs::File::open(path)?;
+        let queue = unsafe { libc::kqueue() };
+        if queue < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let queue = unsafe { std::os::fd::OwnedFd::from_raw_fd(queue) };
+
+        let change = libc::kevent {
+            ident: libc::uintptr_t::try_from(directory.as_raw_fd())
+                .unwrap_or_default(),
+            filter: libc::EVFILT_VNODE,
+            flags: libc::EV_ADD | libc::EV_CLEAR,
+            fflags: libc::NOTE_WRITE
+                | libc::NOTE_EXTEND
+                | libc::NOTE_ATTRIB
+                | libc::NOTE_DELETE
+                | libc::NOTE_RENAME,
+            data: 0,
+            udata: std::ptr::null_mut(),
+        };
+        let code = unsafe {
+            libc::kevent(
+                queue.as_raw_fd(),
+                &change,
+                1,
+                std::ptr::null_mut(),
+                0,
+                std::ptr::null(),
+            )
+        };
+        if code < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(Self {
+            queue,
+            _directory: directory,
+        })
+    }
+
+    /// Block until the directory changes or `timeout` passes.
+    fn wait(&self, timeout: Duration) {
+        use std::os::fd::AsRawFd;
+
+        let timeout = libc::timespec {
+            tv_sec: libc::time_t::try_from(timeout.as_secs())
+                .unwrap_or(libc::time_t::MAX),
+            tv_nsec: libc::c_long::from(timeout.subsec_nanos()),
+        };
+        let mut event: libc::kevent = unsafe { std::mem::zeroed() };
+        unsafe {
+            libc::kevent(
+                self.queue.as_raw_fd(),
+                std::ptr::null(),
+                0,
+                &mut event,
+                1,
+                &timeout,
+            );
+        }
+    }
+}
+
+/// Polling fallback for platforms without kqueue.
+#[cfg(not(target_os = "macos"))]
+struct DirectoryEvents;
+
+#[cfg(not(target_os = "macos"))]
+impl DirectoryEvents {
+    #[allow(clippy::unnecessary_wraps)]
+    fn new(_path: &Path) -> LaunchctlResult<Self> {
+        Ok(Self)
+    }
+
+    #[allow(clippy::unused_self)]
+    fn wait(&self, timeout: Duration) {
+        thread::sleep(timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let time = Some(SystemTime::UNIX_EPOCH);
+        let before = BTreeMap::from([
+            (PathBuf::from("a.plist"), (time, 1)),
+            (PathBuf::from("b.plist"), (time, 1)),
+            (PathBuf::from("c.plist"), (time, 1)),
+        ]);
+        let after = BTreeMap::from([
+            (PathBuf::from("a.plist"), (time, 1)),
+            (PathBuf::from("b.plist"), (time, 2)),
+            (PathBuf::from("d.plist"), (time, 1)),
+        ]);
+
+        assert_eq!(
+            diff(&before, &after),
+            vec![
+                WatchEvent::Modified(PathBuf::from("b.plist")),
+                WatchEvent::Added(PathBuf::from("d.plist")),
+                WatchEvent::Removed(PathBuf::from("c.plist")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watch_channel() {
+        let dir = std::env::temp_dir()
+            .join(format!("lunchctl.test.{}", rand::random_range(0..u32::MAX)));
+        std::fs::create_dir(&dir).unwrap();
+
+        let (handle, events) = PlistWatcher::new("co.myrt.")
+            .directory(&dir)
+            .interval(Duration::from_millis(20))
+            .channel()
+            .unwrap();
+
+        let path = dir.join("co.myrt.ajam.plist");
+        std::fs::write(&path, "a").unwrap();
+        std::fs::write(dir.join("other.plist"), "a").unwrap();
+        let timeout = Duration::from_secs(2);
+        assert_eq!(
+            events.recv_timeout(timeout),
+            Ok(WatchEvent::Added(path.clone()))
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(events.recv_timeout(timeout), Ok(WatchEvent::Removed(path)));
+
+        handle.stop();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
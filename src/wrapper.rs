@@ -0,0 +1,72 @@
+use std::ffi::OsString;
+
+use plist::{Dictionary, Value};
+
+/// How every wrapper script runs the wrapped program, which it gets as `$0`
+/// and `$@`. Wrappers are recognized by it, so they can be stacked.
+const PROGRAM_MARKER: &str = "\"$0\" \"$@\"";
+
+/// Wrap the program in a `/bin/sh` script, which has to run it with
+/// [`PROGRAM_MARKER`].
+///
+/// launchd runs a `Program` key with the program arguments as its argv, so
+/// it would get the script as its arguments. The key is moved into the
+/// arguments instead, replacing `argv[0]`.
+pub(crate) fn wrap(
+    args: &mut Vec<OsString>,
+    extra: Option<&mut Dictionary>,
+    script: &str,
+) {
+    debug_assert!(script.contains(PROGRAM_MARKER));
+    if let Some(Value::String(program)) =
+        extra.and_then(|extra| extra.remove("Program"))
+    {
+        match args.first_mut() {
+            Some(first) => *first = program.into(),
+            None => args.push(program.into()),
+        }
+    }
+    let wrapper = ["/bin/sh", "-c", script].map(OsString::from);
+    args.splice(..0, wrapper);
+}
+
+/// Returns the index of the first wrapper whose script matches, looking
+/// through the stacked wrappers only.
+pub(crate) fn find_wrapper<F>(args: &[OsString], matches: F) -> Option<usize>
+where
+    F: Fn(&str) -> bool,
+{
+    wrappers(args).find(|&index| script_at(args, index).is_some_and(&matches))
+}
+
+/// Remove the first wrapper whose script matches. Returns whether there was
+/// one.
+pub(crate) fn unwrap<F>(args: &mut Vec<OsString>, matches: F) -> bool
+where
+    F: Fn(&str) -> bool,
+{
+    let Some(index) = find_wrapper(args, matches) else {
+        return false;
+    };
+    args.drain(index..index + 3);
+    true
+}
+
+/// Returns the index of the wrapped program, past every wrapper.
+pub(crate) fn program_index(args: &[OsString]) -> usize {
+    wrappers(args).last().map_or(0, |index| index + 3)
+}
+
+fn wrappers(args: &[OsString]) -> impl Iterator<Item = usize> + '_ {
+    std::iter::successors(Some(0), |index| Some(index + 3))
+        .take_while(|&index| script_at(args, index).is_some())
+}
+
+fn script_at(args: &[OsString], index: usize) -> Option<&str> {
+    let [shell, flag, script, _program, ..] = args.get(index..)? else {
+        return None;
+    };
+    let script = script.to_str()?;
+    (shell == "/bin/sh" && flag == "-c" && script.contains(PROGRAM_MARKER))
+        .then_some(script)
+}
@@ -0,0 +1,137 @@
+use std::borrow::Cow;
+use std::path::Path;
+
+use crate::agent::LaunchAgent;
+use crate::context::LaunchctlContext;
+use crate::report::WriteReport;
+use crate::{LaunchAgentError, LaunchctlResult};
+
+/// What writing an agent does when its plist already exists with different
+/// contents. Rewriting identical contents always succeeds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum WritePolicy {
+    /// Fail with [`LaunchAgentError::PlistExists`].
+    ErrorIfExists,
+    /// Replace the plist.
+    #[default]
+    OverwriteAlways,
+    /// Replace the plist if it was written by this crate, see
+    /// [`LaunchAgent::is_managed`], and fail with
+    /// [`LaunchAgentError::NotManaged`] otherwise.
+    OverwriteIfManagedByUs,
+    /// Replace the plist, keeping keys of the existing one that the agent
+    /// does not set, e.g. ones added by hand or by another tool.
+    MergePreservingUnknownKeys,
+}
+
+impl LaunchAgent {
+    /// Same as [`write`](Self::write), following `policy` instead of
+    /// [`WritePolicy::OverwriteAlways`], see
+    /// [`LaunchctlContext::write_policy`].
+    pub fn write_with(&self, policy: WritePolicy) -> LaunchctlResult<WriteReport> {
+        LaunchctlContext::default().write_policy(policy).write(self)
+    }
+
+    /// Returns the configuration as it would be written over `installed`.
+    pub(crate) fn merged_with(&self, installed: &Self) -> Self {
+        let mut merged = self.clone();
+        for (key, value) in &installed.extra {
+            if !merged.extra.contains_key(key) {
+                merged.extra.insert(key.clone(), value.clone());
+            }
+        }
+        merged
+    }
+
+    /// Returns the configuration to write over the plist at `path`
+    /// following `policy`. Fails if the policy, or the ownership
+    /// requirement, refuses to replace the existing plist.
+    pub(crate) fn to_write_over(
+        &self,
+        path: &Path,
+        policy: WritePolicy,
+        require_ownership: bool,
+    ) -> LaunchctlResult<Cow<'_, Self>> {
+        let ours = self.stamped();
+        let Some(installed) = self
+            .read_installed_at(path)?
+            .filter(|installed| *installed != *ours)
+        else {
+            return Ok(ours);
+        };
+        let managed = installed.is_managed();
+        if require_ownership && !managed {
+            return Err(LaunchAgentError::NotManaged(path.to_path_buf()));
+        }
+        match policy {
+            WritePolicy::OverwriteAlways => Ok(ours),
+            WritePolicy::ErrorIfExists => {
+                Err(LaunchAgentError::PlistExists(path.to_path_buf()))
+            }
+            WritePolicy::OverwriteIfManagedByUs if !managed => {
+                Err(LaunchAgentError::NotManaged(path.to_path_buf()))
+            }
+            WritePolicy::OverwriteIfManagedByUs => Ok(ours),
+            WritePolicy::MergePreservingUnknownKeys => {
+                Ok(Cow::Owned(ours.merged_with(&installed)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_policies() {
+        let label = format!("co.myrt.policy.{}", rand::random_range(0..u32::MAX));
+        let mut foreign = LaunchAgent::new(&label);
+        foreign.extra.insert("VendorKey".to_string(), "kept".into());
+        foreign.write_plist_to(&foreign.path().unwrap()).unwrap();
+
+        let mut agent = LaunchAgent::new(&label);
+        agent.run_at_load = true;
+        assert!(matches!(
+            agent.write_with(WritePolicy::ErrorIfExists),
+            Err(LaunchAgentError::PlistExists(_))
+        ));
+        assert!(matches!(
+            agent.write_with(WritePolicy::OverwriteIfManagedByUs),
+            Err(LaunchAgentError::NotManaged(_))
+        ));
+
+        agent
+            .write_with(WritePolicy::MergePreservingUnknownKeys)
+            .unwrap();
+        let installed = agent.read_installed().unwrap().unwrap();
+        assert!(installed.run_at_load);
+        assert_eq!(installed.extra["VendorKey"].as_string(), Some("kept"));
+        assert_eq!(installed, agent.merged_with(&installed));
+
+        agent.set_managed_by("co.myrt.policy");
+        agent.write().unwrap();
+        assert!(agent.read_installed().unwrap().unwrap().is_managed());
+        agent.run_at_load = false;
+        agent
+            .write_with(WritePolicy::OverwriteIfManagedByUs)
+            .unwrap();
+        agent.write_with(WritePolicy::ErrorIfExists).unwrap();
+        agent.remove().unwrap();
+    }
+
+    #[test]
+    fn test_policy_applies_to_case_collision() {
+        let id = rand::random_range(0..u32::MAX);
+        let agent = LaunchAgent::new(&format!("co.myrt.policy.case.{id}"));
+        let shouted = LaunchAgent::new(&format!("CO.MYRT.POLICY.CASE.{id}"));
+        shouted.write().unwrap();
+
+        assert!(matches!(
+            agent.write_with(WritePolicy::ErrorIfExists),
+            Err(LaunchAgentError::PlistExists(_))
+        ));
+        assert!(shouted.path().unwrap().exists());
+        shouted.remove().unwrap();
+    }
+}